@@ -0,0 +1,99 @@
+//! Build manifests, recording which package and options an artifact was built from.
+//!
+//! `cargo v5 build` writes a manifest next to every artifact as `<name>.build.json`. When a
+//! program is uploaded with `--file`, there's no build to ask, so the upload reads the manifest
+//! instead to name and describe the program after the package it came from.
+
+use std::path::{Path, PathBuf};
+
+use serde_json::{Value, json};
+
+use crate::errors::CliError;
+
+/// Extension given to build manifests, in place of the artifact's own extension.
+pub const BUILD_INFO_EXTENSION: &str = "build.json";
+
+/// What an artifact was built from.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BuildInfo {
+    /// Cargo's ID of the package the artifact belongs to.
+    pub package_id: String,
+    /// Name of the package.
+    pub name: String,
+    /// Version of the package.
+    pub version: String,
+    /// Description of the package, if it has one.
+    pub description: Option<String>,
+    /// Features of the package that were enabled.
+    pub features: Vec<String>,
+    /// Name of the profile's output directory, like `debug` or `release`.
+    pub profile: String,
+    /// The git revision the artifact was built from, as given by `git describe`.
+    pub git: Option<String>,
+}
+
+/// Returns where the build manifest for `artifact` is stored.
+///
+/// The ELF and BIN built from the same program share a manifest.
+pub fn build_info_path(artifact: &Path) -> PathBuf {
+    artifact.with_extension(BUILD_INFO_EXTENSION)
+}
+
+/// Returns the git revision of the repository containing `dir`, like `v1.2.0-3-gabc1234-dirty`.
+pub fn git_revision(dir: &Path) -> Option<String> {
+    let output = std::process::Command::new("git")
+        .args(["describe", "--always", "--dirty", "--tags"])
+        .current_dir(dir)
+        .output()
+        .ok()?;
+
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Writes the build manifest for `artifact`.
+pub fn write_build_info(artifact: &Path, info: &BuildInfo) -> Result<(), CliError> {
+    let manifest = json!({
+        "package_id": info.package_id,
+        "name": info.name,
+        "version": info.version,
+        "description": info.description,
+        "features": info.features,
+        "profile": info.profile,
+        "git": info.git,
+    });
+
+    std::fs::write(
+        build_info_path(artifact),
+        serde_json::to_string_pretty(&manifest).unwrap() + "\n",
+    )?;
+
+    Ok(())
+}
+
+/// Reads the build manifest for `artifact`, if there is a readable one.
+pub fn read_build_info(artifact: &Path) -> Option<BuildInfo> {
+    let manifest = std::fs::read_to_string(build_info_path(artifact)).ok()?;
+    let manifest = serde_json::from_str::<Value>(&manifest).ok()?;
+    let string = |key: &str| manifest[key].as_str().map(str::to_string);
+
+    Some(BuildInfo {
+        package_id: string("package_id")?,
+        name: string("name")?,
+        version: string("version").unwrap_or_default(),
+        description: string("description"),
+        features: manifest["features"]
+            .as_array()
+            .map(|features| {
+                features
+                    .iter()
+                    .filter_map(|feature| feature.as_str().map(str::to_string))
+                    .collect()
+            })
+            .unwrap_or_default(),
+        profile: string("profile").unwrap_or_default(),
+        git: string("git"),
+    })
+}
@@ -0,0 +1,213 @@
+//! Asset bundling for `package.metadata.v5.bundle` and `package.metadata.v5.linked-files`.
+//!
+//! Files matching the configured globs are packed into a single blob, and a Rust module
+//! describing the blob is generated alongside it. Programs pull the module in with
+//! `include!(env!("V5_BUNDLE"))`, which embeds the blob into the program's binary and lets
+//! assets be looked up by path without a filesystem.
+//!
+//! Linked files are packed the same way, but the blob is uploaded as the program's linked file
+//! instead of being embedded, so it's only sent again when it changes. Their module is included
+//! with `include!(env!("V5_LINKED_FILES"))` and reads the blob from where VEXos loads it.
+
+use std::{
+    fmt::Write,
+    path::{Path, PathBuf},
+};
+
+use crate::{errors::CliError, reporter::Reporter};
+
+/// Environment variable passed to rustc containing the path of the generated bundle module.
+pub const BUNDLE_ENV_VAR: &str = "V5_BUNDLE";
+
+/// Environment variable passed to rustc containing the path of the generated linked files module.
+pub const LINKED_FILES_ENV_VAR: &str = "V5_LINKED_FILES";
+
+/// Name of the blob written to a bundle's output directory.
+pub const BLOB_FILE_NAME: &str = "bundle.bin";
+
+/// Alignment of each file inside of the bundle blob.
+const ALIGNMENT: usize = 4;
+
+/// Where a bundle's blob is stored when the program runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BundleStorage {
+    /// Embedded in the program's binary.
+    Embedded,
+    /// Uploaded as the program's linked file, which VEXos loads at `load_address`.
+    Linked { load_address: u32 },
+}
+
+/// A file packed into a bundle.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BundleEntry {
+    /// Path of the file relative to the package's manifest directory.
+    pub name: String,
+    pub offset: usize,
+    pub len: usize,
+}
+
+/// Collects every file in `package_dir` matching `patterns`, sorted by path.
+fn collect_files(package_dir: &Path, patterns: &[String]) -> Result<Vec<PathBuf>, CliError> {
+    let mut files = Vec::new();
+
+    for pattern in patterns {
+        let full_pattern = package_dir.join(pattern);
+        let paths = glob::glob(&full_pattern.to_string_lossy())
+            .map_err(|err| CliError::InvalidBundlePattern(pattern.clone(), err.msg))?;
+
+        for path in paths.filter_map(Result::ok) {
+            if path.is_file() && !files.contains(&path) {
+                files.push(path);
+            }
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+/// Packs the files matching `patterns` into `out_dir/bundle.bin` and generates
+/// `out_dir/bundle.rs`, returning the path to the generated module.
+///
+/// Files are only rewritten when their contents change so that the program isn't
+/// needlessly rebuilt.
+pub async fn write_bundle(
+    package_dir: &Path,
+    patterns: &[String],
+    out_dir: &Path,
+    storage: BundleStorage,
+    reporter: &dyn Reporter,
+) -> Result<PathBuf, CliError> {
+    let mut blob = Vec::new();
+    let mut entries = Vec::new();
+
+    for path in collect_files(package_dir, patterns)? {
+        let contents = tokio::fs::read(&path).await?;
+
+        blob.resize(blob.len().next_multiple_of(ALIGNMENT), 0);
+        entries.push(BundleEntry {
+            name: path
+                .strip_prefix(package_dir)
+                .unwrap_or(&path)
+                .to_string_lossy()
+                .replace('\\', "/"),
+            offset: blob.len(),
+            len: contents.len(),
+        });
+        blob.extend(contents);
+    }
+
+    tokio::fs::create_dir_all(out_dir).await?;
+
+    let blob_path = out_dir.join(BLOB_FILE_NAME);
+    let module_path = out_dir.join("bundle.rs");
+
+    write_if_changed(&blob_path, &blob).await?;
+    write_if_changed(
+        &module_path,
+        generate_module(&blob_path.canonicalize()?, &entries, blob.len(), storage).as_bytes(),
+    )
+    .await?;
+
+    reporter.status(
+        match storage {
+            BundleStorage::Embedded => "Bundled",
+            BundleStorage::Linked { .. } => "Linked",
+        },
+        &format!("{} file(s) ({} bytes)", entries.len(), blob.len()),
+    );
+
+    Ok(module_path)
+}
+
+async fn write_if_changed(path: &Path, contents: &[u8]) -> Result<(), CliError> {
+    if tokio::fs::read(path).await.ok().as_deref() != Some(contents) {
+        tokio::fs::write(path, contents).await?;
+    }
+
+    Ok(())
+}
+
+/// Generates the Rust source of a module exposing the bundle at `blob_path`, which is `len` bytes
+/// long.
+fn generate_module(
+    blob_path: &Path,
+    entries: &[BundleEntry],
+    len: usize,
+    storage: BundleStorage,
+) -> String {
+    let mut module = String::new();
+
+    match storage {
+        BundleStorage::Embedded => {
+            module.push_str(
+                "// @generated by cargo-v5 from `package.metadata.v5.bundle`. Do not edit.\n\n",
+            );
+            module.push_str("/// Contents of every bundled file.\n");
+            writeln!(
+                module,
+                "pub static DATA: &[u8] = include_bytes!({:?});\n",
+                blob_path.to_string_lossy()
+            )
+            .unwrap();
+        }
+        BundleStorage::Linked { load_address } => {
+            module.push_str(
+                "// @generated by cargo-v5 from `package.metadata.v5.linked-files`. Do not edit.\n\n",
+            );
+            module.push_str("/// Address VEXos loads the program's linked file at.\n");
+            writeln!(
+                module,
+                "pub const LOAD_ADDRESS: usize = {load_address:#010x};\n"
+            )
+            .unwrap();
+            module.push_str("/// Length of the linked file.\n");
+            writeln!(module, "pub const LEN: usize = {len};\n").unwrap();
+            module.push_str(
+                "/// Contents of every linked file.
+pub fn data() -> &'static [u8] {
+    // SAFETY: VEXos loads the linked file at `LOAD_ADDRESS` before the program starts.
+    unsafe { core::slice::from_raw_parts(LOAD_ADDRESS as *const u8, LEN) }
+}
+
+",
+            );
+        }
+    }
+
+    let data = match storage {
+        BundleStorage::Embedded => "DATA",
+        BundleStorage::Linked { .. } => "data()",
+    };
+
+    writeln!(
+        module,
+        "/// Path, offset, and length of each file in [`{data}`]."
+    )
+    .unwrap();
+    module.push_str("pub static INDEX: &[(&str, usize, usize)] = &[\n");
+    for entry in entries {
+        writeln!(
+            module,
+            "    ({:?}, {}, {}),",
+            entry.name, entry.offset, entry.len
+        )
+        .unwrap();
+    }
+    module.push_str("];\n\n");
+
+    write!(
+        module,
+        "/// Returns the contents of the file at `path`, relative to `Cargo.toml`.
+pub fn get(path: &str) -> Option<&'static [u8]> {{
+    INDEX
+        .iter()
+        .find(|(name, ..)| *name == path)
+        .map(|&(_, offset, len)| &{data}[offset..offset + len])
+}}
+",
+    )
+    .unwrap();
+
+    module
+}
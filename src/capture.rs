@@ -0,0 +1,410 @@
+//! Recording and replaying the raw traffic of a device connection.
+//!
+//! `--capture <FILE>` records every packet and every byte of program I/O exchanged with a device
+//! into a capture file that users can attach to bug reports. Captures are JSON lines: a header
+//! with the cargo-v5 version and command line, followed by one line per event:
+//!
+//! ```text
+//! {"version":"0.12.1","args":["v5","upload","--slot","2"]}
+//! {"t":0,"event":"open","connection":"wired"}
+//! {"t":3,"dir":"tx","data":"c95a24"}
+//! {"t":5,"dir":"rx","data":"aa55240c..."}
+//! ```
+//!
+//! `cargo v5 replay <FILE>` runs the recorded command line again, with its connection replaced by
+//! a [`ReplayConnection`] that answers with the recorded replies. Commands that build the project
+//! or write local files aren't replayed, since captures come from other people.
+
+use std::{
+    collections::VecDeque,
+    fs::{self, File},
+    io::Write,
+    path::Path,
+    sync::{Mutex, OnceLock},
+    time::{Duration, Instant},
+};
+
+use serde_json::{Value, json};
+use vex_v5_serial::{
+    CheckHeader, Connection, ConnectionType,
+    protocol::{Decode, DecodeError, Encode},
+};
+
+use crate::{connection::ConnectionError, errors::CliError};
+
+/// Which way data in a capture was sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// A packet sent to the device.
+    Tx,
+    /// A packet received from the device.
+    Rx,
+    /// Input written to the user program.
+    UserTx,
+    /// Output read from the user program.
+    UserRx,
+}
+
+impl Direction {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Tx => "tx",
+            Self::Rx => "rx",
+            Self::UserTx => "user-tx",
+            Self::UserRx => "user-rx",
+        }
+    }
+
+    fn parse(direction: &str) -> Option<Self> {
+        Some(match direction {
+            "tx" => Self::Tx,
+            "rx" => Self::Rx,
+            "user-tx" => Self::UserTx,
+            "user-rx" => Self::UserRx,
+            _ => return None,
+        })
+    }
+}
+
+fn connection_type_name(connection_type: ConnectionType) -> &'static str {
+    match connection_type {
+        ConnectionType::Wired => "wired",
+        ConnectionType::Controller => "controller",
+        ConnectionType::Bluetooth => "bluetooth",
+    }
+}
+
+fn to_hex(data: &[u8]) -> String {
+    data.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// A capture file being written to.
+struct CaptureWriter {
+    file: File,
+    start: Instant,
+}
+
+impl CaptureWriter {
+    fn write_line(&mut self, mut line: Value) {
+        line["t"] = json!(self.start.elapsed().as_millis() as u64);
+
+        // Each line is written in one go and left unbuffered, so a capture survives a crash.
+        if let Err(err) = writeln!(self.file, "{line}") {
+            log::debug!("Couldn't write to capture file: {err}");
+        }
+    }
+}
+
+static CAPTURE: OnceLock<Mutex<CaptureWriter>> = OnceLock::new();
+
+/// Options whose values are secrets, and are left out of captures since they're shared in bug
+/// reports. Replays don't need them, since they don't connect to anything.
+const SECRET_ARGS: &[&str] = &["--remote-token", "--token"];
+
+/// Replaces the values of [`SECRET_ARGS`] in a command line.
+fn redact_args(args: &[String]) -> Vec<String> {
+    let mut redacted = Vec::with_capacity(args.len());
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        if SECRET_ARGS.contains(&arg.as_str()) {
+            redacted.push(arg.clone());
+            if args.next().is_some() {
+                redacted.push("<redacted>".to_string());
+            }
+        } else if let Some((name, _)) = arg.split_once('=')
+            && SECRET_ARGS.contains(&name)
+        {
+            redacted.push(format!("{name}=<redacted>"));
+        } else {
+            redacted.push(arg.clone());
+        }
+    }
+
+    redacted
+}
+
+/// Starts recording connection traffic to `path`, along with the command line it was run with.
+///
+/// Secrets on the command line, like `--remote-token`, aren't recorded.
+pub fn start_capture(path: &Path, args: &[String]) -> Result<(), CliError> {
+    let mut file = File::create(path)?;
+    writeln!(
+        file,
+        "{}",
+        json!({
+            "version": env!("CARGO_PKG_VERSION"),
+            "args": redact_args(args),
+        })
+    )?;
+
+    _ = CAPTURE.set(Mutex::new(CaptureWriter {
+        file,
+        start: Instant::now(),
+    }));
+
+    Ok(())
+}
+
+/// Whether connection traffic is being recorded.
+pub fn is_capturing() -> bool {
+    CAPTURE.get().is_some()
+}
+
+/// Records that a connection of the given type was opened.
+pub fn record_open(connection_type: ConnectionType) {
+    if let Some(capture) = CAPTURE.get() {
+        capture.lock().unwrap().write_line(json!({
+            "event": "open",
+            "connection": connection_type_name(connection_type),
+        }));
+    }
+}
+
+/// Records data exchanged with the device.
+pub fn record(direction: Direction, data: &[u8]) {
+    if let Some(capture) = CAPTURE.get() {
+        capture.lock().unwrap().write_line(json!({
+            "dir": direction.as_str(),
+            "data": to_hex(data),
+        }));
+    }
+}
+
+/// Records a packet that's about to be sent.
+pub fn record_packet(packet: &impl Encode) {
+    if is_capturing() {
+        let mut encoded = vec![0; packet.size()];
+        packet.encode(&mut encoded);
+        record(Direction::Tx, &encoded);
+    }
+}
+
+/// A received packet, along with the raw bytes it was decoded from.
+pub struct Captured<P> {
+    pub bytes: Vec<u8>,
+    pub packet: P,
+}
+
+impl<P: CheckHeader> CheckHeader for Captured<P> {
+    fn has_valid_header(data: &[u8]) -> bool {
+        P::has_valid_header(data)
+    }
+}
+
+impl<P: Decode> Decode for Captured<P> {
+    fn decode(data: &mut &[u8]) -> Result<Self, DecodeError> {
+        let start = *data;
+        let packet = P::decode(data)?;
+
+        Ok(Self {
+            bytes: start[..start.len() - data.len()].to_vec(),
+            packet,
+        })
+    }
+}
+
+/// A capture file read back for replaying.
+pub struct Capture {
+    /// The version of cargo-v5 that recorded the capture.
+    pub version: String,
+
+    /// Command-line arguments the capture was recorded with, starting with `v5`.
+    pub args: Vec<String>,
+
+    connection_type: ConnectionType,
+    events: Vec<(Direction, Vec<u8>)>,
+}
+
+/// Reads a capture file written with `--capture`.
+pub fn read_capture(path: &Path) -> Result<Capture, CliError> {
+    let malformed = |line: usize, message: &str| {
+        CliError::MalformedCapture(path.to_path_buf(), format!("line {line}: {message}"))
+    };
+
+    let contents = fs::read_to_string(path)?;
+    let mut lines = contents.lines().enumerate().map(|(index, line)| {
+        serde_json::from_str::<Value>(line).map_err(|err| malformed(index + 1, &err.to_string()))
+    });
+
+    let header = lines
+        .next()
+        .transpose()?
+        .ok_or_else(|| malformed(1, "the file is empty"))?;
+    let mut capture = Capture {
+        version: header["version"].as_str().unwrap_or("unknown").to_string(),
+        args: header["args"]
+            .as_array()
+            .and_then(|args| {
+                args.iter()
+                    .map(|arg| arg.as_str().map(str::to_string))
+                    .collect()
+            })
+            .ok_or_else(|| malformed(1, "missing the recorded command line"))?,
+        connection_type: ConnectionType::Wired,
+        events: Vec::new(),
+    };
+
+    for (index, line) in lines.enumerate() {
+        let line = line?;
+        // The header is line 1.
+        let line_number = index + 2;
+
+        if line["event"] == "open" {
+            capture.connection_type = match line["connection"].as_str() {
+                Some("controller") => ConnectionType::Controller,
+                Some("bluetooth") => ConnectionType::Bluetooth,
+                _ => ConnectionType::Wired,
+            };
+            continue;
+        }
+
+        let direction = line["dir"]
+            .as_str()
+            .and_then(Direction::parse)
+            .ok_or_else(|| malformed(line_number, "unknown direction"))?;
+        let data = line["data"]
+            .as_str()
+            .and_then(from_hex)
+            .ok_or_else(|| malformed(line_number, "data isn't valid hex"))?;
+
+        capture.events.push((direction, data));
+    }
+
+    Ok(capture)
+}
+
+/// A connection that answers with the replies recorded in a [`Capture`].
+///
+/// Replies are handed out in the order they were recorded, to the first request that expects
+/// their kind of packet. Packets sent through the connection are checked against the recorded
+/// ones, and a warning is logged where the replay starts to diverge.
+pub struct ReplayConnection {
+    connection_type: ConnectionType,
+    sent: VecDeque<Vec<u8>>,
+    replies: Vec<Option<Vec<u8>>>,
+    user_output: VecDeque<Vec<u8>>,
+    diverged: bool,
+}
+
+impl ReplayConnection {
+    pub fn new(capture: Capture) -> Self {
+        let mut connection = Self {
+            connection_type: capture.connection_type,
+            sent: VecDeque::new(),
+            replies: Vec::new(),
+            user_output: VecDeque::new(),
+            diverged: false,
+        };
+
+        for (direction, data) in capture.events {
+            match direction {
+                Direction::Tx => connection.sent.push_back(data),
+                Direction::Rx => connection.replies.push(Some(data)),
+                Direction::UserRx => connection.user_output.push_back(data),
+                // Program input is whatever is typed during the replay.
+                Direction::UserTx => {}
+            }
+        }
+
+        connection
+    }
+}
+
+impl Connection for ReplayConnection {
+    type Error = ConnectionError;
+
+    fn connection_type(&self) -> ConnectionType {
+        self.connection_type
+    }
+
+    async fn send(&mut self, packet: impl Encode) -> Result<(), ConnectionError> {
+        let mut encoded = vec![0; packet.size()];
+        packet.encode(&mut encoded);
+
+        if self.sent.pop_front().as_ref() != Some(&encoded) && !self.diverged {
+            log::warn!(
+                "Replay diverged from the capture: sent {}, which wasn't recorded here",
+                to_hex(&encoded)
+            );
+            self.diverged = true;
+        }
+
+        Ok(())
+    }
+
+    async fn recv<P: Decode + CheckHeader>(
+        &mut self,
+        _timeout: Duration,
+    ) -> Result<P, ConnectionError> {
+        let reply = self
+            .replies
+            .iter_mut()
+            .find(|reply| reply.as_ref().is_some_and(|data| P::has_valid_header(data)))
+            .and_then(Option::take)
+            .ok_or(ConnectionError::ReplayExhausted)?;
+
+        Ok(P::decode(&mut reply.as_slice())?)
+    }
+
+    async fn read_user(&mut self, buf: &mut [u8]) -> Result<usize, ConnectionError> {
+        let Some(mut output) = self.user_output.pop_front() else {
+            return Ok(0);
+        };
+
+        let len = output.len().min(buf.len());
+        buf[..len].copy_from_slice(&output[..len]);
+        if len < output.len() {
+            self.user_output.push_front(output.split_off(len));
+        }
+
+        Ok(len)
+    }
+
+    async fn write_user(&mut self, buf: &[u8]) -> Result<usize, ConnectionError> {
+        Ok(buf.len())
+    }
+}
+
+/// A replay waiting for the command being replayed to open its connection.
+enum ReplayState {
+    Off,
+    Pending(Box<ReplayConnection>),
+    Taken,
+}
+
+static REPLAY: Mutex<ReplayState> = Mutex::new(ReplayState::Off);
+
+/// Makes the next call to [`open_connection`](crate::connection::open_connection) return
+/// `connection` instead of connecting to a device.
+pub fn set_replay(connection: ReplayConnection) {
+    *REPLAY.lock().unwrap() = ReplayState::Pending(Box::new(connection));
+}
+
+/// Takes the pending replay connection, if a replay is running.
+///
+/// Only one connection is recorded, so once it's taken, opening another fails rather than
+/// reaching for a real device.
+pub fn take_replay() -> Result<Option<ReplayConnection>, CliError> {
+    let mut replay = REPLAY.lock().unwrap();
+
+    match std::mem::replace(&mut *replay, ReplayState::Taken) {
+        ReplayState::Off => {
+            *replay = ReplayState::Off;
+            Ok(None)
+        }
+        ReplayState::Pending(connection) => Ok(Some(*connection)),
+        ReplayState::Taken => Err(CliError::NoDevice),
+    }
+}
@@ -0,0 +1,259 @@
+use std::io::{self, Write};
+
+use tabwriter::TabWriter;
+use vex_v5_serial::protocol::cdc2::system::LogEntry;
+
+use crate::{connection::DeviceConnection, errors::CliError};
+
+use super::log::{decode_match_round, read_all_entries};
+
+/// Battery voltage below which the Brain is at risk of browning out under load.
+pub const BROWNOUT_VOLTAGE: f32 = 11.5;
+
+/// Event log descriptions relevant to power analysis (see [`super::log::decode_error_message`]).
+const PROGRAM_RUN: u8 = 11;
+const PROGRAM_STOP: u8 = 12;
+const POWER: u8 = 13;
+pub const BATTERY: u8 = 14;
+const LOW_BATTERY: u8 = 15;
+const BATTERY_ERROR: u8 = 16;
+const MOTOR_OVER_CURRENT: u8 = 17;
+
+/// Code of a [`POWER`] event caused by the Brain being turned off.
+const POWER_OFF: u8 = 0xff;
+
+/// Log type of match events.
+const MATCH_LOG_TYPE: u8 = 11;
+
+/// How a program run ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RunEnd {
+    /// The program was stopped normally.
+    Stopped,
+
+    /// The Brain powered on or reset while the program was still running.
+    PowerLoss,
+
+    /// The log ends while the program was still running.
+    Unknown,
+}
+
+/// A single program run, reconstructed from the event log.
+#[derive(Debug)]
+struct Run {
+    slot: u8,
+    match_label: Option<String>,
+    start: u32,
+    end_time: u32,
+    end: RunEnd,
+    min_voltage: Option<f32>,
+    min_capacity: Option<u8>,
+    low_battery_events: u32,
+    battery_errors: u32,
+    over_current_events: u32,
+}
+
+impl Run {
+    fn new(slot: u8, start: u32, match_label: Option<String>) -> Self {
+        Self {
+            slot,
+            match_label,
+            start,
+            end_time: start,
+            end: RunEnd::Unknown,
+            min_voltage: None,
+            min_capacity: None,
+            low_battery_events: 0,
+            battery_errors: 0,
+            over_current_events: 0,
+        }
+    }
+
+    /// Reasons this run looks like it suffered a brownout, if any.
+    fn brownout_reasons(&self) -> Vec<&'static str> {
+        let mut reasons = Vec::new();
+
+        if self.end == RunEnd::PowerLoss {
+            reasons.push("lost power while running");
+        }
+        if self.low_battery_events > 0 {
+            reasons.push("low battery");
+        }
+        if self.battery_errors > 0 {
+            reasons.push("battery error");
+        }
+        if self
+            .min_voltage
+            .is_some_and(|voltage| voltage < BROWNOUT_VOLTAGE)
+        {
+            reasons.push("voltage sag");
+        }
+
+        reasons
+    }
+}
+
+/// Splits the event log into program runs, collecting the battery events that happened during
+/// each one.
+fn collect_runs(entries: &[LogEntry]) -> Vec<Run> {
+    let mut runs = Vec::new();
+    let mut current: Option<Run> = None;
+    let mut last_match = None;
+
+    for entry in entries {
+        if entry.log_type == MATCH_LOG_TYPE {
+            last_match = Some(format!(
+                "{}-{}-{}",
+                decode_match_round(entry.description),
+                entry.code,
+                entry.spare
+            ));
+            continue;
+        }
+
+        // Match metadata and other special log types don't use the common descriptions.
+        if !matches!(entry.log_type, 0..=9) {
+            continue;
+        }
+
+        match entry.description {
+            PROGRAM_RUN => {
+                if let Some(mut run) = current.take() {
+                    run.end_time = entry.time;
+                    runs.push(run);
+                }
+                current = Some(Run::new(entry.code, entry.time, last_match.take()));
+            }
+            PROGRAM_STOP => {
+                if let Some(mut run) = current.take() {
+                    run.end_time = entry.time;
+                    run.end = RunEnd::Stopped;
+                    runs.push(run);
+                }
+            }
+            POWER => {
+                if let Some(mut run) = current.take() {
+                    // Turning the Brain off with its power button is a normal way to end a run,
+                    // but powering on or resetting while a program is running is not.
+                    run.end = if entry.code == POWER_OFF {
+                        RunEnd::Stopped
+                    } else {
+                        RunEnd::PowerLoss
+                    };
+                    runs.push(run);
+                }
+            }
+            _ => {
+                let Some(run) = current.as_mut() else {
+                    continue;
+                };
+                run.end_time = run.end_time.max(entry.time);
+
+                match entry.description {
+                    BATTERY => {
+                        let voltage = entry.code as f32 * 0.064;
+                        run.min_voltage =
+                            Some(run.min_voltage.map_or(voltage, |min| min.min(voltage)));
+                        run.min_capacity = Some(
+                            run.min_capacity
+                                .map_or(entry.spare, |min| min.min(entry.spare)),
+                        );
+                    }
+                    LOW_BATTERY => run.low_battery_events += 1,
+                    BATTERY_ERROR => run.battery_errors += 1,
+                    MOTOR_OVER_CURRENT => run.over_current_events += 1,
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    runs.extend(current);
+    runs
+}
+
+fn format_time(ms: u32) -> String {
+    let time = ms / 1000;
+    format!(
+        "{:02}:{:02}:{:02}",
+        (time / 3600) % 24,
+        (time / 60) % 60,
+        time % 60
+    )
+}
+
+/// Reads the entire event log and reports program runs that show signs of a brownout.
+pub async fn brownouts(connection: &mut DeviceConnection) -> Result<(), CliError> {
+    let entries = read_all_entries(connection).await?;
+    let runs = collect_runs(&entries);
+
+    if runs.is_empty() {
+        eprintln!("No program runs were found in the event log.");
+        return Ok(());
+    }
+
+    let mut tw = TabWriter::new(io::stdout());
+    writeln!(
+        &mut tw,
+        "\x1b[1mStart\tSlot\tMatch\tDuration\tMin Battery\tOver Current\tResult\x1b[0m"
+    )?;
+
+    let mut suspicious = 0;
+    for run in &runs {
+        let reasons = run.brownout_reasons();
+
+        writeln!(
+            &mut tw,
+            "{}\t{}\t{}\t{}s\t{}\t{}\t{}",
+            format_time(run.start),
+            run.slot,
+            run.match_label.as_deref().unwrap_or("-"),
+            run.end_time.saturating_sub(run.start) / 1000,
+            match (run.min_voltage, run.min_capacity) {
+                (Some(voltage), Some(capacity)) => format!("{voltage:.2}V ({capacity}%)"),
+                _ => "-".to_string(),
+            },
+            run.over_current_events,
+            if !reasons.is_empty() {
+                suspicious += 1;
+                format!("\x1b[1;91mLikely brownout\x1b[0m ({})", reasons.join(", "))
+            } else {
+                match run.end {
+                    RunEnd::Stopped => "\x1b[32mOK\x1b[0m",
+                    RunEnd::PowerLoss => unreachable!(),
+                    RunEnd::Unknown => "\x1b[33mNo end recorded\x1b[0m",
+                }
+                .to_string()
+            }
+        )?;
+    }
+
+    tw.flush()?;
+
+    println!();
+    if suspicious == 0 {
+        println!(
+            "\x1b[1;92mNo likely brownouts\x1b[0m in {} program run(s).",
+            runs.len()
+        );
+    } else {
+        println!(
+            "\x1b[1;93m{suspicious} of {} program run(s)\x1b[0m show signs of a brownout.",
+            runs.len()
+        );
+        println!(
+            "Make sure batteries are fully charged before matches, replace batteries that sag below {BROWNOUT_VOLTAGE}V, and check the battery cable and port for damage."
+        );
+
+        if runs
+            .iter()
+            .any(|run| run.over_current_events > 0 && !run.brownout_reasons().is_empty())
+        {
+            println!(
+                "Some of these runs also had motors over their current limit, so reducing drivetrain current draw may help."
+            );
+        }
+    }
+
+    Ok(())
+}
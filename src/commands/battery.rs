@@ -0,0 +1,146 @@
+//! Battery diagnostics.
+//!
+//! VEXos reports the battery's charge directly, and its firmware version along with the other
+//! smart devices. The voltage isn't reported on its own, but the Brain logs it every so often, so
+//! the most recent reading is taken from the event log. Current and temperature are only readable
+//! from a running program and aren't shown.
+
+use std::{
+    io::{self, Write},
+    time::Duration,
+};
+
+use tabwriter::TabWriter;
+use tokio::time::interval;
+use vex_v5_serial::protocol::cdc2::system::DeviceType;
+
+use crate::{connection::DeviceConnection, errors::CliError};
+
+use super::{
+    analyze::BATTERY,
+    devices::{format_device_version, list_devices},
+    info::battery_level,
+    log::{log_count, read_newest_entries},
+};
+
+/// Number of the newest event log entries searched for a voltage reading.
+const VOLTAGE_SEARCH_DEPTH: u32 = 100;
+
+/// What the Brain reports about its battery.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatteryStatus {
+    /// Charge as a percentage.
+    pub charge: u32,
+    pub charging: bool,
+    /// Most recently logged voltage, if the newest log entries include one.
+    pub voltage: Option<f32>,
+    /// Firmware version of the battery, if it's connected.
+    pub firmware: Option<String>,
+}
+
+/// Levels below which battery readings are highlighted.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BatteryThresholds {
+    /// Charge as a percentage.
+    pub charge: u32,
+    /// Voltage in volts.
+    pub voltage: f32,
+}
+
+/// Reads the battery's status from the Brain.
+pub async fn battery_status(connection: &mut DeviceConnection) -> Result<BatteryStatus, CliError> {
+    let (charge, charging) = battery_level(connection).await?;
+
+    let depth = log_count(connection).await?.min(VOLTAGE_SEARCH_DEPTH);
+    let entries = read_newest_entries(connection, depth).await?;
+    let voltage = entries
+        .iter()
+        .rev()
+        .find(|entry| entry.description == BATTERY)
+        .map(|entry| entry.code as f32 * 0.064);
+
+    let firmware = list_devices(connection)
+        .await?
+        .devices
+        .into_iter()
+        .find(|device| device.device_type == DeviceType::Battery)
+        .map(|device| format_device_version(device.version, device.beta_version));
+
+    Ok(BatteryStatus {
+        charge,
+        charging,
+        voltage,
+        firmware,
+    })
+}
+
+/// Prints the battery's status, refreshing it every `watch` interval if one is given.
+pub async fn battery(
+    connection: &mut DeviceConnection,
+    watch: Option<Duration>,
+    thresholds: BatteryThresholds,
+) -> Result<(), CliError> {
+    let Some(period) = watch else {
+        let status = battery_status(connection).await?;
+        io::stdout().write_all(&format_status(&status, thresholds)?)?;
+        return Ok(());
+    };
+
+    let mut refresh = interval(period);
+    loop {
+        refresh.tick().await;
+        let status = battery_status(connection).await?;
+
+        let mut stdout = io::stdout().lock();
+        // Clear the screen and move the cursor home before redrawing.
+        write!(stdout, "\x1b[2J\x1b[H")?;
+        stdout.write_all(&format_status(&status, thresholds)?)?;
+        stdout.flush()?;
+    }
+}
+
+fn format_status(status: &BatteryStatus, thresholds: BatteryThresholds) -> io::Result<Vec<u8>> {
+    let mut tw = TabWriter::new(Vec::new());
+
+    let charge = format!(
+        "{}%{}",
+        status.charge,
+        if status.charging { " (charging)" } else { "" }
+    );
+    writeln!(
+        tw,
+        "\x1b[1mCharge\x1b[0m\t{}",
+        highlight(charge, status.charge < thresholds.charge)
+    )?;
+
+    match status.voltage {
+        Some(voltage) => writeln!(
+            tw,
+            "\x1b[1mVoltage\x1b[0m\t{} \x1b[2m(last logged)\x1b[0m",
+            highlight(format!("{voltage:.2}V"), voltage < thresholds.voltage)
+        )?,
+        None => writeln!(
+            tw,
+            "\x1b[1mVoltage\x1b[0m\t\x1b[2mnot logged recently\x1b[0m"
+        )?,
+    }
+
+    match &status.firmware {
+        Some(firmware) => writeln!(tw, "\x1b[1mFirmware\x1b[0m\t{firmware}")?,
+        None => writeln!(
+            tw,
+            "\x1b[1mFirmware\x1b[0m\t\x1b[2mno battery connected\x1b[0m"
+        )?,
+    }
+
+    Ok(tw.into_inner().unwrap())
+}
+
+/// Colors `value` as a warning if `low` is set.
+fn highlight(value: String, low: bool) -> String {
+    if low {
+        format!("\x1b[1;93m{value}\x1b[0m")
+    } else {
+        value
+    }
+}
@@ -0,0 +1,187 @@
+//! Lists the largest symbols in a program, like `cargo bloat`, and compares them between builds.
+
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use humansize::{BINARY, format_size};
+use tabwriter::TabWriter;
+
+use super::size::{crate_name, symbol_sizes};
+use crate::errors::CliError;
+
+/// Longest symbol name printed before it's cut short.
+const MAX_NAME_LEN: usize = 120;
+
+/// Options for `cargo v5 bloat`.
+#[derive(Debug, Clone, Default)]
+pub struct BloatOpts {
+    /// An older build of the program to compare against.
+    pub compare: Option<PathBuf>,
+
+    /// List crates instead of individual symbols.
+    pub crates: bool,
+
+    /// Number of entries to list.
+    pub count: usize,
+}
+
+/// Prints the largest symbols (or crates) in the ELF at `elf_path`, or how they changed since the
+/// build given in `opts.compare`.
+pub fn bloat(elf_path: &Path, opts: BloatOpts) -> Result<(), CliError> {
+    let new = entries(elf_path, opts.crates)?;
+
+    match &opts.compare {
+        Some(old_path) => print_changes(&entries(old_path, opts.crates)?, &new, &opts),
+        None => print_sizes(&new, &opts),
+    }
+}
+
+/// Reads the size of every symbol or crate in an ELF, largest first.
+fn entries(path: &Path, crates: bool) -> Result<Vec<(String, u64)>, CliError> {
+    let data = fs_err::read(path)?;
+    let symbols = symbol_sizes(&object::File::parse(data.as_slice())?);
+
+    if !crates {
+        return Ok(symbols);
+    }
+
+    let mut sizes = HashMap::<String, u64>::new();
+    for (name, size) in symbols {
+        *sizes.entry(crate_name(&name).to_string()).or_default() += size;
+    }
+
+    let mut sizes = sizes.into_iter().collect::<Vec<_>>();
+    sizes.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    Ok(sizes)
+}
+
+fn truncate(name: &str) -> String {
+    match name.char_indices().nth(MAX_NAME_LEN) {
+        Some((end, _)) => format!("{}...", &name[..end]),
+        None => name.to_string(),
+    }
+}
+
+fn print_sizes(entries: &[(String, u64)], opts: &BloatOpts) -> Result<(), CliError> {
+    let total = entries.iter().map(|(_, size)| size).sum::<u64>();
+    let share = |size: u64| size as f64 / total.max(1) as f64 * 100.0;
+
+    let mut tw = TabWriter::new(io::stdout());
+    if opts.crates {
+        writeln!(tw, "\x1b[1mSize\tShare\tCrate\x1b[0m")?;
+    } else {
+        writeln!(tw, "\x1b[1mSize\tShare\tCrate\tName\x1b[0m")?;
+    }
+
+    for (name, size) in entries.iter().take(opts.count) {
+        write!(tw, "{}\t{:.1}%\t", format_size(*size, BINARY), share(*size))?;
+        if opts.crates {
+            writeln!(tw, "{name}")?;
+        } else {
+            writeln!(tw, "{}\t{}", crate_name(name), truncate(name))?;
+        }
+    }
+
+    let rest = &entries[entries.len().min(opts.count)..];
+    if !rest.is_empty() {
+        let size = rest.iter().map(|(_, size)| size).sum::<u64>();
+        writeln!(
+            tw,
+            "{}\t{:.1}%\t{} more",
+            format_size(size, BINARY),
+            share(size),
+            rest.len()
+        )?;
+    }
+    writeln!(
+        tw,
+        "\x1b[1m{}\x1b[0m\t\t\x1b[1mTotal\x1b[0m",
+        format_size(total, BINARY)
+    )?;
+
+    tw.flush()?;
+    Ok(())
+}
+
+/// Formats a change in size with its sign, colored red for growth and green for shrinkage.
+fn format_delta(delta: i64) -> String {
+    let size = format_size(delta.unsigned_abs(), BINARY);
+    match delta.signum() {
+        1 => format!("\x1b[91m+{size}\x1b[0m"),
+        -1 => format!("\x1b[92m-{size}\x1b[0m"),
+        _ => size,
+    }
+}
+
+fn print_changes(
+    old: &[(String, u64)],
+    new: &[(String, u64)],
+    opts: &BloatOpts,
+) -> Result<(), CliError> {
+    let old_sizes = old.iter().cloned().collect::<HashMap<_, _>>();
+    let new_sizes = new.iter().cloned().collect::<HashMap<_, _>>();
+
+    let mut changes = old_sizes
+        .keys()
+        .chain(
+            new_sizes
+                .keys()
+                .filter(|name| !old_sizes.contains_key(*name)),
+        )
+        .map(|name| {
+            let old = old_sizes.get(name).copied();
+            let new = new_sizes.get(name).copied();
+            let delta = new.unwrap_or(0) as i64 - old.unwrap_or(0) as i64;
+            (name, old, new, delta)
+        })
+        .filter(|(_, _, _, delta)| *delta != 0)
+        .collect::<Vec<_>>();
+    changes.sort_by(|a, b| {
+        b.3.unsigned_abs()
+            .cmp(&a.3.unsigned_abs())
+            .then_with(|| a.0.cmp(b.0))
+    });
+
+    let size_or_dash =
+        |size: Option<u64>| size.map_or("-".to_string(), |size| format_size(size, BINARY));
+
+    let mut tw = TabWriter::new(io::stdout());
+    writeln!(
+        tw,
+        "\x1b[1mOld\tNew\tChange\t{}\x1b[0m",
+        if opts.crates { "Crate" } else { "Name" }
+    )?;
+
+    for (name, old, new, delta) in changes.iter().take(opts.count) {
+        writeln!(
+            tw,
+            "{}\t{}\t{}\t{}",
+            size_or_dash(*old),
+            size_or_dash(*new),
+            format_delta(*delta),
+            truncate(name)
+        )?;
+    }
+
+    if changes.len() > opts.count {
+        writeln!(tw, "\t\t\t{} more changed", changes.len() - opts.count)?;
+    } else if changes.is_empty() {
+        writeln!(tw, "\t\t\tNothing changed")?;
+    }
+
+    let old_total = old.iter().map(|(_, size)| size).sum::<u64>();
+    let new_total = new.iter().map(|(_, size)| size).sum::<u64>();
+    writeln!(
+        tw,
+        "\x1b[1m{}\t{}\x1b[0m\t{}\t\x1b[1mTotal\x1b[0m",
+        format_size(old_total, BINARY),
+        format_size(new_total, BINARY),
+        format_delta(new_total as i64 - old_total as i64)
+    )?;
+
+    tw.flush()?;
+    Ok(())
+}
@@ -0,0 +1,175 @@
+//! Republishes a program's output to WebSocket clients, for feeding external dashboards.
+//!
+//! Every line the program prints becomes one JSON text message. Lines that are themselves JSON
+//! objects or arrays are forwarded as structured `data`, so a program can emit telemetry with
+//! something like `println!(r#"{{"heading": {heading}}}"#)`. Anything else is sent as a plain
+//! `line`.
+//!
+//! Web pages can open WebSockets to any address, so clients from pages whose origin wasn't
+//! allowed with `--allow-origin` are refused. Clients outside a browser don't send an origin and
+//! are always accepted.
+
+use std::{
+    io,
+    net::SocketAddr,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde_json::{Value, json};
+use tokio::{
+    io::AsyncWriteExt,
+    net::{TcpListener, TcpStream},
+    select,
+    sync::{broadcast, mpsc},
+};
+use vex_v5_serial::Connection;
+
+use crate::{connection::DeviceConnection, errors::CliError};
+
+use super::{
+    terminal::TerminalHub,
+    websocket::{
+        HandshakeRequest, OPCODE_CLOSE, OPCODE_PING, OPCODE_PONG, OPCODE_TEXT, accept, frame,
+        read_frame,
+    },
+};
+
+/// Reads a program's output and publishes it to WebSocket clients connecting to `addr` until the
+/// command is interrupted. Web pages can only connect if their origin is in `allowed_origins`.
+///
+/// Program output is also shared with `cargo v5 terminal`, which can attach while the bridge is
+/// running.
+pub async fn bridge(
+    connection: &mut DeviceConnection,
+    addr: SocketAddr,
+    allowed_origins: Vec<String>,
+) -> Result<(), CliError> {
+    let listener = TcpListener::bind(addr).await?;
+    let (messages, _) = broadcast::channel::<String>(256);
+    let allowed_origins = Arc::<[String]>::from(allowed_origins);
+
+    tokio::spawn({
+        let messages = messages.clone();
+        async move {
+            while let Ok((client, peer)) = listener.accept().await {
+                let messages = messages.subscribe();
+                let allowed_origins = allowed_origins.clone();
+                tokio::spawn(async move {
+                    if let Err(err) = serve_client(client, &allowed_origins, messages).await {
+                        log::debug!("WebSocket client {peer} disconnected: {err}");
+                    }
+                });
+            }
+        }
+    });
+
+    eprintln!("    \x1b[1;92mBridging\x1b[0m program output to ws://{addr}");
+
+    let mut hub = TerminalHub::bind().await;
+    let mut output = [0; 2048];
+    let mut line = Vec::new();
+
+    loop {
+        let size = connection.read_user(&mut output).await?;
+        let data = &output[..size];
+
+        if let Some(hub) = &mut hub {
+            hub.publish(data);
+
+            while let Some(input) = hub.try_recv_input() {
+                connection.write_user(&input).await?;
+            }
+        }
+
+        for &byte in data {
+            if byte != b'\n' {
+                line.push(byte);
+                continue;
+            }
+
+            let text = String::from_utf8_lossy(&line)
+                .trim_end_matches('\r')
+                .to_string();
+            line.clear();
+
+            // Sending only fails if no clients are connected.
+            _ = messages.send(telemetry_message(&text).to_string());
+        }
+    }
+}
+
+/// Wraps a line of program output in the JSON message sent to clients.
+fn telemetry_message(line: &str) -> Value {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_millis() as u64)
+        .unwrap_or_default();
+
+    match serde_json::from_str::<Value>(line) {
+        Ok(data @ (Value::Object(_) | Value::Array(_))) => json!({
+            "timestamp": timestamp,
+            "data": data,
+        }),
+        _ => json!({
+            "timestamp": timestamp,
+            "line": line,
+        }),
+    }
+}
+
+/// Completes a client's WebSocket handshake, then sends it messages until it disconnects.
+async fn serve_client(
+    client: TcpStream,
+    allowed_origins: &[String],
+    mut messages: broadcast::Receiver<String>,
+) -> io::Result<()> {
+    let allowed = |request: &HandshakeRequest| {
+        request
+            .origin
+            .as_ref()
+            .is_none_or(|origin| allowed_origins.contains(origin))
+    };
+    let Some((mut reader, mut writer)) = accept(client, allowed).await? else {
+        return Ok(());
+    };
+
+    // Reads aren't cancel-safe, so client frames are read on their own task and only the control
+    // frames that need a reply are passed back.
+    let (control_tx, mut control) = mpsc::channel(8);
+    tokio::spawn(async move {
+        while let Ok((opcode, payload)) = read_frame(&mut reader).await {
+            let close = opcode == OPCODE_CLOSE;
+            if matches!(opcode, OPCODE_CLOSE | OPCODE_PING)
+                && control_tx.send((opcode, payload)).await.is_err()
+            {
+                break;
+            }
+            if close {
+                break;
+            }
+        }
+    });
+
+    loop {
+        select! {
+            message = messages.recv() => match message {
+                Ok(message) => writer.write_all(&frame(OPCODE_TEXT, message.as_bytes())).await?,
+                // A slow client misses some messages, but stays connected.
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return Ok(()),
+            },
+            control = control.recv() => match control {
+                Some((OPCODE_PING, payload)) => {
+                    writer.write_all(&frame(OPCODE_PONG, &payload)).await?;
+                }
+                Some((_, payload)) => {
+                    writer.write_all(&frame(OPCODE_CLOSE, &payload)).await?;
+                    return Ok(());
+                }
+                // The client went away without closing the connection.
+                None => return Ok(()),
+            },
+        }
+    }
+}
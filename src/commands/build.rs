@@ -1,27 +1,62 @@
 use cargo_metadata::{Message, PackageId};
 use clap::Args;
-use object::{Object, ObjectSection, ObjectSegment};
+use object::{Architecture, Object, ObjectSection, ObjectSegment};
 use std::{
     ffi::OsStr,
     path::{Path, PathBuf},
-    process::{Stdio, exit},
+    process::Stdio,
 };
 use tokio::{process::Command, task::block_in_place};
+use vex_v5_serial::commands::file::USER_PROGRAM_LOAD_ADDR;
 
-use crate::errors::CliError;
+use super::migrate;
+use crate::{
+    build_info::{BuildInfo, git_revision, write_build_info},
+    bundle::{BLOB_FILE_NAME, BUNDLE_ENV_VAR, BundleStorage, LINKED_FILES_ENV_VAR, write_bundle},
+    errors::CliError,
+    metadata::{Metadata, manifest_dir, manifest_path},
+    provenance::{remove_stale_provenance, write_provenance},
+    reporter::Reporter,
+};
 
 /// Common Cargo options to forward.
-#[derive(Args, Debug)]
+#[derive(Args, Debug, Clone, Default)]
 pub struct CargoOpts {
+    /// Build in release mode, with optimizations.
+    #[arg(short, long)]
+    pub release: bool,
+
+    /// Comma-separated list of features to activate.
+    #[arg(short = 'F', long, value_delimiter = ',')]
+    pub features: Vec<String>,
+
+    /// Do not activate the `default` feature.
+    #[arg(long)]
+    pub no_default_features: bool,
+
+    /// Package to build.
+    #[arg(short, long, value_name = "SPEC")]
+    pub package: Option<String>,
+
+    /// Write a provenance manifest next to the built artifact.
+    ///
+    /// The manifest records the artifact's SHA-256, the git commit, and the toolchain versions
+    /// used. Later uploads of the artifact are checked against it.
+    #[arg(long)]
+    pub provenance: bool,
+
     /// Arguments forwarded to cargo.
     #[arg(
         trailing_var_arg = true,
         allow_hyphen_values = true,
         value_name = "CARGO-OPTIONS"
     )]
-    args: Vec<String>,
+    pub args: Vec<String>,
 }
 
+/// Target that programs are built for unless another is passed to cargo.
+pub const DEFAULT_TARGET: &str = "armv7a-vex-v5";
+
 pub fn cargo_bin() -> std::ffi::OsString {
     std::env::var_os("CARGO").unwrap_or_else(|| "cargo".to_owned().into())
 }
@@ -37,25 +72,94 @@ async fn is_supported_release_channel(cargo_bin: &OsStr) -> bool {
 }
 
 pub struct BuildOutput {
+    /// Name of the Cargo target that was built.
+    pub target: String,
     pub elf_artifact: PathBuf,
     pub bin_artifact: PathBuf,
     pub package_id: PackageId,
 }
 
-pub async fn build(path: &Path, opts: CargoOpts) -> Result<Option<BuildOutput>, CliError> {
-    let cargo = cargo_bin();
+pub async fn build(
+    path: &Path,
+    opts: CargoOpts,
+    reporter: &dyn Reporter,
+) -> Result<Option<BuildOutput>, CliError> {
+    Ok(cargo_build(path, opts, false, reporter).await?.pop())
+}
+
+/// Builds the project's test harnesses without running them, like `cargo test --no-run`.
+///
+/// Each test target (unit tests of the library and binaries, and each integration test) is a
+/// separate harness program.
+pub async fn build_tests(
+    path: &Path,
+    opts: CargoOpts,
+    reporter: &dyn Reporter,
+) -> Result<Vec<BuildOutput>, CliError> {
+    cargo_build(path, opts, true, reporter).await
+}
+
+/// Runs `cargo check`, or `cargo clippy` if `clippy` is set, with the same target and flags as
+/// [`build`], so the project isn't checked for the host by mistake.
+pub async fn check(
+    path: &Path,
+    opts: CargoOpts,
+    clippy: bool,
+    reporter: &dyn Reporter,
+) -> Result<(), CliError> {
+    let manifest_path = manifest_path(path);
+    prepare_project(&manifest_path, reporter).await?;
+
+    let subcommand = if clippy { "clippy" } else { "check" };
+    let mut check_cmd =
+        Command::from(cargo_command(&[subcommand], &manifest_path, &opts, reporter).await?);
+
+    let status = check_cmd.status().await?;
+    if !status.success() {
+        return Err(CliError::BuildFailed(status.code().unwrap_or(1)));
+    }
 
-    if !is_supported_release_channel(&cargo).await {
+    Ok(())
+}
+
+/// Makes sure the project at `manifest_path` can be built for the Brain, offering to migrate
+/// projects made for older vexide versions.
+async fn prepare_project(manifest_path: &Path, reporter: &dyn Reporter) -> Result<(), CliError> {
+    if !is_supported_release_channel(&cargo_bin()).await {
         return Err(CliError::UnsupportedReleaseChannel)?;
     }
 
-    let mut build_cmd = std::process::Command::new(cargo);
-    build_cmd
-        .current_dir(path)
-        .stdout(Stdio::piped())
-        .arg("build")
-        .arg("--message-format")
-        .arg("json-render-diagnostics");
+    // Projects made for older vexide versions fail with confusing linker errors, so offer to fix
+    // them up front. Non-interactive runs only get the warning.
+    if let Some(reason) = migrate::migration_needed(manifest_dir(manifest_path)).await {
+        reporter.warning(&reason);
+
+        if !migrate::offer_migration(manifest_dir(manifest_path)).await? {
+            reporter.note("Run `cargo v5 migrate` to update your project.");
+        }
+    }
+
+    Ok(())
+}
+
+/// Creates a command running Cargo with `args` on the project at `manifest_path`, targeting the
+/// Brain unless another target is passed and forwarding `opts`.
+pub async fn cargo_command(
+    args: &[&str],
+    manifest_path: &Path,
+    opts: &CargoOpts,
+    reporter: &dyn Reporter,
+) -> Result<std::process::Command, CliError> {
+    let mut cmd = std::process::Command::new(cargo_bin());
+    cmd.current_dir(manifest_dir(manifest_path)).args(args);
+
+    if !opts
+        .args
+        .iter()
+        .any(|arg| arg == "--manifest-path" || arg.starts_with("--manifest-path="))
+    {
+        cmd.arg("--manifest-path").arg(manifest_path);
+    }
 
     let mut explicit_target_specified = false;
     for arg in &opts.args {
@@ -66,19 +170,89 @@ pub async fn build(path: &Path, opts: CargoOpts) -> Result<Option<BuildOutput>,
     }
 
     if !explicit_target_specified {
-        build_cmd.arg("--target").arg("armv7a-vex-v5");
+        cmd.arg("--target").arg(DEFAULT_TARGET);
+    }
+
+    if opts.release {
+        cmd.arg("--release");
+    }
+    if !opts.features.is_empty() {
+        cmd.arg("--features").arg(opts.features.join(","));
     }
+    if opts.no_default_features {
+        cmd.arg("--no-default-features");
+    }
+    if let Some(package) = &opts.package {
+        cmd.arg("--package").arg(package);
+    }
+
+    cmd.args(&opts.args);
+
+    // Pack any assets listed in `package.metadata.v5.bundle` and tell the program where to find them.
+    if let Some(module) = prepare_bundle(manifest_path, opts.package.as_deref(), reporter).await? {
+        cmd.env(BUNDLE_ENV_VAR, module);
+    }
+    if let Some(linked) =
+        prepare_linked_files(manifest_path, opts.package.as_deref(), reporter).await?
+    {
+        cmd.env(LINKED_FILES_ENV_VAR, linked.module);
+    }
+
+    Ok(cmd)
+}
+
+/// Runs `cargo build`, or `cargo test --no-run` if `tests` is set, converting every executable
+/// that's built to a BIN.
+async fn cargo_build(
+    path: &Path,
+    opts: CargoOpts,
+    tests: bool,
+    reporter: &dyn Reporter,
+) -> Result<Vec<BuildOutput>, CliError> {
+    let manifest_path = manifest_path(path);
+    prepare_project(&manifest_path, reporter).await?;
 
-    build_cmd.args(opts.args);
+    let args: &[&str] = if tests {
+        &[
+            "test",
+            "--no-run",
+            "--message-format",
+            "json-render-diagnostics",
+        ]
+    } else {
+        &["build", "--message-format", "json-render-diagnostics"]
+    };
+    let mut build_cmd = cargo_command(args, &manifest_path, &opts, reporter).await?;
+    build_cmd.stdout(Stdio::piped());
+
+    // Recorded in the provenance manifest.
+    let provenance_build = opts.provenance.then(|| {
+        serde_json::json!({
+            "release": opts.release,
+            "features": opts.features,
+            "no_default_features": opts.no_default_features,
+            "args": opts.args,
+        })
+    });
+
+    block_in_place::<_, Result<Vec<BuildOutput>, CliError>>(|| {
+        // Recorded in each artifact's build manifest.
+        let packages = cargo_metadata::MetadataCommand::new()
+            .manifest_path(&manifest_path)
+            .no_deps()
+            .exec()
+            .map(|metadata| metadata.packages)
+            .unwrap_or_default();
+        let git = git_revision(manifest_dir(&manifest_path));
 
-    block_in_place::<_, Result<Option<BuildOutput>, CliError>>(|| {
         let mut out = build_cmd.spawn()?;
         let reader = std::io::BufReader::new(out.stdout.take().unwrap());
 
-        let mut output = None;
+        let mut outputs = Vec::new();
 
         for message in Message::parse_stream(reader) {
             if let Message::CompilerArtifact(artifact) = message?
+                && artifact.profile.test == tests
                 && let Some(elf_artifact_path) = artifact.executable
             {
                 let binary = objcopy(&std::fs::read(&elf_artifact_path)?)?;
@@ -86,9 +260,37 @@ pub async fn build(path: &Path, opts: CargoOpts) -> Result<Option<BuildOutput>,
 
                 // Write the binary to a file.
                 std::fs::write(&binary_path, binary)?;
-                eprintln!("     \x1b[1;92mObjcopy\x1b[0m {binary_path}");
+                reporter.status("Objcopy", binary_path.as_str());
 
-                output = Some(BuildOutput {
+                if let Some(package) = packages.iter().find(|p| p.id == artifact.package_id) {
+                    write_build_info(
+                        binary_path.as_std_path(),
+                        &BuildInfo {
+                            package_id: artifact.package_id.repr.clone(),
+                            name: package.name.to_string(),
+                            version: package.version.to_string(),
+                            description: package.description.clone(),
+                            features: artifact.features.clone(),
+                            profile: profile_dir_name(elf_artifact_path.as_std_path()),
+                            git: git.clone(),
+                        },
+                    )?;
+                }
+
+                if let Some(provenance_build) = &provenance_build {
+                    let manifest = write_provenance(
+                        manifest_dir(&manifest_path),
+                        elf_artifact_path.as_std_path(),
+                        binary_path.as_std_path(),
+                        provenance_build.clone(),
+                    )?;
+                    reporter.status("Provenance", &manifest.display().to_string());
+                } else {
+                    remove_stale_provenance(binary_path.as_std_path())?;
+                }
+
+                outputs.push(BuildOutput {
+                    target: artifact.target.name.to_string(),
                     bin_artifact: binary_path.into_std_path_buf(),
                     elf_artifact: elf_artifact_path.into_std_path_buf(),
                     package_id: artifact.package_id,
@@ -98,17 +300,145 @@ pub async fn build(path: &Path, opts: CargoOpts) -> Result<Option<BuildOutput>,
 
         let status = out.wait()?;
         if !status.success() {
-            exit(status.code().unwrap_or(1));
+            return Err(CliError::BuildFailed(status.code().unwrap_or(1)));
         }
 
-        Ok(output)
+        Ok(outputs)
+    })
+}
+
+/// Name of the profile directory `artifact` was built into, like `debug` or `release`.
+fn profile_dir_name(artifact: &Path) -> String {
+    artifact
+        .ancestors()
+        .skip(1)
+        .filter_map(|dir| dir.file_name())
+        .find(|name| *name != "deps" && *name != "examples")
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string()
+}
+
+/// Finds the package named `package` in the workspace at `manifest_path`, defaulting to the root
+/// package, along with the workspace's target directory.
+fn workspace_package(
+    manifest_path: &Path,
+    package: Option<&str>,
+) -> Option<(cargo_metadata::Package, PathBuf)> {
+    let metadata = block_in_place(|| {
+        cargo_metadata::MetadataCommand::new()
+            .manifest_path(manifest_path)
+            .no_deps()
+            .exec()
     })
+    .ok()?;
+
+    let package = match package {
+        Some(spec) => metadata.packages.iter().find(|p| p.name.as_str() == spec),
+        None => metadata.root_package(),
+    }?;
+
+    Some((
+        package.clone(),
+        metadata.target_directory.into_std_path_buf(),
+    ))
+}
+
+/// Packs the assets listed in the package's `package.metadata.v5.bundle` field, returning the
+/// path of the generated module, or `None` if the package doesn't bundle any assets.
+///
+/// `package` selects a package in the workspace by name, defaulting to the root package.
+pub async fn prepare_bundle(
+    manifest_path: &Path,
+    package: Option<&str>,
+    reporter: &dyn Reporter,
+) -> Result<Option<PathBuf>, CliError> {
+    let Some((package, target_dir)) = workspace_package(manifest_path, package) else {
+        return Ok(None);
+    };
+
+    let v5_metadata = Metadata::new(&package)?;
+    if v5_metadata.bundle.is_empty() {
+        return Ok(None);
+    }
+
+    write_bundle(
+        manifest_dir(manifest_path),
+        &v5_metadata.bundle,
+        &target_dir.join("v5-bundle").join(package.name.to_string()),
+        BundleStorage::Embedded,
+        reporter,
+    )
+    .await
+    .map(Some)
+}
+
+/// Assets packed from `package.metadata.v5.linked-files`, to be uploaded as a program's linked
+/// file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinkedFiles {
+    /// The generated module describing the linked file.
+    pub module: PathBuf,
+    /// The packed linked file.
+    pub blob: PathBuf,
+    /// Where VEXos loads the linked file.
+    pub load_address: u32,
+}
+
+/// Packs the assets listed in the package's `package.metadata.v5.linked-files` table, or returns
+/// `None` if the package doesn't link any.
+///
+/// `package` selects a package in the workspace by name, defaulting to the root package.
+pub async fn prepare_linked_files(
+    manifest_path: &Path,
+    package: Option<&str>,
+    reporter: &dyn Reporter,
+) -> Result<Option<LinkedFiles>, CliError> {
+    let Some((package, target_dir)) = workspace_package(manifest_path, package) else {
+        return Ok(None);
+    };
+
+    let Some(config) = Metadata::new(&package)?.linked_files else {
+        return Ok(None);
+    };
+    if config.files.is_empty() {
+        return Ok(None);
+    }
+
+    let out_dir = target_dir.join("v5-linked").join(package.name.to_string());
+    let module = write_bundle(
+        manifest_dir(manifest_path),
+        &config.files,
+        &out_dir,
+        BundleStorage::Linked {
+            load_address: config.load_address,
+        },
+        reporter,
+    )
+    .await?;
+
+    Ok(Some(LinkedFiles {
+        module,
+        blob: out_dir.join(BLOB_FILE_NAME),
+        load_address: config.load_address,
+    }))
 }
 
 /// Implementation of `objcopy -O binary`.
 pub fn objcopy(elf: &[u8]) -> Result<Vec<u8>, CliError> {
     let elf = object::File::parse(elf)?; // parse ELF file
 
+    // Refuse to convert binaries that weren't built for the Brain's Cortex-A9. Uploading
+    // a host-architecture ELF would otherwise "succeed" and leave an unrunnable program
+    // in the slot.
+    if elf.architecture() != Architecture::Arm || elf.is_64() {
+        return Err(CliError::IncompatibleArtifact(format!(
+            "{:?}{}",
+            elf.architecture(),
+            if elf.is_64() { " (64-bit)" } else { "" }
+        )));
+    }
+
     // First we need to find the loadable sections of the program
     // (the parts of the ELF that will be actually loaded into memory)
     let mut loadable_sections = elf
@@ -150,6 +480,18 @@ pub fn objcopy(elf: &[u8]) -> Result<Vec<u8>, CliError> {
         last_section.address() + last_section.size()
     };
 
+    if start_address != USER_PROGRAM_LOAD_ADDR as u64 {
+        log::warn!(
+            "Program is linked at {start_address:#x}, but user programs are loaded at {USER_PROGRAM_LOAD_ADDR:#x}. It will likely crash when run."
+        );
+    }
+    if !(start_address..end_address).contains(&elf.entry()) {
+        log::warn!(
+            "Program entrypoint ({:#x}) lies outside of the loaded binary ({start_address:#x}..{end_address:#x}).",
+            elf.entry()
+        );
+    }
+
     // Pre-fill the binary with zeroes for the specified binary length
     // (determined by start address of first and end address of last loadable
     // sections respectively).
@@ -1,4 +1,7 @@
-use std::{path::PathBuf, str::FromStr};
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
 use tokio::io::{AsyncWriteExt, stdout};
 use vex_v5_serial::{
@@ -8,10 +11,10 @@ use vex_v5_serial::{
         FixedString,
         cdc2::file::{FileTransferTarget, FileVendor},
     },
-    serial::{SerialConnection, SerialError},
+    serial::SerialError,
 };
 
-use crate::errors::CliError;
+use crate::{connection::DeviceConnection, errors::CliError};
 
 pub fn vendor_from_prefix(prefix: &str) -> FileVendor {
     match prefix {
@@ -29,7 +32,25 @@ pub fn vendor_from_prefix(prefix: &str) -> FileVendor {
     }
 }
 
-pub async fn cat(connection: &mut SerialConnection, file: PathBuf) -> Result<(), CliError> {
+/// Splits a remote path like `user/slot_1.ini` into its vendor and file name.
+///
+/// Paths without a vendor prefix are assumed to refer to user files.
+pub fn parse_remote_path(path: &Path) -> Result<(FileVendor, FixedString<23>), CliError> {
+    let vendor = match path.parent().and_then(|parent| parent.to_str()) {
+        Some("") | None => FileVendor::User,
+        Some(prefix) => vendor_from_prefix(prefix),
+    };
+
+    let file_name = FixedString::from_str(
+        path.file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or_default(),
+    )?;
+
+    Ok((vendor, file_name))
+}
+
+pub async fn cat(connection: &mut DeviceConnection, file: PathBuf) -> Result<(), CliError> {
     let vendor = if let Some(parent) = file.parent() {
         vendor_from_prefix(parent.to_str().unwrap())
     } else {
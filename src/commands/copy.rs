@@ -0,0 +1,141 @@
+use std::path::Path;
+
+use vex_v5_serial::{
+    Connection,
+    commands::file::{DownloadFile, UploadFile},
+    protocol::cdc2::file::{FileExitAction, FileTransferTarget},
+};
+
+use crate::{
+    connection::{DeviceConnection, switch_to_download_channel},
+    errors::CliError,
+    reporter::{Reporter, Task, TaskKind, progress_callback},
+};
+
+use super::{cat::parse_remote_path, rm::erase_file, upload::brain_file_metadata};
+
+/// Downloads a file and uploads it again under another name, keeping its type, version,
+/// timestamp, and load address.
+///
+/// Files linked to another one, like the program half of a differential upload, are refused.
+async fn copy_file(
+    connection: &mut DeviceConnection,
+    source: &Path,
+    destination: &Path,
+    force: bool,
+    reporter: &dyn Reporter,
+) -> Result<(), CliError> {
+    let (source_vendor, source_name) = parse_remote_path(source)?;
+    let (destination_vendor, destination_name) = parse_remote_path(destination)?;
+
+    if (source_vendor, &source_name) == (destination_vendor, &destination_name) {
+        return Err(CliError::RemoteFileExists(
+            destination.display().to_string(),
+        ));
+    }
+
+    switch_to_download_channel(connection).await?;
+
+    let metadata = brain_file_metadata(connection, source_name.clone(), source_vendor)
+        .await?
+        .ok_or_else(|| CliError::RemoteFileNotFound(source.display().to_string()))?;
+
+    // Only the vendor of a linked file is reported, not its name, so the link can't be recreated.
+    if metadata.linked_vendor.is_some() {
+        return Err(CliError::LinkedFileCopy(source.display().to_string()));
+    }
+
+    if !force
+        && brain_file_metadata(connection, destination_name.clone(), destination_vendor)
+            .await?
+            .is_some()
+    {
+        return Err(CliError::RemoteFileExists(
+            destination.display().to_string(),
+        ));
+    }
+
+    let progress = reporter.start_task(Task {
+        kind: TaskKind::Download,
+        name: source_name.as_str(),
+        color: "blue",
+    });
+    let data = connection
+        .execute_command(DownloadFile {
+            file_name: source_name,
+            size: metadata.size,
+            vendor: source_vendor,
+            target: FileTransferTarget::Qspi,
+            address: metadata.load_address,
+            progress_callback: Some(progress_callback(&progress)),
+        })
+        .await?;
+    progress.finish();
+
+    let progress = reporter.start_task(Task {
+        kind: TaskKind::Upload,
+        name: destination_name.as_str(),
+        color: "yellow",
+    });
+    connection
+        .execute_command(UploadFile {
+            file_name: destination_name,
+            metadata: metadata.metadata,
+            vendor: destination_vendor,
+            data: &data,
+            target: FileTransferTarget::Qspi,
+            load_address: metadata.load_address,
+            linked_file: None,
+            after_upload: FileExitAction::DoNothing,
+            progress_callback: Some(progress_callback(&progress)),
+        })
+        .await?;
+    progress.finish();
+
+    Ok(())
+}
+
+/// Copy a file in flash to another name, possibly under another vendor.
+///
+/// VEXos can't copy files itself, so the file is downloaded and uploaded again under its new
+/// name. Both paths may be prefixed with a vendor (e.g. `user/slot_1.bin`), and default to the
+/// user vendor otherwise.
+pub async fn cp(
+    connection: &mut DeviceConnection,
+    source: &Path,
+    destination: &Path,
+    force: bool,
+    reporter: &dyn Reporter,
+) -> Result<(), CliError> {
+    copy_file(connection, source, destination, force, reporter).await?;
+
+    reporter.status(
+        "Copied",
+        &format!("{} to {}", source.display(), destination.display()),
+    );
+
+    Ok(())
+}
+
+/// Rename a file in flash, possibly moving it to another vendor.
+///
+/// VEXos can't rename files, so the file is copied like [`cp`] does, then the original is erased.
+pub async fn mv(
+    connection: &mut DeviceConnection,
+    source: &Path,
+    destination: &Path,
+    force: bool,
+    reporter: &dyn Reporter,
+) -> Result<(), CliError> {
+    copy_file(connection, source, destination, force, reporter).await?;
+
+    let (vendor, file_name) = parse_remote_path(source)?;
+    erase_file(connection, vendor, file_name).await?;
+
+    reporter.status(
+        "Moved",
+        &format!("{} to {}", source.display(), destination.display()),
+    );
+
+    Ok(())
+}
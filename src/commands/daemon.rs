@@ -0,0 +1,556 @@
+//! A long-running server that holds the connection to a Brain open, so editors and scripts can
+//! control it without reconnecting for every command.
+//!
+//! Clients connect over WebSocket and send [JSON-RPC 2.0](https://www.jsonrpc.org/specification)
+//! requests as text messages:
+//!
+//! ```text
+//! --> {"jsonrpc": "2.0", "id": 1, "method": "upload", "params": {"slot": 2, "after": "run"}}
+//! <-- {"jsonrpc": "2.0", "method": "progress", "params": {"reason": "upload-progress", ...}}
+//! <-- {"jsonrpc": "2.0", "id": 1, "result": {"slot": 2, "name": "my-robot", "artifact": "..."}}
+//! ```
+//!
+//! The daemon understands these methods:
+//!
+//! - `upload`: builds and uploads a program. Takes `path`, `file`, `slot`, `name`, `release`,
+//!   `features`, `package`, and `after` (`"none"`, `"run"`, or `"screen"`), all optional.
+//! - `run`: runs the program in `slot`.
+//! - `stop`: stops the running program.
+//! - `screenshot`: captures the screen, returning a base64-encoded PNG with its `width` and
+//!   `height`.
+//! - `terminal.write`: sends `data` to the program's stdin.
+//!
+//! Clients must send the token the daemon writes to `daemon.token` in cargo-v5's runtime directory
+//! (`$XDG_RUNTIME_DIR/cargo-v5`, or `~/.config/cargo-v5`) as an `Authorization: Bearer <token>`
+//! header. Requests from web pages, which carry an `Origin` header, are refused, and `upload` only
+//! builds and uploads files inside the project the daemon was started in.
+//!
+//! Requests are handled one at a time, in the order they arrive. While a request runs, every
+//! client receives `progress` notifications with the same events as `--message-format json`.
+//! Program output is sent to every client as `terminal` notifications.
+
+use std::{
+    io::{self, Cursor},
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use image::ImageFormat;
+use miette::Diagnostic;
+use serde_json::{Map, Value, json};
+use tokio::{
+    io::AsyncWriteExt,
+    net::{TcpListener, TcpStream},
+    select,
+    sync::{broadcast, mpsc},
+    time::timeout,
+};
+use vex_v5_serial::Connection;
+
+use super::{
+    build::CargoOpts,
+    program::{run_slot, stop},
+    screenshot::capture_screen,
+    terminal::TerminalHub,
+    upload::{AfterUpload, UploadOpts, upload_with},
+    websocket::{
+        HandshakeRequest, OPCODE_CLOSE, OPCODE_PING, OPCODE_PONG, OPCODE_TEXT, accept, frame,
+        read_frame,
+    },
+};
+use crate::{
+    config::{runtime_dir, write_private_file},
+    connection::DeviceConnection,
+    errors::CliError,
+    interactive::set_non_interactive,
+    remote::{generate_token, token_matches},
+    reporter::JsonReporter,
+};
+
+/// Name of the file in the runtime directory holding the token clients authenticate with.
+pub const DAEMON_TOKEN_FILE: &str = "daemon.token";
+
+/// How long to wait for program output before checking for new requests.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A request from a client, passed to the task that owns the connection.
+struct Request {
+    id: Value,
+    method: String,
+    params: Map<String, Value>,
+    /// Where to send the response.
+    reply: mpsc::Sender<String>,
+}
+
+/// An error returned to a client in place of a result.
+struct RpcError {
+    code: i64,
+    message: String,
+    data: Option<Value>,
+}
+
+impl RpcError {
+    const PARSE_ERROR: i64 = -32700;
+    const INVALID_REQUEST: i64 = -32600;
+    const METHOD_NOT_FOUND: i64 = -32601;
+    const INVALID_PARAMS: i64 = -32602;
+    /// Returned when a method fails, with the diagnostic's code in `data.code`.
+    const COMMAND_FAILED: i64 = -32000;
+
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+
+    fn invalid_params(message: impl Into<String>) -> Self {
+        Self::new(Self::INVALID_PARAMS, message)
+    }
+
+    fn failed(err: &dyn Diagnostic) -> Self {
+        Self {
+            code: Self::COMMAND_FAILED,
+            message: err.to_string(),
+            data: Some(json!({
+                "code": err.code().map(|code| code.to_string()),
+                "help": err.help().map(|help| help.to_string()),
+            })),
+        }
+    }
+
+    fn response(self, id: Value) -> Value {
+        let mut error = json!({
+            "code": self.code,
+            "message": self.message,
+        });
+        if let Some(data) = self.data {
+            error["data"] = data;
+        }
+
+        json!({ "jsonrpc": "2.0", "id": id, "error": error })
+    }
+}
+
+impl From<CliError> for RpcError {
+    fn from(err: CliError) -> Self {
+        Self::failed(&err)
+    }
+}
+
+/// Typed access to a request's parameters.
+struct Params<'a>(&'a Map<String, Value>);
+
+impl Params<'_> {
+    fn string(&self, key: &str) -> Result<Option<String>, RpcError> {
+        match self.0.get(key) {
+            None | Some(Value::Null) => Ok(None),
+            Some(Value::String(value)) => Ok(Some(value.clone())),
+            Some(_) => Err(RpcError::invalid_params(format!(
+                "`{key}` should be a string"
+            ))),
+        }
+    }
+
+    fn bool(&self, key: &str) -> Result<bool, RpcError> {
+        match self.0.get(key) {
+            None | Some(Value::Null) => Ok(false),
+            Some(Value::Bool(value)) => Ok(*value),
+            Some(_) => Err(RpcError::invalid_params(format!(
+                "`{key}` should be a boolean"
+            ))),
+        }
+    }
+
+    fn slot(&self) -> Result<Option<u8>, RpcError> {
+        match self.0.get("slot") {
+            None | Some(Value::Null) => Ok(None),
+            Some(value) => value
+                .as_u64()
+                .and_then(|slot| u8::try_from(slot).ok())
+                .filter(|slot| (1..=8).contains(slot))
+                .map(Some)
+                .ok_or_else(|| RpcError::invalid_params("`slot` should be between 1 and 8")),
+        }
+    }
+
+    fn strings(&self, key: &str) -> Result<Vec<String>, RpcError> {
+        match self.0.get(key) {
+            None | Some(Value::Null) => Ok(Vec::new()),
+            Some(Value::Array(values)) => values
+                .iter()
+                .map(|value| value.as_str().map(str::to_string))
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(|| {
+                    RpcError::invalid_params(format!("`{key}` should be a list of strings"))
+                }),
+            Some(_) => Err(RpcError::invalid_params(format!(
+                "`{key}` should be a list of strings"
+            ))),
+        }
+    }
+}
+
+/// Serves the control API on `addr` until the command is interrupted.
+///
+/// `path` is the project that `upload` requests build when they don't name one. Program output is
+/// also shared with `cargo v5 terminal`, which can attach while the daemon is running.
+pub async fn daemon(
+    connection: &mut DeviceConnection,
+    path: &Path,
+    addr: SocketAddr,
+) -> Result<(), CliError> {
+    // There's nobody at the terminal to answer prompts.
+    set_non_interactive(true);
+
+    let listener = TcpListener::bind(addr).await?;
+    let (notifications, _) = broadcast::channel::<String>(256);
+    let (requests_tx, mut requests) = mpsc::channel::<Request>(16);
+
+    let token = generate_token();
+    match runtime_dir().map(|dir| dir.join(DAEMON_TOKEN_FILE)) {
+        Some(token_path) => {
+            write_private_file(&token_path, &token)?;
+            eprintln!(
+                "Clients authenticate with `Authorization: Bearer <token>`, using the token in {}.",
+                token_path.display()
+            );
+        }
+        None => eprintln!("Clients authenticate with `Authorization: Bearer {token}`."),
+    }
+
+    tokio::spawn({
+        let notifications = notifications.clone();
+        async move {
+            while let Ok((client, peer)) = listener.accept().await {
+                let notifications = notifications.subscribe();
+                let requests = requests_tx.clone();
+                let token = token.clone();
+                tokio::spawn(async move {
+                    if let Err(err) = serve_client(client, &token, notifications, requests).await {
+                        log::debug!("Daemon client {peer} disconnected: {err}");
+                    }
+                });
+            }
+        }
+    });
+
+    eprintln!("   \x1b[1;92mListening\x1b[0m for clients on ws://{addr}");
+
+    let mut hub = TerminalHub::bind().await;
+    let mut output = [0; 2048];
+
+    loop {
+        while let Ok(request) = requests.try_recv() {
+            log::debug!("Handling daemon request `{}`", request.method);
+
+            let response = match handle(connection, path, &notifications, &request).await {
+                Ok(result) => json!({ "jsonrpc": "2.0", "id": request.id, "result": result }),
+                Err(err) => err.response(request.id),
+            };
+
+            // The client may have disconnected while the request ran.
+            _ = request.reply.send(response.to_string()).await;
+        }
+
+        if let Some(hub) = &mut hub {
+            while let Some(input) = hub.try_recv_input() {
+                connection.write_user(&input).await?;
+            }
+        }
+
+        match timeout(POLL_INTERVAL, connection.read_user(&mut output)).await {
+            Ok(Ok(size)) if size > 0 => {
+                let data = &output[..size];
+                if let Some(hub) = &hub {
+                    hub.publish(data);
+                }
+
+                notify(
+                    &notifications,
+                    "terminal",
+                    json!({ "data": String::from_utf8_lossy(data) }),
+                );
+            }
+            Ok(Err(err)) => {
+                // Usually means no program is running.
+                log::trace!("Couldn't read program output: {err}");
+                tokio::time::sleep(POLL_INTERVAL).await;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Sends a JSON-RPC notification to every connected client.
+fn notify(notifications: &broadcast::Sender<String>, method: &str, params: Value) {
+    // Sending only fails if no clients are connected.
+    _ = notifications
+        .send(json!({ "jsonrpc": "2.0", "method": method, "params": params }).to_string());
+}
+
+/// Runs a request's method, returning its result.
+async fn handle(
+    connection: &mut DeviceConnection,
+    path: &Path,
+    notifications: &broadcast::Sender<String>,
+    request: &Request,
+) -> Result<Value, RpcError> {
+    let params = Params(&request.params);
+
+    match request.method.as_str() {
+        "upload" => {
+            let after = match params.string("after")?.as_deref() {
+                None | Some("none") => AfterUpload::None,
+                Some("run") => AfterUpload::Run,
+                Some("screen") => AfterUpload::ShowScreen,
+                Some(_) => {
+                    return Err(RpcError::invalid_params(
+                        "`after` should be \"none\", \"run\", or \"screen\"",
+                    ));
+                }
+            };
+            let file = params
+                .string("file")?
+                .map(|file| inside_project(path, &file, "file"))
+                .transpose()?;
+            let path = params
+                .string("path")?
+                .map(|requested| inside_project(path, &requested, "path"))
+                .transpose()?
+                .unwrap_or_else(|| path.to_path_buf());
+            let opts = UploadOpts {
+                slot: params.slot()?,
+                name: params.string("name")?,
+                file,
+                cargo_opts: CargoOpts {
+                    release: params.bool("release")?,
+                    features: params.strings("features")?,
+                    package: params.string("package")?,
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+
+            let reporter = JsonReporter::with_sink({
+                let notifications = notifications.clone();
+                move |message| notify(&notifications, "progress", message)
+            });
+            let outcome = upload_with(
+                &path,
+                opts,
+                after,
+                async { Ok(&mut *connection) },
+                &reporter,
+            )
+            .await
+            .map_err(|err| RpcError::failed(&*err))?;
+
+            Ok(json!({
+                "slot": outcome.slot,
+                "name": outcome.name,
+                "artifact": outcome.artifact,
+            }))
+        }
+        "run" => {
+            let slot = params
+                .slot()?
+                .ok_or_else(|| RpcError::invalid_params("missing `slot`"))?;
+            run_slot(connection, slot).await?;
+
+            Ok(Value::Null)
+        }
+        "stop" => {
+            stop(connection).await?;
+
+            Ok(Value::Null)
+        }
+        "screenshot" => {
+            let image = capture_screen(connection, None).await?;
+
+            let mut png = Cursor::new(Vec::new());
+            image
+                .write_to(&mut png, ImageFormat::Png)
+                .map_err(CliError::from)?;
+
+            Ok(json!({
+                "width": image.width(),
+                "height": image.height(),
+                "png": BASE64.encode(png.get_ref()),
+            }))
+        }
+        "terminal.write" => {
+            let data = params
+                .string("data")?
+                .ok_or_else(|| RpcError::invalid_params("missing `data`"))?;
+            connection
+                .write_user(data.as_bytes())
+                .await
+                .map_err(CliError::from)?;
+
+            Ok(Value::Null)
+        }
+        method => Err(RpcError::new(
+            RpcError::METHOD_NOT_FOUND,
+            format!("unknown method `{method}`"),
+        )),
+    }
+}
+
+/// Resolves a path sent by a client against the project the daemon was started in, refusing
+/// paths outside of it so that clients can't build or upload arbitrary code.
+fn inside_project(project: &Path, requested: &str, key: &str) -> Result<PathBuf, RpcError> {
+    // The project may be given as its `Cargo.toml`.
+    let root = match project.parent() {
+        Some(parent) if project.is_file() => parent,
+        _ => project,
+    };
+    let root = if root.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        root
+    };
+    let root = root.canonicalize().map_err(CliError::from)?;
+
+    let resolved = root
+        .join(requested)
+        .canonicalize()
+        .map_err(|_| RpcError::invalid_params(format!("`{key}` doesn't exist")))?;
+    if !resolved.starts_with(&root) {
+        return Err(RpcError::invalid_params(format!(
+            "`{key}` must be inside the project the daemon was started in"
+        )));
+    }
+
+    Ok(resolved)
+}
+
+/// Parses a text message from a client into a request.
+///
+/// Notifications (requests without an `id`) are answered like any other request, since every
+/// method has an effect worth confirming.
+fn parse_request(
+    message: &[u8],
+    reply: &mpsc::Sender<String>,
+) -> Result<Request, (Value, RpcError)> {
+    let message = serde_json::from_slice::<Value>(message).map_err(|err| {
+        (
+            Value::Null,
+            RpcError::new(RpcError::PARSE_ERROR, err.to_string()),
+        )
+    })?;
+
+    let id = message.get("id").cloned().unwrap_or(Value::Null);
+    let invalid = |message: &str| {
+        (
+            id.clone(),
+            RpcError::new(RpcError::INVALID_REQUEST, message),
+        )
+    };
+
+    let method = message
+        .get("method")
+        .and_then(Value::as_str)
+        .ok_or_else(|| invalid("missing `method`"))?
+        .to_string();
+    let params = match message.get("params") {
+        None | Some(Value::Null) => Map::new(),
+        Some(Value::Object(params)) => params.clone(),
+        Some(_) => return Err(invalid("`params` should be an object")),
+    };
+
+    Ok(Request {
+        id,
+        method,
+        params,
+        reply: reply.clone(),
+    })
+}
+
+/// Completes a client's WebSocket handshake if it presents `token` and doesn't come from a web
+/// page, then passes its requests on and sends it responses and notifications until it
+/// disconnects.
+async fn serve_client(
+    client: TcpStream,
+    token: &str,
+    mut notifications: broadcast::Receiver<String>,
+    requests: mpsc::Sender<Request>,
+) -> io::Result<()> {
+    let authorize = |request: &HandshakeRequest| {
+        request.origin.is_none()
+            && request
+                .authorization
+                .as_deref()
+                .and_then(|authorization| authorization.strip_prefix("Bearer "))
+                .is_some_and(|presented| token_matches(token, presented.trim()))
+    };
+    let Some((mut reader, mut writer)) = accept(client, authorize).await? else {
+        return Ok(());
+    };
+
+    // Reads aren't cancel-safe, so client frames are read on their own task. Requests go to the
+    // device, while control frames and errors are sent straight back.
+    let (outgoing_tx, mut outgoing) = mpsc::channel::<(u8, Vec<u8>)>(8);
+    let (responses_tx, mut responses) = mpsc::channel::<String>(8);
+    tokio::spawn(async move {
+        while let Ok((opcode, payload)) = read_frame(&mut reader).await {
+            match opcode {
+                OPCODE_TEXT => match parse_request(&payload, &responses_tx) {
+                    Ok(request) => {
+                        if requests.send(request).await.is_err() {
+                            break;
+                        }
+                    }
+                    Err((id, err)) => {
+                        let response = err.response(id).to_string();
+                        if outgoing_tx
+                            .send((OPCODE_TEXT, response.into_bytes()))
+                            .await
+                            .is_err()
+                        {
+                            break;
+                        }
+                    }
+                },
+                OPCODE_PING | OPCODE_CLOSE => {
+                    if outgoing_tx.send((opcode, payload)).await.is_err() || opcode == OPCODE_CLOSE
+                    {
+                        break;
+                    }
+                }
+                _ => {}
+            }
+        }
+    });
+
+    loop {
+        select! {
+            notification = notifications.recv() => match notification {
+                Ok(notification) => {
+                    writer.write_all(&frame(OPCODE_TEXT, notification.as_bytes())).await?;
+                }
+                // A slow client misses some notifications, but stays connected.
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return Ok(()),
+            },
+            Some(response) = responses.recv() => {
+                writer.write_all(&frame(OPCODE_TEXT, response.as_bytes())).await?;
+            }
+            outgoing = outgoing.recv() => match outgoing {
+                Some((OPCODE_PING, payload)) => {
+                    writer.write_all(&frame(OPCODE_PONG, &payload)).await?;
+                }
+                Some((OPCODE_CLOSE, payload)) => {
+                    writer.write_all(&frame(OPCODE_CLOSE, &payload)).await?;
+                    return Ok(());
+                }
+                Some((opcode, payload)) => writer.write_all(&frame(opcode, &payload)).await?,
+                // The client went away without closing the connection.
+                None => return Ok(()),
+            },
+        }
+    }
+}
@@ -0,0 +1,118 @@
+//! Building and uploading several configured programs at once.
+
+use std::path::Path;
+
+use inquire::Select;
+use tokio::task::block_in_place;
+
+use crate::{
+    connection::ConnectionOpts,
+    errors::CliError,
+    interactive::ensure_interactive,
+    metadata::{DeployProfile, Metadata, manifest_path},
+};
+
+use super::{
+    build::CargoOpts,
+    upload::{AfterUpload, UploadOpts, upload},
+};
+
+/// Which deploy profiles to upload.
+#[derive(Debug, Clone)]
+pub enum DeploySelection {
+    /// A single profile, by name.
+    Profile(String),
+
+    /// Every configured profile.
+    All,
+
+    /// The only configured profile, or one chosen interactively if there are several.
+    Prompt,
+}
+
+/// Builds and uploads the selected profiles from `package.metadata.v5.deploy`, one after another.
+pub async fn deploy(
+    path: &Path,
+    selection: DeploySelection,
+    cargo_opts: CargoOpts,
+    after: AfterUpload,
+    connection_opts: &ConnectionOpts,
+) -> miette::Result<()> {
+    let manifest_path = manifest_path(path);
+    let cargo_metadata = block_in_place(|| {
+        cargo_metadata::MetadataCommand::new()
+            .manifest_path(&manifest_path)
+            .no_deps()
+            .exec()
+    })
+    .map_err(|_| CliError::NoDeployProfiles)?;
+
+    let package = match &cargo_opts.package {
+        Some(spec) => cargo_metadata
+            .packages
+            .iter()
+            .find(|p| p.name.as_str() == spec),
+        None => cargo_metadata.root_package(),
+    }
+    .ok_or(CliError::NoDeployProfiles)?;
+    let mut profiles = Metadata::new(package)?.deploy;
+
+    if profiles.is_empty() {
+        Err(CliError::NoDeployProfiles)?;
+    }
+
+    let selected: Vec<(String, DeployProfile)> = match selection {
+        DeploySelection::All => profiles.into_iter().collect(),
+        DeploySelection::Profile(name) => match profiles.remove(&name) {
+            Some(profile) => vec![(name, profile)],
+            None => Err(CliError::UnknownDeployProfile {
+                name,
+                available: profiles.into_keys().collect::<Vec<_>>().join(", "),
+            })?,
+        },
+        DeploySelection::Prompt if profiles.len() == 1 => profiles.into_iter().collect(),
+        DeploySelection::Prompt => {
+            ensure_interactive(
+                "choose a deploy profile",
+                &format!(
+                    "Pass the name of a profile ({}) or `--all`.",
+                    profiles.keys().cloned().collect::<Vec<_>>().join(", ")
+                ),
+            )?;
+
+            let name = Select::new(
+                "Choose a profile to deploy",
+                profiles.keys().cloned().collect(),
+            )
+            .prompt()
+            .map_err(CliError::from)?;
+            let profile = profiles.remove(&name).unwrap();
+
+            vec![(name, profile)]
+        }
+    };
+
+    for (profile_name, profile) in selected {
+        eprintln!("   \x1b[1;92mDeploying\x1b[0m profile `{profile_name}`");
+
+        let mut cargo_opts = cargo_opts.clone();
+        cargo_opts.features.extend(profile.features);
+
+        upload(
+            path,
+            UploadOpts {
+                slot: profile.slot,
+                name: profile.name,
+                description: profile.description,
+                icon: profile.icon,
+                cargo_opts,
+                ..Default::default()
+            },
+            after,
+            connection_opts,
+        )
+        .await?;
+    }
+
+    Ok(())
+}
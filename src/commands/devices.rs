@@ -1,58 +1,237 @@
+use std::collections::BTreeMap;
 use std::io::{self, Write};
 use std::time::Duration;
 
 use vex_v5_serial::{
     Connection,
-    protocol::cdc2::system::{DeviceStatusPacket, DeviceStatusReplyPacket},
-    serial::SerialConnection,
+    protocol::cdc2::system::{DeviceStatus, DeviceStatusPacket, DeviceStatusReplyPacket},
 };
 
 use tabwriter::TabWriter;
+use tokio::time::{Instant, interval};
 
-use crate::errors::CliError;
+use crate::{
+    connection::{DeviceConnection, brain_id},
+    errors::CliError,
+};
 
-pub async fn devices(connection: &mut SerialConnection) -> Result<(), CliError> {
-    let mut tw = TabWriter::new(io::stdout());
+/// The devices plugged into a Brain's smart ports.
+#[derive(Debug)]
+pub struct ConnectedDevices {
+    /// The Brain's unique ID, if it could be read.
+    pub brain_id: Option<u32>,
+    pub devices: Vec<DeviceStatus>,
+}
+
+/// How long a port stays highlighted after its device changes in watch mode.
+const CHANGE_HIGHLIGHT: Duration = Duration::from_secs(3);
+
+/// Reads the status of every device connected to the Brain.
+pub async fn list_devices(connection: &mut DeviceConnection) -> Result<ConnectedDevices, CliError> {
+    let brain_id = brain_id(connection).await?;
+
+    Ok(ConnectedDevices {
+        brain_id,
+        devices: device_status(connection).await?,
+    })
+}
 
-    let status = connection
+/// Reads the status of every device connected to the Brain, without the Brain's ID.
+async fn device_status(connection: &mut DeviceConnection) -> Result<Vec<DeviceStatus>, CliError> {
+    Ok(connection
         .handshake::<DeviceStatusReplyPacket>(
             Duration::from_millis(500),
             10,
             DeviceStatusPacket::new(()),
         )
         .await?
-        .payload?;
+        .payload?
+        .devices)
+}
+
+/// Formats a device's packed firmware version.
+pub fn format_device_version(version: u16, beta: u8) -> String {
+    format!("{}.b{beta}", format_boot_version(version))
+}
+
+/// Formats a device's packed bootloader version.
+pub fn format_boot_version(version: u16) -> String {
+    format!(
+        "{}.{}.{}",
+        (u32::from(version) >> 14) as u8,
+        ((u32::from(version) << 18) >> 26) as u8,
+        (version & 0xff) as u8
+    )
+}
+
+/// Lists the devices connected to a Brain, or keeps refreshing the list every `watch` interval
+/// if one is given.
+pub async fn devices(
+    connection: &mut DeviceConnection,
+    watch: Option<Duration>,
+) -> Result<(), CliError> {
+    if let Some(period) = watch {
+        return watch_devices(connection, period).await;
+    }
+
+    let ConnectedDevices { brain_id, devices } = list_devices(connection).await?;
+    let mut tw = TabWriter::new(io::stdout());
+
+    if let Some(id) = brain_id {
+        println!("\x1B[1mBrain ID\x1B[0m {id:08X}\n");
+    }
+
     writeln!(
         &mut tw,
         "\x1B[1mPort\tType\tStatus\tFirmware\tBootloader\x1B[0m"
-    )
-    .unwrap();
+    )?;
+
+    for device in devices {
+        writeln!(&mut tw, "{}", device_row(&device))?;
+    }
+
+    tw.flush()?;
+
+    Ok(())
+}
+
+/// Shows the firmware of each device, limited to the device on `port` if one is given, and how to
+/// update it.
+///
+/// VEXos updates smart devices itself from the firmware bundled with it, and there's no way to
+/// send it device firmware over the serial protocol, or to ask whether a device is out of date.
+pub async fn device_firmware(
+    connection: &mut DeviceConnection,
+    port: Option<u8>,
+) -> Result<(), CliError> {
+    let mut devices = device_status(connection).await?;
+    if let Some(port) = port {
+        devices.retain(|device| device.port == port);
+        if devices.is_empty() {
+            return Err(CliError::NoDeviceOnPort(port));
+        }
+    }
 
-    for device in status.devices {
+    let mut tw = TabWriter::new(io::stdout());
+    writeln!(&mut tw, "\x1B[1mPort\tType\tFirmware\tBootloader\x1B[0m")?;
+    for device in &devices {
         writeln!(
             &mut tw,
-            "{}\t{:?}\t{:#x}\t{}\t{}",
+            "{}\t{:?}\t{}\t{}",
             device.port,
             device.device_type,
-            device.status,
-            format_args!(
-                "{}.{}.{}.b{}",
-                (u32::from(device.version) >> 14) as u8,
-                ((u32::from(device.version) << 18) >> 26) as u8,
-                (device.version & 0xff) as u8,
-                device.beta_version
-            ),
-            format_args!(
-                "{}.{}.{}",
-                (u32::from(device.boot_version) >> 14) as u8,
-                ((u32::from(device.boot_version) << 18) >> 26) as u8,
-                (device.boot_version & 0xff) as u8
-            ),
-        )
-        .unwrap();
+            format_device_version(device.version, device.beta_version),
+            format_boot_version(device.boot_version),
+        )?;
     }
+    tw.flush()?;
 
-    tw.flush().unwrap();
+    eprintln!(
+        "\n        \x1b[1;96mNote\x1b[0m VEXos updates devices from the firmware bundled with it. To update them, update the Brain's firmware (see `cargo v5 firmware check`), then replug each device or update it from the Brain's Devices screen."
+    );
 
     Ok(())
 }
+
+/// Formats a device as a row of the devices table.
+fn device_row(device: &DeviceStatus) -> String {
+    format!(
+        "{}\t{:?}\t{:#x}\t{}\t{}",
+        device.port,
+        device.device_type,
+        device.status,
+        format_device_version(device.version, device.beta_version),
+        format_boot_version(device.boot_version),
+    )
+}
+
+/// How a port's device changed while watching.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PortChange {
+    Connected,
+    Changed,
+    Disconnected,
+}
+
+impl PortChange {
+    fn label(self) -> &'static str {
+        match self {
+            Self::Connected => "\x1B[1;92mconnected\x1B[0m",
+            Self::Changed => "\x1B[1;93mchanged\x1B[0m",
+            Self::Disconnected => "\x1B[1;91mdisconnected\x1B[0m",
+        }
+    }
+}
+
+/// Redraws the devices table every `period`, highlighting ports whose devices were plugged in,
+/// changed status, or were unplugged. Unplugged devices stay listed until they come back.
+async fn watch_devices(
+    connection: &mut DeviceConnection,
+    period: Duration,
+) -> Result<(), CliError> {
+    let brain_id = brain_id(connection).await?;
+    let mut ports = BTreeMap::<u8, DeviceStatus>::new();
+    let mut changes = BTreeMap::<u8, (PortChange, Instant)>::new();
+    let mut first_poll = true;
+    let mut refresh = interval(period);
+
+    loop {
+        refresh.tick().await;
+        let devices = device_status(connection).await?;
+        let now = Instant::now();
+
+        for device in &devices {
+            let was_unplugged = changes
+                .get(&device.port)
+                .is_some_and(|(change, _)| *change == PortChange::Disconnected);
+
+            let change = match ports.insert(device.port, *device) {
+                None if first_poll => None,
+                None => Some(PortChange::Connected),
+                Some(_) if was_unplugged => Some(PortChange::Connected),
+                Some(previous) if previous != *device => Some(PortChange::Changed),
+                Some(_) => None,
+            };
+            if let Some(change) = change {
+                changes.insert(device.port, (change, now));
+            }
+        }
+
+        for port in ports.keys() {
+            if !devices.iter().any(|device| device.port == *port) {
+                changes
+                    .entry(*port)
+                    .and_modify(|(change, _)| *change = PortChange::Disconnected)
+                    .or_insert((PortChange::Disconnected, now));
+            }
+        }
+
+        changes.retain(|_, (change, since)| {
+            *change == PortChange::Disconnected || now.duration_since(*since) < CHANGE_HIGHLIGHT
+        });
+        first_poll = false;
+
+        let mut tw = TabWriter::new(Vec::new());
+        writeln!(
+            &mut tw,
+            "\x1B[1mPort\tType\tStatus\tFirmware\tBootloader\t\x1B[0m"
+        )?;
+        for (port, device) in &ports {
+            let change = changes
+                .get(port)
+                .map(|(change, _)| change.label())
+                .unwrap_or_default();
+            writeln!(&mut tw, "{}\t{change}", device_row(device))?;
+        }
+
+        let mut stdout = io::stdout().lock();
+
+        // Clear the screen and move the cursor home before redrawing.
+        write!(stdout, "\x1b[2J\x1b[H")?;
+        if let Some(id) = brain_id {
+            writeln!(stdout, "\x1B[1mBrain ID\x1B[0m {id:08X}\n")?;
+        }
+        stdout.write_all(&tw.into_inner().unwrap())?;
+        stdout.flush()?;
+    }
+}
@@ -0,0 +1,77 @@
+//! Flash usage summary.
+//!
+//! VEXos doesn't report the capacity of the Brain's flash over the serial protocol, so this can
+//! only report how much space files are using, not how much is left.
+
+use std::io::{self, Write};
+
+use humansize::{BINARY, format_size};
+use tabwriter::TabWriter;
+use vex_v5_serial::protocol::cdc2::file::FileVendor;
+
+use crate::{connection::DeviceConnection, errors::CliError};
+
+use super::dir::{USEFUL_VIDS, enable_factory_access, read_directory, vendor_prefix};
+
+/// Print how much flash each vendor's files use, followed by the `largest` biggest files.
+pub async fn df(connection: &mut DeviceConnection, largest: usize) -> Result<(), CliError> {
+    enable_factory_access(connection).await;
+
+    let mut files = Vec::<(FileVendor, String, u32)>::new();
+    let mut tw = TabWriter::new(io::stdout());
+
+    writeln!(tw, "\x1b[1mVendor\tFiles\tUsed\x1b[0m")?;
+
+    let mut total_files = 0;
+    let mut total_size = 0;
+
+    for vid in USEFUL_VIDS {
+        let entries = read_directory(connection, vid).await?;
+        if entries.is_empty() {
+            continue;
+        }
+
+        let size = entries.iter().map(|entry| entry.size as u64).sum::<u64>();
+        total_files += entries.len();
+        total_size += size;
+
+        writeln!(
+            tw,
+            "{}\t{}\t{}",
+            vendor_prefix(vid),
+            entries.len(),
+            format_size(size, BINARY)
+        )?;
+
+        files.extend(
+            entries
+                .into_iter()
+                .map(|entry| (vid, entry.file_name.to_string(), entry.size)),
+        );
+    }
+
+    writeln!(
+        tw,
+        "\x1b[1mTotal\x1b[0m\t{total_files}\t{}",
+        format_size(total_size, BINARY)
+    )?;
+
+    if largest > 0 && !files.is_empty() {
+        files.sort_by(|a, b| b.2.cmp(&a.2));
+
+        writeln!(tw)?;
+        writeln!(tw, "\x1b[1mLargest Files\x1b[0m")?;
+        for (vid, file_name, size) in files.iter().take(largest) {
+            writeln!(
+                tw,
+                "{}{file_name}\t{}",
+                vendor_prefix(*vid),
+                format_size(*size, BINARY)
+            )?;
+        }
+    }
+
+    tw.flush()?;
+
+    Ok(())
+}
@@ -1,27 +1,46 @@
-use chrono::{TimeZone, Utc};
+use chrono::{DateTime, TimeZone, Utc};
 use std::io::{self, Write};
 use std::time::Duration;
 
 use vex_v5_serial::{
     Connection,
     commands::file::J2000_EPOCH,
-    protocol::cdc2::{
-        factory::{FactoryEnablePacket, FactoryEnableReplyPacket},
-        file::{
-            DirectoryEntryPacket, DirectoryEntryPayload, DirectoryEntryReplyPacket,
-            DirectoryFileCountPacket, DirectoryFileCountPayload, DirectoryFileCountReplyPacket,
-            ExtensionType, FileVendor,
+    protocol::{
+        Version,
+        cdc2::{
+            factory::{FactoryEnablePacket, FactoryEnableReplyPacket},
+            file::{
+                DirectoryEntryPacket, DirectoryEntryPayload, DirectoryEntryReplyPacket,
+                DirectoryEntryReplyPayload, DirectoryFileCountPacket, DirectoryFileCountPayload,
+                DirectoryFileCountReplyPacket, ExtensionType, FileVendor,
+            },
         },
     },
-    serial::SerialConnection,
 };
 
 use humansize::{BINARY, format_size};
 use tabwriter::TabWriter;
 
-use crate::errors::CliError;
+use crate::{connection::DeviceConnection, errors::CliError};
 
-fn vendor_prefix(vid: FileVendor) -> &'static str {
+use super::firmware::format_version;
+
+/// Vendor namespaces that files can be listed from.
+pub const USEFUL_VIDS: [FileVendor; 11] = [
+    FileVendor::User,
+    FileVendor::Sys,
+    FileVendor::Dev1,
+    FileVendor::Dev2,
+    FileVendor::Dev3,
+    FileVendor::Dev4,
+    FileVendor::Dev5,
+    FileVendor::Dev6,
+    FileVendor::VexVm,
+    FileVendor::Vex,
+    FileVendor::Undefined,
+];
+
+pub fn vendor_prefix(vid: FileVendor) -> &'static str {
     match vid {
         FileVendor::User => "user/",
         FileVendor::Sys => "sys_/",
@@ -37,51 +56,44 @@ fn vendor_prefix(vid: FileVendor) -> &'static str {
     }
 }
 
-pub async fn dir(connection: &mut SerialConnection) -> Result<(), CliError> {
-    let mut tw = TabWriter::new(io::stdout());
-
-    const USEFUL_VIDS: [FileVendor; 11] = [
-        FileVendor::User,
-        FileVendor::Sys,
-        FileVendor::Dev1,
-        FileVendor::Dev2,
-        FileVendor::Dev3,
-        FileVendor::Dev4,
-        FileVendor::Dev5,
-        FileVendor::Dev6,
-        FileVendor::VexVm,
-        FileVendor::Vex,
-        FileVendor::Undefined,
-    ];
-
-    connection
+/// Allows files outside of the user vendor to be listed.
+///
+/// Not every Brain supports this (EXP Brains don't), in which case only files that are visible
+/// without it are listed.
+pub async fn enable_factory_access(connection: &mut DeviceConnection) {
+    if let Err(err) = connection
         .handshake::<FactoryEnableReplyPacket>(
             Duration::from_millis(500),
             1,
             FactoryEnablePacket::new(FactoryEnablePacket::MAGIC),
         )
         .await
-        .unwrap();
+    {
+        log::debug!("Failed to enable factory access: {err}");
+    }
+}
 
-    write!(
-        &mut tw,
-        "\x1B[1mName\tSize\tLoad Address\tVendor\tType\tTimestamp\tVersion\tCRC32\n\x1B[0m"
-    )
-    .unwrap();
-    for vid in USEFUL_VIDS {
-        let file_count = connection
-            .handshake::<DirectoryFileCountReplyPacket>(
-                Duration::from_millis(500),
-                1,
-                DirectoryFileCountPacket::new(DirectoryFileCountPayload {
-                    vendor: vid,
-                    reserved: 0,
-                }),
-            )
-            .await?;
-
-        for n in 0..file_count.payload? {
-            let entry = connection
+/// Lists every file stored under a vendor.
+pub async fn read_directory(
+    connection: &mut DeviceConnection,
+    vid: FileVendor,
+) -> Result<Vec<DirectoryEntryReplyPayload>, CliError> {
+    let file_count = connection
+        .handshake::<DirectoryFileCountReplyPacket>(
+            Duration::from_millis(500),
+            1,
+            DirectoryFileCountPacket::new(DirectoryFileCountPayload {
+                vendor: vid,
+                reserved: 0,
+            }),
+        )
+        .await?
+        .payload?;
+
+    let mut entries = Vec::with_capacity(file_count as usize);
+    for n in 0..file_count {
+        entries.push(
+            connection
                 .handshake::<DirectoryEntryReplyPacket>(
                     Duration::from_millis(500),
                     1,
@@ -91,57 +103,100 @@ pub async fn dir(connection: &mut SerialConnection) -> Result<(), CliError> {
                     }),
                 )
                 .await?
-                .payload?;
-
-            writeln!(
-                &mut tw,
-                "{}{}\t{}\t{}\t{:?}\t{}\t{}\t{}\t{}",
-                vendor_prefix(vid),
-                entry.file_name,
-                format_size(entry.size, BINARY),
-                if entry.load_address == u32::MAX {
-                    "-".to_string()
-                } else {
-                    format!("{:#x}", entry.load_address)
-                },
-                vid,
-                entry
-                    .metadata
-                    .as_ref()
-                    .map(|m| match m.extension_type {
-                        ExtensionType::Binary => "binary",
-                        ExtensionType::EncryptedBinary => "encrypted",
-                        ExtensionType::Vm => "vm",
-                    })
-                    .unwrap_or("system"),
-                entry
-                    .metadata
-                    .as_ref()
-                    .map(|m| Utc
-                        .timestamp_millis_opt((J2000_EPOCH as i64 + m.timestamp as i64) * 1000)
-                        .unwrap()
-                        .format("%Y-%m-%d %H:%M:%S")
-                        .to_string())
-                    .unwrap_or("-".to_string()),
-                entry
-                    .metadata
-                    .as_ref()
-                    .map(|m| format!(
-                        "{}.{}.{}.b{}",
-                        m.version.major, m.version.minor, m.version.build, m.version.beta
-                    ))
-                    .unwrap_or("-".to_string()),
-                if entry.crc == u32::MAX {
-                    "-".to_string()
-                } else {
-                    format!("{:#x}", entry.crc)
-                },
-            )
-            .unwrap();
+                .payload?,
+        );
+    }
+
+    Ok(entries)
+}
+
+/// A file stored on the Brain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileInfo {
+    pub vendor: FileVendor,
+    pub name: String,
+    pub size: u32,
+    /// Address the file is loaded at, if it's a program.
+    pub load_address: Option<u32>,
+    /// Type of the file, or `None` for system files without metadata.
+    pub extension_type: Option<ExtensionType>,
+    pub timestamp: Option<DateTime<Utc>>,
+    pub version: Option<Version>,
+    pub crc32: Option<u32>,
+}
+
+impl FileInfo {
+    fn new(vendor: FileVendor, entry: DirectoryEntryReplyPayload) -> Self {
+        Self {
+            vendor,
+            name: entry.file_name.to_string(),
+            size: entry.size,
+            load_address: (entry.load_address != u32::MAX).then_some(entry.load_address),
+            extension_type: entry.metadata.as_ref().map(|m| m.extension_type),
+            timestamp: entry.metadata.as_ref().and_then(|m| {
+                Utc.timestamp_opt(J2000_EPOCH as i64 + m.timestamp as i64, 0)
+                    .single()
+            }),
+            version: entry.metadata.as_ref().map(|m| m.version),
+            crc32: (entry.crc != u32::MAX).then_some(entry.crc),
         }
     }
+}
+
+/// Lists the files in every vendor namespace that can be read.
+pub async fn list_files(connection: &mut DeviceConnection) -> Result<Vec<FileInfo>, CliError> {
+    enable_factory_access(connection).await;
+
+    let mut files = Vec::new();
+    for vid in USEFUL_VIDS {
+        files.extend(
+            read_directory(connection, vid)
+                .await?
+                .into_iter()
+                .map(|entry| FileInfo::new(vid, entry)),
+        );
+    }
+
+    Ok(files)
+}
+
+pub async fn dir(connection: &mut DeviceConnection) -> Result<(), CliError> {
+    let files = list_files(connection).await?;
+    let mut tw = TabWriter::new(io::stdout());
+
+    write!(
+        &mut tw,
+        "\x1B[1mName\tSize\tLoad Address\tVendor\tType\tTimestamp\tVersion\tCRC32\n\x1B[0m"
+    )?;
+    for file in files {
+        writeln!(
+            &mut tw,
+            "{}{}\t{}\t{}\t{:?}\t{}\t{}\t{}\t{}",
+            vendor_prefix(file.vendor),
+            file.name,
+            format_size(file.size, BINARY),
+            file.load_address
+                .map(|address| format!("{address:#x}"))
+                .unwrap_or("-".to_string()),
+            file.vendor,
+            file.extension_type
+                .map(|extension_type| match extension_type {
+                    ExtensionType::Binary => "binary",
+                    ExtensionType::EncryptedBinary => "encrypted",
+                    ExtensionType::Vm => "vm",
+                })
+                .unwrap_or("system"),
+            file.timestamp
+                .map(|timestamp| timestamp.format("%Y-%m-%d %H:%M:%S").to_string())
+                .unwrap_or("-".to_string()),
+            file.version.map(format_version).unwrap_or("-".to_string()),
+            file.crc32
+                .map(|crc| format!("{crc:#x}"))
+                .unwrap_or("-".to_string()),
+        )?;
+    }
 
-    tw.flush().unwrap();
+    tw.flush()?;
 
     Ok(())
 }
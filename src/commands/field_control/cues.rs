@@ -0,0 +1,172 @@
+//! Sounds and desktop notifications marking the periods of a match.
+//!
+//! Cues are played with whatever the platform already has: system sounds through `afplay` on
+//! macOS, a console beep on Windows, and the terminal bell elsewhere. Notifications are sent with
+//! `osascript` on macOS and `notify-send` on Linux.
+
+use std::{
+    io::{self, Write},
+    process::{Command, Stdio},
+    time::Duration,
+};
+
+use vex_v5_serial::protocol::cdc2::controller::MatchMode;
+
+/// How much time is left in driver control when the warning cue plays.
+const WARNING_TIME: Duration = Duration::from_secs(30);
+
+/// Something worth announcing during a match.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Cue {
+    /// An autonomous or driver control period started.
+    Start(MatchMode),
+    /// The match ended.
+    Stop,
+    /// Driver control is almost over.
+    Warning,
+}
+
+impl Cue {
+    fn message(self) -> &'static str {
+        match self {
+            Self::Start(MatchMode::Auto) => "Autonomous period started",
+            Self::Start(_) => "Driver control started",
+            Self::Stop => "Match ended",
+            Self::Warning => "30 seconds left",
+        }
+    }
+
+    fn sound_command(self) -> Option<Command> {
+        if cfg!(target_os = "macos") {
+            let sound = match self {
+                Self::Start(_) => "Glass",
+                Self::Stop => "Sosumi",
+                Self::Warning => "Ping",
+            };
+
+            let mut command = Command::new("afplay");
+            command.arg(format!("/System/Library/Sounds/{sound}.aiff"));
+            Some(command)
+        } else if cfg!(target_os = "windows") {
+            let (frequency, duration) = match self {
+                Self::Start(_) => (880, 600),
+                Self::Stop => (440, 900),
+                Self::Warning => (660, 300),
+            };
+
+            let mut command = Command::new("powershell");
+            command.args([
+                "-NoProfile",
+                "-Command",
+                &format!("[console]::beep({frequency},{duration})"),
+            ]);
+            Some(command)
+        } else {
+            None
+        }
+    }
+
+    fn notification_command(self) -> Option<Command> {
+        if cfg!(target_os = "macos") {
+            let mut command = Command::new("osascript");
+            command.args([
+                "-e",
+                &format!(
+                    "display notification \"{}\" with title \"cargo v5 fc\"",
+                    self.message()
+                ),
+            ]);
+            Some(command)
+        } else if cfg!(target_os = "linux") {
+            let mut command = Command::new("notify-send");
+            command.args(["cargo v5 fc", self.message()]);
+            Some(command)
+        } else {
+            None
+        }
+    }
+}
+
+/// Runs `command` in the background, returning whether it could be started.
+fn spawn_quietly(mut command: Command) -> bool {
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .inspect_err(|err| log::debug!("Couldn't play match cue: {err}"))
+        .is_ok()
+}
+
+/// Watches the field control countdown and plays cues as the match moves between periods.
+#[derive(Debug)]
+pub struct MatchCues {
+    sounds: bool,
+    notifications: bool,
+
+    last_mode: MatchMode,
+    was_running: bool,
+    warned: bool,
+}
+
+impl MatchCues {
+    pub fn new(sounds: bool, notifications: bool) -> Self {
+        Self {
+            sounds,
+            notifications,
+            last_mode: MatchMode::Disabled,
+            was_running: false,
+            warned: false,
+        }
+    }
+
+    /// Updates the cues with the countdown's state, playing any that are due.
+    ///
+    /// `remaining` is the time left in the current period and `period` is its full length.
+    pub fn update(
+        &mut self,
+        running: bool,
+        mode: MatchMode,
+        remaining: Duration,
+        period: Duration,
+    ) {
+        let period_changed = mode != self.last_mode || running != self.was_running;
+
+        if period_changed && running && mode != MatchMode::Disabled {
+            self.warned = false;
+            self.play(Cue::Start(mode));
+        } else if period_changed
+            && self.was_running
+            && self.last_mode != MatchMode::Disabled
+            && mode != self.last_mode
+        {
+            self.play(Cue::Stop);
+        } else if running
+            && mode == MatchMode::Driver
+            && !self.warned
+            && period > WARNING_TIME
+            && remaining <= WARNING_TIME
+        {
+            self.warned = true;
+            self.play(Cue::Warning);
+        }
+
+        self.last_mode = mode;
+        self.was_running = running;
+    }
+
+    fn play(&self, cue: Cue) {
+        if self.sounds && !cue.sound_command().is_some_and(spawn_quietly) {
+            // The terminal bell works everywhere, even if it can't tell cues apart.
+            let mut stdout = io::stdout().lock();
+            _ = stdout.write_all(b"\x07");
+            _ = stdout.flush();
+        }
+
+        if self.notifications
+            && let Some(command) = cue.notification_command()
+        {
+            spawn_quietly(command);
+        }
+    }
+}
@@ -3,12 +3,15 @@ use std::{
     time::{Duration, Instant},
 };
 
+use clap::ValueEnum;
 use crossterm::event::{self, Event, KeyCode, KeyModifiers};
+use futures::future::join_all;
 use ratatui::{
     Frame,
     layout::{Constraint, Flex, Layout, Rect},
     style::{Color, Style, Stylize},
     symbols::{self, border::Set},
+    text::Line,
     widgets::{Block, Borders, Paragraph},
 };
 use tui_term::{
@@ -24,18 +27,138 @@ use vex_v5_serial::{
             MatchMode, UserDataPacket, UserDataPayload, UserDataReplyPacket,
         },
     },
-    serial::{SerialConnection, SerialError},
+    serial::{self, SerialDevice},
 };
 use widgets::{HelpPopup, Mode, set_duration_digit};
 
-use crate::errors::CliError;
+use super::{terminal::TerminalHub, upload::ascii_output};
+use crate::{
+    connection::{ConnectionError, DeviceConnection},
+    errors::CliError,
+};
 
+mod cues;
+mod script;
+mod session;
 mod widgets;
 
+pub use cues::MatchCues;
+pub use script::{MatchScript, run_match_script};
+pub use session::MatchLog;
+
+/// Panel border drawn when output is restricted to ASCII.
+const ASCII_BORDER: Set = Set {
+    top_left: "+",
+    top_right: "+",
+    bottom_left: "+",
+    bottom_right: "+",
+    vertical_left: "|",
+    vertical_right: "|",
+    horizontal_top: "-",
+    horizontal_bottom: "-",
+};
+
+/// Returns the border panels should be drawn with.
+fn border_set() -> Set {
+    if ascii_output() {
+        ASCII_BORDER
+    } else {
+        symbols::border::ROUNDED
+    }
+}
+
+/// A controller whose robot is under field control.
+pub struct FieldController {
+    /// Serial port the controller is connected through.
+    pub port: String,
+    pub connection: DeviceConnection,
+
+    /// Output of the program running on the controller's robot.
+    parser: vt100::Parser,
+
+    /// Why the controller last failed to respond, if it did.
+    error: Option<String>,
+}
+
+impl FieldController {
+    pub fn new(port: String, connection: DeviceConnection) -> Self {
+        Self {
+            port,
+            connection,
+            parser: vt100::Parser::new(1, 1, 0),
+            error: None,
+        }
+    }
+}
+
+/// Connects to the controllers plugged into USB.
+///
+/// Controllers are chosen by serial port from `ports`. When no ports are given, every connected
+/// controller is used if `all` is set, and otherwise the first one (or the one on `device`).
+pub async fn connect_controllers(
+    ports: Vec<String>,
+    all: bool,
+    device: Option<String>,
+) -> Result<Vec<FieldController>, CliError> {
+    let controllers = serial::find_devices()
+        .map_err(CliError::SerialError)?
+        .into_iter()
+        .filter(|device| matches!(device, SerialDevice::Controller { system_port: _ }))
+        .collect::<Vec<_>>();
+
+    let selected = if !ports.is_empty() {
+        ports
+            .iter()
+            .map(|port| {
+                controllers
+                    .iter()
+                    .find(|device| device.system_port().eq_ignore_ascii_case(port))
+                    .cloned()
+                    .ok_or_else(|| CliError::ControllerNotFound(port.clone()))
+            })
+            .collect::<Result<Vec<_>, _>>()?
+    } else if all {
+        controllers
+    } else {
+        controllers
+            .into_iter()
+            .find(|controller| {
+                device
+                    .as_ref()
+                    .is_none_or(|port| controller.system_port().eq_ignore_ascii_case(port))
+            })
+            .into_iter()
+            .collect()
+    };
+
+    if selected.is_empty() {
+        return Err(CliError::NoController);
+    }
+
+    let connections = join_all(selected.into_iter().map(|device| {
+        tokio::task::spawn_blocking(move || {
+            let connection = device
+                .connect(Duration::from_secs(5))
+                .map_err(CliError::SerialError)?;
+            Ok::<_, CliError>(FieldController::new(
+                device.system_port(),
+                DeviceConnection::Serial(connection),
+            ))
+        })
+    }))
+    .await;
+
+    connections
+        .into_iter()
+        // A connection attempt that panicked is reported like any other I/O failure.
+        .map(|result| result.map_err(std::io::Error::from)?)
+        .collect()
+}
+
 async fn set_match_mode(
-    connection: &mut SerialConnection,
+    connection: &mut DeviceConnection,
     match_mode: MatchMode,
-) -> Result<(), SerialError> {
+) -> Result<(), ConnectionError> {
     connection
         .handshake::<CompetitionControlReplyPacket>(
             Duration::from_millis(500),
@@ -50,7 +173,57 @@ async fn set_match_mode(
     Ok(())
 }
 
-async fn try_read_terminal(connection: &mut SerialConnection) -> Result<Vec<u8>, CliError> {
+/// Sets the match mode of every controller at once, so that all robots change modes in lockstep.
+///
+/// A controller that fails to respond is marked as such in the TUI rather than ending the session
+/// for everyone else.
+async fn broadcast_match_mode(
+    controllers: &mut [FieldController],
+    match_mode: MatchMode,
+    session: Option<&mut MatchLog>,
+) {
+    join_all(controllers.iter_mut().map(|controller| async move {
+        controller.error = set_match_mode(&mut controller.connection, match_mode)
+            .await
+            .err()
+            .map(|err| err.to_string());
+    }))
+    .await;
+
+    if let Some(session) = session {
+        session.record_mode(match_mode, controllers);
+    }
+}
+
+fn mode_name(mode: MatchMode) -> &'static str {
+    match mode {
+        MatchMode::Auto => "auto",
+        MatchMode::Driver => "driver",
+        MatchMode::Disabled => "disabled",
+    }
+}
+
+/// Makes sure every connection is to a controller, since match modes can't be set otherwise.
+async fn check_controllers(controllers: &mut [FieldController]) -> Result<(), CliError> {
+    for controller in controllers.iter_mut() {
+        let response = controller
+            .connection
+            .handshake::<SystemVersionReplyPacket>(
+                Duration::from_millis(700),
+                5,
+                SystemVersionPacket::new(()),
+            )
+            .await?
+            .payload;
+        if response.product_type != ProductType::Controller {
+            return Err(CliError::BrainConnectionSetMatchMode);
+        }
+    }
+
+    Ok(())
+}
+
+async fn try_read_terminal(connection: &mut DeviceConnection) -> Result<Vec<u8>, CliError> {
     let read = connection
         .handshake::<UserDataReplyPacket>(
             Duration::from_millis(100),
@@ -71,6 +244,72 @@ async fn try_read_terminal(connection: &mut SerialConnection) -> Result<Vec<u8>,
     Ok(data)
 }
 
+/// Preset match timings for the field control TUI.
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MatchTemplate {
+    /// A 15 second autonomous period followed by 1:45 of driver control.
+    #[default]
+    HeadToHead,
+
+    /// 60 seconds of driver control.
+    DriverSkills,
+
+    /// 60 seconds of autonomous.
+    ProgrammingSkills,
+
+    /// Period lengths set by hand, run like a head-to-head match.
+    Custom,
+}
+
+impl MatchTemplate {
+    const ALL: [Self; 4] = [
+        Self::HeadToHead,
+        Self::DriverSkills,
+        Self::ProgrammingSkills,
+        Self::Custom,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::HeadToHead => "Head-to-head",
+            Self::DriverSkills => "Driver Skills",
+            Self::ProgrammingSkills => "Programming Skills",
+            Self::Custom => "Custom",
+        }
+    }
+
+    /// The template after this one, for cycling through them in the TUI.
+    fn next(self) -> Self {
+        let index = Self::ALL
+            .iter()
+            .position(|template| *template == self)
+            .unwrap();
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    /// Lengths of the autonomous, driver, and disabled periods, or `None` to keep the current
+    /// ones.
+    fn durations(self) -> Option<[Duration; 3]> {
+        let secs = Duration::from_secs;
+        match self {
+            Self::HeadToHead => Some([secs(15), secs(105), secs(0)]),
+            Self::DriverSkills => Some([secs(0), secs(60), secs(0)]),
+            Self::ProgrammingSkills => Some([secs(60), secs(0), secs(0)]),
+            Self::Custom => None,
+        }
+    }
+
+    /// The mode to switch to once `mode`'s period runs out, or `None` if the match is over.
+    fn next_mode(self, mode: MatchMode) -> Option<MatchMode> {
+        match (self, mode) {
+            (Self::DriverSkills, MatchMode::Disabled) => Some(MatchMode::Driver),
+            (_, MatchMode::Disabled) => Some(MatchMode::Auto),
+            (Self::HeadToHead | Self::Custom, MatchMode::Auto) => Some(MatchMode::Driver),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum MatchModeFocus {
     Auto,
@@ -111,6 +350,14 @@ struct CountdownState {
     running: bool,
 }
 impl CountdownState {
+    fn apply_template(&mut self, template: MatchTemplate) {
+        if let Some([auto, driver, disabled]) = template.durations() {
+            self.auto_set_time = auto;
+            self.driver_set_time = driver;
+            self.disabled_set_time = disabled;
+        }
+    }
+
     fn current_set_time(&self, match_mode: MatchMode) -> Duration {
         match match_mode {
             MatchMode::Auto => self.auto_set_time,
@@ -123,26 +370,43 @@ impl CountdownState {
 struct TuiState {
     current_mode: MatchMode,
     focus: Focus,
-    parser: vt100::Parser,
+
+    /// Index of the controller whose program output is shown.
+    viewed: usize,
+
+    template: MatchTemplate,
 
     countdown: CountdownState,
 }
 
-fn draw_tui(frame: &mut Frame, state: &mut TuiState) {
+fn draw_tui(frame: &mut Frame, state: &mut TuiState, controllers: &mut [FieldController]) {
     let title_style = Style::default().fg(Color::White).bold();
 
     let minutes = state.countdown.current_time.as_secs() / 60;
     let seconds = state.countdown.current_time.as_secs() % 60;
-    let countdown_text = format!("{minutes:02}:{seconds:02}");
+    let countdown_text = vec![
+        Line::from(format!("{minutes:02}:{seconds:02}")),
+        Line::from(state.template.name()).dark_gray(),
+    ];
 
     let main_sections = Layout::horizontal([Constraint::Min(20), Constraint::Percentage(100)]);
     let [left_area, terminal_area] = main_sections.areas(frame.area());
-    let options = Layout::vertical([Constraint::Min(2), Constraint::Percentage(100)]);
-    let [countdown_area, mode_area] = options.areas(left_area);
+    // The controller list is only worth the space when there's more than one.
+    let controllers_height = if controllers.len() > 1 {
+        controllers.len() as u16 + 2
+    } else {
+        0
+    };
+    let options = Layout::vertical([
+        Constraint::Min(3),
+        Constraint::Percentage(100),
+        Constraint::Length(controllers_height),
+    ]);
+    let [countdown_area, mode_area, controllers_area] = options.areas(left_area);
 
     let countdown_block = Block::default()
         .borders(Borders::BOTTOM.complement())
-        .border_set(symbols::border::ROUNDED)
+        .border_set(border_set())
         .title("Countdown")
         .title_style(title_style);
     let mut countdown = Paragraph::new(countdown_text);
@@ -157,10 +421,14 @@ fn draw_tui(frame: &mut Frame, state: &mut TuiState) {
     frame.render_widget(countdown_block, countdown_area);
 
     let mut mode_block = Block::bordered()
-        .border_set(Set {
-            top_left: symbols::line::NORMAL.vertical_right,
-            top_right: symbols::line::NORMAL.vertical_left,
-            ..symbols::border::ROUNDED
+        .border_set(if ascii_output() {
+            ASCII_BORDER
+        } else {
+            Set {
+                top_left: symbols::line::NORMAL.vertical_right,
+                top_right: symbols::line::NORMAL.vertical_left,
+                ..symbols::border::ROUNDED
+            }
         })
         .title("Match Mode")
         .title_style(title_style);
@@ -207,18 +475,54 @@ fn draw_tui(frame: &mut Frame, state: &mut TuiState) {
     frame.render_widget(disabled, disabled_area);
     frame.render_widget(mode_block, mode_area);
 
-    let terminal_block = Block::bordered()
-        .border_set(symbols::border::ROUNDED)
-        .title("Program Output")
+    if controllers.len() > 1 {
+        let controllers_block = Block::bordered()
+            .border_set(border_set())
+            .title("Controllers")
+            .title_style(title_style);
+        let lines = controllers
+            .iter()
+            .enumerate()
+            .map(|(index, controller)| {
+                let marker = if index == state.viewed { "> " } else { "  " };
+                let line = Line::from(format!("{marker}{}", controller.port));
+                if controller.error.is_some() {
+                    line.red()
+                } else {
+                    line.green()
+                }
+            })
+            .collect::<Vec<_>>();
+
+        frame.render_widget(
+            Paragraph::new(lines).block(controllers_block),
+            controllers_area,
+        );
+    }
+
+    let mut terminal_block = Block::bordered()
+        .border_set(border_set())
+        .title(if controllers.len() > 1 {
+            format!("Program Output ({})", controllers[state.viewed].port)
+        } else {
+            String::from("Program Output")
+        })
         .title_style(title_style);
+    if let Some(error) = &controllers[state.viewed].error {
+        terminal_block =
+            terminal_block.title_bottom(Line::from(format!("Not responding: {error}")).red());
+    }
 
+    // Keep every controller's output sized to the panel so nothing is lost when switching.
     let size = terminal_block.inner(terminal_area).as_size();
-    state.parser.set_size(size.height + 1, size.width);
+    for controller in controllers.iter_mut() {
+        controller.parser.set_size(size.height + 1, size.width);
+    }
 
     let mut cursor = Cursor::default();
     cursor.hide();
 
-    let terminal = PseudoTerminal::new(state.parser.screen())
+    let terminal = PseudoTerminal::new(controllers[state.viewed].parser.screen())
         .cursor(cursor)
         .block(terminal_block)
         .style(Style::default().fg(Color::White).bg(Color::Black));
@@ -243,7 +547,7 @@ enum Control {
     ChangeMode(MatchMode),
 }
 
-fn handle_events(tui_state: &mut TuiState) -> io::Result<Control> {
+fn handle_events(tui_state: &mut TuiState, controller_count: usize) -> io::Result<Control> {
     Ok(match event::read()? {
         Event::Key(key) => match key.code {
             KeyCode::Esc | KeyCode::Char('q') => {
@@ -265,6 +569,19 @@ fn handle_events(tui_state: &mut TuiState) -> io::Result<Control> {
                 tui_state.focus = new_focus;
                 Control::None
             }
+            KeyCode::Char('t') => {
+                // Switching templates mid-match would leave the robots in a mode the new
+                // template might not have, so start over from disabled.
+                tui_state.template = tui_state.template.next();
+                tui_state.countdown.apply_template(tui_state.template);
+                tui_state.countdown.running = false;
+                tui_state.current_mode = MatchMode::Disabled;
+                Control::ChangeMode(MatchMode::Disabled)
+            }
+            KeyCode::Tab => {
+                tui_state.viewed = (tui_state.viewed + 1) % controller_count;
+                Control::None
+            }
             KeyCode::Char('j') | KeyCode::Down => {
                 match tui_state.focus {
                     Focus::Countdown => tui_state.focus = Focus::MatchMode(MatchModeFocus::Driver),
@@ -345,6 +662,7 @@ fn handle_events(tui_state: &mut TuiState) -> io::Result<Control> {
                 let digit = ch.to_digit(10).unwrap() as u8;
 
                 if let Focus::MatchMode(mode) = tui_state.focus {
+                    tui_state.template = MatchTemplate::Custom;
                     match mode {
                         MatchModeFocus::Auto => {
                             tui_state.countdown.auto_set_time = set_duration_digit(
@@ -390,21 +708,15 @@ fn handle_countdown(tui_state: &mut TuiState) -> Control {
             .unwrap_or_default();
         if tui_state.countdown.current_time.as_secs() == 0 {
             tui_state.countdown.start_time = Instant::now();
-            match tui_state.current_mode {
-                MatchMode::Auto => {
-                    tui_state.current_mode = MatchMode::Driver;
-                    return Control::ChangeMode(MatchMode::Driver);
-                }
-                MatchMode::Driver => {
-                    tui_state.current_mode = MatchMode::Disabled;
+            let next_mode = tui_state
+                .template
+                .next_mode(tui_state.current_mode)
+                .unwrap_or_else(|| {
                     tui_state.countdown.running = false;
-                    return Control::ChangeMode(MatchMode::Disabled);
-                }
-                MatchMode::Disabled => {
-                    tui_state.current_mode = MatchMode::Auto;
-                    return Control::ChangeMode(MatchMode::Auto);
-                }
-            }
+                    MatchMode::Disabled
+                });
+            tui_state.current_mode = next_mode;
+            return Control::ChangeMode(next_mode);
         }
     } else {
         tui_state.countdown.current_time =
@@ -415,23 +727,23 @@ fn handle_countdown(tui_state: &mut TuiState) -> Control {
     Control::None
 }
 
-pub async fn run_field_control_tui(connection: &mut SerialConnection) -> Result<(), CliError> {
-    let response = connection
-        .handshake::<SystemVersionReplyPacket>(
-            Duration::from_millis(700),
-            5,
-            SystemVersionPacket::new(()),
-        )
-        .await?
-        .payload;
-    if response.product_type != ProductType::Controller {
-        return Err(CliError::BrainConnectionSetMatchMode);
-    }
+/// Runs the field control TUI, switching every controller's robot between match modes together.
+///
+/// Period lengths start out as set by `template`, and `cues` are played as the match progresses.
+/// Mode changes and program output are recorded to `session`, if given.
+pub async fn run_field_control_tui(
+    controllers: &mut [FieldController],
+    template: MatchTemplate,
+    mut cues: MatchCues,
+    mut session: Option<&mut MatchLog>,
+) -> Result<(), CliError> {
+    check_controllers(controllers).await?;
 
     let mut tui_state = TuiState {
         current_mode: MatchMode::Disabled,
         focus: Focus::MatchMode(MatchModeFocus::Driver),
-        parser: vt100::Parser::new(1, 1, 0),
+        viewed: 0,
+        template,
         countdown: CountdownState {
             auto_set_time: Duration::from_secs(15),
             auto_cursor_pos: CursorPos(0),
@@ -444,39 +756,86 @@ pub async fn run_field_control_tui(connection: &mut SerialConnection) -> Result<
             running: false,
         },
     };
+    tui_state.countdown.apply_template(template);
+
+    broadcast_match_mode(controllers, tui_state.current_mode, session.as_deref_mut()).await;
 
-    set_match_mode(connection, tui_state.current_mode).await?;
+    // Let `cargo v5 terminal` attach to this session rather than fighting over the stdio channel.
+    // It follows whichever controller's output is being viewed.
+    let mut hub = TerminalHub::bind().await;
 
     let mut terminal = ratatui::init();
     'main: loop {
         if let Control::ChangeMode(mode) = handle_countdown(&mut tui_state) {
-            set_match_mode(connection, mode).await?;
+            broadcast_match_mode(controllers, mode, session.as_deref_mut()).await;
         }
+        cues.update(
+            tui_state.countdown.running,
+            tui_state.current_mode,
+            tui_state.countdown.current_time,
+            tui_state.countdown.current_set_time(tui_state.current_mode),
+        );
         while event::poll(Duration::from_millis(1))? {
-            match handle_events(&mut tui_state)? {
+            match handle_events(&mut tui_state, controllers.len())? {
                 Control::None => {}
                 Control::Exit => break 'main,
                 Control::ChangeMode(mode) => {
-                    set_match_mode(connection, mode).await?;
+                    broadcast_match_mode(controllers, mode, session.as_deref_mut()).await;
                 }
             }
         }
-        terminal.draw(|frame| draw_tui(frame, &mut tui_state))?;
+        terminal.draw(|frame| draw_tui(frame, &mut tui_state, controllers))?;
+
+        if let Some(hub) = &mut hub {
+            while let Some(input) = hub.try_recv_input() {
+                // Input is sent as a string, so invalid UTF-8 can't be forwarded as-is.
+                let input = String::from_utf8_lossy(&input);
+                let connection = &mut controllers[tui_state.viewed].connection;
+                if let Err(err) = connection.write_user(input.as_bytes()).await {
+                    log::debug!("Failed to forward terminal input: {err}");
+                }
+            }
+        }
+
+        let outputs = join_all(
+            controllers
+                .iter_mut()
+                .map(|controller| try_read_terminal(&mut controller.connection)),
+        )
+        .await;
+        for (index, (controller, output)) in controllers.iter_mut().zip(outputs).enumerate() {
+            let Some(output) = output.ok().filter(|output| !output.is_empty()) else {
+                continue;
+            };
+
+            if index == tui_state.viewed
+                && let Some(hub) = &hub
+            {
+                hub.publish(&output);
+            }
+
+            if let Some(session) = session.as_deref_mut() {
+                session.record_output(&controller.port, &output);
+            }
 
-        if let Ok(output) = try_read_terminal(connection).await
-            && !output.is_empty()
-        {
             for byte in output.iter() {
                 let byte = if *byte == b'\n' {
                     b"\r\n"
                 } else {
                     std::slice::from_ref(byte)
                 };
-                tui_state.parser.process(byte);
+                controller.parser.process(byte);
             }
         }
     }
     ratatui::restore();
-    set_match_mode(connection, MatchMode::Disabled).await?;
+
+    broadcast_match_mode(controllers, MatchMode::Disabled, session).await;
+    for controller in controllers.iter() {
+        if let Some(error) = &controller.error {
+            log::warn!("Couldn't disable the robot on {}: {error}", controller.port);
+        }
+    }
+
     Ok(())
 }
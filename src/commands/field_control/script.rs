@@ -0,0 +1,289 @@
+//! Headless match timelines for `cargo v5 fc run --script <FILE>`.
+//!
+//! A script is a TOML file listing the steps of a match in order. Durations are in seconds.
+//!
+//! ```toml
+//! # Run the whole timeline this many times.
+//! repeat = 20
+//!
+//! [[step]]
+//! mode = "auto"
+//! duration = 15
+//!
+//! [[step]]
+//! mode = "driver"
+//! duration = 105
+//!
+//! # Wait without changing modes.
+//! [[step]]
+//! delay = 5
+//!
+//! # Steps can be grouped and repeated.
+//! [[step]]
+//! repeat = 3
+//! step = [
+//!     { mode = "auto", duration = 15 },
+//!     { mode = "disabled", duration = 5 },
+//! ]
+//! ```
+
+use std::{
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use toml_edit::{DocumentMut, Item, TableLike};
+use vex_v5_serial::protocol::cdc2::controller::MatchMode;
+
+/// How often program output is read while waiting between steps.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+use super::{
+    FieldController, MatchLog, broadcast_match_mode, check_controllers, mode_name,
+    try_read_terminal,
+};
+use crate::errors::CliError;
+
+/// One step of a match script.
+#[derive(Debug, Clone, PartialEq)]
+enum Step {
+    /// Switch every robot to `mode`, then wait for `duration`.
+    Mode { mode: MatchMode, duration: Duration },
+
+    /// Wait without changing modes.
+    Delay(Duration),
+
+    /// Run `steps` the given number of times.
+    Repeat { times: u32, steps: Vec<Step> },
+}
+
+/// A match timeline read from a script file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchScript {
+    timeline: Step,
+}
+
+/// Reads steps out of a parsed script, reporting errors against its path.
+struct ScriptReader<'a> {
+    path: &'a Path,
+}
+
+impl ScriptReader<'_> {
+    fn malformed(&self, step: &str, message: &str) -> CliError {
+        let message = if step.is_empty() {
+            message.to_string()
+        } else {
+            format!("step {step}: {message}")
+        };
+
+        CliError::MalformedMatchScript(self.path.to_path_buf(), message)
+    }
+
+    fn seconds(&self, step: &str, table: &dyn TableLike, key: &str) -> Result<Duration, CliError> {
+        let item = table
+            .get(key)
+            .ok_or_else(|| self.malformed(step, &format!("missing `{key}`")))?;
+
+        item.as_float()
+            .or_else(|| item.as_integer().map(|secs| secs as f64))
+            .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+            .ok_or_else(|| {
+                self.malformed(
+                    step,
+                    &format!("`{key}` should be a positive number of seconds"),
+                )
+            })
+    }
+
+    /// Reads a `step` list (either an array of tables or an array of inline tables), along with
+    /// the `repeat` count next to it.
+    fn repeat(&self, step: &str, table: &dyn TableLike) -> Result<Step, CliError> {
+        let times = match table.get("repeat") {
+            Some(item) => item
+                .as_integer()
+                .and_then(|times| u32::try_from(times).ok())
+                .filter(|times| *times > 0)
+                .ok_or_else(|| self.malformed(step, "`repeat` should be a positive integer"))?,
+            None => 1,
+        };
+
+        let children: Vec<&dyn TableLike> = match table.get("step") {
+            Some(Item::ArrayOfTables(tables)) => {
+                tables.iter().map(|table| table as &dyn TableLike).collect()
+            }
+            Some(item) => item
+                .as_array()
+                .and_then(|array| {
+                    array
+                        .iter()
+                        .map(|value| value.as_inline_table().map(|t| t as &dyn TableLike))
+                        .collect()
+                })
+                .ok_or_else(|| self.malformed(step, "`step` should be a list of steps"))?,
+            None => return Err(self.malformed(step, "no steps to run")),
+        };
+
+        let steps = children
+            .into_iter()
+            .enumerate()
+            .map(|(index, child)| {
+                let child_step = if step.is_empty() {
+                    format!("{}", index + 1)
+                } else {
+                    format!("{step}.{}", index + 1)
+                };
+                self.step(&child_step, child)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Step::Repeat { times, steps })
+    }
+
+    fn step(&self, step: &str, table: &dyn TableLike) -> Result<Step, CliError> {
+        if let Some(mode) = table.get("mode") {
+            let mode = match mode.as_str() {
+                Some("auto" | "autonomous") => MatchMode::Auto,
+                Some("driver") => MatchMode::Driver,
+                Some("disabled") => MatchMode::Disabled,
+                _ => {
+                    return Err(self.malformed(
+                        step,
+                        "`mode` should be \"auto\", \"driver\", or \"disabled\"",
+                    ));
+                }
+            };
+
+            Ok(Step::Mode {
+                mode,
+                duration: self.seconds(step, table, "duration")?,
+            })
+        } else if table.contains_key("delay") {
+            Ok(Step::Delay(self.seconds(step, table, "delay")?))
+        } else if table.contains_key("step") {
+            self.repeat(step, table)
+        } else {
+            Err(self.malformed(step, "expected `mode`, `delay`, or a `step` list"))
+        }
+    }
+}
+
+impl MatchScript {
+    /// Parses the contents of the script at `path`.
+    pub fn parse(path: &Path, contents: &str) -> Result<Self, CliError> {
+        let document = contents.parse::<DocumentMut>().map_err(|err| {
+            CliError::MalformedMatchScript(path.to_path_buf(), err.message().to_string())
+        })?;
+
+        Ok(Self {
+            timeline: ScriptReader { path }.repeat("", document.as_table())?,
+        })
+    }
+
+    /// Reads the script at `path`.
+    pub fn load(path: &Path) -> Result<Self, CliError> {
+        Self::parse(path, &fs_err::read_to_string(path)?)
+    }
+}
+
+/// Waits for `duration`, recording program output to `session` in the meantime if given.
+async fn wait(
+    controllers: &mut [FieldController],
+    duration: Duration,
+    session: Option<&mut MatchLog>,
+) {
+    let Some(session) = session else {
+        tokio::time::sleep(duration).await;
+        return;
+    };
+
+    let deadline = tokio::time::Instant::now() + duration;
+    while tokio::time::Instant::now() < deadline {
+        for controller in controllers.iter_mut() {
+            if let Ok(output) = try_read_terminal(&mut controller.connection).await {
+                session.record_output(&controller.port, &output);
+            }
+        }
+
+        tokio::time::sleep_until(deadline.min(tokio::time::Instant::now() + POLL_INTERVAL)).await;
+    }
+}
+
+/// Runs the steps of a script, logging each transition to stdout.
+async fn run_step(
+    controllers: &mut [FieldController],
+    step: &Step,
+    start: Instant,
+    mut session: Option<&mut MatchLog>,
+) {
+    let elapsed = start.elapsed().as_secs_f64();
+
+    match step {
+        Step::Mode { mode, duration } => {
+            println!(
+                "[{elapsed:>8.1}s] {} for {}s",
+                mode_name(*mode),
+                duration.as_secs_f64()
+            );
+            broadcast_match_mode(controllers, *mode, session.as_deref_mut()).await;
+
+            for controller in controllers.iter() {
+                if let Some(error) = &controller.error {
+                    log::warn!(
+                        "The controller on {} didn't respond: {error}",
+                        controller.port
+                    );
+                }
+            }
+
+            wait(controllers, *duration, session).await;
+        }
+        Step::Delay(duration) => {
+            println!("[{elapsed:>8.1}s] wait {}s", duration.as_secs_f64());
+            wait(controllers, *duration, session).await;
+        }
+        Step::Repeat { times, steps } => {
+            for iteration in 1..=*times {
+                if *times > 1 {
+                    println!(
+                        "[{:>8.1}s] loop {iteration}/{times}",
+                        start.elapsed().as_secs_f64()
+                    );
+                }
+
+                for step in steps {
+                    Box::pin(run_step(controllers, step, start, session.as_deref_mut())).await;
+                }
+            }
+        }
+    }
+}
+
+/// Runs `script` on every controller, then disables their robots.
+///
+/// The robots are also disabled if the script is interrupted with Ctrl-C. Mode changes and program
+/// output are recorded to `session`, if given.
+pub async fn run_match_script(
+    controllers: &mut [FieldController],
+    script: &MatchScript,
+    mut session: Option<&mut MatchLog>,
+) -> Result<(), CliError> {
+    check_controllers(controllers).await?;
+
+    let start = Instant::now();
+    tokio::select! {
+        () = run_step(controllers, &script.timeline, start, session.as_deref_mut()) => {}
+        _ = tokio::signal::ctrl_c() => {
+            println!("[{:>8.1}s] interrupted", start.elapsed().as_secs_f64());
+        }
+    }
+
+    println!("[{:>8.1}s] disabled", start.elapsed().as_secs_f64());
+    broadcast_match_mode(controllers, MatchMode::Disabled, session).await;
+    for controller in controllers.iter() {
+        if let Some(error) = &controller.error {
+            log::warn!("Couldn't disable the robot on {}: {error}", controller.port);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,124 @@
+//! Match session logs for `cargo v5 fc --export <FILE>`.
+//!
+//! A session log is a JSON file recording every match mode change and every line of program output
+//! while field control ran, so autonomous failures can be reviewed after practice:
+//!
+//! ```json
+//! {
+//!   "version": "0.12.1",
+//!   "started": "2025-03-01T17:04:12.511Z",
+//!   "controllers": ["/dev/ttyACM0"],
+//!   "events": [
+//!     { "t": 0.0, "event": "mode", "mode": "disabled", "unresponsive": [] },
+//!     { "t": 4.2, "event": "mode", "mode": "auto", "unresponsive": [] },
+//!     { "t": 4.9, "event": "output", "controller": "/dev/ttyACM0", "line": "Starting auton" }
+//!   ]
+//! }
+//! ```
+//!
+//! Times are in seconds since field control started.
+
+use std::{collections::HashMap, path::Path, time::Instant};
+
+use chrono::{SecondsFormat, Utc};
+use serde_json::{Value, json};
+use vex_v5_serial::protocol::cdc2::controller::MatchMode;
+
+use super::{FieldController, mode_name};
+use crate::errors::CliError;
+
+/// A record of a field control session, written out with [`MatchLog::export`].
+pub struct MatchLog {
+    started: String,
+    start: Instant,
+    controllers: Vec<String>,
+    events: Vec<Value>,
+
+    /// Program output that hasn't ended in a newline yet, by controller.
+    partial_lines: HashMap<String, Vec<u8>>,
+}
+
+impl MatchLog {
+    pub fn new(controllers: &[FieldController]) -> Self {
+        Self {
+            started: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+            start: Instant::now(),
+            controllers: controllers
+                .iter()
+                .map(|controller| controller.port.clone())
+                .collect(),
+            events: Vec::new(),
+            partial_lines: HashMap::new(),
+        }
+    }
+
+    fn push(&mut self, mut event: Value) {
+        event["t"] = json!((self.start.elapsed().as_millis() as f64) / 1000.0);
+        self.events.push(event);
+    }
+
+    /// Records that the controllers were switched to `mode`, along with any that didn't respond.
+    pub fn record_mode(&mut self, mode: MatchMode, controllers: &[FieldController]) {
+        let unresponsive = controllers
+            .iter()
+            .filter(|controller| controller.error.is_some())
+            .map(|controller| controller.port.as_str())
+            .collect::<Vec<_>>();
+
+        self.push(json!({
+            "event": "mode",
+            "mode": mode_name(mode),
+            "unresponsive": unresponsive,
+        }));
+    }
+
+    /// Records program output from the robot on `port`, a line at a time.
+    pub fn record_output(&mut self, port: &str, data: &[u8]) {
+        let mut partial = self.partial_lines.remove(port).unwrap_or_default();
+
+        for &byte in data {
+            if byte == b'\n' {
+                self.push_line(port, &partial);
+                partial.clear();
+            } else {
+                partial.push(byte);
+            }
+        }
+
+        self.partial_lines.insert(port.to_string(), partial);
+    }
+
+    fn push_line(&mut self, port: &str, line: &[u8]) {
+        let line = String::from_utf8_lossy(line);
+        self.push(json!({
+            "event": "output",
+            "controller": port,
+            "line": line.trim_end_matches('\r'),
+        }));
+    }
+
+    /// Writes the session to `path`, including any output that never ended in a newline.
+    pub fn export(mut self, path: &Path) -> Result<(), CliError> {
+        for (port, partial) in std::mem::take(&mut self.partial_lines) {
+            if !partial.is_empty() {
+                self.push_line(&port, &partial);
+            }
+        }
+
+        let session = json!({
+            "version": env!("CARGO_PKG_VERSION"),
+            "started": self.started,
+            "controllers": self.controllers,
+            "events": self.events,
+        });
+        fs_err::write(path, format!("{session:#}\n"))?;
+
+        eprintln!(
+            "\x1b[1;92m{:>12}\x1b[0m match log to {}",
+            "Exported",
+            path.display()
+        );
+
+        Ok(())
+    }
+}
@@ -4,10 +4,11 @@ use ratatui::{
     Frame,
     layout::{Position, Rect},
     style::{Color, Style, Stylize},
-    symbols::border::ROUNDED,
     widgets::{Block, Clear, Paragraph, Widget, Wrap},
 };
 
+use super::border_set;
+
 pub fn set_duration_digit(digit: u8, pos: usize, duration: Duration) -> Duration {
     assert!((0..=9).contains(&digit), "Digit out of bounds");
     let digit = digit as u64;
@@ -138,15 +139,17 @@ impl HelpPopup {
         'j', 'down' - Move focus down
         'k', 'up' - Move focus up
         'space', 'enter' - Select
+        't' - Switch match template
+        'tab' - Show the next controller's program output
         '0'-'9' - Set digit in mode duration input
         '?' - Show this help";
-    pub const LINES: u16 = 9;
+    pub const LINES: u16 = 11;
 }
 impl Widget for HelpPopup {
     fn render(self, area: Rect, buf: &mut ratatui::prelude::Buffer) {
         Clear.render(area, buf);
         let block = Block::bordered()
-            .border_set(ROUNDED)
+            .border_set(border_set())
             .title("Help")
             .title_style(Style::default().fg(Color::White).bold());
         Paragraph::new(Self::HELP_TEXT)
@@ -0,0 +1,134 @@
+use std::time::Duration;
+
+use vex_v5_serial::{
+    Connection,
+    protocol::{
+        Version,
+        cdc2::system::{SystemStatus, SystemStatusPacket, SystemStatusReplyPacket},
+    },
+};
+
+use crate::{connection::DeviceConnection, errors::CliError};
+
+/// Catalog listing the latest public VEXos release for the V5 Brain.
+#[cfg(feature = "firmware")]
+pub const CATALOG_URL: &str = "https://content.vexrobotics.com/vexos/public/V5/vexos/catalog.txt";
+
+pub fn format_version(version: Version) -> String {
+    format!(
+        "{}.{}.{}.b{}",
+        version.major, version.minor, version.build, version.beta
+    )
+}
+
+pub async fn system_status(connection: &mut DeviceConnection) -> Result<SystemStatus, CliError> {
+    Ok(connection
+        .handshake::<SystemStatusReplyPacket>(
+            Duration::from_millis(500),
+            10,
+            SystemStatusPacket::new(()),
+        )
+        .await?
+        .payload?)
+}
+
+/// Print the firmware versions installed on the Brain.
+pub async fn status(connection: &mut DeviceConnection) -> Result<(), CliError> {
+    let status = system_status(connection).await?;
+
+    println!(
+        "\x1b[1mVEXos\x1b[0m   {}",
+        status
+            .system_version
+            .map(format_version)
+            .unwrap_or_else(|| "unknown (connected through a controller)".to_string())
+    );
+    println!(
+        "\x1b[1mCPU0\x1b[0m    {}",
+        format_version(status.cpu0_version)
+    );
+    println!(
+        "\x1b[1mCPU1\x1b[0m    {}",
+        format_version(status.cpu1_version)
+    );
+    println!(
+        "\x1b[1mTouch\x1b[0m   {}",
+        format_version(status.touch_version)
+    );
+
+    if let Some(details) = status.details {
+        println!(
+            "\x1b[1mGolden\x1b[0m  {}",
+            format_version(details.golden_version)
+        );
+        if let Some(nxp_version) = details.nxp_version {
+            println!("\x1b[1mNXP\x1b[0m     {}", format_version(nxp_version));
+        }
+    }
+
+    Ok(())
+}
+
+/// Parses a catalog entry such as `VEXOS_V5_1_1_5_0` into a version.
+#[cfg(feature = "firmware")]
+fn parse_catalog(catalog: &str) -> Option<Version> {
+    let mut parts = catalog.trim().rsplitn(5, '_');
+    let beta = parts.next()?.parse().ok()?;
+    let build = parts.next()?.parse().ok()?;
+    let minor = parts.next()?.parse().ok()?;
+    let major = parts.next()?.parse().ok()?;
+
+    Some(Version {
+        major,
+        minor,
+        build,
+        beta,
+    })
+}
+
+/// Fetches the latest public VEXos version from VEX's CDN.
+#[cfg(feature = "firmware")]
+pub async fn latest_version() -> Result<Version, CliError> {
+    let catalog = reqwest::get(CATALOG_URL)
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+
+    parse_catalog(&catalog).ok_or(CliError::MalformedResponse)
+}
+
+/// Compare the Brain's VEXos version against the latest public release.
+///
+/// cargo-v5 doesn't install firmware, since flashing it uses a different transfer sequence than
+/// user files and an interrupted transfer can leave the Brain unbootable, so this only reports
+/// whether an update is available and how to install it.
+#[cfg(feature = "firmware")]
+pub async fn check(connection: &mut DeviceConnection) -> Result<(), CliError> {
+    let (status, latest) = tokio::try_join!(system_status(connection), latest_version())?;
+
+    let Some(current) = status.system_version else {
+        eprintln!(
+            "     \x1b[1;93mWarning\x1b[0m Could not read the VEXos version over a controller. Connect to the Brain directly over USB."
+        );
+        return Ok(());
+    };
+
+    if current >= latest {
+        eprintln!(
+            "  \x1b[1;92mUp to date\x1b[0m VEXos {}",
+            format_version(current)
+        );
+    } else {
+        eprintln!(
+            "   \x1b[1;93mAvailable\x1b[0m VEXos {} (installed: {})",
+            format_version(latest),
+            format_version(current)
+        );
+        eprintln!(
+            "\n        \x1b[1;96mNote\x1b[0m Update the Brain's firmware using VEXcode or the VEXos Utility."
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,188 @@
+//! A history of every upload made from this machine, shown by `cargo v5 history`.
+//!
+//! Each upload is appended as a line of JSON to `~/.local/share/cargo-v5/history.jsonl` (or
+//! under `XDG_DATA_HOME`), so transfer speeds can be compared over time to spot a failing radio
+//! link. Set `CARGO_V5_HISTORY` to use another file.
+
+use std::{
+    collections::BTreeMap,
+    env,
+    io::{self, Write},
+    path::PathBuf,
+    time::Duration,
+};
+
+use fs_err::OpenOptions;
+use humansize::{BINARY, format_size};
+use serde_json::{Value, json};
+use tabwriter::TabWriter;
+use vex_v5_serial::ConnectionType;
+
+use crate::errors::CliError;
+
+/// Returns the path of the upload history file, whether or not it exists.
+pub fn history_path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("CARGO_V5_HISTORY") {
+        return Some(PathBuf::from(path));
+    }
+
+    let data_dir = env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| env::home_dir().map(|home| home.join(".local").join("share")))?;
+
+    Some(data_dir.join("cargo-v5").join("history.jsonl"))
+}
+
+/// A finished upload, as recorded in the history.
+#[derive(Debug, Clone)]
+pub struct UploadHistoryEntry {
+    pub slot: u8,
+    pub name: String,
+    /// Size of the program.
+    pub size: u64,
+    /// Bytes actually sent to the Brain, which is zero if the upload was skipped.
+    pub transferred: u64,
+    pub strategy: &'static str,
+    pub link: ConnectionType,
+    pub duration: Duration,
+}
+
+fn link_name(link: ConnectionType) -> &'static str {
+    match link {
+        ConnectionType::Wired => "wired",
+        ConnectionType::Controller => "controller",
+        ConnectionType::Bluetooth => "bluetooth",
+    }
+}
+
+/// Appends an upload to the history.
+///
+/// The history is only for diagnostics, so failing to write it is logged rather than failing the
+/// upload.
+pub fn record_upload_history(entry: UploadHistoryEntry) {
+    let Some(path) = history_path() else {
+        return;
+    };
+
+    let line = json!({
+        "date": chrono::Utc::now().format("%Y-%m-%d %H:%M:%S UTC").to_string(),
+        "slot": entry.slot,
+        "name": entry.name,
+        "size": entry.size,
+        "transferred": entry.transferred,
+        "strategy": entry.strategy,
+        "link": link_name(entry.link),
+        "duration": entry.duration.as_secs_f64(),
+    });
+
+    let result = (|| {
+        if let Some(dir) = path.parent() {
+            fs_err::create_dir_all(dir)?;
+        }
+
+        let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+        writeln!(file, "{line}")
+    })();
+
+    if let Err(err) = result {
+        log::warn!("Couldn't record the upload in {}: {err}", path.display());
+    }
+}
+
+/// Reads every upload in the history, oldest first. Lines that can't be read are skipped.
+fn read_history() -> Result<Vec<Value>, CliError> {
+    let Some(path) = history_path() else {
+        return Ok(Vec::new());
+    };
+
+    let contents = match fs_err::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err.into()),
+    };
+
+    Ok(contents
+        .lines()
+        .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+        .filter(Value::is_object)
+        .collect())
+}
+
+fn format_speed(bytes: u64, seconds: f64) -> String {
+    if seconds > 0.0 {
+        format!("{}/s", format_size((bytes as f64 / seconds) as u64, BINARY))
+    } else {
+        "-".to_string()
+    }
+}
+
+/// Prints the `count` most recent uploads, followed by the average transfer speed over each kind
+/// of link.
+pub fn history(count: usize) -> Result<(), CliError> {
+    let entries = read_history()?;
+    if entries.is_empty() {
+        eprintln!("No uploads have been recorded yet.");
+        return Ok(());
+    }
+
+    let mut tw = TabWriter::new(io::stdout());
+    writeln!(
+        tw,
+        "\x1b[1mDate\tSlot\tProgram\tLink\tStrategy\tSize\tSent\tTime\tSpeed\x1b[0m"
+    )?;
+
+    for entry in entries.iter().rev().take(count).rev() {
+        let transferred = entry["transferred"].as_u64().unwrap_or_default();
+        let duration = entry["duration"].as_f64().unwrap_or_default();
+
+        writeln!(
+            tw,
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{duration:.1}s\t{}",
+            entry["date"].as_str().unwrap_or("-"),
+            entry["slot"],
+            entry["name"].as_str().unwrap_or("-"),
+            entry["link"].as_str().unwrap_or("-"),
+            entry["strategy"].as_str().unwrap_or("-"),
+            format_size(entry["size"].as_u64().unwrap_or_default(), BINARY),
+            if transferred > 0 {
+                format_size(transferred, BINARY)
+            } else {
+                "skipped".to_string()
+            },
+            if transferred > 0 {
+                format_speed(transferred, duration)
+            } else {
+                "-".to_string()
+            },
+        )?;
+    }
+
+    // Total bytes sent, time taken, and number of uploads over each kind of link.
+    let mut links = BTreeMap::<&str, (u64, f64, usize)>::new();
+    for entry in &entries {
+        let transferred = entry["transferred"].as_u64().unwrap_or_default();
+        if transferred == 0 {
+            continue;
+        }
+
+        let link = links
+            .entry(entry["link"].as_str().unwrap_or("unknown"))
+            .or_default();
+        link.0 += transferred;
+        link.1 += entry["duration"].as_f64().unwrap_or_default();
+        link.2 += 1;
+    }
+
+    if !links.is_empty() {
+        writeln!(tw)?;
+        writeln!(tw, "\x1b[1mLink\tUploads\tAverage Speed\x1b[0m")?;
+        for (link, (bytes, seconds, uploads)) in links {
+            writeln!(tw, "{link}\t{uploads}\t{}", format_speed(bytes, seconds))?;
+        }
+    }
+
+    tw.flush()?;
+
+    Ok(())
+}
@@ -0,0 +1,208 @@
+//! A summary of a Brain's state, similar to `vexcom --status`.
+
+use std::{
+    io::{self, Write},
+    time::Duration,
+};
+
+use humansize::{BINARY, format_size};
+use serde_json::json;
+use tabwriter::TabWriter;
+use vex_v5_serial::{
+    Connection, ConnectionType,
+    protocol::{
+        cdc::{ProductType, SystemVersionPacket, SystemVersionReplyPacket},
+        cdc2::system::{
+            RadioStatus, RadioStatusPacket, RadioStatusReplyPacket, SystemFlagsPacket,
+            SystemFlagsReplyPacket,
+        },
+    },
+};
+
+use crate::{connection::DeviceConnection, errors::CliError};
+
+use super::{
+    dir::{USEFUL_VIDS, enable_factory_access, read_directory},
+    firmware::{format_version, system_status},
+    key_value::kv_get,
+    upload::{MessageFormat, emit_message},
+};
+
+/// Reads the Brain's battery charge as a percentage, and whether it's charging.
+pub async fn battery_level(connection: &mut DeviceConnection) -> Result<(u32, bool), CliError> {
+    let flags = connection
+        .handshake::<SystemFlagsReplyPacket>(
+            Duration::from_millis(500),
+            2,
+            SystemFlagsPacket::new(()),
+        )
+        .await?
+        .payload?;
+
+    // The low nibble counts the battery's charge in steps of 8%.
+    let battery = ((flags.byte_1 & 0x0F) as u32 * 8).min(100);
+    let charging = flags.flags & (1 << 14) != 0;
+
+    Ok((battery, charging))
+}
+
+/// Charge of the connected controllers' batteries, as percentages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ControllerBatteries {
+    pub primary: u32,
+    /// `None` if there's no partner controller.
+    pub partner: Option<u32>,
+}
+
+/// Reads the charge of the controllers connected to the Brain.
+pub async fn controller_batteries(
+    connection: &mut DeviceConnection,
+) -> Result<ControllerBatteries, CliError> {
+    let flags = connection
+        .handshake::<SystemFlagsReplyPacket>(
+            Duration::from_millis(500),
+            2,
+            SystemFlagsPacket::new(()),
+        )
+        .await?
+        .payload?;
+
+    // The high nibbles count the controllers' charge in steps of 8%, like the Brain's battery.
+    let primary = ((flags.byte_1 >> 4) as u32 * 8).min(100);
+    let partner = (flags.flags & (1 << 18) != 0).then(|| ((flags.byte_2 >> 4) as u32 * 8).min(100));
+
+    Ok(ControllerBatteries { primary, partner })
+}
+
+/// Reads the status of the Brain's VEXnet radio, or `None` if it doesn't have one.
+pub async fn radio_status(connection: &mut DeviceConnection) -> Option<RadioStatus> {
+    connection
+        .handshake::<RadioStatusReplyPacket>(
+            Duration::from_millis(500),
+            2,
+            RadioStatusPacket::new(()),
+        )
+        .await
+        .ok()
+        .and_then(|reply| reply.payload.ok())
+}
+
+/// Print an overview of the connected Brain.
+///
+/// Flash usage is the total size of the files on the Brain, since VEXos doesn't report how much
+/// space is left.
+pub async fn info(
+    connection: &mut DeviceConnection,
+    message_format: MessageFormat,
+) -> Result<(), CliError> {
+    let connection_type = match connection.connection_type() {
+        ConnectionType::Wired => "USB",
+        ConnectionType::Controller => "Controller",
+        ConnectionType::Bluetooth => "Bluetooth",
+    };
+
+    let version = connection
+        .handshake::<SystemVersionReplyPacket>(
+            Duration::from_millis(500),
+            1,
+            SystemVersionPacket::new(()),
+        )
+        .await?
+        .payload;
+    let product = match version.product_type {
+        ProductType::V5Brain => "V5 Brain",
+        ProductType::ExpBrain => "EXP Brain",
+        ProductType::Controller => "V5 Controller",
+    };
+
+    let status = system_status(connection).await?;
+    let (battery, charging) = battery_level(connection).await?;
+
+    // EXP Brains don't have a VEXnet radio.
+    let radio = if version.product_type == ProductType::ExpBrain {
+        None
+    } else {
+        radio_status(connection).await
+    };
+
+    let name = kv_get(connection, "robotname").await?;
+    let team = kv_get(connection, "teamnumber").await?;
+
+    enable_factory_access(connection).await;
+    let mut files = 0;
+    let mut used = 0;
+    for vid in USEFUL_VIDS {
+        let entries = read_directory(connection, vid).await?;
+        files += entries.len();
+        used += entries.iter().map(|entry| entry.size as u64).sum::<u64>();
+    }
+
+    if message_format == MessageFormat::Json {
+        emit_message(json!({
+            "connection": connection_type,
+            "product": product,
+            "product_version": format_version(version.version),
+            "vexos": status.system_version.map(format_version),
+            "name": name,
+            "team": team,
+            "battery": battery,
+            "charging": charging,
+            "radio": radio.as_ref().map(|radio| json!({
+                "channel": radio.channel,
+                "quality": radio.quality,
+                "strength": radio.strength,
+            })),
+            "files": files,
+            "used_bytes": used,
+        }));
+        return Ok(());
+    }
+
+    let mut tw = TabWriter::new(io::stdout());
+
+    writeln!(tw, "\x1b[1mConnection\x1b[0m\t{connection_type}")?;
+    writeln!(
+        tw,
+        "\x1b[1mDevice\x1b[0m\t{product} ({})",
+        format_version(version.version)
+    )?;
+    writeln!(
+        tw,
+        "\x1b[1mVEXos\x1b[0m\t{}",
+        status
+            .system_version
+            .map(format_version)
+            .unwrap_or_else(|| "unknown (connected through a controller)".to_string())
+    )?;
+    writeln!(
+        tw,
+        "\x1b[1mName\x1b[0m\t{}",
+        name.as_deref().unwrap_or("(not set)")
+    )?;
+    writeln!(
+        tw,
+        "\x1b[1mTeam\x1b[0m\t{}",
+        team.as_deref().unwrap_or("(not set)")
+    )?;
+    writeln!(
+        tw,
+        "\x1b[1mBattery\x1b[0m\t{battery}%{}",
+        if charging { " (charging)" } else { "" }
+    )?;
+    if let Some(radio) = radio {
+        writeln!(
+            tw,
+            "\x1b[1mRadio\x1b[0m\tchannel {}, quality {}%, {} dBm",
+            radio.channel, radio.quality, radio.strength
+        )?;
+    }
+    writeln!(
+        tw,
+        "\x1b[1mFlash\x1b[0m\t{} used by {files} files",
+        format_size(used, BINARY)
+    )?;
+
+    tw.flush()?;
+
+    Ok(())
+}
@@ -1,16 +1,51 @@
-use std::time::Duration;
+use std::{path::Path, time::Duration};
+
+use clap::ValueEnum;
+use serde_json::{Map, Value};
+use toml_edit::DocumentMut;
 use vex_v5_serial::Connection;
 use vex_v5_serial::protocol::FixedString;
+use vex_v5_serial::protocol::cdc2::Cdc2Ack;
 use vex_v5_serial::protocol::cdc2::system::{
     KeyValueLoadPacket, KeyValueLoadReplyPacket, KeyValueSavePacket, KeyValueSavePayload,
     KeyValueSaveReplyPacket,
 };
-use vex_v5_serial::serial::SerialConnection;
 
-use crate::errors::CliError;
+use crate::{connection::DeviceConnection, errors::CliError};
+
+use super::team::TEAM_KEYS;
+
+/// Format of a key/value dump.
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DumpFormat {
+    /// A TOML table of keys and their values.
+    #[default]
+    Toml,
+
+    /// A JSON object of keys and their values.
+    Json,
+}
+
+impl DumpFormat {
+    /// Guesses the format of a dump from its file extension, defaulting to TOML.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => Self::Json,
+            _ => Self::Toml,
+        }
+    }
+}
+
+/// Returns every key cargo-v5 knows about.
+///
+/// VEXos has no way to list the keys it stores, so dumps are limited to these.
+pub fn known_keys() -> Vec<&'static str> {
+    TEAM_KEYS.to_vec()
+}
 
+/// Sets the value of `key`.
 pub async fn kv_set(
-    connection: &mut SerialConnection,
+    connection: &mut DeviceConnection,
     key: &str,
     value: &str,
 ) -> Result<(), CliError> {
@@ -29,14 +64,122 @@ pub async fn kv_set(
     Ok(())
 }
 
-pub async fn kv_get(connection: &mut SerialConnection, key: &str) -> Result<String, CliError> {
-    Ok(connection
+/// Reads the value of `key`, returning `None` if the Brain doesn't have it set.
+pub async fn kv_get<C: Connection>(
+    connection: &mut C,
+    key: &str,
+) -> Result<Option<String>, CliError>
+where
+    CliError: From<C::Error>,
+{
+    let reply = connection
         .handshake::<KeyValueLoadReplyPacket>(
             Duration::from_millis(500),
             1,
             KeyValueLoadPacket::new(FixedString::new(key)?),
         )
-        .await?
-        .payload?
-        .to_string())
+        .await?;
+
+    // VEXos either replies to unknown keys with a general NACK or with an empty value, depending
+    // on the key. Any other NACK means the read itself failed.
+    match reply.payload {
+        Ok(value) if !value.is_empty() => Ok(Some(value.to_string())),
+        Ok(_) | Err(Cdc2Ack::Nack) => Ok(None),
+        Err(nack) => Err(nack.into()),
+    }
+}
+
+/// Sets every key in `entries` in order, printing each one once it's set.
+///
+/// The Brain keeps the keys set before a failure, so the error lists them.
+pub async fn kv_import(
+    connection: &mut DeviceConnection,
+    entries: Vec<(String, String)>,
+) -> Result<(), CliError> {
+    let mut applied = Vec::new();
+
+    for (key, value) in entries {
+        if let Err(err) = kv_set(connection, &key, &value).await {
+            return Err(CliError::PartialKeyValueImport {
+                key,
+                applied: if applied.is_empty() {
+                    "none".to_string()
+                } else {
+                    applied.join(", ")
+                },
+                source: Box::new(err),
+            });
+        }
+
+        println!("{key} = {value}");
+        applied.push(key);
+    }
+
+    Ok(())
+}
+
+/// Reads every known key that's set on the Brain and formats them as a dump.
+pub async fn kv_dump(
+    connection: &mut DeviceConnection,
+    format: DumpFormat,
+) -> Result<String, CliError> {
+    let mut values = Vec::new();
+    for key in known_keys() {
+        if let Some(value) = kv_get(connection, key).await? {
+            values.push((key, value));
+        }
+    }
+
+    Ok(match format {
+        DumpFormat::Toml => {
+            let mut document = DocumentMut::new();
+            for (key, value) in values {
+                document[key] = toml_edit::value(value);
+            }
+            document.to_string()
+        }
+        DumpFormat::Json => {
+            let object = values
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), Value::String(value)))
+                .collect::<Map<_, _>>();
+            serde_json::to_string_pretty(&object).unwrap() + "\n"
+        }
+    })
+}
+
+/// Parses a dump written by [`kv_dump`], returning its keys and values in order.
+pub fn parse_dump(path: &Path, contents: &str) -> Result<Vec<(String, String)>, CliError> {
+    let malformed = |message: String| CliError::MalformedKeyValueDump(path.to_path_buf(), message);
+
+    match DumpFormat::from_path(path) {
+        DumpFormat::Toml => {
+            let document = contents
+                .parse::<DocumentMut>()
+                .map_err(|err| malformed(err.message().to_string()))?;
+
+            document
+                .iter()
+                .map(|(key, item)| match item.as_str() {
+                    Some(value) => Ok((key.to_string(), value.to_string())),
+                    None => Err(malformed(format!("`{key}` must be a string"))),
+                })
+                .collect()
+        }
+        DumpFormat::Json => {
+            let value = serde_json::from_str::<Value>(contents)
+                .map_err(|err| malformed(err.to_string()))?;
+            let object = value
+                .as_object()
+                .ok_or_else(|| malformed("expected an object of keys and values".to_string()))?;
+
+            object
+                .iter()
+                .map(|(key, value)| match value.as_str() {
+                    Some(value) => Ok((key.clone(), value.to_string())),
+                    None => Err(malformed(format!("`{key}` must be a string"))),
+                })
+                .collect()
+        }
+    }
 }
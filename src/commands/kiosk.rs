@@ -0,0 +1,77 @@
+use std::time::Duration;
+
+use tokio::time::{Instant, sleep};
+use vex_v5_serial::protocol::cdc2::file::FileLoadAction;
+
+use super::program::{current_program, load_action};
+use crate::{connection::DeviceConnection, errors::CliError};
+
+/// How often the Brain is polled to check if the program is still running.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Time to wait between a program exiting and it being started again.
+const RESTART_DELAY: Duration = Duration::from_secs(1);
+
+/// Runs the program in `slot`, optionally restarting it whenever it exits.
+///
+/// If `max_runtime` is provided, the program is stopped once it has been running for that long
+/// (and then restarted, if looping).
+pub async fn kiosk(
+    connection: &mut DeviceConnection,
+    slot: u8,
+    looping: bool,
+    max_runtime: Option<Duration>,
+) -> Result<(), CliError> {
+    if !(1..=8).contains(&slot) {
+        return Err(CliError::SlotOutOfRange);
+    }
+
+    let file_name = format!("slot_{slot}.bin");
+    let mut runs = 0;
+
+    loop {
+        load_action(connection, FileLoadAction::Run, &file_name).await?;
+        runs += 1;
+        eprintln!("     \x1b[1;92mRunning\x1b[0m `{file_name}` (run {runs})");
+
+        let started = Instant::now();
+
+        // Give the program a moment to start before checking on it.
+        sleep(RESTART_DELAY).await;
+
+        loop {
+            if let Some(max_runtime) = max_runtime
+                && started.elapsed() >= max_runtime
+            {
+                eprintln!(
+                    "    \x1b[1;96mStopping\x1b[0m `{file_name}` after {}s",
+                    max_runtime.as_secs()
+                );
+                load_action(connection, FileLoadAction::Stop, "").await?;
+                break;
+            }
+
+            match current_program(connection).await {
+                Ok(Some(_)) => {}
+                Ok(None) => {
+                    eprintln!(
+                        "      \x1b[1;93mExited\x1b[0m `{file_name}` after {}s",
+                        started.elapsed().as_secs()
+                    );
+                    break;
+                }
+                // The Brain can briefly stop responding (e.g. during a radio hiccup), so we keep
+                // polling rather than giving up on the session.
+                Err(err) => log::debug!("Failed to poll program status: {err}"),
+            }
+
+            sleep(POLL_INTERVAL).await;
+        }
+
+        if !looping {
+            return Ok(());
+        }
+
+        sleep(RESTART_DELAY).await;
+    }
+}
@@ -1,79 +1,290 @@
+use std::fmt;
 use std::io::{self, Write};
 use std::num::NonZeroU32;
 use std::time::Duration;
 use tabwriter::{Alignment, TabWriter};
 use vex_v5_serial::{
     Connection,
-    protocol::cdc2::system::{LogReadPacket, LogReadPayload, LogReadReplyPacket},
-    serial::SerialConnection,
+    protocol::cdc2::system::{
+        LogEntry, LogReadPacket, LogReadPayload, LogReadReplyPacket, LogStatusPacket,
+        LogStatusReplyPacket,
+    },
 };
 
-use crate::errors::CliError;
+use crate::{connection::DeviceConnection, errors::CliError};
 
 const MAX_LOGS_PER_PAGE: u32 = 254;
 
-pub async fn log(connection: &mut SerialConnection, page: NonZeroU32) -> Result<(), CliError> {
+/// How often the log is checked for new entries while following it.
+const FOLLOW_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Number of existing entries shown before following the log if `--count` isn't given.
+const DEFAULT_FOLLOW_COUNT: u32 = 10;
+
+/// Number of the newest entries read first while following the log. More of the log is only read
+/// if the newest entry seen so far isn't among them.
+const FOLLOW_WINDOW: u32 = 16;
+
+/// An entry in the Brain's event log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LogRecord {
+    /// The entry's label. Pages label entries by their distance from the newest entry at the time
+    /// they were read, while reads of the whole log number them in order from the oldest entry
+    /// read, so that entries found while following the log keep counting up.
+    pub number: u32,
+    pub entry: LogEntry,
+}
+
+/// Prints entries from the Brain's event log.
+///
+/// Without any filters, a single page of the log is printed. `since` (milliseconds on the log's
+/// clock) and `count` instead select entries from the whole log, and `follow` keeps printing new
+/// entries as they are recorded until the command is interrupted.
+pub async fn log(
+    connection: &mut DeviceConnection,
+    page: NonZeroU32,
+    follow: bool,
+    since: Option<u32>,
+    count: Option<u32>,
+) -> Result<(), CliError> {
+    if !follow && since.is_none() && count.is_none() {
+        return print_records(&read_log_page(connection, page).await?);
+    }
+
+    let count = count.or((follow && since.is_none()).then_some(DEFAULT_FOLLOW_COUNT));
+    let records = read_log(connection, since, count).await?;
+    print_records(&records)?;
+
+    if follow {
+        let next_number = records.last().map_or(1, |record| record.number + 1);
+        follow_log(connection, since, next_number, |records| {
+            print_records(&records)
+        })
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Reads a single page of the log, where page 1 holds the newest entries.
+pub async fn read_log_page(
+    connection: &mut DeviceConnection,
+    page: NonZeroU32,
+) -> Result<Vec<LogRecord>, CliError> {
+    let offset = MAX_LOGS_PER_PAGE * page.get();
+    let entries = connection
+        .handshake::<LogReadReplyPacket>(
+            Duration::from_millis(500),
+            10,
+            LogReadPacket::new(LogReadPayload {
+                offset,
+                count: MAX_LOGS_PER_PAGE,
+            }),
+        )
+        .await?
+        .payload?
+        .entries;
+
+    Ok(entries
+        .into_iter()
+        .enumerate()
+        .map(|(i, entry)| LogRecord {
+            number: offset - i as u32,
+            entry,
+        })
+        .collect())
+}
+
+/// Reads entries from the whole log, oldest first.
+///
+/// Only entries recorded at or after `since` (milliseconds on the log's clock) are returned, and
+/// at most the newest `count` of those.
+pub async fn read_log(
+    connection: &mut DeviceConnection,
+    since: Option<u32>,
+    count: Option<u32>,
+) -> Result<Vec<LogRecord>, CliError> {
+    let total = log_count(connection).await?;
+    let wanted = match (since, count) {
+        (None, Some(count)) => count.min(total),
+        _ => total,
+    };
+
+    let entries = read_newest_entries(connection, wanted).await?;
+    let mut records = select_records(entries, total - wanted + 1, since);
+    if let Some(count) = count {
+        records.drain(..records.len().saturating_sub(count as usize));
+    }
+
+    Ok(records)
+}
+
+/// Passes new log entries to `on_records` as they are recorded, numbering them from
+/// `next_number`. This never returns unless an error occurs.
+///
+/// Once the log is full, the Brain drops its oldest entry for each new one, so new entries are
+/// found by looking for the newest entry seen so far rather than by counting them.
+pub async fn follow_log(
+    connection: &mut DeviceConnection,
+    since: Option<u32>,
+    mut next_number: u32,
+    mut on_records: impl FnMut(Vec<LogRecord>) -> Result<(), CliError>,
+) -> Result<(), CliError> {
+    let mut newest = read_newest_entries(connection, 1).await?.pop();
+
+    loop {
+        tokio::time::sleep(FOLLOW_INTERVAL).await;
+
+        let total = log_count(connection).await?;
+        let mut window = FOLLOW_WINDOW.min(total);
+        let new_entries = loop {
+            let mut entries = read_newest_entries(connection, window).await?;
+            let seen = newest.and_then(|newest| entries.iter().rposition(|entry| *entry == newest));
+
+            match seen {
+                Some(seen) => break entries.split_off(seen + 1),
+                // The newest entry seen so far might be further back, unless the log was
+                // cleared and every entry in it is new.
+                None if window < total.min(MAX_LOGS_PER_PAGE) => {
+                    window = total.min(MAX_LOGS_PER_PAGE);
+                }
+                None => break entries,
+            }
+        };
+
+        let Some(&last) = new_entries.last() else {
+            continue;
+        };
+        newest = Some(last);
+
+        let first_number = next_number;
+        next_number += new_entries.len() as u32;
+        on_records(select_records(new_entries, first_number, since))?;
+    }
+}
+
+/// Numbers entries (given oldest first) starting from `first_number` and drops any recorded
+/// before `since`.
+fn select_records(entries: Vec<LogEntry>, first_number: u32, since: Option<u32>) -> Vec<LogRecord> {
+    entries
+        .into_iter()
+        .zip(first_number..)
+        .map(|(entry, number)| LogRecord { number, entry })
+        .filter(|record| since.is_none_or(|since| record.entry.time >= since))
+        .collect()
+}
+
+fn print_records(records: &[LogRecord]) -> Result<(), CliError> {
     let mut tw = TabWriter::new(io::stdout())
         .tab_indent(false)
         .padding(1)
         .alignment(Alignment::Right);
 
-    let mut entries = Vec::new();
-    entries.extend(
-        connection
-            .handshake::<LogReadReplyPacket>(
-                Duration::from_millis(500),
-                10,
-                LogReadPacket::new(LogReadPayload {
-                    offset: MAX_LOGS_PER_PAGE * page.get(),
-                    count: MAX_LOGS_PER_PAGE,
-                }),
-            )
-            .await?
-            .payload?
-            .entries,
-    );
-
-    for (i, log) in entries.into_iter().enumerate() {
-        let time = log.time / 1000;
-        write!(
-            &mut tw,
-            "{}:\t[{:02}:{:02}:{:02}]\t",
-            (MAX_LOGS_PER_PAGE * page.get()) - (i as u32),
-            (time / 3600) % 24,
-            (time / 60) % 60,
-            time % 60
-        )?;
+    for record in records {
+        write_entry(&mut tw, record)?;
+    }
 
+    tw.flush()?;
+
+    Ok(())
+}
+
+/// Formats a log timestamp (in milliseconds) the way it's shown in the log.
+pub fn format_log_time(time: u32) -> String {
+    let time = time / 1000;
+    format!(
+        "{:02}:{:02}:{:02}",
+        (time / 3600) % 24,
+        (time / 60) % 60,
+        time % 60
+    )
+}
+
+/// Parses a `[[HH:]MM:]SS` timestamp on the log's clock into milliseconds.
+pub fn parse_log_time(time: &str) -> Result<u32, String> {
+    let parts = time.split(':').collect::<Vec<_>>();
+    if parts.len() > 3 {
+        return Err("expected a time like HH:MM:SS".to_string());
+    }
+
+    let too_far = || "time is too far in the future".to_string();
+
+    let mut seconds: u32 = 0;
+    for part in parts {
+        let value = part
+            .parse::<u32>()
+            .map_err(|_| format!("`{part}` is not a number"))?;
+        seconds = seconds
+            .checked_mul(60)
+            .and_then(|seconds| seconds.checked_add(value))
+            .ok_or_else(too_far)?;
+    }
+
+    seconds.checked_mul(1000).ok_or_else(too_far)
+}
+
+/// Rough category of a log entry, used to color it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryCategory {
+    /// Match and competition events.
+    Match,
+    Warning,
+    Error,
+    /// Power and battery events.
+    Battery,
+    Info,
+}
+
+impl EntryCategory {
+    pub fn of(log: &LogEntry) -> Self {
         if matches!(log.log_type, 10..=0xc) {
-            write!(&mut tw, "\x1B[1m")?; // Bold white
+            Self::Match
         } else if (128..u8::MAX).contains(&log.log_type) {
-            write!(&mut tw, "\x1B[33m")?; // Yellow (warning)
+            Self::Warning
         } else if matches!(
             log.description,
             2 | 8 | 9 | 0xf | 0x10 | 0x11 | 0x12 | 0x16 | 0x17 | 0x18 | 14
         ) {
-            write!(&mut tw, "\x1B[31m")?; // Error
+            Self::Error
         } else if log.description == 13 {
-            write!(&mut tw, "\x1B[32m")?; // Green (battery-related)
+            Self::Battery
         } else {
-            write!(&mut tw, "\x1B[34m")?; // Blue (default)
+            Self::Info
+        }
+    }
+
+    const fn color(self) -> &'static str {
+        match self {
+            Self::Match => "\x1B[1m",    // Bold white
+            Self::Warning => "\x1B[33m", // Yellow
+            Self::Error => "\x1B[31m",   // Red
+            Self::Battery => "\x1B[32m", // Green
+            Self::Info => "\x1B[34m",    // Blue
         }
+    }
+}
+
+/// Displays the human-readable description of a log entry.
+pub struct EntryMessage<'a>(pub &'a LogEntry);
+
+impl fmt::Display for EntryMessage<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let log = self.0;
 
         match log.log_type {
-            4 if log.description == 7 => writeln!(&mut tw, "Field tether connected")?,
-            9 if log.description == 7 => writeln!(&mut tw, "Radio linked")?,
+            4 if log.description == 7 => write!(f, "Field tether connected")?,
+            9 if log.description == 7 => write!(f, "Radio linked")?,
             10 => {
                 if log.description & 0b11000000 == 0 {
-                    writeln!(
-                        &mut tw,
+                    write!(
+                        f,
                         "VRC-{}-{}",
                         log.description & 0b00111111,
                         u32::from(log.code) * 256 + u32::from(log.spare)
                     )?
                 } else {
-                    writeln!(
-                        &mut tw,
+                    write!(
+                        f,
                         "XXX-{}-{}",
                         log.description & 0b00111111,
                         u32::from(log.code) * 256 + u32::from(log.spare)
@@ -83,18 +294,18 @@ pub async fn log(connection: &mut SerialConnection, page: NonZeroU32) -> Result<
             11 => {
                 let match_round = decode_match_round(log.description);
                 match log.description {
-                    2..=8 => writeln!(&mut tw, "{}-{}-{}", match_round, log.code, log.spare)?,
-                    9 | 99 => writeln!(
-                        &mut tw,
+                    2..=8 => write!(f, "{}-{}-{}", match_round, log.code, log.spare)?,
+                    9 | 99 => write!(
+                        f,
                         "{}-{:.04}",
                         match_round,
                         u32::from(log.code) * 256 + u32::from(log.spare)
                     )?,
-                    _ => writeln!(&mut tw, "Match error")?,
+                    _ => write!(f, "Match error")?,
                 }
             }
-            12 => writeln!(
-                &mut tw,
+            12 => write!(
+                f,
                 "--> {:.02}:{:.02}:{:.02}",
                 log.code, log.spare, log.description
             )?,
@@ -104,37 +315,33 @@ pub async fn log(connection: &mut SerialConnection, page: NonZeroU32) -> Result<
                 let error_string = decode_error_message(log.description);
 
                 match log.description {
-                    2 => writeln!(&mut tw, "{type_string} {error_string}")?,
+                    2 => write!(f, "{type_string} {error_string}")?,
                     7 | 8 => match log.log_type {
-                        3 => writeln!(
-                            &mut tw,
-                            "{} {} on port {}",
-                            device_string, error_string, log.code
-                        )?,
-                        4 => writeln!(&mut tw, "Field tether disconnected")?,
-                        _ => writeln!(&mut tw, "{type_string} {error_string}")?,
+                        3 => write!(f, "{} {} on port {}", device_string, error_string, log.code)?,
+                        4 => write!(f, "Field tether disconnected")?,
+                        _ => write!(f, "{type_string} {error_string}")?,
                     },
-                    9 => writeln!(&mut tw, "{error_string}")?,
+                    9 => write!(f, "{error_string}")?,
                     11 => {
                         if log.spare == 2 {
-                            writeln!(&mut tw, "{} Run", decode_default_program(0))?;
+                            write!(f, "{} Run", decode_default_program(0))?;
                         } else if log.spare == 1 && log.code == 0 {
-                            writeln!(&mut tw, "{} Run", decode_default_program(1))?;
+                            write!(f, "{} Run", decode_default_program(1))?;
                         } else {
-                            writeln!(&mut tw, "{} slot {}", error_string, log.code)?;
+                            write!(f, "{} slot {}", error_string, log.code)?;
                         }
                     }
                     13 => {
                         if log.code == 0 {
-                            writeln!(&mut tw, "{error_string}")?;
+                            write!(f, "{error_string}")?;
                         } else if log.code == 0xff {
-                            writeln!(&mut tw, "Power off")?;
+                            write!(f, "Power off")?;
                         } else if log.code == 0xf0 {
-                            writeln!(&mut tw, "Reset")?;
+                            write!(f, "Reset")?;
                         }
                     }
-                    14 => writeln!(
-                        &mut tw,
+                    14 => write!(
+                        f,
                         "{} {:.2}V {}% Capacity",
                         error_string,
                         log.code as f32 * 0.064,
@@ -142,27 +349,27 @@ pub async fn log(connection: &mut SerialConnection, page: NonZeroU32) -> Result<
                     )?,
                     15 => {
                         if log.spare == 0 {
-                            writeln!(&mut tw, "{error_string} Voltage")?;
+                            write!(f, "{error_string} Voltage")?;
                         } else {
-                            writeln!(&mut tw, "{} Cell {}", error_string, log.spare)?;
+                            write!(f, "{} Cell {}", error_string, log.spare)?;
                         }
                     }
-                    16 => writeln!(&mut tw, "{error_string} AFE fault")?,
-                    17 => writeln!(&mut tw, "Motor {} on port {}", error_string, log.code)?,
-                    18 => writeln!(
-                        &mut tw,
+                    16 => write!(f, "{error_string} AFE fault")?,
+                    17 => write!(f, "Motor {} on port {}", error_string, log.code)?,
+                    18 => write!(
+                        f,
                         "Motor {} {} on port {}",
                         error_string, log.spare, log.code
                     )?,
-                    22 => writeln!(&mut tw, "{error_string} Error")?,
-                    23 => writeln!(&mut tw, "Motor {error_string} Error")?,
-                    24 => writeln!(&mut tw, "{error_string}")?,
+                    22 => write!(f, "{error_string} Error")?,
+                    23 => write!(f, "Motor {error_string} Error")?,
+                    24 => write!(f, "{error_string}")?,
                     _ => {
                         if log.description < 26 {
-                            writeln!(&mut tw, "{error_string}")?;
+                            write!(f, "{error_string}")?;
                         } else {
-                            writeln!(
-                                &mut tw,
+                            write!(
+                                f,
                                 "?: {:.02X} {:.02X} {:.02X} {:.02X}",
                                 log.code, log.spare, log.description, log.log_type
                             )?;
@@ -171,17 +378,17 @@ pub async fn log(connection: &mut SerialConnection, page: NonZeroU32) -> Result<
                 }
             }
             128 => match log.code {
-                0x11 => writeln!(&mut tw, "Program error: Invalid")?,
-                0x12 => writeln!(&mut tw, "Program error: Abort")?,
-                0x13 => writeln!(&mut tw, "Program error: SDK")?,
-                0x14 => writeln!(&mut tw, "Program error: SDK Mismatch")?,
-                _ => writeln!(
-                    &mut tw,
+                0x11 => write!(f, "Program error: Invalid")?,
+                0x12 => write!(f, "Program error: Abort")?,
+                0x13 => write!(f, "Program error: SDK")?,
+                0x14 => write!(f, "Program error: SDK Mismatch")?,
+                _ => write!(
+                    f,
                     "U {:.02X}:{:.02X}:{:.02X}",
                     log.code, log.spare, log.description
                 )?,
             },
-            144 => writeln!(&mut tw, "Program: Tamper")?,
+            144 => write!(f, "Program: Tamper")?,
             160 => {
                 let r1 = if (log.spare & 1) != 0 {
                     Some("R1")
@@ -205,8 +412,8 @@ pub async fn log(connection: &mut SerialConnection, page: NonZeroU32) -> Result<
                 };
 
                 match log.code {
-                    1 => writeln!(
-                        &mut tw,
+                    1 => write!(
+                        f,
                         "FC: Cable - {}{}{}{}{}",
                         r1.unwrap_or_default(),
                         b1.unwrap_or_default(),
@@ -214,8 +421,8 @@ pub async fn log(connection: &mut SerialConnection, page: NonZeroU32) -> Result<
                         b2.unwrap_or_default(),
                         log.description
                     )?,
-                    2 => writeln!(
-                        &mut tw,
+                    2 => write!(
+                        f,
                         "FC: Radio - {}{}{}{}{}",
                         r1.unwrap_or_default(),
                         b1.unwrap_or_default(),
@@ -223,25 +430,83 @@ pub async fn log(connection: &mut SerialConnection, page: NonZeroU32) -> Result<
                         b2.unwrap_or_default(),
                         log.description
                     )?,
-                    _ => writeln!(
-                        &mut tw,
+                    _ => write!(
+                        f,
                         "FC: {:.02X}:{:.02X}:{:.02X}",
                         log.code, log.spare, log.description
                     )?,
                 }
             }
-            _ => writeln!(
-                &mut tw,
+            _ => write!(
+                f,
                 "X: {:.02X}:{:.02X}:{:.02X}",
                 log.code, log.spare, log.description
             )?,
         }
-        write!(&mut tw, "\x1B[0m")?;
+
+        Ok(())
     }
+}
 
-    tw.flush()?;
+/// Writes a single colored log entry, labeled with its distance from the newest entry.
+fn write_entry(tw: &mut impl Write, record: &LogRecord) -> io::Result<()> {
+    writeln!(
+        tw,
+        "{}:\t[{}]\t{}{}\x1B[0m",
+        record.number,
+        format_log_time(record.entry.time),
+        EntryCategory::of(&record.entry).color(),
+        EntryMessage(&record.entry),
+    )
+}
 
-    Ok(())
+/// Returns how many entries are in the Brain's event log.
+pub async fn log_count(connection: &mut DeviceConnection) -> Result<u32, CliError> {
+    Ok(connection
+        .handshake::<LogStatusReplyPacket>(Duration::from_millis(500), 10, LogStatusPacket::new(()))
+        .await?
+        .payload?
+        .count)
+}
+
+/// Reads every entry in the Brain's event log, oldest first.
+pub async fn read_all_entries(
+    connection: &mut DeviceConnection,
+) -> Result<Vec<LogEntry>, CliError> {
+    let total = log_count(connection).await?;
+    read_newest_entries(connection, total).await
+}
+
+/// Reads the `total` newest entries in the Brain's event log, oldest first.
+pub async fn read_newest_entries(
+    connection: &mut DeviceConnection,
+    total: u32,
+) -> Result<Vec<LogEntry>, CliError> {
+    // Logs are addressed by their distance from the newest entry, so read pages backwards from
+    // the end of the log and then put them back in order.
+    let mut pages = Vec::new();
+    let mut read = 0;
+    while read < total {
+        let count = MAX_LOGS_PER_PAGE.min(total - read);
+        read += count;
+
+        pages.push(
+            connection
+                .handshake::<LogReadReplyPacket>(
+                    Duration::from_millis(500),
+                    10,
+                    LogReadPacket::new(LogReadPayload {
+                        offset: read,
+                        count,
+                    }),
+                )
+                .await?
+                .payload?
+                .entries,
+        );
+    }
+
+    Ok(pages.into_iter().rev().flatten().collect())
 }
 
 pub const fn decode_match_round(description: u8) -> &'static str {
@@ -0,0 +1,226 @@
+//! Management of cargo-v5's own log files.
+//!
+//! Every invocation logs to a new timestamped file in the system's temporary directory. Old files
+//! are cleaned up automatically according to a [`LogRetention`] policy, and can be listed, opened,
+//! or purged manually with `cargo v5 logs`. These are unrelated to the Brain's event log, which
+//! is read by `cargo v5 log`.
+
+use std::{
+    env, fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process::Command,
+    time::{Duration, SystemTime},
+};
+
+use chrono::Utc;
+use flexi_logger::FileSpec;
+use humansize::{BINARY, format_size};
+use tabwriter::TabWriter;
+
+use crate::errors::CliError;
+
+/// Prefix shared by the names of all cargo-v5 log files.
+const LOG_FILE_PREFIX: &str = "cargo-v5-";
+
+/// Extension of cargo-v5 log files.
+const LOG_FILE_EXTENSION: &str = "log";
+
+/// Directory log files are written to.
+pub fn log_directory() -> PathBuf {
+    env::temp_dir()
+}
+
+/// Location of this invocation's log file.
+pub fn log_file_spec() -> FileSpec {
+    FileSpec::default()
+        .directory(log_directory())
+        .use_timestamp(false)
+        .basename(format!(
+            "{LOG_FILE_PREFIX}{}",
+            Utc::now().format("%Y-%m-%d_%H-%M-%S")
+        ))
+        .suffix(LOG_FILE_EXTENSION)
+}
+
+/// How many old log files are kept around.
+///
+/// Both limits apply, so a file is deleted once it's too old *or* there are too many newer runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LogRetention {
+    /// Number of most recent runs to keep logs for.
+    pub runs: usize,
+
+    /// Number of days to keep logs for.
+    pub days: u64,
+}
+
+impl LogRetention {
+    pub const DEFAULT: Self = Self { runs: 20, days: 14 };
+
+    /// Reads the retention policy from `CARGO_V5_LOG_KEEP_RUNS` and `CARGO_V5_LOG_KEEP_DAYS`,
+    /// using the defaults for variables that aren't set.
+    pub fn from_env() -> Self {
+        let var = |name| env::var(name).ok().and_then(|value| value.parse().ok());
+
+        Self {
+            runs: var("CARGO_V5_LOG_KEEP_RUNS").map_or(Self::DEFAULT.runs, |runs| runs as usize),
+            days: var("CARGO_V5_LOG_KEEP_DAYS").unwrap_or(Self::DEFAULT.days),
+        }
+    }
+
+    fn max_age(self) -> Duration {
+        Duration::from_secs(self.days * 24 * 60 * 60)
+    }
+}
+
+impl Default for LogRetention {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// A log file written by a previous run of cargo-v5.
+#[derive(Debug, Clone)]
+pub struct LogFile {
+    pub path: PathBuf,
+    pub size: u64,
+    pub modified: SystemTime,
+}
+
+/// Lists cargo-v5's log files, newest first.
+pub fn log_files() -> io::Result<Vec<LogFile>> {
+    let mut files = Vec::new();
+
+    for entry in fs::read_dir(log_directory())? {
+        let entry = entry?;
+        let path = entry.path();
+
+        let is_log_file = path
+            .extension()
+            .is_some_and(|ext| ext == LOG_FILE_EXTENSION)
+            && entry
+                .file_name()
+                .to_str()
+                .is_some_and(|name| name.starts_with(LOG_FILE_PREFIX));
+        if !is_log_file {
+            continue;
+        }
+
+        let metadata = entry.metadata()?;
+        if !metadata.is_file() {
+            continue;
+        }
+
+        files.push(LogFile {
+            path,
+            size: metadata.len(),
+            modified: metadata.modified()?,
+        });
+    }
+
+    // File names start with the time of the run, so they sort chronologically.
+    files.sort_by(|a, b| b.path.cmp(&a.path));
+
+    Ok(files)
+}
+
+/// Deletes log files that fall outside of `retention`, except for the files in `keep`.
+///
+/// Files that can't be deleted, for example because another running instance still has them
+/// open, are skipped. Returns the files that were deleted.
+pub fn clean_up_log_files(retention: LogRetention, keep: &[PathBuf]) -> io::Result<Vec<PathBuf>> {
+    let now = SystemTime::now();
+    let mut removed = Vec::new();
+
+    for (index, file) in log_files()?.into_iter().enumerate() {
+        if keep.contains(&file.path) {
+            continue;
+        }
+
+        let age = now.duration_since(file.modified).unwrap_or_default();
+        if index < retention.runs && age <= retention.max_age() {
+            continue;
+        }
+
+        match fs::remove_file(&file.path) {
+            Ok(()) => removed.push(file.path),
+            Err(err) => log::debug!("Couldn't remove {}: {err}", file.path.display()),
+        }
+    }
+
+    Ok(removed)
+}
+
+/// Prints cargo-v5's log files, newest first, leaving out `current`.
+pub fn list_logs(current: &[PathBuf]) -> Result<(), CliError> {
+    let files = log_files()?
+        .into_iter()
+        .filter(|file| !current.contains(&file.path))
+        .collect::<Vec<_>>();
+
+    if files.is_empty() {
+        eprintln!("No log files in {}.", log_directory().display());
+        return Ok(());
+    }
+
+    let mut tw = TabWriter::new(io::stdout());
+    writeln!(tw, "\x1b[1mFile\tSize\x1b[0m")?;
+    for file in files {
+        writeln!(
+            tw,
+            "{}\t{}",
+            file.path.display(),
+            format_size(file.size, BINARY)
+        )?;
+    }
+    tw.flush()?;
+
+    Ok(())
+}
+
+/// Opens the log file of the `run`th most recent run (starting at 1) with the system's default
+/// application, leaving out `current`.
+pub fn open_log(run: usize, current: &[PathBuf]) -> Result<(), CliError> {
+    let file = log_files()?
+        .into_iter()
+        .filter(|file| !current.contains(&file.path))
+        .nth(run.saturating_sub(1))
+        .ok_or(CliError::NoLogFile(run))?;
+
+    eprintln!("\x1b[1;92m{:>12}\x1b[0m {}", "Opening", file.path.display());
+
+    open_in_default_app(&file.path)?;
+
+    Ok(())
+}
+
+/// Deletes every log file except `current`.
+pub fn purge_logs(current: &[PathBuf]) -> Result<(), CliError> {
+    let retention = LogRetention { runs: 0, days: 0 };
+    let removed = clean_up_log_files(retention, current)?;
+
+    eprintln!(
+        "\x1b[1;92m{:>12}\x1b[0m {} log file{}",
+        "Purged",
+        removed.len(),
+        if removed.len() == 1 { "" } else { "s" }
+    );
+
+    Ok(())
+}
+
+fn open_in_default_app(path: &Path) -> io::Result<()> {
+    let mut command = if cfg!(target_os = "windows") {
+        let mut command = Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    } else if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else {
+        Command::new("xdg-open")
+    };
+
+    command.arg(path).spawn()?;
+    Ok(())
+}
@@ -4,26 +4,62 @@ use std::{
     fmt::Display,
     io::ErrorKind,
     path::{Path, PathBuf},
+    process::Stdio,
 };
 
+use clap::Args;
 use fs_err::tokio as fs;
 use miette::Diagnostic;
-use semver::Version;
+use semver::{BuildMetadata, Version, VersionReq};
 use supports_color::Stream;
 use thiserror::Error;
-use tokio::{process::Command, task::block_in_place};
+use tokio::{
+    io::{AsyncBufReadExt, BufReader},
+    process::Command,
+    task::block_in_place,
+};
 use toml_edit::{Document, DocumentMut, Item, Table, Value, table};
 
-use crate::errors::CliError;
+use crate::{
+    commands::build::{DEFAULT_TARGET, cargo_bin},
+    errors::CliError,
+    interactive::{ensure_interactive, is_interactive},
+    metadata::manifest_path,
+};
 
+mod pros;
 mod source_code;
 mod vfs;
 
-/// Applies all available upgrades to the workspace.
-pub async fn migrate_workspace(root: &Path) -> Result<(), CliError> {
+pub use pros::migrate_pros;
+
+/// Options controlling how a migration's changes are applied.
+#[derive(Args, Debug, Clone, Default)]
+pub struct MigrateOpts {
+    /// After applying the changes, run `cargo fmt` and check that the project builds.
+    #[arg(long, conflicts_with_all = ["dry_run", "output_patch"])]
+    pub check: bool,
+
+    /// Print the changes without applying them, exiting with an error if there are any.
+    #[arg(long, conflicts_with_all = ["yes", "output_patch"])]
+    pub dry_run: bool,
+
+    /// Apply the changes without asking for confirmation.
+    #[arg(short, long)]
+    pub yes: bool,
+
+    /// Write the changes to a patch file that `git apply` accepts, instead of applying them.
+    #[arg(long, value_name = "FILE", conflicts_with = "yes")]
+    pub output_patch: Option<PathBuf>,
+}
+
+/// Applies all available upgrades to the workspace containing the project at `root`.
+///
+/// `root` may be either a project directory or the path to a `Cargo.toml` file.
+pub async fn migrate_workspace(root: &Path, opts: &MigrateOpts) -> Result<(), CliError> {
     let metadata_task = block_in_place(|| {
         cargo_metadata::MetadataCommand::new()
-            .current_dir(root)
+            .manifest_path(manifest_path(root))
             .exec()
             .ok()
     });
@@ -34,17 +70,21 @@ pub async fn migrate_workspace(root: &Path) -> Result<(), CliError> {
 
     let mut ctx = ChangesCtx::new(&metadata.workspace_root);
 
-    update_vexide(&mut ctx).await?;
-    update_rust(&mut ctx).await?;
-    update_cargo_config(&mut ctx).await?;
-    source_code::update_targets(&mut ctx, &metadata).await?;
-
-    // Print pending changes - in the future we will apply them too.
-    let highlight = supports_color::on_cached(Stream::Stdout).is_some();
+    let current = vexide_version(&ctx).await;
+    let steps = MigrationStep::needed_from(current.as_ref())?;
+    for step in &steps {
+        step.apply(&mut ctx, &metadata).await?;
+    }
 
-    println!(
-        "The upgrade tool will now update your project configuration to the vexide 0.8.0 recommended defaults."
-    );
+    let latest = MigrationStep::LATEST.to();
+    match &current {
+        Some(current) if steps.len() > 1 => println!(
+            "The upgrade tool will now update your project from vexide {current} to {latest}, one release at a time."
+        ),
+        _ => println!(
+            "The upgrade tool will now update your project configuration to the vexide {latest} recommended defaults."
+        ),
+    }
     println!(
         "After applying these changes, make sure to check out the upgrade guide on the vexide website"
     );
@@ -60,6 +100,374 @@ pub async fn migrate_workspace(root: &Path) -> Result<(), CliError> {
     }
     println!();
 
+    finish_migration(&mut ctx, opts).await
+}
+
+/// A migration from one vexide release to the next.
+///
+/// `cargo v5 migrate` detects the project's vexide version and chains every step after it, so
+/// projects several releases behind end up fully migrated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MigrationStep {
+    V0_6ToV0_7,
+    V0_7ToV0_8,
+}
+
+impl MigrationStep {
+    /// Every step, oldest first.
+    const ALL: [Self; 2] = [Self::V0_6ToV0_7, Self::V0_7ToV0_8];
+
+    /// The step to the newest release the upgrade tool supports.
+    const LATEST: Self = Self::V0_7ToV0_8;
+
+    /// The oldest vexide version this step migrates from.
+    fn from(self) -> Version {
+        match self {
+            Self::V0_6ToV0_7 => Version::new(0, 6, 0),
+            Self::V0_7ToV0_8 => Version::new(0, 7, 0),
+        }
+    }
+
+    /// The vexide version this step migrates to.
+    fn to(self) -> Version {
+        match self {
+            Self::V0_6ToV0_7 => Version::new(0, 7, 0),
+            Self::V0_7ToV0_8 => Version::new(0, 8, 0),
+        }
+    }
+
+    /// The steps needed to bring a project on vexide `current` up to date.
+    ///
+    /// The latest step is always included, since it also updates the build configuration of
+    /// projects already on the latest release. Projects whose version can't be detected only get
+    /// the latest step, which sets up the vexide dependency from scratch.
+    fn needed_from(current: Option<&Version>) -> Result<Vec<Self>, MigrateError> {
+        let Some(current) = current else {
+            return Ok(vec![Self::LATEST]);
+        };
+
+        let oldest = Self::ALL[0].from();
+        if *current < oldest {
+            return Err(MigrateError::UnsupportedVersion {
+                current: current.clone(),
+                oldest,
+            });
+        }
+
+        Ok(Self::ALL
+            .into_iter()
+            .filter(|&step| *current < step.to() || step == Self::LATEST)
+            .collect())
+    }
+
+    async fn apply(
+        self,
+        ctx: &mut ChangesCtx,
+        metadata: &cargo_metadata::Metadata,
+    ) -> Result<(), CliError> {
+        match self {
+            Self::V0_6ToV0_7 => {
+                set_vexide_version(ctx, &self.to()).await?;
+                source_code::update_imports(ctx, metadata, &source_code::VEXIDE_0_7_IMPORTS).await
+            }
+            Self::V0_7ToV0_8 => {
+                update_vexide(ctx).await?;
+                update_rust(ctx).await?;
+                update_cargo_config(ctx).await?;
+                source_code::update_targets(ctx, metadata).await?;
+                source_code::update_imports(ctx, metadata, &source_code::VEXIDE_0_8_IMPORTS).await
+            }
+        }
+    }
+}
+
+/// Returns the version of vexide that the workspace's `Cargo.toml` depends on.
+async fn vexide_version(ctx: &ChangesCtx) -> Option<Version> {
+    let manifest = ctx.fs.read_to_string("Cargo.toml").await.ok()?;
+    manifest_vexide_version(&manifest)
+}
+
+/// Returns the version of vexide that `manifest` depends on.
+///
+/// Version requirements like `0.7` are treated as the lowest version they allow.
+fn manifest_vexide_version(manifest: &str) -> Option<Version> {
+    let manifest = Document::parse(manifest).ok()?;
+
+    let vexide = manifest.get("dependencies")?.get("vexide")?;
+    let requirement = vexide
+        .as_str()
+        .or_else(|| vexide.get("version")?.as_str())?;
+    let comparator = VersionReq::parse(requirement)
+        .ok()?
+        .comparators
+        .into_iter()
+        .next()?;
+
+    Some(Version {
+        major: comparator.major,
+        minor: comparator.minor.unwrap_or(0),
+        patch: comparator.patch.unwrap_or(0),
+        pre: comparator.pre,
+        build: BuildMetadata::EMPTY,
+    })
+}
+
+/// Moves the vexide dependency to `version`, keeping its features.
+async fn set_vexide_version(ctx: &mut ChangesCtx, version: &Version) -> Result<(), CliError> {
+    ctx.edit_toml("Cargo.toml", |mut ctx| {
+        let dependencies = ctx.document.table("dependencies");
+        match dependencies.get_mut("vexide") {
+            Some(Item::Value(Value::InlineTable(vexide))) => {
+                vexide.insert("version", version.to_string().into());
+            }
+            Some(Item::Table(vexide)) => vexide["version"] = version.to_string().into(),
+            Some(vexide) => *vexide = version.to_string().into(),
+            None => return,
+        }
+
+        ctx.explain_change(format!(
+            "Updated to vexide {version} (see its changelog for code changes)"
+        ));
+    })
+    .await
+}
+
+/// Applies, prints, or exports the pending changes in `ctx` as `opts` asks, then checks the
+/// project if requested.
+async fn finish_migration(ctx: &mut ChangesCtx, opts: &MigrateOpts) -> Result<(), CliError> {
+    if let Some(path) = &opts.output_patch {
+        fs::write(path, ctx.fs.unified_diff().await?).await?;
+        println!(
+            "Wrote the changes to {}. Apply them with `git apply`.",
+            path.display()
+        );
+        return Ok(());
+    }
+
+    if opts.dry_run {
+        let highlight = supports_color::on_cached(Stream::Stdout).is_some();
+        println!("{}", ctx.fs.display(true, highlight).await);
+        return Err(MigrateError::ChangesPending.into());
+    }
+
+    let applied = if opts.yes {
+        ctx.apply().await?;
+        true
+    } else {
+        confirm_and_apply(ctx).await?
+    };
+
+    if applied && opts.check {
+        check_migration(ctx).await?;
+    }
+
+    Ok(())
+}
+
+/// Formats the migrated workspace with `cargo fmt` and checks that it builds, so the user knows
+/// whether the migration worked.
+///
+/// If the check fails, the changes to the build configuration and to any file mentioned in the
+/// errors are printed after them.
+async fn check_migration(ctx: &ChangesCtx) -> Result<(), CliError> {
+    let root = fs::canonicalize(ctx.fs.root()).await?;
+
+    println!("Formatting the project with `cargo fmt`...");
+    match Command::new(cargo_bin())
+        .args(["fmt", "--all"])
+        .current_dir(&root)
+        .status()
+        .await
+    {
+        Ok(status) if status.success() => {}
+        Ok(_) => log::warn!("`cargo fmt` failed, so the project may have syntax errors."),
+        Err(err) => log::warn!("Couldn't run `cargo fmt`: {err}"),
+    }
+
+    println!("Checking that the project builds with `cargo check`...");
+    let mut cargo = Command::new(cargo_bin())
+        .args(["check", "--workspace", "--target", DEFAULT_TARGET])
+        .current_dir(&root)
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    // Cargo's output is shown as it runs, and kept to find the files with errors.
+    let mut errors = String::new();
+    let mut lines = BufReader::new(cargo.stderr.take().unwrap()).lines();
+    while let Some(line) = lines.next_line().await? {
+        eprintln!("{line}");
+        errors.push_str(&line);
+        errors.push('\n');
+    }
+
+    if cargo.wait().await?.success() {
+        println!("The migrated project builds.");
+        return Ok(());
+    }
+
+    let relevant = ctx
+        .applied_changes
+        .iter()
+        .filter(|(path, _)| {
+            let relative = path.strip_prefix(&root).unwrap_or(path);
+            let is_build_config = relative.file_name().is_some_and(|name| {
+                name == "Cargo.toml" || name == "config.toml" || name == "rust-toolchain.toml"
+            });
+
+            is_build_config || errors.contains(relative.to_string_lossy().as_ref())
+        })
+        .collect::<Vec<_>>();
+
+    if !relevant.is_empty() {
+        println!();
+        println!(
+            "The migration made these changes to the build configuration and files with errors:"
+        );
+        for (_, diff) in relevant {
+            println!("{diff}");
+        }
+    }
+
+    Err(MigrateError::CheckFailed.into())
+}
+
+/// Returns why the project at `root` needs `cargo v5 migrate` before it can be built, if it does.
+///
+/// Projects made before vexide 0.8 either depend on an older vexide or build against a custom
+/// target JSON file forced through the `build.target` Cargo config key. Cargo reads config from
+/// the project's directory and all of its parents, so those are all checked.
+pub async fn migration_needed(root: &Path) -> Option<String> {
+    if let Ok(manifest) = fs::read_to_string(root.join("Cargo.toml")).await
+        && let Some(version) = manifest_vexide_version(&manifest)
+        && version < Version::new(0, 8, 0)
+    {
+        return Some(format!(
+            "This project depends on vexide {version}, which cargo-v5 no longer supports."
+        ));
+    }
+
+    for dir in root.ancestors() {
+        if fs::metadata(dir.join("armv7a-vex-v5.json")).await.is_ok() {
+            return Some(format!(
+                "{} is a pre-0.8 target configuration, which is no longer supported.",
+                dir.join("armv7a-vex-v5.json").display()
+            ));
+        }
+
+        for config in [".cargo/config.toml", ".cargo/config"] {
+            let config = dir.join(config);
+            let Ok(contents) = fs::read_to_string(&config).await else {
+                continue;
+            };
+            let Ok(contents) = Document::parse(contents) else {
+                continue;
+            };
+
+            if contents
+                .get("build")
+                .and_then(|build| build.get("target"))
+                .and_then(|target| target.as_str())
+                .is_some_and(|target| target.ends_with(".json"))
+            {
+                return Some(format!(
+                    "{} uses the pre-0.8 target configuration, which is no longer supported.",
+                    config.display()
+                ));
+            }
+        }
+    }
+
+    None
+}
+
+/// Offers to apply the parts of `cargo v5 migrate` that the workspace containing `root` needs
+/// before it can be built, after confirming with the user.
+///
+/// Projects on an older vexide get the migration steps from their version, and projects already
+/// on 0.8 only get their Rust toolchain and Cargo config updated. Declining is recorded in the
+/// workspace's target directory so later builds don't ask again. Without a way to confirm, the
+/// changes are left for `cargo v5 migrate`.
+///
+/// Returns whether the changes were applied.
+pub async fn offer_migration(root: &Path) -> Result<bool, CliError> {
+    if !is_interactive() {
+        return Ok(false);
+    }
+
+    let Some(metadata) = block_in_place(|| {
+        cargo_metadata::MetadataCommand::new()
+            .manifest_path(manifest_path(root))
+            .exec()
+            .ok()
+    }) else {
+        return Ok(false);
+    };
+
+    let declined_marker = metadata
+        .target_directory
+        .join("v5")
+        .join("migration-declined")
+        .into_std_path_buf();
+    if fs::metadata(&declined_marker).await.is_ok() {
+        return Ok(false);
+    }
+
+    let mut ctx = ChangesCtx::new(&metadata.workspace_root);
+
+    let current = vexide_version(&ctx).await;
+    if current
+        .as_ref()
+        .is_some_and(|current| *current < MigrationStep::LATEST.to())
+    {
+        // Projects too old to migrate are left to the warning.
+        let Ok(steps) = MigrationStep::needed_from(current.as_ref()) else {
+            return Ok(false);
+        };
+        for step in steps {
+            step.apply(&mut ctx, &metadata).await?;
+        }
+    } else {
+        update_rust(&mut ctx).await?;
+        update_cargo_config(&mut ctx).await?;
+    }
+
+    // The outdated configuration may be in a parent directory, which isn't migrated.
+    if ctx.description.is_empty() {
+        return Ok(false);
+    }
+
+    eprintln!(
+        "The following changes will update your project to build with vexide {}:",
+        MigrationStep::LATEST.to()
+    );
+    for desc in &ctx.description {
+        eprintln!("  - {desc}");
+    }
+    eprintln!();
+
+    if confirm_and_apply(&mut ctx).await? {
+        return Ok(true);
+    }
+
+    if let Some(parent) = declined_marker.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+    fs::write(&declined_marker, "").await?;
+
+    Ok(false)
+}
+
+/// Prompts the user to apply the pending changes in `ctx`, optionally viewing a diff first.
+///
+/// Returns whether the changes were applied.
+async fn confirm_and_apply(ctx: &mut ChangesCtx) -> Result<bool, CliError> {
+    ensure_interactive(
+        "confirm the changes",
+        "Run `cargo v5 migrate` from an interactive terminal to review and apply them, or pass `--yes` to apply them without confirming.",
+    )?;
+
+    let highlight = supports_color::on_cached(Stream::Stdout).is_some();
+
     loop {
         let confirmation: inquire::Select<'_, ConfirmOptions> = inquire::Select::new(
             "Apply changes?",
@@ -75,16 +483,12 @@ pub async fn migrate_workspace(root: &Path) -> Result<(), CliError> {
         match reply {
             ConfirmOptions::Confirm => {
                 ctx.apply().await?;
-                break;
+                return Ok(true);
             }
             ConfirmOptions::ViewDiff => println!("{}", ctx.fs.display(true, highlight).await),
-            ConfirmOptions::Abort => {
-                break;
-            }
+            ConfirmOptions::Abort => return Ok(false),
         }
     }
-
-    Ok(())
 }
 
 #[derive(Default)]
@@ -111,7 +515,7 @@ async fn update_rust(ctx: &mut ChangesCtx) -> Result<(), CliError> {
 
         let toolchain = ctx.document.table("toolchain");
         toolchain["channel"] = latest.into();
-        ctx.explain_change(format!("Updated to Rust {}", latest));
+        ctx.explain_change(format!("Updated to Rust {latest}"));
     })
     .await?;
 
@@ -205,7 +609,14 @@ async fn update_cargo_config(ctx: &mut ChangesCtx) -> Result<(), CliError> {
 }
 
 async fn update_vexide(ctx: &mut ChangesCtx) -> Result<(), CliError> {
-    let latest = "0.8.0";
+    let latest = MigrationStep::V0_7ToV0_8.to();
+    if vexide_version(ctx)
+        .await
+        .is_some_and(|current| current >= latest)
+    {
+        return Ok(());
+    }
+    let latest = latest.to_string();
 
     ctx.edit_toml("Cargo.toml", |mut ctx| {
         // Update to Rust 2024 edition (required by 0.8.0).
@@ -220,24 +631,6 @@ async fn update_vexide(ctx: &mut ChangesCtx) -> Result<(), CliError> {
             .get("dependencies")
             .and_then(|d| d.get("vexide"));
 
-        let old_version = old_entry
-            .and_then(|v| v.get("version"))
-            .and_then(|d| d.as_str());
-
-        if let Some(old_version) = old_version
-            && let Ok(current) = Version::parse(old_version)
-        {
-            let supported_by_tool = Version::new(0, 7, 0);
-            let latest = Version::parse(latest).unwrap();
-
-            let is_eligible = current < latest && current >= supported_by_tool;
-            println!("eligible for upgrade: {is_eligible}");
-            if !is_eligible {
-                log::warn!("vexide v{current} not eligible for upgrade");
-                return;
-            }
-        }
-
         let old_features_array = old_entry
             .and_then(|v| v.get("features"))
             .and_then(|d| d.as_array());
@@ -300,7 +693,7 @@ async fn update_vexide(ctx: &mut ChangesCtx) -> Result<(), CliError> {
         let mut vexide = Table::new();
 
         println!("new version: {latest}");
-        vexide["version"] = latest.into();
+        vexide["version"] = latest.as_str().into();
         vexide["features"] = Value::from_iter(features).into();
         if !default_features {
             vexide["default-features"] = default_features.into();
@@ -321,12 +714,51 @@ pub enum MigrateError {
     #[error("Cannot determine the current Cargo workspace")]
     #[diagnostic(code(cargo_v5::upgrade::no_metadata))]
     Metadata,
+    #[error("{0} is not a PROS project")]
+    #[diagnostic(
+        code(cargo_v5::upgrade::not_pros_project),
+        help("Run `cargo v5 migrate --from pros` in the directory containing `project.pros`.")
+    )]
+    NotProsProject(PathBuf),
+    #[error("{0} already has a Cargo.toml")]
+    #[diagnostic(
+        code(cargo_v5::upgrade::already_cargo_project),
+        help("Run `cargo v5 migrate` without `--from pros` to update an existing vexide project.")
+    )]
+    AlreadyCargoProject(PathBuf),
+    #[error("failed to parse project.pros")]
+    #[diagnostic(code(cargo_v5::upgrade::invalid_pros_project))]
+    ProsProjectParse(#[from] serde_json::Error),
+    #[error("The migrated project doesn't build")]
+    #[diagnostic(
+        code(cargo_v5::upgrade::check_failed),
+        help(
+            "Fix the errors above. The vexide upgrade guide covers code changes the migration can't make."
+        )
+    )]
+    CheckFailed,
+    #[error("The upgrade tool can't migrate projects on vexide {current}")]
+    #[diagnostic(
+        code(cargo_v5::upgrade::unsupported_version),
+        help(
+            "Update the project to vexide {oldest} by hand, following the breaking changes listed for each release in between at https://github.com/vexide/vexide/blob/main/CHANGELOG.md, then run `cargo v5 migrate` again."
+        )
+    )]
+    UnsupportedVersion { current: Version, oldest: Version },
+    #[error("The project has changes to migrate")]
+    #[diagnostic(
+        code(cargo_v5::upgrade::changes_pending),
+        help("Run `cargo v5 migrate` without `--dry-run` to apply them.")
+    )]
+    ChangesPending,
 }
 
 struct ChangesCtx {
     fs: vfs::FileOperationStore,
     will_disable_rustup_override: bool,
     description: Vec<String>,
+    /// The diff of each file changed by [`ChangesCtx::apply`].
+    applied_changes: Vec<(PathBuf, String)>,
 }
 
 impl ChangesCtx {
@@ -335,6 +767,7 @@ impl ChangesCtx {
             fs: vfs::FileOperationStore::new(root),
             will_disable_rustup_override: false,
             description: vec![],
+            applied_changes: vec![],
         }
     }
 
@@ -368,6 +801,7 @@ impl ChangesCtx {
     }
 
     pub async fn apply(&mut self) -> Result<(), CliError> {
+        self.applied_changes = self.fs.diffs().await;
         self.fs.apply().await?;
 
         if self.will_disable_rustup_override {
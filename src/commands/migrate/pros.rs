@@ -0,0 +1,623 @@
+//! Migration from PROS C++ projects, run by `cargo v5 migrate --from pros`.
+//!
+//! The project's upload options are read from `project.pros`, and devices declared in its C++
+//! sources (like `pros::Motor left_front(1)`) are bound to the same ports in a vexide skeleton.
+//! Code can't be translated automatically, so the skeleton leaves TODOs pointing at the C++
+//! functions that still need porting.
+
+use std::{
+    collections::HashSet,
+    fmt::Write,
+    path::{Path, PathBuf},
+};
+
+use serde_json::Value;
+use toml_edit::{Array, value};
+
+use super::{
+    ChangesCtx, MigrateError, MigrateOpts, TableExt, finish_migration, update_cargo_config,
+    update_rust,
+};
+use crate::errors::CliError;
+
+/// Directories of PROS sources to scan for device declarations.
+const SOURCE_DIRS: &[&str] = &["src", "include"];
+
+/// Directories of libraries installed into PROS projects, which declare no robot devices.
+const LIBRARY_DIRS: &[&str] = &[
+    "pros",
+    "okapi",
+    "liblvgl",
+    "display",
+    "lemlib",
+    "EZ-Template",
+];
+
+/// PROS devices that aren't translated, but are still listed in the skeleton for porting.
+const UNSUPPORTED_DEVICES: &[&str] = &[
+    "Gps",
+    "Vision",
+    "AIVision",
+    "Link",
+    "Serial",
+    "Encoder",
+    "ADIEncoder",
+    "AnalogIn",
+    "ADIAnalogIn",
+    "Potentiometer",
+    "ADIPotentiometer",
+    "Ultrasonic",
+    "ADIUltrasonic",
+    "Gyro",
+    "ADIGyro",
+    "Led",
+    "ADIMotor",
+];
+
+/// The vexide version new projects depend on.
+const VEXIDE_VERSION: &str = "0.8.0";
+
+/// Upload options from a PROS project's `project.pros` file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct ProsProject {
+    name: String,
+    slot: Option<u8>,
+    icon: Option<&'static str>,
+    description: Option<String>,
+}
+
+impl ProsProject {
+    fn parse(contents: &str) -> Result<Self, MigrateError> {
+        let project = serde_json::from_str::<Value>(contents)?;
+        let state = &project["py/state"];
+        let upload_options = &state["upload_options"];
+
+        let name = upload_options["remote_name"]
+            .as_str()
+            .or(state["project_name"].as_str())
+            .unwrap_or("robot")
+            .to_string();
+
+        Ok(Self {
+            name,
+            slot: upload_options["slot"]
+                .as_u64()
+                .and_then(|slot| u8::try_from(slot).ok())
+                .filter(|slot| (1..=8).contains(slot)),
+            icon: upload_options["icon"].as_str().and_then(icon_name),
+            description: upload_options["description"]
+                .as_str()
+                .filter(|description| !description.is_empty())
+                .map(str::to_string),
+        })
+    }
+}
+
+/// Maps an icon name from `project.pros` to the name of the same icon in cargo-v5.
+fn icon_name(pros_icon: &str) -> Option<&'static str> {
+    Some(match pros_icon.to_ascii_lowercase().as_str() {
+        "pros" => "pros",
+        "pizza" => "pizza",
+        "planet" => "planets",
+        "alien" => "alien",
+        "ufo" => "alien-in-ufo",
+        "clawbot" => "clawbot",
+        "robot" => "robot",
+        "question" => "question-mark",
+        "power" => "power-button",
+        "x" => "cool-x",
+        _ => return None,
+    })
+}
+
+/// A device declared in the PROS sources.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Device {
+    /// The declared variable name, in snake case.
+    name: String,
+    /// The field's type in the skeleton, or [`None`] if there's no equivalent.
+    ty: Option<String>,
+    /// Expression that creates the device from `peripherals`.
+    init: String,
+    /// The original declaration and where it was found.
+    declaration: String,
+    /// Why the device isn't bound in the skeleton, if it isn't.
+    problem: &'static str,
+    /// Ports the device uses.
+    ports: Vec<String>,
+}
+
+/// Converts a C++ `camelCase` name to `snake_case`.
+fn snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    for (i, c) in name.char_indices() {
+        if c.is_ascii_uppercase() {
+            if i > 0 && !snake.ends_with('_') {
+                snake.push('_');
+            }
+            snake.push(c.to_ascii_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+    snake
+}
+
+/// Parses a smart port like `-3` into its vexide name and whether it's reversed.
+fn smart_port(arg: &str) -> Option<(String, bool)> {
+    let arg = arg.trim();
+    let (reversed, port) = match arg.strip_prefix('-') {
+        Some(port) => (true, port.trim()),
+        None => (false, arg),
+    };
+
+    let port = port
+        .parse::<u8>()
+        .ok()
+        .filter(|port| (1..=21).contains(port))?;
+    Some((format!("port_{port}"), reversed))
+}
+
+/// Parses a three-wire port like `'A'` into its vexide name.
+fn adi_port(arg: &str) -> Option<String> {
+    let port = arg.trim().trim_matches(['\'', '"']);
+    match port.as_bytes() {
+        [port @ (b'A'..=b'H' | b'a'..=b'h')] => {
+            Some(format!("adi_{}", port.to_ascii_lowercase() as char))
+        }
+        _ => None,
+    }
+}
+
+/// Splits a C++ argument list at top-level commas.
+fn split_args(args: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+
+    for (i, c) in args.char_indices() {
+        match c {
+            '(' | '{' | '[' => depth += 1,
+            ')' | '}' | ']' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(args[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if !args[start..].trim().is_empty() {
+        parts.push(args[start..].trim());
+    }
+    parts
+}
+
+fn gearset(args: &[&str]) -> &'static str {
+    let args = args.join(",").to_ascii_lowercase();
+    if args.contains("_06") || args.contains("blue") || args.contains("600") {
+        "Gearset::Blue"
+    } else if args.contains("_36") || args.contains("red") || args.contains("100") {
+        "Gearset::Red"
+    } else {
+        "Gearset::Green"
+    }
+}
+
+fn direction(reversed: bool) -> &'static str {
+    if reversed {
+        "Direction::Reverse"
+    } else {
+        "Direction::Forward"
+    }
+}
+
+fn motor(port: &str, reversed: bool, args: &[&str]) -> String {
+    format!(
+        "Motor::new(peripherals.{port}, {}, {})",
+        gearset(args),
+        direction(reversed)
+    )
+}
+
+/// Translates a PROS device declaration into vexide, if there's an equivalent.
+fn translate_device(ty: &str, args: &[&str]) -> Option<(String, String, Vec<String>)> {
+    let first = args.first().copied().unwrap_or_default();
+
+    match ty {
+        "Controller" => {
+            let controller = if first.contains("PARTNER") {
+                "partner_controller"
+            } else {
+                "primary_controller"
+            };
+            Some((
+                "Controller".to_string(),
+                format!("peripherals.{controller}"),
+                vec![controller.to_string()],
+            ))
+        }
+        "Motor" => {
+            let (port, reversed) = smart_port(first)?;
+            // Older PROS versions pass `reversed` as the third argument.
+            let reversed = reversed ^ args.get(2).is_some_and(|arg| arg.trim() == "true");
+            Some((
+                "Motor".to_string(),
+                motor(&port, reversed, args),
+                vec![port],
+            ))
+        }
+        "MotorGroup" => {
+            let ports = first
+                .trim()
+                .strip_prefix('{')?
+                .strip_suffix('}')?
+                .split(',')
+                .map(smart_port)
+                .collect::<Option<Vec<_>>>()?;
+            let motors = ports
+                .iter()
+                .map(|(port, reversed)| motor(port, *reversed, args))
+                .collect::<Vec<_>>();
+            Some((
+                format!("[Motor; {}]", motors.len()),
+                format!(
+                    "[\n            {},\n        ]",
+                    motors.join(",\n            ")
+                ),
+                ports.into_iter().map(|(port, _)| port).collect(),
+            ))
+        }
+        "Imu" | "IMU" => smart_device("InertialSensor", first),
+        "Distance" => smart_device("DistanceSensor", first),
+        "Optical" => smart_device("OpticalSensor", first),
+        "Rotation" => {
+            let (port, reversed) = smart_port(first)?;
+            let reversed = reversed || args.get(1).is_some_and(|arg| arg.trim() == "true");
+            Some((
+                "RotationSensor".to_string(),
+                format!(
+                    "RotationSensor::new(peripherals.{port}, {})",
+                    direction(reversed)
+                ),
+                vec![port],
+            ))
+        }
+        "DigitalOut" | "ADIDigitalOut" | "Pneumatics" => adi_device("AdiDigitalOut", first),
+        "DigitalIn" | "ADIDigitalIn" | "ADIButton" => adi_device("AdiDigitalIn", first),
+        _ => None,
+    }
+}
+
+fn smart_device(ty: &str, arg: &str) -> Option<(String, String, Vec<String>)> {
+    let (port, _) = smart_port(arg)?;
+    Some((
+        ty.to_string(),
+        format!("{ty}::new(peripherals.{port})"),
+        vec![port],
+    ))
+}
+
+fn adi_device(ty: &str, arg: &str) -> Option<(String, String, Vec<String>)> {
+    let port = adi_port(arg)?;
+    Some((
+        ty.to_string(),
+        format!("{ty}::new(peripherals.{port})"),
+        vec![port],
+    ))
+}
+
+/// Finds declarations like `pros::Motor left(1, pros::MotorGears::blue);` in C++ source.
+fn find_devices(source: &str, file: &Path, devices: &mut Vec<Device>) {
+    let is_ident = |c: char| c.is_ascii_alphanumeric() || c == '_';
+
+    for (start, _) in source.match_indices("pros::") {
+        // `pros::v5::Motor` and `pros::adi::DigitalOut` are the same devices.
+        let rest = &source[start..];
+        let path_len = rest
+            .find(|c: char| !is_ident(c) && c != ':')
+            .unwrap_or(rest.len());
+        let ty = rest[..path_len].rsplit("::").next().unwrap_or_default();
+
+        let rest = rest[path_len..].trim_start();
+        let name_len = rest.find(|c: char| !is_ident(c)).unwrap_or(rest.len());
+        let name = &rest[..name_len];
+        if ty.is_empty() || name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+            continue;
+        }
+
+        let rest = rest[name_len..].trim_start();
+        let Some(open) = rest.chars().next().filter(|c| matches!(c, '(' | '{')) else {
+            continue;
+        };
+        let close = if open == '(' { ')' } else { '}' };
+
+        let mut depth = 0;
+        let Some(end) = rest.char_indices().find_map(|(i, c)| {
+            if c == open {
+                depth += 1;
+            } else if c == close {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            None
+        }) else {
+            continue;
+        };
+
+        let args = &rest[1..end];
+        let rest_start = source.len() - rest.len();
+        let declaration = format!(
+            "`{}` in {}",
+            source[start..rest_start + end + 1]
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" "),
+            file.display()
+        );
+
+        let (ty, init, ports) = match translate_device(ty, &split_args(args)) {
+            Some((ty, init, ports)) => (Some(ty), init, ports),
+            None if UNSUPPORTED_DEVICES.contains(&ty) => (None, String::new(), Vec::new()),
+            // Not a device, like `pros::Task`.
+            None => continue,
+        };
+        devices.push(Device {
+            name: snake_case(name),
+            ty,
+            init,
+            declaration,
+            problem: "has no vexide equivalent yet",
+            ports,
+        });
+    }
+}
+
+/// Lists the C++ sources in the project, leaving out installed libraries.
+async fn source_files(ctx: &ChangesCtx) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut dirs = SOURCE_DIRS
+        .iter()
+        .map(|dir| ctx.fs.root().join(dir))
+        .collect::<Vec<_>>();
+
+    while let Some(dir) = dirs.pop() {
+        let Ok(mut entries) = tokio::fs::read_dir(&dir).await else {
+            continue;
+        };
+
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let path = entry.path();
+            let Ok(file_type) = entry.file_type().await else {
+                continue;
+            };
+
+            if file_type.is_dir() {
+                if !LIBRARY_DIRS.iter().any(|lib| entry.file_name() == *lib) {
+                    dirs.push(path);
+                }
+            } else if path
+                .extension()
+                .is_some_and(|ext| ["c", "cpp", "cc", "h", "hpp"].iter().any(|e| ext == *e))
+            {
+                files.push(path);
+            }
+        }
+    }
+
+    files.sort();
+    files
+}
+
+/// Finds which file defines a PROS competition function like `void autonomous()`.
+fn find_function<'a>(sources: &'a [(PathBuf, String)], name: &str) -> Option<&'a Path> {
+    let pattern = format!("void {name}(");
+    sources
+        .iter()
+        .find(|(_, source)| source.contains(&pattern))
+        .map(|(path, _)| path.as_path())
+}
+
+/// Writes `src/main.rs` for the migrated project.
+fn skeleton(project: &ProsProject, devices: &[Device], sources: &[(PathBuf, String)]) -> String {
+    let todo = |function: &str| match find_function(sources, function) {
+        Some(file) => format!("// TODO: Port `{function}()` from {}.", file.display()),
+        None => format!("// TODO: Port `{function}()` from the PROS project."),
+    };
+
+    let mut fields = String::new();
+    let mut inits = String::new();
+    for device in devices {
+        match &device.ty {
+            Some(ty) => {
+                _ = writeln!(fields, "    {}: {ty},", device.name);
+                _ = writeln!(inits, "        {}: {},", device.name, device.init);
+            }
+            None => {
+                _ = writeln!(
+                    fields,
+                    "    // TODO: Add a field for {}, which {}.",
+                    device.declaration, device.problem
+                );
+            }
+        }
+    }
+
+    format!(
+        r#"//! Migrated from the PROS project "{name}" by `cargo v5 migrate --from pros`.
+//!
+//! The devices below were found in the project's C++ sources. Its code still needs to be ported
+//! by hand: see the TODO comments.
+
+use vexide::prelude::*;
+
+struct Robot {{
+{fields}}}
+
+impl Compete for Robot {{
+    async fn autonomous(&mut self) {{
+        {autonomous}
+    }}
+
+    async fn driver(&mut self) {{
+        {opcontrol}
+    }}
+}}
+
+#[vexide::main]
+async fn main(peripherals: Peripherals) {{
+    let robot = Robot {{
+{inits}    }};
+
+    {initialize}
+
+    robot.compete().await;
+}}
+"#,
+        name = project.name,
+        autonomous = todo("autonomous"),
+        opcontrol = todo("opcontrol"),
+        initialize = todo("initialize"),
+    )
+}
+
+/// Turns the PROS project's name into a valid package name.
+fn package_name(name: &str) -> String {
+    let name = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '-'
+            }
+        })
+        .collect::<String>();
+    let name = name.trim_matches('-');
+
+    if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("robot-{name}").trim_end_matches('-').to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+/// Sets up a vexide project alongside the PROS project at `root`, applying the changes as `opts`
+/// asks.
+pub async fn migrate_pros(root: &Path, opts: &MigrateOpts) -> Result<(), CliError> {
+    let mut ctx = ChangesCtx::new(root);
+
+    let project = match ctx.fs.read_to_string("project.pros").await {
+        Ok(contents) => ProsProject::parse(&contents)?,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            return Err(MigrateError::NotProsProject(root.to_path_buf()).into());
+        }
+        Err(err) => return Err(err.into()),
+    };
+    if ctx.fs.read_to_string("Cargo.toml").await.is_ok() {
+        return Err(MigrateError::AlreadyCargoProject(root.to_path_buf()).into());
+    }
+
+    let mut sources = Vec::new();
+    for path in source_files(&ctx).await {
+        if let Ok(source) = tokio::fs::read_to_string(&path).await {
+            let relative = path.strip_prefix(root).unwrap_or(&path).to_path_buf();
+            sources.push((relative, source));
+        }
+    }
+
+    let mut devices = Vec::new();
+    for (path, source) in &sources {
+        find_devices(source, path, &mut devices);
+    }
+
+    // Headers often declare the devices defined in a source file, and each port can only be
+    // bound once.
+    let mut names = HashSet::new();
+    let mut ports = HashSet::new();
+    devices.retain_mut(|device| {
+        if !names.insert(device.name.clone()) {
+            return false;
+        }
+        if device.ports.iter().any(|port| ports.contains(port)) {
+            device.ty = None;
+            device.problem = "uses a port that's already bound";
+        }
+        ports.extend(device.ports.iter().cloned());
+        true
+    });
+
+    ctx.edit_toml("Cargo.toml", |mut ctx| {
+        let package = ctx.document.table("package");
+        package["name"] = value(package_name(&project.name));
+        package["version"] = value("0.1.0");
+        package["edition"] = value("2024");
+        if let Some(description) = &project.description {
+            package["description"] = value(description);
+        }
+        ctx.explain_change(format!(
+            "Created the package `{}`",
+            package_name(&project.name)
+        ));
+
+        let v5 = ctx.document.table("package").table("metadata").table("v5");
+        if let Some(slot) = project.slot {
+            v5["slot"] = value(i64::from(slot));
+        }
+        if let Some(icon) = project.icon {
+            v5["icon"] = value(icon);
+        }
+        ctx.explain_change("Copied the slot and icon from project.pros");
+
+        let mut features = Array::new();
+        features.extend(["full", "default-sdk"]);
+        let dependencies = ctx.document.table("dependencies");
+        dependencies["vexide"]["version"] = value(VEXIDE_VERSION);
+        dependencies["vexide"]["features"] = value(features);
+        ctx.explain_change(format!("Added vexide {VEXIDE_VERSION}"));
+    })
+    .await?;
+
+    update_rust(&mut ctx).await?;
+    update_cargo_config(&mut ctx).await?;
+
+    ctx.fs
+        .write("src/main.rs", skeleton(&project, &devices, &sources))
+        .await?;
+    let bound = devices.iter().filter(|device| device.ty.is_some()).count();
+    ctx.describe(format!(
+        "Created src/main.rs with {bound} of the {} devices found in the PROS sources",
+        devices.len()
+    ));
+
+    let gitignore = ctx
+        .fs
+        .read_to_string(".gitignore")
+        .await
+        .unwrap_or_default();
+    if !gitignore
+        .lines()
+        .any(|line| matches!(line.trim(), "target" | "/target" | "target/"))
+    {
+        let mut gitignore = gitignore;
+        if !gitignore.is_empty() && !gitignore.ends_with('\n') {
+            gitignore.push('\n');
+        }
+        gitignore.push_str("/target\n");
+        ctx.fs.write(".gitignore", gitignore).await?;
+        ctx.describe("Ignored Cargo's build output in .gitignore");
+    }
+
+    println!(
+        "The upgrade tool will now set up a vexide project next to your PROS project, keeping its slot, icon, and device ports."
+    );
+    println!("Your C++ code is left in place for reference; port it to src/main.rs by hand.");
+    println!("Changes Summary:");
+    for desc in &ctx.description {
+        println!("  - {desc}");
+    }
+    println!();
+
+    finish_migration(&mut ctx, opts).await
+}
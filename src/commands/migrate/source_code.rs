@@ -1,9 +1,12 @@
-use std::str::FromStr;
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
 use cargo_metadata::Metadata;
 use ra_ap_syntax::{
-    AstNode, SourceFile,
-    ast::{Attr, ExternCrate, HasAttrs},
+    AstNode, SourceFile, TextRange,
+    ast::{Attr, ExternCrate, HasAttrs, HasVisibility, Use, UseTree},
 };
 
 use crate::{commands::migrate::ChangesCtx, errors::CliError};
@@ -91,3 +94,443 @@ pub fn remove_no_std(node: SourceFile) {
         attr.detach();
     }
 }
+
+/// The imports to rewrite when migrating to a vexide release.
+pub struct ImportChanges {
+    /// The release the imports are migrated to.
+    release: &'static str,
+    /// Items that moved in the release, as `(old path, new path)`. Paths under a moved module are
+    /// moved with it, and the longest matching old path wins. A new path may end in `as Name` to
+    /// keep code using the old name compiling.
+    renames: &'static [(&'static str, &'static str)],
+    /// Items that were removed in the release without needing a replacement.
+    removals: &'static [&'static str],
+}
+
+/// vexide 0.6 items that moved in 0.7, which replaced the `core` and `async_runtime` crate
+/// re-exports with top-level modules.
+pub const VEXIDE_0_7_IMPORTS: ImportChanges = ImportChanges {
+    release: "0.7",
+    renames: &[
+        ("vexide::core", "vexide"),
+        ("vexide::core::print", "vexide::io::print"),
+        ("vexide::core::println", "vexide::io::println"),
+        ("vexide::core::dbg", "vexide::io::dbg"),
+        (
+            "vexide::async_runtime::block_on",
+            "vexide::runtime::block_on",
+        ),
+        ("vexide::async_runtime::spawn", "vexide::task::spawn"),
+        ("vexide::async_runtime::task", "vexide::task"),
+        ("vexide::async_runtime::time", "vexide::time"),
+    ],
+    removals: &[],
+};
+
+/// vexide 0.7 items that moved in 0.8, which split up the `devices` module and left I/O to the
+/// Standard Library.
+pub const VEXIDE_0_8_IMPORTS: ImportChanges = ImportChanges {
+    release: "0.8",
+    renames: &[
+        ("vexide::devices::adi", "vexide::adi"),
+        ("vexide::devices::battery", "vexide::battery"),
+        ("vexide::devices::controller", "vexide::controller"),
+        ("vexide::devices::display", "vexide::display"),
+        ("vexide::devices::math", "vexide::math"),
+        ("vexide::devices::peripherals", "vexide::peripherals"),
+        ("vexide::devices::smart", "vexide::smart"),
+        (
+            "vexide::devices::smart::motor::Direction",
+            "vexide::math::Direction",
+        ),
+        (
+            "vexide::devices::position::Position",
+            "vexide::math::Angle as Position",
+        ),
+        ("vexide::devices::rgb::Rgb", "vexide::color::Color as Rgb"),
+        ("vexide::io", "std::io"),
+        ("vexide::io::print", "std::print"),
+        ("vexide::io::println", "std::println"),
+        ("vexide::io::dbg", "std::dbg"),
+        ("vexide::fs", "std::fs"),
+        ("vexide::path", "std::path"),
+        ("vexide::panic::set_hook", "std::panic::set_hook"),
+        ("vexide::panic::take_hook", "std::panic::take_hook"),
+    ],
+    // The Standard Library's floating point types have the methods that `Float` added.
+    removals: &["vexide::float"],
+};
+
+/// Whether `path` is `parent` or an item under it.
+fn is_under(path: &str, parent: &str) -> bool {
+    path.strip_prefix(parent)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+}
+
+/// Renames `path` if it's in or under a path in `renames`.
+fn rename_path(path: &str, renames: &[(&str, &str)]) -> Option<String> {
+    renames
+        .iter()
+        .filter(|(old, _)| is_under(path, old))
+        .max_by_key(|(old, _)| old.len())
+        .map(|(old, new)| match &path[old.len()..] {
+            "" => new.to_string(),
+            // Aliases only stand in for the item itself, not for anything under it.
+            rest => format!("{}{rest}", new.split(" as ").next().unwrap_or(new)),
+        })
+}
+
+/// Flattens a use tree into the full path of everything it imports. The last segment of each
+/// path includes its rename, like `Motor as M`, or is `*` or `self`.
+fn flatten_use_tree(prefix: &[String], tree: &UseTree, paths: &mut Vec<Vec<String>>) {
+    let mut path = prefix.to_vec();
+    if let Some(tree_path) = tree.path() {
+        let text = tree_path.syntax().text().to_string();
+        path.extend(
+            text.split("::")
+                .map(|segment| segment.split_whitespace().collect::<String>()),
+        );
+    }
+
+    if let Some(list) = tree.use_tree_list() {
+        for child in list.use_trees() {
+            flatten_use_tree(&path, &child, paths);
+        }
+    } else if tree.star_token().is_some() {
+        path.push("*".to_string());
+        paths.push(path);
+    } else if !path.is_empty() {
+        if let Some(rename) = tree.rename() {
+            let rename = rename
+                .syntax()
+                .text()
+                .to_string()
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ");
+            let last = path.last_mut().unwrap();
+            *last = format!("{last} {rename}");
+        }
+        paths.push(path);
+    }
+}
+
+/// Paths that share their first segments, for nesting them back into a use tree.
+#[derive(Default)]
+struct UseTrie {
+    /// Whether a path ends here, in addition to any that continue into its children.
+    leaf: bool,
+    children: Vec<(String, UseTrie)>,
+}
+
+impl UseTrie {
+    fn insert(&mut self, path: &[String]) {
+        let Some((first, rest)) = path.split_first() else {
+            self.leaf = true;
+            return;
+        };
+
+        let index = match self
+            .children
+            .iter()
+            .position(|(segment, _)| segment == first)
+        {
+            Some(index) => index,
+            None => {
+                self.children.push((first.clone(), UseTrie::default()));
+                self.children.len() - 1
+            }
+        };
+        self.children[index].1.insert(rest);
+    }
+
+    /// Formats each child as its own use tree.
+    fn trees(&self) -> Vec<String> {
+        self.children
+            .iter()
+            .map(|(segment, child)| {
+                let mut trees = child.trees();
+                if child.leaf && !trees.is_empty() {
+                    trees.insert(0, "self".to_string());
+                }
+
+                match trees.as_slice() {
+                    [] => segment.clone(),
+                    [tree] => format!("{segment}::{tree}"),
+                    trees => format!("{segment}::{{{}}}", trees.join(", ")),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Rewrites the paths in a `use` item that were moved or removed in a vexide release, returning the
+/// edit to make to the source if any were.
+fn rewrite_use(item: &Use, source: &str, changes: &ImportChanges) -> Option<(TextRange, String)> {
+    let tree = item.use_tree()?;
+
+    // Paths from the crate root (`::vexide::...`) are rare enough to leave alone.
+    if tree.coloncolon_token().is_some() && tree.path().is_none() {
+        return None;
+    }
+
+    let mut paths = Vec::new();
+    flatten_use_tree(&[], &tree, &mut paths);
+
+    let mut changed = false;
+    let mut trie = UseTrie::default();
+    for path in paths {
+        // Renames apply to the path without the imported item's rename.
+        let (last, prefix) = path.split_last()?;
+        let (name, rename) = match last.split_once(' ') {
+            Some((name, rename)) => (name, Some(rename)),
+            None => (last.as_str(), None),
+        };
+        let full = prefix
+            .iter()
+            .map(String::as_str)
+            .chain(std::iter::once(name))
+            .collect::<Vec<_>>()
+            .join("::");
+
+        if changes
+            .removals
+            .iter()
+            .any(|removed| is_under(&full, removed))
+        {
+            changed = true;
+            continue;
+        }
+
+        let path = match rename_path(&full, changes.renames) {
+            Some(renamed) => {
+                changed = true;
+                let mut path = renamed.split("::").map(str::to_string).collect::<Vec<_>>();
+                if let Some(rename) = rename {
+                    // The item's own rename replaces any alias from the table.
+                    let last = path.last_mut().unwrap();
+                    let name = last.split(' ').next().unwrap_or_default().to_string();
+                    *last = format!("{name} {rename}");
+                }
+                path
+            }
+            None => path,
+        };
+        trie.insert(&path);
+    }
+
+    if !changed {
+        return None;
+    }
+
+    match trie.trees().as_slice() {
+        // Everything the item imported was removed.
+        [] => Some((item.syntax().text_range(), String::new())),
+        [tree_text] => Some((tree.syntax().text_range(), tree_text.clone())),
+        // Items now imported from different crates get a `use` each, unless attributes would
+        // have to be repeated.
+        trees if item.attrs().next().is_none() => {
+            let range = item.syntax().text_range();
+            let line_start = source[..usize::from(range.start())]
+                .rfind('\n')
+                .map_or(0, |i| i + 1);
+            let indent = &source[line_start..usize::from(range.start())];
+            let indent = if indent.trim().is_empty() { indent } else { "" };
+            let visibility = item
+                .visibility()
+                .map(|visibility| format!("{visibility} "))
+                .unwrap_or_default();
+
+            let items = trees
+                .iter()
+                .map(|tree| format!("{visibility}use {tree};"))
+                .collect::<Vec<_>>();
+            Some((range, items.join(&format!("\n{indent}"))))
+        }
+        trees => Some((
+            tree.syntax().text_range(),
+            format!("{{{}}}", trees.join(", ")),
+        )),
+    }
+}
+
+/// Rewrites every `use` of an item that `changes` moves or removes in `source`. Returns [`None`] if
+/// nothing needed changing.
+pub fn rewrite_imports(
+    source: &str,
+    edition: ra_ap_syntax::Edition,
+    changes: &ImportChanges,
+) -> Option<String> {
+    let file = SourceFile::parse(source, edition).tree();
+
+    let mut edits = file
+        .syntax()
+        .descendants()
+        .filter_map(Use::cast)
+        .filter_map(|item| rewrite_use(&item, source, changes))
+        .collect::<Vec<_>>();
+    if edits.is_empty() {
+        return None;
+    }
+
+    // Edits are applied back to front so earlier ranges stay valid. `use` items can't overlap.
+    edits.sort_by_key(|(range, _)| std::cmp::Reverse(range.start()));
+    let mut source = source.to_string();
+    for (range, text) in edits {
+        source.replace_range(usize::from(range.start())..usize::from(range.end()), &text);
+    }
+    Some(source)
+}
+
+/// Lists the Rust source files under `dir`, leaving out build output and hidden directories.
+async fn rust_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut dirs = vec![dir.to_path_buf()];
+
+    while let Some(dir) = dirs.pop() {
+        let Ok(mut entries) = tokio::fs::read_dir(&dir).await else {
+            continue;
+        };
+
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let name = entry.file_name();
+            let Ok(file_type) = entry.file_type().await else {
+                continue;
+            };
+
+            if file_type.is_dir() {
+                if name != "target" && !name.to_string_lossy().starts_with('.') {
+                    dirs.push(entry.path());
+                }
+            } else if entry.path().extension().is_some_and(|ext| ext == "rs") {
+                files.push(entry.path());
+            }
+        }
+    }
+
+    files.sort();
+    files
+}
+
+/// Rewrites imports of items that `changes` moves or removes in every source file of the
+/// workspace.
+pub async fn update_imports(
+    ctx: &mut ChangesCtx,
+    metadata: &Metadata,
+    changes: &ImportChanges,
+) -> Result<(), CliError> {
+    let mut updated = 0;
+
+    for package in metadata.workspace_packages() {
+        let edition =
+            ra_ap_syntax::Edition::from_str(package.edition.as_str()).expect("unknown edition");
+        let Some(package_dir) = package.manifest_path.parent() else {
+            continue;
+        };
+
+        for file in rust_files(package_dir.as_std_path()).await {
+            log::debug!("Updating imports in {}", file.display());
+
+            let contents = ctx.fs.read_to_string(&file).await?;
+            if let Some(new_contents) = rewrite_imports(&contents, edition, changes) {
+                ctx.fs.write(&file, new_contents).await?;
+                updated += 1;
+            }
+        }
+    }
+
+    if updated > 0 {
+        ctx.describe(format!(
+            "Updated imports of items that moved in vexide {} ({updated} {})",
+            changes.release,
+            if updated == 1 { "file" } else { "files" }
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use ra_ap_syntax::Edition;
+
+    use super::{VEXIDE_0_7_IMPORTS, VEXIDE_0_8_IMPORTS, rewrite_imports};
+
+    fn rewrite(source: &str) -> Option<String> {
+        rewrite_imports(source, Edition::Edition2024, &VEXIDE_0_8_IMPORTS)
+    }
+
+    #[test]
+    fn moves_device_modules_to_the_crate_root() {
+        assert_eq!(
+            rewrite("use vexide::devices::smart::{Motor, motor::Gearset};").as_deref(),
+            Some("use vexide::smart::{Motor, motor::Gearset};")
+        );
+        assert_eq!(
+            rewrite("use vexide::devices::{adi::AdiPort, controller::Controller};").as_deref(),
+            Some("use vexide::{adi::AdiPort, controller::Controller};")
+        );
+    }
+
+    #[test]
+    fn keeps_the_old_name_of_renamed_items() {
+        assert_eq!(
+            rewrite("use vexide::devices::position::Position;").as_deref(),
+            Some("use vexide::math::Angle as Position;")
+        );
+        assert_eq!(
+            rewrite("use vexide::devices::position::Position as Pos;").as_deref(),
+            Some("use vexide::math::Angle as Pos;")
+        );
+        assert_eq!(
+            rewrite("use vexide::devices::{rgb::Rgb, smart::motor::{Direction, Motor}};")
+                .as_deref(),
+            Some("use vexide::{color::Color as Rgb, math::Direction, smart::motor::Motor};")
+        );
+    }
+
+    #[test]
+    fn splits_imports_moved_to_std() {
+        assert_eq!(
+            rewrite("    pub use vexide::{io::{println, Write}, time::Instant};").as_deref(),
+            Some("    pub use std::{println, io::Write};\n    pub use vexide::time::Instant;")
+        );
+        assert_eq!(
+            rewrite("use vexide::{fs::File, path::Path};").as_deref(),
+            Some("use std::{fs::File, path::Path};")
+        );
+    }
+
+    #[test]
+    fn removes_float_imports() {
+        assert_eq!(
+            rewrite("use vexide::float::Float;\nfn main() {}").as_deref(),
+            Some("\nfn main() {}")
+        );
+        assert_eq!(
+            rewrite("use vexide::{float::Float, prelude::*};").as_deref(),
+            Some("use vexide::prelude::*;")
+        );
+    }
+
+    #[test]
+    fn moves_crate_re_exports_to_the_crate_root() {
+        let source = "use vexide::{core::{println, time::Instant}, async_runtime::block_on};";
+        let migrated = rewrite_imports(source, Edition::Edition2021, &VEXIDE_0_7_IMPORTS);
+        assert_eq!(
+            migrated.as_deref(),
+            Some("use vexide::{io::println, time::Instant, runtime::block_on};")
+        );
+        assert_eq!(
+            rewrite(&migrated.unwrap()).as_deref(),
+            Some("use std::println;\nuse vexide::{time::Instant, runtime::block_on};")
+        );
+    }
+
+    #[test]
+    fn leaves_current_paths_alone() {
+        assert_eq!(rewrite("use vexide::prelude::*;"), None);
+        assert_eq!(rewrite("use vexide::smart::Motor;"), None);
+        assert_eq!(rewrite("use vexide::iops::Thing;"), None);
+    }
+}
@@ -84,7 +84,56 @@ impl FileOperationStore {
     }
 
     pub async fn display(&self, show_contents: bool, highlight: bool) -> FileOperationsDisplay<'_> {
-        FileOperationsDisplay::new(self, show_contents, highlight).await
+        FileOperationsDisplay::new(self, None, show_contents, highlight).await
+    }
+
+    /// Renders the diff of each pending change on its own, without highlighting.
+    pub async fn diffs(&self) -> Vec<(PathBuf, String)> {
+        let mut diffs = Vec::new();
+        for path in self.changes.keys() {
+            let display = FileOperationsDisplay::new(self, Some(path), true, false).await;
+            diffs.push((path.clone(), display.to_string()));
+        }
+        diffs.sort();
+        diffs
+    }
+
+    /// Formats the pending changes as a unified diff, with paths relative to the root, that
+    /// `git apply` accepts.
+    pub async fn unified_diff(&self) -> io::Result<String> {
+        let root = fs::canonicalize(&self.root).await?;
+
+        let mut paths = self.changes.keys().collect::<Vec<_>>();
+        paths.sort();
+
+        let mut patch = String::new();
+        for path in paths {
+            let relative = path
+                .strip_prefix(&root)
+                .unwrap_or(path)
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+
+            let old_contents = match fs::read_to_string(path).await {
+                Ok(contents) => Some(contents),
+                Err(err) if err.kind() == ErrorKind::NotFound => None,
+                Err(err) => return Err(err),
+            };
+            let new_contents = match &self.changes[path] {
+                FileChange::Change(contents) => Some(contents.as_str()),
+                FileChange::Delete => None,
+            };
+
+            patch.push_str(&file_patch(
+                &relative,
+                old_contents.as_deref(),
+                new_contents,
+            ));
+        }
+
+        Ok(patch)
     }
 
     pub async fn apply(&mut self) -> std::io::Result<()> {
@@ -94,6 +143,9 @@ impl FileOperationStore {
                     fs::remove_file(path).await?;
                 }
                 FileChange::Change(new_contents) => {
+                    if let Some(parent) = path.parent() {
+                        fs::create_dir_all(parent).await?;
+                    }
                     fs::write(path, new_contents).await?;
                 }
             }
@@ -106,6 +158,8 @@ impl FileOperationStore {
 /// Prints created files, deleted files, and modified files.
 pub struct FileOperationsDisplay<'a> {
     store: &'a FileOperationStore,
+    /// The only file to show, if not all of them.
+    only: Option<&'a Path>,
     /// The contents of the files before the pending changes
     old_files: BTreeMap<PathBuf, String>,
     highlight: bool,
@@ -113,11 +167,16 @@ pub struct FileOperationsDisplay<'a> {
 }
 
 impl<'a> FileOperationsDisplay<'a> {
-    async fn new(store: &'a FileOperationStore, show_contents: bool, highlight: bool) -> Self {
+    async fn new(
+        store: &'a FileOperationStore,
+        only: Option<&'a Path>,
+        show_contents: bool,
+        highlight: bool,
+    ) -> Self {
         let mut read_tasks = JoinSet::new();
 
         for (file, change) in &store.changes {
-            if matches!(change, FileChange::Change(_)) {
+            if matches!(change, FileChange::Change(_)) && only.is_none_or(|only| only == file) {
                 let file = file.clone();
 
                 read_tasks.spawn(async move {
@@ -136,6 +195,7 @@ impl<'a> FileOperationsDisplay<'a> {
 
         Self {
             store,
+            only,
             highlight,
             old_files,
             show_contents,
@@ -278,6 +338,10 @@ impl Display for FileOperationsDisplay<'_> {
         let theme = &THEMES.themes["Solarized (dark)"];
 
         for (path, change) in &self.store.changes {
+            if self.only.is_some_and(|only| only != path) {
+                continue;
+            }
+
             let old_contents = self.old_files.get(path).map(|s| s.as_str());
 
             self.write_header(f, path, change, old_contents.is_none())?;
@@ -302,6 +366,98 @@ impl Display for FileOperationsDisplay<'_> {
     }
 }
 
+/// Lines of unchanged context around each hunk of a patch.
+const PATCH_CONTEXT: usize = 3;
+
+/// Formats the change of the file at `path` from `old` to `new` as a Git-style unified diff, where
+/// `None` means the file doesn't exist.
+fn file_patch(path: &str, old: Option<&str>, new: Option<&str>) -> String {
+    let old_lines = old
+        .unwrap_or_default()
+        .split_inclusive('\n')
+        .collect::<Vec<_>>();
+    let new_lines = new
+        .unwrap_or_default()
+        .split_inclusive('\n')
+        .collect::<Vec<_>>();
+
+    let lines = diff::slice(&old_lines, &new_lines)
+        .into_iter()
+        .map(|line| match line {
+            diff::Result::Left(text) => ('-', *text),
+            diff::Result::Both(text, _) => (' ', *text),
+            diff::Result::Right(text) => ('+', *text),
+        })
+        .collect::<Vec<_>>();
+
+    // Group the changed lines into hunks, merging any whose context would overlap.
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+    for (index, _) in lines
+        .iter()
+        .enumerate()
+        .filter(|(_, (kind, _))| *kind != ' ')
+    {
+        let start = index.saturating_sub(PATCH_CONTEXT);
+        let end = (index + PATCH_CONTEXT + 1).min(lines.len());
+
+        match hunks.last_mut() {
+            Some((_, last_end)) if start <= *last_end => *last_end = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+
+    if hunks.is_empty() && old.is_some() == new.is_some() {
+        return String::new();
+    }
+
+    let mut patch = format!("diff --git a/{path} b/{path}\n");
+    match (old, new) {
+        (None, _) => patch.push_str("new file mode 100644\n"),
+        (_, None) => patch.push_str("deleted file mode 100644\n"),
+        _ => {}
+    }
+    if hunks.is_empty() {
+        // Empty files are created or deleted without any hunks.
+        return patch;
+    }
+
+    patch.push_str(&match old {
+        Some(_) => format!("--- a/{path}\n"),
+        None => "--- /dev/null\n".to_string(),
+    });
+    patch.push_str(&match new {
+        Some(_) => format!("+++ b/{path}\n"),
+        None => "+++ /dev/null\n".to_string(),
+    });
+
+    for (start, end) in hunks {
+        let count = |lines: &[(char, &str)], skip: char| {
+            lines.iter().filter(|(kind, _)| *kind != skip).count()
+        };
+        let old_start = count(&lines[..start], '+');
+        let new_start = count(&lines[..start], '-');
+        let old_count = count(&lines[start..end], '+');
+        let new_count = count(&lines[start..end], '-');
+
+        // Hunks start at the line before an empty range, and at the first line otherwise.
+        patch.push_str(&format!(
+            "@@ -{},{old_count} +{},{new_count} @@\n",
+            old_start + usize::from(old_count > 0),
+            new_start + usize::from(new_count > 0),
+        ));
+
+        for (kind, text) in &lines[start..end] {
+            patch.push(*kind);
+            patch.push_str(text);
+            if !text.ends_with('\n') {
+                patch.push_str("\n\\ No newline at end of file\n");
+            }
+        }
+    }
+
+    patch
+}
+
 #[derive(Debug)]
 enum FileChange {
     Delete,
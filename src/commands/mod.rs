@@ -1,14 +1,53 @@
+pub mod analyze;
+pub mod battery;
+pub mod bloat;
+pub mod bridge;
 pub mod build;
 pub mod cat;
+pub mod copy;
+pub mod daemon;
+pub mod deploy;
 pub mod devices;
+pub mod df;
 pub mod dir;
 #[cfg(feature = "field-control")]
 pub mod field_control;
+pub mod firmware;
+pub mod history;
+pub mod info;
+pub mod kiosk;
 pub mod log;
+pub mod logs;
 pub mod new;
+pub mod passthrough;
+pub mod patch;
+pub mod patch_bases;
+#[cfg(feature = "plot")]
+pub mod plot;
+pub mod portcheck;
+pub mod preflight;
+pub mod program;
+pub mod pull;
+pub mod ra_config;
+pub mod push;
+pub mod radio;
 pub mod rm;
+#[cfg(feature = "screen")]
+pub mod screen;
 pub mod screenshot;
+pub mod serial_bridge;
+pub mod serve;
+pub mod size;
+pub mod slots;
+pub mod stamp;
+pub mod symbolize;
+pub mod team;
+pub mod telemetry;
 pub mod terminal;
+pub mod test;
+pub mod top;
+pub mod transfer;
 pub mod migrate;
 pub mod upload;
+pub mod websocket;
 pub mod key_value;
\ No newline at end of file
@@ -1,64 +1,284 @@
+use inquire::{
+    Confirm, CustomType, Select,
+    validator::{ErrorMessage, Validation},
+};
 use log::{debug, info, warn};
+#[cfg(feature = "fetch-template")]
 use serde_json::Value;
+use toml_edit::{DocumentMut, value};
 
-use crate::errors::CliError;
+use crate::{config::user_config, errors::CliError};
 use std::{
+    fmt::{self, Display},
     io,
     path::{Path, PathBuf},
+    str::FromStr,
 };
 
+/// The template new projects are created from by default.
+#[cfg(feature = "fetch-template")]
+const DEFAULT_TEMPLATE: &str = "vexide/vexide-template@main";
+
+#[cfg(feature = "fetch-template")]
+const TEMPLATE_FILE_NAME: &str = "template.tar.gz";
+#[cfg(feature = "fetch-template")]
+const SHA_FILE_NAME: &str = "cache-id.txt";
+
+/// A template as a `.tar.gz` archive, whose files are all inside one top-level directory.
 #[derive(Debug, Clone)]
 struct Template {
     pub data: Vec<u8>,
     pub sha: Option<String>,
 }
 
-const TEMPLATE_FILE_NAME: &str = "vexide-template.tar.gz";
-const SHA_FILE_NAME: &str = "cache-id.txt";
+/// Where a project template comes from, as passed to `--template`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TemplateSource {
+    /// A directory or `.tar.gz` archive on disk.
+    Path(PathBuf),
 
-#[cfg(feature = "fetch-template")]
-async fn get_current_sha() -> Result<String, CliError> {
-    let client = reqwest::Client::new();
-    let response = client
-        .get("https://api.github.com/repos/vexide/vexide-template/commits/main?per-page=1")
-        .header("User-Agent", "vexide/cargo-v5")
-        .send()
+    /// A GitHub repository (`org/repo`), optionally at a branch, tag, or commit.
+    GitHub { repo: String, rev: Option<String> },
+
+    /// Any repository `git clone` can fetch.
+    Git { url: String },
+}
+
+impl FromStr for TemplateSource {
+    type Err = CliError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if Path::new(s).exists() {
+            return Ok(Self::Path(PathBuf::from(s)));
+        }
+
+        if s.contains("://") || s.starts_with("git@") || s.ends_with(".git") {
+            return Ok(Self::Git { url: s.to_string() });
+        }
+
+        let (repo, rev) = match s.split_once('@') {
+            Some((repo, rev)) if !rev.is_empty() => (repo, Some(rev.to_string())),
+            Some(_) => return Err(CliError::InvalidTemplateSource(s.to_string())),
+            None => (s, None),
+        };
+        let is_name = |name: &str| {
+            !name.is_empty()
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        };
+
+        match repo.split_once('/') {
+            Some((org, name)) if is_name(org) && is_name(name) => Ok(Self::GitHub {
+                repo: repo.to_string(),
+                rev,
+            }),
+            _ => Err(CliError::InvalidTemplateSource(s.to_string())),
+        }
+    }
+}
+
+impl Display for TemplateSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Path(path) => write!(f, "{}", path.display()),
+            Self::GitHub { repo, rev: None } => f.write_str(repo),
+            Self::GitHub {
+                repo,
+                rev: Some(rev),
+            } => write!(f, "{repo}@{rev}"),
+            Self::Git { url } => f.write_str(url),
+        }
+    }
+}
+
+impl TemplateSource {
+    /// The URL to clone the template's repository from.
+    fn git_url(&self) -> Option<String> {
+        match self {
+            Self::Path(_) => None,
+            Self::GitHub { repo, .. } => Some(format!("https://github.com/{repo}.git")),
+            Self::Git { url } => Some(url.clone()),
+        }
+    }
+
+    /// The branch, tag, or commit to use.
+    fn rev(&self) -> Option<&str> {
+        match self {
+            Self::GitHub { rev, .. } => rev.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+/// Runs a Git command, returning its output.
+async fn git(source: &TemplateSource, dir: &Path, args: &[&str]) -> Result<String, CliError> {
+    let output = tokio::process::Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
         .await
-        .map_err(CliError::ReqwestError)?;
-    let response_text = response.text().await.map_err(CliError::ReqwestError)?;
-    match &serde_json::from_str::<Value>(&response_text).unwrap_or_default()["sha"] {
-        Value::String(str) => Ok(str.clone()),
-        _ => Err(CliError::MalformedResponse),
+        .map_err(|err| CliError::TemplateFetchFailed(source.to_string(), err.to_string()))?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    } else {
+        Err(CliError::TemplateFetchFailed(
+            source.to_string(),
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ))
     }
 }
 
-#[cfg(feature = "fetch-template")]
-async fn fetch_template() -> Result<Template, CliError> {
-    debug!("Fetching template...");
-    let response =
-        reqwest::get("https://github.com/vexide/vexide-template/archive/refs/heads/main.tar.gz")
-            .await;
-    let response = match response {
-        Ok(response) => response,
-        Err(err) => return Err(CliError::ReqwestError(err)),
-    };
-    let bytes = response.bytes().await?;
+/// Finds the commit the template's branch or tag currently points to.
+async fn get_current_sha(source: &TemplateSource) -> Result<String, CliError> {
+    #[cfg(feature = "fetch-template")]
+    if let TemplateSource::GitHub { repo, rev } = source {
+        let client = reqwest::Client::new();
+        let response = client
+            .get(format!(
+                "https://api.github.com/repos/{repo}/commits/{}?per-page=1",
+                rev.as_deref().unwrap_or("HEAD")
+            ))
+            .header("User-Agent", "vexide/cargo-v5")
+            .send()
+            .await
+            .map_err(CliError::ReqwestError)?;
+        let response_text = response.text().await.map_err(CliError::ReqwestError)?;
+        return match &serde_json::from_str::<Value>(&response_text).unwrap_or_default()["sha"] {
+            Value::String(str) => Ok(str.clone()),
+            _ => Err(CliError::MalformedResponse),
+        };
+    }
+
+    let url = source
+        .git_url()
+        .expect("only remote templates have a current commit");
+    let output = git(
+        source,
+        Path::new("."),
+        &["ls-remote", &url, source.rev().unwrap_or("HEAD")],
+    )
+    .await?;
+
+    match output.split_whitespace().next() {
+        Some(sha) => Ok(sha.to_string()),
+        // The revision is probably a commit, which never changes.
+        None => Ok(source.rev().unwrap_or_default().to_string()),
+    }
+}
+
+/// Downloads a remote template.
+async fn fetch_template(source: &TemplateSource) -> Result<Template, CliError> {
+    debug!("Fetching template {source}...");
 
+    #[cfg(feature = "fetch-template")]
+    if let TemplateSource::GitHub { repo, rev } = source {
+        let response = reqwest::get(format!(
+            "https://github.com/{repo}/archive/{}.tar.gz",
+            rev.as_deref().unwrap_or("HEAD")
+        ))
+        .await?
+        .error_for_status()?;
+        let bytes = response.bytes().await?;
+
+        debug!("Successfully fetched template.");
+        return Ok(Template {
+            data: bytes.to_vec(),
+            sha: get_current_sha(source).await.ok(),
+        });
+    }
+
+    let url = source.git_url().expect("only remote templates are fetched");
+    let clone_dir = std::env::temp_dir().join(format!("cargo-v5-template-{}", std::process::id()));
+    _ = tokio::fs::remove_dir_all(&clone_dir).await;
+
+    let mut args = vec!["clone", "--quiet", "--depth", "1"];
+    if let Some(rev) = source.rev() {
+        args.extend(["--branch", rev]);
+    }
+    let clone_path = clone_dir.to_string_lossy();
+    args.extend([url.as_str(), &clone_path]);
+
+    let result = async {
+        git(source, Path::new("."), &args).await?;
+        let sha = git(source, &clone_dir, &["rev-parse", "HEAD"]).await?;
+
+        Ok(Template {
+            data: pack_template(&clone_dir)?,
+            sha: Some(sha),
+        })
+    }
+    .await;
+
+    _ = tokio::fs::remove_dir_all(&clone_dir).await;
     debug!("Successfully fetched template.");
-    let template = Template {
-        data: bytes.to_vec(),
-        sha: get_current_sha().await.ok(),
-    };
-    store_cached_template(template.clone()).await;
-    Ok(template)
+    result
+}
+
+/// Uses a cached copy of a remote template if it's current, downloading it otherwise.
+async fn get_remote_template(
+    source: &TemplateSource,
+    download_template: bool,
+) -> Result<Template, CliError> {
+    let cached_template = get_cached_template(source).await;
+    if !download_template {
+        return cached_template.ok_or_else(|| CliError::TemplateNotCached(source.to_string()));
+    }
+
+    match (cached_template, get_current_sha(source).await) {
+        (Some(cached_template), Ok(current_sha))
+            if cached_template.sha.as_ref() == Some(&current_sha) =>
+        {
+            debug!("Cached template is current, skipping download.");
+            Ok(cached_template)
+        }
+        (cached_template, ..) => {
+            debug!("Cached template is out of date.");
+            match fetch_template(source).await {
+                Ok(template) => {
+                    store_cached_template(source, template.clone()).await;
+                    Ok(template)
+                }
+                Err(err) => match cached_template {
+                    Some(cached_template) => {
+                        warn!("Could not fetch template, falling back to cache.");
+                        Ok(cached_template)
+                    }
+                    None => Err(err),
+                },
+            }
+        }
+    }
+}
+
+/// Reads a template from a directory or archive, or downloads it.
+async fn get_template(
+    source: &TemplateSource,
+    download_template: bool,
+) -> Result<Template, CliError> {
+    match source {
+        TemplateSource::Path(path) => {
+            debug!("Using template from {}.", path.display());
+            Ok(Template {
+                data: if path.is_dir() {
+                    pack_template(path)?
+                } else {
+                    tokio::fs::read(path).await?
+                },
+                sha: None,
+            })
+        }
+        remote => get_remote_template(remote, download_template).await,
+    }
 }
 
 #[cfg(feature = "fetch-template")]
-async fn get_cached_template() -> Option<Template> {
-    match cached_template_dir() {
+async fn get_cached_template(source: &TemplateSource) -> Option<Template> {
+    match cached_template_dir(source) {
         Some(dir) => {
-            let cache_file = dir.with_file_name(TEMPLATE_FILE_NAME);
-            let sha_file = dir.with_file_name(SHA_FILE_NAME);
+            let cache_file = dir.join(TEMPLATE_FILE_NAME);
+            let sha_file = dir.join(SHA_FILE_NAME);
             let sha = tokio::fs::read_to_string(sha_file).await.ok();
             let data = tokio::fs::read(cache_file).await.ok();
             data.map(|data| Template { data, sha })
@@ -68,10 +288,11 @@ async fn get_cached_template() -> Option<Template> {
 }
 
 #[cfg(feature = "fetch-template")]
-async fn store_cached_template(template: Template) -> () {
-    if let Some(dir) = cached_template_dir() {
-        let cache_file = dir.with_file_name(TEMPLATE_FILE_NAME);
-        let sha_file = dir.with_file_name(SHA_FILE_NAME);
+async fn store_cached_template(source: &TemplateSource, template: Template) {
+    if let Some(dir) = cached_template_dir(source) {
+        let _ = tokio::fs::create_dir_all(&dir).await;
+        let cache_file = dir.join(TEMPLATE_FILE_NAME);
+        let sha_file = dir.join(SHA_FILE_NAME);
         let _ = tokio::fs::write(cache_file, &template.data).await;
         if let Some(sha) = template.sha {
             let _ = tokio::fs::write(sha_file, sha).await;
@@ -79,12 +300,36 @@ async fn store_cached_template(template: Template) -> () {
     }
 }
 
+/// The directory a remote template is cached in, named after its source.
 #[cfg(feature = "fetch-template")]
-fn cached_template_dir() -> Option<PathBuf> {
+fn cached_template_dir(source: &TemplateSource) -> Option<PathBuf> {
     use directories::ProjectDirs;
-    ProjectDirs::from("", "vexide", "cargo-v5").map(|dirs| dirs.cache_dir().to_owned())
+
+    let key = source
+        .to_string()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '-' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+
+    ProjectDirs::from("", "vexide", "cargo-v5")
+        .map(|dirs| dirs.cache_dir().join("templates").join(key))
+}
+
+// Templates can't be cached without knowing where the cache directory is.
+#[cfg(not(feature = "fetch-template"))]
+async fn get_cached_template(_source: &TemplateSource) -> Option<Template> {
+    None
 }
 
+#[cfg(not(feature = "fetch-template"))]
+async fn store_cached_template(_source: &TemplateSource, _template: Template) {}
+
 fn baked_in_template() -> Template {
     Template {
         data: include_bytes!("./vexide-template.tar.gz").to_vec(),
@@ -92,6 +337,39 @@ fn baked_in_template() -> Template {
     }
 }
 
+/// Archives a template directory, leaving out its Git repository and build output.
+fn pack_template(dir: &Path) -> io::Result<Vec<u8>> {
+    fn append_dir(
+        builder: &mut tar::Builder<flate2::write::GzEncoder<Vec<u8>>>,
+        dir: &Path,
+        archive_dir: &Path,
+    ) -> io::Result<()> {
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            if name == ".git" || name == "target" {
+                continue;
+            }
+
+            let path = entry.path();
+            let archive_path = archive_dir.join(&name);
+            if entry.file_type()?.is_dir() {
+                append_dir(builder, &path, &archive_path)?;
+            } else {
+                builder.append_path_with_name(&path, &archive_path)?;
+            }
+        }
+        Ok(())
+    }
+
+    let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(
+        Vec::new(),
+        flate2::Compression::default(),
+    ));
+    append_dir(&mut builder, dir, Path::new("template"))?;
+    builder.into_inner()?.finish()
+}
+
 fn unpack_template(template: Vec<u8>, dir: &PathBuf) -> io::Result<()> {
     let mut archive: tar::Archive<flate2::read::GzDecoder<&[u8]>> =
         tar::Archive::new(flate2::read::GzDecoder::new(&template[..]));
@@ -114,10 +392,245 @@ fn unpack_template(template: Vec<u8>, dir: &PathBuf) -> io::Result<()> {
     Ok(())
 }
 
+/// The program a new project starts with.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ProjectKind {
+    /// A robot with autonomous and driver control routines, run by competition control.
+    #[default]
+    Competition,
+
+    /// A `main` function that runs once.
+    Basic,
+}
+
+impl Display for ProjectKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Competition => "Competition (autonomous and driver control)",
+            Self::Basic => "Basic (a single main function)",
+        })
+    }
+}
+
+/// Licenses offered for new projects, as SPDX expressions.
+const LICENSES: &[&str] = &["MIT", "Apache-2.0", "MIT OR Apache-2.0"];
+
+/// How a new project is set up, as answered in the `cargo v5 new` wizard.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProjectOptions {
+    /// Program slot to upload to by default.
+    pub slot: u8,
+
+    /// The program the project starts with.
+    pub kind: ProjectKind,
+
+    /// Whether to set up a controller and drivetrain motors on ports 1 and 2.
+    pub devices: bool,
+
+    /// Whether to create a Git repository in the project.
+    pub git: bool,
+
+    /// SPDX license expression for the `license` field of `Cargo.toml`.
+    pub license: Option<String>,
+}
+
+impl Default for ProjectOptions {
+    /// The template's setup, unchanged.
+    fn default() -> Self {
+        Self {
+            slot: 1,
+            kind: ProjectKind::default(),
+            devices: false,
+            git: false,
+            license: None,
+        }
+    }
+}
+
+/// Asks how a new project should be set up.
+pub fn prompt_project_options() -> Result<ProjectOptions, CliError> {
+    let slot = CustomType::<u8>::new("Which slot should the program upload to?")
+        .with_default(1)
+        .with_validator(|slot: &u8| {
+            Ok(if (1..=8).contains(slot) {
+                Validation::Valid
+            } else {
+                Validation::Invalid(ErrorMessage::Custom("Slot out of range".to_string()))
+            })
+        })
+        .with_help_message("Type a slot number from 1 to 8, inclusive")
+        .prompt()?;
+
+    let kind = Select::new(
+        "What kind of program is this?",
+        vec![ProjectKind::Competition, ProjectKind::Basic],
+    )
+    .prompt()?;
+
+    let devices = Confirm::new("Set up a controller and motors on ports 1 and 2?")
+        .with_default(false)
+        .prompt()?;
+
+    let git = Confirm::new("Create a Git repository?")
+        .with_default(true)
+        .prompt()?;
+
+    let mut licenses = LICENSES.to_vec();
+    licenses.push("None");
+    let license = Select::new("Which license should the project use?", licenses)
+        .prompt()
+        .map(|license| (license != "None").then(|| license.to_string()))?;
+
+    Ok(ProjectOptions {
+        slot,
+        kind,
+        devices,
+        git,
+        license,
+    })
+}
+
+/// Source for `src/main.rs`, or [`None`] to keep the template's.
+fn main_source(options: &ProjectOptions) -> Option<String> {
+    let source = match (options.kind, options.devices) {
+        (ProjectKind::Competition, false) => return None,
+        (ProjectKind::Competition, true) => COMPETITION_DEVICES_MAIN,
+        (ProjectKind::Basic, false) => BASIC_MAIN,
+        (ProjectKind::Basic, true) => BASIC_DEVICES_MAIN,
+    };
+
+    Some(source.to_string())
+}
+
+const COMPETITION_DEVICES_MAIN: &str = r#"use vexide::prelude::*;
+
+struct Robot {
+    controller: Controller,
+    left_motor: Motor,
+    right_motor: Motor,
+}
+
+impl Compete for Robot {
+    async fn autonomous(&mut self) {
+        println!("Autonomous!");
+    }
+
+    async fn driver(&mut self) {
+        println!("Driver!");
+
+        loop {
+            let state = self.controller.state().unwrap_or_default();
+
+            // Tank drive: each stick drives one side of the robot.
+            _ = self
+                .left_motor
+                .set_voltage(state.left_stick.y() * Motor::V5_MAX_VOLTAGE);
+            _ = self
+                .right_motor
+                .set_voltage(state.right_stick.y() * Motor::V5_MAX_VOLTAGE);
+
+            sleep(Controller::UPDATE_INTERVAL).await;
+        }
+    }
+}
+
+#[vexide::main]
+async fn main(peripherals: Peripherals) {
+    let robot = Robot {
+        controller: peripherals.primary_controller,
+        left_motor: Motor::new(peripherals.port_1, Gearset::Green, Direction::Forward),
+        right_motor: Motor::new(peripherals.port_2, Gearset::Green, Direction::Reverse),
+    };
+
+    robot.compete().await;
+}
+"#;
+
+const BASIC_MAIN: &str = r#"use vexide::prelude::*;
+
+#[vexide::main]
+async fn main(_peripherals: Peripherals) {
+    println!("Hello, world!");
+}
+"#;
+
+const BASIC_DEVICES_MAIN: &str = r#"use vexide::prelude::*;
+
+#[vexide::main]
+async fn main(peripherals: Peripherals) {
+    let controller = peripherals.primary_controller;
+    let mut left_motor = Motor::new(peripherals.port_1, Gearset::Green, Direction::Forward);
+    let mut right_motor = Motor::new(peripherals.port_2, Gearset::Green, Direction::Reverse);
+
+    loop {
+        let state = controller.state().unwrap_or_default();
+
+        // Tank drive: each stick drives one side of the robot.
+        _ = left_motor.set_voltage(state.left_stick.y() * Motor::V5_MAX_VOLTAGE);
+        _ = right_motor.set_voltage(state.right_stick.y() * Motor::V5_MAX_VOLTAGE);
+
+        sleep(Controller::UPDATE_INTERVAL).await;
+    }
+}
+"#;
+
+/// Writes the answers from the wizard into an unpacked template.
+async fn apply_project_options(
+    dir: &Path,
+    name: &str,
+    options: &ProjectOptions,
+) -> Result<(), CliError> {
+    let manifest_path = dir.join("Cargo.toml");
+    let manifest = tokio::fs::read_to_string(&manifest_path).await?;
+
+    match manifest.parse::<DocumentMut>() {
+        Ok(mut document) if document["package"].is_table_like() => {
+            document["package"]["name"] = value(name);
+            if let Some(license) = &options.license {
+                document["package"]["license"] = value(license);
+            }
+            document["package"]["metadata"]["v5"]["slot"] = value(i64::from(options.slot));
+
+            tokio::fs::write(&manifest_path, document.to_string()).await?;
+        }
+        Ok(_) => {
+            warn!("The template's Cargo.toml has no package, so its settings weren't changed.")
+        }
+        Err(err) => warn!("Couldn't change the settings in the template's Cargo.toml: {err}"),
+    }
+
+    if let Some(source) = main_source(options) {
+        tokio::fs::create_dir_all(dir.join("src")).await?;
+        tokio::fs::write(dir.join("src").join("main.rs"), source).await?;
+    }
+
+    if options.git {
+        debug!("Creating a Git repository...");
+        let status = tokio::process::Command::new("git")
+            .arg("init")
+            .arg("--quiet")
+            .current_dir(dir)
+            .status()
+            .await;
+
+        match status {
+            Ok(status) if status.success() => {}
+            Ok(_) => warn!("`git init` failed, so the project isn't a Git repository."),
+            Err(err) => {
+                warn!("Couldn't run `git init` ({err}), so the project isn't a Git repository.")
+            }
+        }
+    }
+
+    Ok(())
+}
+
 pub async fn new(
     path: PathBuf,
     name: Option<String>,
+    template: Option<TemplateSource>,
     download_template: bool,
+    options: ProjectOptions,
 ) -> Result<(), CliError> {
     let dir = if let Some(name) = &name {
         let dir = path.join(name);
@@ -143,35 +656,26 @@ pub async fn new(
         })
         .unwrap_or("vexide project".to_string());
 
-    #[cfg(feature = "fetch-template")]
-    let template = match (get_cached_template().await, get_current_sha().await) {
-        (cached_template, ..) if !download_template => cached_template,
-        (Some(cached_template), Ok(current_sha))
-            if cached_template.sha == Some(current_sha.clone()) =>
-        {
-            debug!("Cached template is current, skipping download.");
-            Some(cached_template)
-        }
-        (cached_template, ..) => {
-            debug!("Cached template is out of date.");
-            let fetched_template = fetch_template().await.ok();
-            fetched_template.or_else(|| {
-                warn!("Could not fetch template, falling back to cache.");
-                cached_template
-            })
-        }
-    }
-    .unwrap_or_else(|| {
-        debug!("No template found in cache, using builtin template.");
-        baked_in_template()
-    });
-
-    #[cfg(not(feature = "fetch-template"))]
-    let template = baked_in_template();
+    let template =
+        match template.or_else(|| user_config().new.template.clone().map(TemplateSource::Path)) {
+            Some(source) => get_template(&source, download_template).await?,
+            #[cfg(feature = "fetch-template")]
+            None => {
+                let source = DEFAULT_TEMPLATE.parse::<TemplateSource>()?;
+                get_remote_template(&source, download_template)
+                    .await
+                    .unwrap_or_else(|err| {
+                        debug!("Couldn't get {source} ({err}), using builtin template.");
+                        baked_in_template()
+                    })
+            }
+            #[cfg(not(feature = "fetch-template"))]
+            None => baked_in_template(),
+        };
 
     debug!("Unpacking template...");
     unpack_template(template.data, &dir)?;
-    debug!("Successfully unpacked vexide-template!");
+    debug!("Successfully unpacked template!");
 
     debug!("Renaming project to {}...", &name);
     let manifest_path = dir.join("Cargo.toml");
@@ -179,6 +683,8 @@ pub async fn new(
     let manifest = manifest.replace("vexide-template", &name);
     tokio::fs::write(manifest_path, manifest).await?;
 
+    apply_project_options(&dir, &name, &options).await?;
+
     info!("Successfully created new project at {dir:?}");
     Ok(())
 }
@@ -0,0 +1,258 @@
+//! Raw passthrough of a program's I/O, for programs that speak binary protocols over the user
+//! port.
+//!
+//! Unlike the regular terminal, output is passed on byte for byte, without any line handling or
+//! backtrace symbolization. With COBS framing, both directions carry zero-terminated COBS frames,
+//! and only whole frames are passed on, so a frame is never split between writes. Frames that
+//! fail to decode are dropped.
+//!
+//! VEXos only carries text over a controller's wireless link, so passthrough needs a direct
+//! connection to the Brain.
+
+use std::time::Duration;
+
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    select,
+    time::sleep,
+};
+use vex_v5_serial::{Connection, ConnectionType};
+
+use crate::{connection::DeviceConnection, errors::CliError};
+
+/// Encodes `data` as a COBS frame, including the trailing zero byte.
+pub fn cobs_encode(data: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(data.len() + data.len() / 254 + 2);
+    let mut code_index = 0;
+    let mut code = 1u8;
+    frame.push(0);
+
+    for &byte in data {
+        if byte != 0 {
+            frame.push(byte);
+            code += 1;
+        }
+
+        if byte == 0 || code == 0xFF {
+            frame[code_index] = code;
+            code_index = frame.len();
+            frame.push(0);
+            code = 1;
+        }
+    }
+
+    frame[code_index] = code;
+    frame.push(0);
+    frame
+}
+
+/// Decodes a COBS frame without its trailing zero byte, returning `None` if it's malformed.
+pub fn cobs_decode(frame: &[u8]) -> Option<Vec<u8>> {
+    let mut data = Vec::with_capacity(frame.len());
+    let mut i = 0;
+
+    while i < frame.len() {
+        let code = frame[i] as usize;
+        let end = i + code;
+        if code == 0 || end > frame.len() {
+            return None;
+        }
+
+        data.extend_from_slice(&frame[i + 1..end]);
+        i = end;
+
+        if code != 0xFF && i < frame.len() {
+            data.push(0);
+        }
+    }
+
+    Some(data)
+}
+
+/// Splits a stream of bytes into COBS frames and decodes them.
+#[derive(Debug, Default)]
+pub struct CobsDecoder {
+    frame: Vec<u8>,
+}
+
+impl CobsDecoder {
+    /// Returns the decoded contents of every frame completed by `data`.
+    pub fn feed(&mut self, data: &[u8]) -> Vec<Vec<u8>> {
+        let mut frames = Vec::new();
+
+        for &byte in data {
+            if byte != 0 {
+                self.frame.push(byte);
+                continue;
+            }
+
+            if !self.frame.is_empty() {
+                frames.extend(cobs_decode(&self.frame));
+                self.frame.clear();
+            }
+        }
+
+        frames
+    }
+}
+
+/// Passes a program's I/O through to `output` and from `input` unchanged, optionally as whole
+/// COBS frames, forever.
+///
+/// Output keeps being passed on after `input` reaches its end.
+pub async fn passthrough(
+    connection: &mut DeviceConnection,
+    mut input: impl AsyncRead + Unpin,
+    mut output: impl AsyncWrite + Unpin,
+    cobs: bool,
+) -> Result<(), CliError> {
+    if connection.connection_type() == ConnectionType::Controller {
+        return Err(CliError::PassthroughOverController);
+    }
+
+    let mut output_frames = cobs.then(CobsDecoder::default);
+    let mut input_frames = cobs.then(CobsDecoder::default);
+    let mut program_output = [0; 2048];
+    let mut program_input = [0; 4096];
+    let mut input_open = true;
+
+    loop {
+        select! {
+            read = connection.read_user(&mut program_output) => {
+                let Ok(size @ 1..) = read else {
+                    sleep(Duration::from_millis(10)).await;
+                    continue;
+                };
+
+                match &mut output_frames {
+                    Some(frames) => {
+                        for frame in frames.feed(&program_output[..size]) {
+                            output.write_all(&cobs_encode(&frame)).await?;
+                        }
+                    }
+                    None => output.write_all(&program_output[..size]).await?,
+                }
+                output.flush().await?;
+            },
+            read = input.read(&mut program_input), if input_open => {
+                let data = match read {
+                    Ok(0) | Err(_) => {
+                        input_open = false;
+                        continue;
+                    }
+                    Ok(size) => match &mut input_frames {
+                        Some(frames) => frames
+                            .feed(&program_input[..size])
+                            .iter()
+                            .flat_map(|frame| cobs_encode(frame))
+                            .collect(),
+                        None => program_input[..size].to_vec(),
+                    },
+                };
+
+                let mut data = data.as_slice();
+                while !data.is_empty() {
+                    let size = connection.write_user(data).await?;
+                    data = &data[size..];
+                }
+            }
+        }
+    }
+}
+
+/// A pseudoterminal that other programs can open like a serial port.
+#[cfg(unix)]
+pub struct Pty {
+    /// Path of the pseudoterminal's device, like `/dev/pts/3`.
+    pub path: std::path::PathBuf,
+    /// Reads what other programs write to the pseudoterminal.
+    pub reader: tokio::fs::File,
+    /// Writes to the pseudoterminal for other programs to read.
+    pub writer: tokio::fs::File,
+    // Reading the pseudoterminal fails while nothing has it open, so it's held open here.
+    _device: std::os::fd::OwnedFd,
+}
+
+#[cfg(unix)]
+impl Pty {
+    /// Opens a new pseudoterminal in raw mode, so that bytes pass through it unchanged.
+    pub fn open() -> std::io::Result<Self> {
+        use nix::{
+            pty::openpty,
+            sys::termios::{SetArg, cfmakeraw, tcgetattr, tcsetattr},
+            unistd::ttyname,
+        };
+
+        let pty = openpty(None, None)?;
+
+        let mut termios = tcgetattr(&pty.slave)?;
+        cfmakeraw(&mut termios);
+        tcsetattr(&pty.slave, SetArg::TCSANOW, &termios)?;
+
+        let reader = std::fs::File::from(pty.master);
+        let writer = reader.try_clone()?;
+
+        Ok(Self {
+            path: ttyname(&pty.slave)?,
+            reader: tokio::fs::File::from_std(reader),
+            writer: tokio::fs::File::from_std(writer),
+            _device: pty.slave,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cobs_round_trips() {
+        let long = (0..=255).cycle().take(600).collect::<Vec<u8>>();
+        let cases: [&[u8]; 7] = [
+            &[],
+            &[0],
+            &[0, 0],
+            &[1, 2, 3],
+            &[1, 0, 2, 0],
+            &[0xFF; 254],
+            &long,
+        ];
+
+        for data in cases {
+            let frame = cobs_encode(data);
+            assert_eq!(frame.last(), Some(&0));
+            assert!(!frame[..frame.len() - 1].contains(&0));
+            assert_eq!(
+                cobs_decode(&frame[..frame.len() - 1]).as_deref(),
+                Some(data)
+            );
+        }
+    }
+
+    #[test]
+    fn cobs_encodes_known_frames() {
+        assert_eq!(cobs_encode(&[]), [1, 0]);
+        assert_eq!(cobs_encode(&[0]), [1, 1, 0]);
+        assert_eq!(
+            cobs_encode(&[0x11, 0x22, 0, 0x33]),
+            [3, 0x11, 0x22, 2, 0x33, 0]
+        );
+    }
+
+    #[test]
+    fn cobs_rejects_malformed_frames() {
+        assert_eq!(cobs_decode(&[0]), None);
+        assert_eq!(cobs_decode(&[5, 1, 2]), None);
+    }
+
+    #[test]
+    fn decoder_splits_frames_across_reads() {
+        let mut stream = cobs_encode(b"first");
+        stream.extend(cobs_encode(&[1, 0, 2]));
+        let (start, end) = stream.split_at(4);
+
+        let mut decoder = CobsDecoder::default();
+        assert!(decoder.feed(start).is_empty());
+        assert_eq!(decoder.feed(end), [b"first".to_vec(), vec![1, 0, 2]]);
+    }
+}
@@ -0,0 +1,61 @@
+use std::path::{Path, PathBuf};
+
+use humansize::{BINARY, format_size};
+
+use crate::errors::CliError;
+
+use super::upload::{DIFFERENTIAL_UPLOAD_MAX_SIZE, build_patch, gzip_compress};
+
+/// Writes the patch that a differential upload would send to turn `old` into `new`.
+///
+/// The patch is gzipped like it is during uploads unless `uncompressed` is set. Returns the path
+/// the patch was written to, which defaults to `new` with a `.patch` extension.
+pub async fn patch(
+    old: &Path,
+    new: &Path,
+    output: Option<PathBuf>,
+    uncompressed: bool,
+) -> Result<PathBuf, CliError> {
+    let old_data = tokio::fs::read(old).await?;
+    let new_data = tokio::fs::read(new).await?;
+
+    if old_data.len() > DIFFERENTIAL_UPLOAD_MAX_SIZE {
+        return Err(CliError::ProgramTooLarge(old_data.len()));
+    } else if new_data.len() > DIFFERENTIAL_UPLOAD_MAX_SIZE {
+        return Err(CliError::ProgramTooLarge(new_data.len()));
+    }
+
+    let mut patch = build_patch(&old_data, &new_data);
+    let patch_size = patch.len();
+
+    if patch_size > DIFFERENTIAL_UPLOAD_MAX_SIZE {
+        return Err(CliError::PatchTooLarge(patch_size));
+    }
+
+    if !uncompressed {
+        gzip_compress(&mut patch);
+    }
+
+    let output = output.unwrap_or_else(|| new.with_extension("patch"));
+    tokio::fs::write(&output, &patch).await?;
+
+    eprintln!("       \x1b[1;92mWrote\x1b[0m {}", output.display());
+    eprintln!(
+        "             {} -> {}",
+        format_size(old_data.len(), BINARY),
+        format_size(new_data.len(), BINARY)
+    );
+    eprintln!(
+        "             patch is {} ({:.1}% of the new binary)",
+        format_size(patch_size, BINARY),
+        patch_size as f64 / new_data.len().max(1) as f64 * 100.0
+    );
+    if !uncompressed {
+        eprintln!(
+            "             {} after compression",
+            format_size(patch.len(), BINARY)
+        );
+    }
+
+    Ok(output)
+}
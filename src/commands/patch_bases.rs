@@ -0,0 +1,193 @@
+//! Storage for the base binaries that differential uploads patch against.
+//!
+//! A differential upload only sends a patch against the base binary last uploaded in full to the
+//! slot, so a copy of every base is kept on disk. Bases live in `target/v5/patch-bases/`, keyed by
+//! the target, profile, and slot they were uploaded to, so switching profiles doesn't patch
+//! against the wrong base:
+//!
+//! ```text
+//! target/v5/patch-bases/armv7a-vex-v5/release/slot_1.bin
+//! target/v5/patch-bases/armv7a-vex-v5/release/slot_1.json
+//! ```
+//!
+//! The JSON file records the checksum the Brain reports for the uploaded base, which is checked
+//! before every patch so that a base changed by another machine or branch is never patched
+//! against, along with the commit it was built from. Bases that go unused for
+//! [`BASE_LIFETIME`] are deleted.
+
+use std::{
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use serde_json::{Value, json};
+
+use crate::errors::CliError;
+
+/// How long a base can go unused before it's deleted.
+pub const BASE_LIFETIME: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// A base binary previously uploaded to a slot.
+#[derive(Debug, Clone)]
+pub struct PatchBase {
+    /// The uncompressed base binary.
+    pub data: Vec<u8>,
+    /// CRC32 of the base as uploaded, which the Brain reports in the file's metadata.
+    pub crc32: u32,
+    /// The commit the base was built from, if known.
+    pub commit: Option<String>,
+}
+
+/// Where base binaries for a particular target and profile are kept.
+#[derive(Debug, Clone)]
+pub struct PatchBases {
+    root: PathBuf,
+    dir: PathBuf,
+    /// The commit being uploaded, recorded with new bases.
+    commit: Option<String>,
+}
+
+impl PatchBases {
+    /// Bases in `dir`, without a commit to record.
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            root: dir.clone(),
+            dir,
+            commit: None,
+        }
+    }
+
+    /// Bases for `artifact`, kept under `target_dir`.
+    ///
+    /// Artifacts built by Cargo are keyed by their target and profile. Anything else, like an ELF
+    /// passed to `--file` from outside the target directory, shares an `external` directory.
+    /// Without a target directory, bases are kept in the system's temporary directory.
+    pub fn for_artifact(target_dir: Option<&Path>, artifact: &Path) -> Self {
+        let Some(target_dir) = target_dir else {
+            return Self::new(std::env::temp_dir().join("cargo-v5").join("patch-bases"));
+        };
+
+        let root = target_dir.join("v5").join("patch-bases");
+        let key = artifact
+            .parent()
+            .and_then(|dir| dir.strip_prefix(target_dir).ok())
+            .filter(|key| key.components().count() == 2)
+            .map_or_else(|| PathBuf::from("external"), Path::to_path_buf);
+
+        Self {
+            dir: root.join(key),
+            root,
+            commit: None,
+        }
+    }
+
+    /// Records `commit` with any bases written from now on.
+    pub fn with_commit(mut self, commit: Option<String>) -> Self {
+        self.commit = commit;
+        self
+    }
+
+    fn paths(&self, slot: u8) -> (PathBuf, PathBuf) {
+        (
+            self.dir.join(format!("slot_{slot}.bin")),
+            self.dir.join(format!("slot_{slot}.json")),
+        )
+    }
+
+    /// Reads the base last uploaded to `slot`, if there is one.
+    pub async fn read(&self, slot: u8) -> Option<PatchBase> {
+        let (data_path, info_path) = self.paths(slot);
+
+        let info = tokio::fs::read_to_string(&info_path).await.ok()?;
+        let info = serde_json::from_str::<Value>(&info).ok()?;
+        let crc32 = u32::try_from(info["crc32"].as_u64()?).ok()?;
+
+        Some(PatchBase {
+            data: tokio::fs::read(&data_path).await.ok()?,
+            crc32,
+            commit: info["commit"].as_str().map(str::to_string),
+        })
+    }
+
+    /// Stores `data` as the base uploaded to `slot`, whose uploaded copy has the checksum `crc32`.
+    pub async fn write(&self, slot: u8, data: &[u8], crc32: u32) -> Result<(), CliError> {
+        let (data_path, info_path) = self.paths(slot);
+
+        tokio::fs::create_dir_all(&self.dir).await?;
+        tokio::fs::write(&data_path, data).await?;
+        self.write_info(
+            &info_path,
+            json!({
+                "crc32": crc32,
+                "commit": self.commit,
+            }),
+        )
+        .await
+    }
+
+    async fn write_info(&self, path: &Path, info: Value) -> Result<(), CliError> {
+        tokio::fs::write(path, format!("{info:#}\n")).await?;
+        Ok(())
+    }
+
+    /// Marks the base for `slot` as used, so it isn't garbage collected.
+    pub async fn touch(&self, slot: u8) {
+        let (_, info_path) = self.paths(slot);
+
+        // Rewriting the file updates its modification time.
+        if let Ok(info) = tokio::fs::read(&info_path).await {
+            _ = tokio::fs::write(&info_path, info).await;
+        }
+    }
+
+    /// Deletes bases that haven't been used in [`BASE_LIFETIME`], along with any bases left next
+    /// to artifacts by older versions of cargo-v5.
+    pub async fn collect_garbage(&self, artifact_dir: Option<&Path>) {
+        let now = SystemTime::now();
+        let mut dirs = vec![self.root.clone()];
+
+        while let Some(dir) = dirs.pop() {
+            let Ok(mut entries) = tokio::fs::read_dir(&dir).await else {
+                continue;
+            };
+
+            while let Ok(Some(entry)) = entries.next_entry().await {
+                let path = entry.path();
+                let Ok(metadata) = entry.metadata().await else {
+                    continue;
+                };
+
+                if metadata.is_dir() {
+                    dirs.push(path);
+                    continue;
+                }
+
+                let stale = metadata
+                    .modified()
+                    .ok()
+                    .and_then(|modified| now.duration_since(modified).ok())
+                    .is_some_and(|age| age > BASE_LIFETIME);
+                if stale && path.extension().is_some_and(|ext| ext == "json") {
+                    log::debug!("Removing stale patch base {}", path.display());
+                    remove_if_exists(&path.with_extension("bin")).await;
+                    remove_if_exists(&path).await;
+                }
+            }
+        }
+
+        if let Some(artifact_dir) = artifact_dir {
+            for slot in 1..=8 {
+                remove_if_exists(&artifact_dir.join(format!("slot_{slot}.base.bin"))).await;
+            }
+        }
+    }
+}
+
+async fn remove_if_exists(path: &Path) {
+    match tokio::fs::remove_file(path).await {
+        Ok(()) => {}
+        Err(err) if err.kind() == ErrorKind::NotFound => {}
+        Err(err) => log::debug!("Couldn't remove {}: {err}", path.display()),
+    }
+}
@@ -0,0 +1,307 @@
+//! Live plots of values printed by a program.
+//!
+//! A program plots values by printing lines of the form:
+//!
+//! ```text
+//! #plot <name>=<value> [<name>=<value>...]
+//! ```
+//!
+//! Each name is drawn as its own series, with values that aren't numbers ignored. All other
+//! output is shown below the chart.
+
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+    time::Duration,
+};
+
+use crossterm::event::{self, Event, KeyCode, KeyModifiers};
+use ratatui::{
+    Frame,
+    buffer::Buffer,
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Style, Stylize},
+    symbols::{self, Marker, border, line},
+    text::Line,
+    widgets::{Axis, Block, Chart, Dataset, GraphType, Paragraph},
+};
+use tokio::{
+    select,
+    time::{Instant, interval},
+};
+use vex_v5_serial::Connection;
+
+use crate::{connection::DeviceConnection, errors::CliError};
+
+use super::upload::ascii_output;
+
+/// Prefix of lines containing values to plot.
+pub const PLOT_PREFIX: &str = "#plot";
+
+/// How often the chart is redrawn.
+const REFRESH_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Number of lines of regular program output kept on screen.
+const OUTPUT_LINES: usize = 5;
+
+/// Colors given to series, in order of their names.
+const SERIES_COLORS: [Color; 6] = [
+    Color::Cyan,
+    Color::Yellow,
+    Color::Magenta,
+    Color::Green,
+    Color::Red,
+    Color::Blue,
+];
+
+/// Parses a plot line into its names and values, returning `None` if the line isn't one.
+pub fn parse_plot_line(line: &str) -> Option<Vec<(&str, f64)>> {
+    let fields = line.trim().strip_prefix(PLOT_PREFIX)?;
+
+    Some(
+        fields
+            .split_whitespace()
+            .filter_map(|field| {
+                let (name, value) = field.split_once('=')?;
+                Some((name, value.parse().ok()?))
+            })
+            .collect(),
+    )
+}
+
+/// Recent values of each series, as seconds since plotting started and the value at that time.
+#[derive(Debug, Default)]
+struct Series {
+    points: BTreeMap<String, VecDeque<(f64, f64)>>,
+}
+
+impl Series {
+    fn push(&mut self, name: &str, time: f64, value: f64) {
+        self.points
+            .entry(name.to_string())
+            .or_default()
+            .push_back((time, value));
+    }
+
+    /// Drops points older than `window` seconds before `now`.
+    fn trim(&mut self, now: f64, window: f64) {
+        for points in self.points.values_mut() {
+            while points.front().is_some_and(|(time, _)| *time < now - window) {
+                points.pop_front();
+            }
+        }
+    }
+
+    /// The range of the values currently shown, padded so flat lines stay visible.
+    fn value_bounds(&self) -> [f64; 2] {
+        let (min, max) = self.points.values().flatten().fold(
+            (f64::INFINITY, f64::NEG_INFINITY),
+            |(min, max), (_, value)| (min.min(*value), max.max(*value)),
+        );
+
+        if min > max {
+            [-1.0, 1.0]
+        } else if min == max {
+            [min - 1.0, max + 1.0]
+        } else {
+            let padding = (max - min) * 0.05;
+            [min - padding, max + padding]
+        }
+    }
+}
+
+/// Plots values printed by the running program until the user quits, showing the last `window`
+/// of each series. Every value is also written to `csv` if a path is given.
+pub async fn plot(
+    connection: &mut DeviceConnection,
+    window: Duration,
+    csv: Option<&Path>,
+) -> Result<(), CliError> {
+    let mut csv = csv
+        .map(|path| -> io::Result<_> {
+            let mut file = BufWriter::new(File::create(path)?);
+            writeln!(file, "time,series,value")?;
+            Ok(file)
+        })
+        .transpose()?;
+
+    let mut terminal = ratatui::init();
+
+    let result = async {
+        let started = Instant::now();
+        let mut series = Series::default();
+        let mut output = VecDeque::with_capacity(OUTPUT_LINES);
+        let mut partial_line = Vec::new();
+        let mut buf = [0; 2048];
+        let mut refresh = interval(REFRESH_INTERVAL);
+
+        loop {
+            select! {
+                read = connection.read_user(&mut buf) => {
+                    let Ok(size) = read else {
+                        continue;
+                    };
+
+                    partial_line.extend_from_slice(&buf[..size]);
+
+                    while let Some(newline) = partial_line.iter().position(|&b| b == b'\n') {
+                        let line = String::from_utf8_lossy(&partial_line[..newline])
+                            .trim_end()
+                            .to_string();
+                        partial_line.drain(..=newline);
+
+                        let time = started.elapsed().as_secs_f64();
+                        if let Some(values) = parse_plot_line(&line) {
+                            for (name, value) in values {
+                                series.push(name, time, value);
+                                if let Some(csv) = &mut csv {
+                                    writeln!(csv, "{time:.3},{name},{value}")?;
+                                }
+                            }
+                        } else {
+                            if output.len() == OUTPUT_LINES {
+                                output.pop_front();
+                            }
+                            output.push_back(line);
+                        }
+                    }
+                },
+                _ = refresh.tick() => {
+                    if quit_requested()? {
+                        return Ok(());
+                    }
+
+                    let now = started.elapsed().as_secs_f64();
+                    series.trim(now, window.as_secs_f64());
+                    terminal.draw(|frame| draw(frame, &series, &output, now, window))?;
+                }
+            }
+        }
+    }
+    .await;
+
+    ratatui::restore();
+
+    if let Some(mut csv) = csv {
+        csv.flush()?;
+    }
+
+    result
+}
+
+/// Whether the user pressed a key to quit since the last frame.
+fn quit_requested() -> io::Result<bool> {
+    while event::poll(Duration::ZERO)? {
+        if let Event::Key(key) = event::read()?
+            && (matches!(key.code, KeyCode::Char('q') | KeyCode::Esc)
+                || (key.code == KeyCode::Char('c')
+                    && key.modifiers.contains(KeyModifiers::CONTROL)))
+        {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+/// Border drawn around the chart when only ASCII can be shown.
+const ASCII_BORDER: border::Set = border::Set {
+    top_left: "+",
+    top_right: "+",
+    bottom_left: "+",
+    bottom_right: "+",
+    vertical_left: "|",
+    vertical_right: "|",
+    horizontal_top: "-",
+    horizontal_bottom: "-",
+};
+
+/// Replaces the symbols ratatui always draws a chart's points, axes, and legend with in `area`
+/// with ASCII ones, since it has no ASCII versions of them.
+fn asciify(buffer: &mut Buffer, area: Rect) {
+    for position in area.positions() {
+        let cell = &mut buffer[position];
+        let ascii = match cell.symbol() {
+            symbols::DOT => "*",
+            line::HORIZONTAL => "-",
+            line::VERTICAL => "|",
+            line::TOP_LEFT | line::TOP_RIGHT | line::BOTTOM_LEFT | line::BOTTOM_RIGHT => "+",
+            _ => continue,
+        };
+        cell.set_symbol(ascii);
+    }
+}
+
+fn draw(frame: &mut Frame, series: &Series, output: &VecDeque<String>, now: f64, window: Duration) {
+    let [chart_area, output_area, status_area] = Layout::vertical([
+        Constraint::Min(5),
+        Constraint::Length(OUTPUT_LINES as u16),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+
+    let points = series
+        .points
+        .values()
+        .map(|points| points.iter().copied().collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    // Braille dots draw smoother lines, but plain ASCII only has room for one point per cell.
+    let ascii = ascii_output();
+    let marker = if ascii { Marker::Dot } else { Marker::Braille };
+    let datasets = series
+        .points
+        .keys()
+        .zip(&points)
+        .enumerate()
+        .map(|(i, (name, points))| {
+            Dataset::default()
+                .name(name.as_str())
+                .marker(marker)
+                .graph_type(GraphType::Line)
+                .style(Style::default().fg(SERIES_COLORS[i % SERIES_COLORS.len()]))
+                .data(points)
+        })
+        .collect::<Vec<_>>();
+
+    let start = (now - window.as_secs_f64()).max(0.0);
+    let [min, max] = series.value_bounds();
+    let border = if ascii { ASCII_BORDER } else { border::PLAIN };
+    let chart = Chart::new(datasets)
+        .block(Block::bordered().border_set(border))
+        .x_axis(
+            Axis::default()
+                .bounds([start, start + window.as_secs_f64()])
+                .labels([
+                    format!("{start:.0}s"),
+                    format!("{:.0}s", start + window.as_secs_f64()),
+                ]),
+        )
+        .y_axis(
+            Axis::default()
+                .bounds([min, max])
+                .labels([format!("{min:.2}"), format!("{max:.2}")]),
+        );
+    frame.render_widget(chart, chart_area);
+    if ascii {
+        asciify(frame.buffer_mut(), chart_area);
+    }
+
+    frame.render_widget(
+        Paragraph::new(
+            output
+                .iter()
+                .map(|line| Line::from(line.as_str()))
+                .collect::<Vec<_>>(),
+        ),
+        output_area,
+    );
+
+    let status = if series.points.is_empty() {
+        format!(" waiting for `{PLOT_PREFIX} <name>=<value>` from the program | q to quit")
+    } else {
+        format!(" {} series | q to quit", series.points.len())
+    };
+    frame.render_widget(Line::from(status).dim(), status_area);
+}
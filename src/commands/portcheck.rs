@@ -0,0 +1,166 @@
+use std::{
+    collections::BTreeMap,
+    io::{self, Write},
+    time::Duration,
+};
+
+use indicatif::{ProgressBar, ProgressStyle};
+use tabwriter::TabWriter;
+use tokio::time::{Instant, sleep};
+use vex_v5_serial::{
+    Connection,
+    protocol::cdc2::system::{DeviceStatusPacket, DeviceStatusReplyPacket, DeviceType},
+};
+
+use crate::{connection::DeviceConnection, errors::CliError};
+
+use super::upload::progress_chars;
+
+/// How often device status is sampled.
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Observed connection history of a single smart port.
+#[derive(Debug)]
+struct PortHealth {
+    device_type: DeviceType,
+    present_samples: u32,
+    disconnects: u32,
+    type_changes: u32,
+    status_changes: u32,
+    last_status: u8,
+    connected: bool,
+    was_connected: bool,
+}
+
+/// Repeatedly samples the status of every smart port for `duration`, then reports ports
+/// whose devices dropped out or changed state during that time.
+pub async fn portcheck(
+    connection: &mut DeviceConnection,
+    duration: Duration,
+) -> Result<(), CliError> {
+    let mut ports = BTreeMap::<u8, PortHealth>::new();
+    let mut samples = 0u32;
+    let mut failed_samples = 0u32;
+
+    let progress = ProgressBar::new(duration.as_millis() as u64)
+        .with_style(
+            ProgressStyle::with_template(
+                "    \x1b[1;96mChecking\x1b[0m {percent:>3}% {bar:40.green} {msg}",
+            )
+            .unwrap() // Okay to unwrap, since this just validates style formatting.
+            .progress_chars(progress_chars()),
+        )
+        .with_message("smart ports");
+
+    let start = Instant::now();
+    while start.elapsed() < duration {
+        let status = match connection
+            .handshake::<DeviceStatusReplyPacket>(
+                Duration::from_millis(500),
+                2,
+                DeviceStatusPacket::new(()),
+            )
+            .await
+        {
+            Ok(reply) => reply.payload?,
+            // If the Brain never responds, there's nothing meaningful to report.
+            Err(err) if samples == 0 => return Err(err.into()),
+            Err(err) => {
+                log::debug!("Device status request failed: {err}");
+                failed_samples += 1;
+                continue;
+            }
+        };
+        samples += 1;
+
+        for port in ports.values_mut() {
+            port.was_connected = port.connected;
+            port.connected = false;
+        }
+
+        for device in status.devices {
+            let port = ports.entry(device.port).or_insert(PortHealth {
+                device_type: device.device_type,
+                present_samples: 0,
+                disconnects: 0,
+                type_changes: 0,
+                status_changes: 0,
+                last_status: device.status,
+                connected: false,
+                was_connected: false,
+            });
+
+            if port.device_type != device.device_type {
+                port.type_changes += 1;
+                port.device_type = device.device_type;
+            }
+            if port.last_status != device.status {
+                port.status_changes += 1;
+                port.last_status = device.status;
+            }
+
+            port.present_samples += 1;
+            port.connected = true;
+        }
+
+        // Any port that was connected in the previous sample but is missing from this one
+        // has dropped out since then.
+        for port in ports.values_mut() {
+            if port.was_connected && !port.connected {
+                port.disconnects += 1;
+            }
+        }
+
+        progress.set_position(start.elapsed().as_millis() as u64);
+        sleep(SAMPLE_INTERVAL).await;
+    }
+
+    progress.finish_and_clear();
+
+    let mut tw = TabWriter::new(io::stdout());
+    writeln!(
+        &mut tw,
+        "\x1B[1mPort\tType\tUptime\tDisconnects\tStatus Changes\tHealth\x1B[0m"
+    )
+    .unwrap();
+
+    let mut flaky_ports = 0;
+    for (number, port) in &ports {
+        let uptime = f64::from(port.present_samples) / f64::from(samples) * 100.0;
+        let flaky = port.disconnects > 0 || port.type_changes > 0;
+        if flaky {
+            flaky_ports += 1;
+        }
+
+        writeln!(
+            &mut tw,
+            "{number}\t{:?}\t{uptime:.1}%\t{}\t{}\t{}",
+            port.device_type,
+            port.disconnects,
+            port.status_changes + port.type_changes,
+            if flaky {
+                "\x1b[1;91mFlaky\x1b[0m"
+            } else {
+                "\x1b[1;92mOK\x1b[0m"
+            },
+        )
+        .unwrap();
+    }
+
+    tw.flush().unwrap();
+
+    eprintln!();
+    eprintln!(
+        "Took {samples} samples over {:.1}s ({failed_samples} failed).",
+        start.elapsed().as_secs_f64()
+    );
+    if flaky_ports > 0 {
+        eprintln!(
+            "\x1b[1;93m{flaky_ports} port(s) lost connection during the check.\x1b[0m Inspect or replace their cables before competing."
+        );
+    } else {
+        eprintln!("No connection problems detected.");
+    }
+
+    Ok(())
+}
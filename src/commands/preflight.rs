@@ -0,0 +1,459 @@
+//! A pass/fail checklist to run on a robot before a match.
+
+use std::path::{Path, PathBuf};
+
+use clap::{Args, ValueEnum};
+use tokio::task::block_in_place;
+use vex_v5_serial::{
+    commands::file::USER_PROGRAM_LOAD_ADDR,
+    protocol::{
+        FixedString, VEX_CRC32,
+        cdc2::{file::FileVendor, system::LogEntry},
+    },
+};
+
+use crate::{
+    build_info::read_build_info,
+    config::user_config,
+    connection::{ConnectionOpts, DeviceConnection, open_connection},
+    errors::CliError,
+    metadata::{Metadata, manifest_path},
+    reporter::TerminalReporter,
+};
+
+use super::{
+    build::{CargoOpts, build},
+    devices::{format_device_version, list_devices},
+    info::{battery_level, radio_status},
+    log::{EntryCategory, EntryMessage, log_count, read_newest_entries},
+    stamp::Stamp,
+    upload::{ascii_output, brain_file_metadata, gzip_compress},
+};
+
+/// Battery charge, in percent, below which the battery check fails by default.
+pub const DEFAULT_MIN_BATTERY: u8 = 70;
+
+/// Number of the newest event log entries searched for errors.
+const RECENT_LOG_ENTRIES: u32 = 100;
+
+/// Event log description of power events (see [`super::log::decode_error_message`]).
+const POWER: u8 = 13;
+
+/// Code of a [`POWER`] event caused by the Brain being turned off.
+const POWER_OFF: u8 = 0xff;
+
+/// Radio channels that aren't used for driving: the download channel, Bluetooth, and the channel
+/// a radio uses while it's looking for its controller.
+const NON_PIT_CHANNELS: [(u8, &str); 3] =
+    [(5, "download"), (245, "Bluetooth"), (9, "reconnecting")];
+
+/// A check performed by `cargo v5 preflight`.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PreflightCheck {
+    /// The program in the competition slot matches the local build.
+    Program,
+
+    /// The battery is charged above the minimum.
+    Battery,
+
+    /// The radio is on a pit channel rather than the download channel.
+    Radio,
+
+    /// Devices of the same type all run the same firmware.
+    Firmware,
+
+    /// No errors were logged since the Brain was turned on.
+    EventLog,
+}
+
+impl PreflightCheck {
+    const ALL: [Self; 5] = [
+        Self::Program,
+        Self::Battery,
+        Self::Radio,
+        Self::Firmware,
+        Self::EventLog,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Program => "Program",
+            Self::Battery => "Battery",
+            Self::Radio => "Radio",
+            Self::Firmware => "Firmware",
+            Self::EventLog => "Event log",
+        }
+    }
+}
+
+/// Settings from `package.metadata.v5.preflight`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PreflightConfig {
+    /// Slot the competition program should be installed in.
+    pub slot: Option<u8>,
+
+    /// Battery charge, in percent, below which the battery check fails.
+    pub min_battery: Option<u8>,
+
+    /// Checks that aren't run.
+    pub skip: Vec<PreflightCheck>,
+}
+
+#[derive(Args, Debug)]
+pub struct PreflightOpts {
+    /// Slot the competition program should be installed in. Defaults to `preflight.slot`, then
+    /// `slot` in `package.metadata.v5`.
+    #[arg(short, long)]
+    pub slot: Option<u8>,
+
+    /// Battery charge, in percent, below which the battery check fails.
+    #[arg(long, value_name = "PERCENT")]
+    pub min_battery: Option<u8>,
+
+    /// Checks to skip, in addition to those in `package.metadata.v5.preflight.skip`.
+    #[arg(long, value_delimiter = ',')]
+    pub skip: Vec<PreflightCheck>,
+
+    /// A BIN to compare the installed program against, instead of building the project.
+    #[arg(long)]
+    pub file: Option<PathBuf>,
+
+    /// Arguments forwarded to `cargo`.
+    #[clap(flatten)]
+    pub cargo_opts: CargoOpts,
+}
+
+/// The outcome of a single check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    Pass,
+    Fail,
+    Skipped,
+}
+
+#[derive(Debug)]
+struct CheckResult {
+    check: PreflightCheck,
+    outcome: Outcome,
+    detail: String,
+}
+
+impl CheckResult {
+    fn new(check: PreflightCheck, outcome: Outcome, detail: impl Into<String>) -> Self {
+        Self {
+            check,
+            outcome,
+            detail: detail.into(),
+        }
+    }
+}
+
+/// Runs every enabled check against the connected Brain and prints a report.
+///
+/// Fails with [`CliError::PreflightFailed`] if any check didn't pass.
+pub async fn preflight(
+    path: &Path,
+    opts: PreflightOpts,
+    connection_opts: &ConnectionOpts,
+) -> miette::Result<()> {
+    let manifest_path = manifest_path(path);
+    let package = block_in_place(|| {
+        cargo_metadata::MetadataCommand::new()
+            .manifest_path(&manifest_path)
+            .no_deps()
+            .exec()
+    })
+    .ok()
+    .and_then(|metadata| match &opts.cargo_opts.package {
+        Some(spec) => metadata
+            .packages
+            .iter()
+            .find(|p| p.name.as_str() == spec)
+            .cloned(),
+        None => metadata.root_package().cloned(),
+    });
+    let metadata = package
+        .as_ref()
+        .map(Metadata::new)
+        .transpose()?
+        .unwrap_or_default();
+
+    let mut skip = opts.skip;
+    skip.extend(metadata.preflight.skip);
+
+    let slot = opts
+        .slot
+        .or(metadata.preflight.slot)
+        .or(metadata.slot)
+        .or(user_config().upload.slot);
+    let min_battery = opts
+        .min_battery
+        .or(metadata.preflight.min_battery)
+        .unwrap_or(DEFAULT_MIN_BATTERY);
+
+    // Build before connecting, since a failed build makes the rest of the checklist moot.
+    let local_binary = match (skip.contains(&PreflightCheck::Program), opts.file) {
+        (true, _) => None,
+        (false, Some(file)) => Some(file),
+        (false, None) => build(path, opts.cargo_opts, &TerminalReporter::new())
+            .await?
+            .map(|output| output.bin_artifact),
+    };
+
+    let mut connection = open_connection(connection_opts).await?;
+
+    let mut results = Vec::new();
+    for check in PreflightCheck::ALL {
+        if skip.contains(&check) {
+            results.push(CheckResult::new(check, Outcome::Skipped, "skipped"));
+            continue;
+        }
+
+        results.push(match check {
+            PreflightCheck::Program => {
+                check_program(
+                    &mut connection,
+                    slot,
+                    local_binary.as_deref(),
+                    metadata.compress,
+                )
+                .await?
+            }
+            PreflightCheck::Battery => check_battery(&mut connection, min_battery).await?,
+            PreflightCheck::Radio => check_radio(&mut connection).await,
+            PreflightCheck::Firmware => check_firmware(&mut connection).await?,
+            PreflightCheck::EventLog => check_event_log(&mut connection).await?,
+        });
+    }
+
+    print_report(&results);
+
+    let failed = results
+        .iter()
+        .filter(|result| result.outcome == Outcome::Fail)
+        .count();
+    if failed > 0 {
+        Err(CliError::PreflightFailed(failed))?;
+    }
+
+    Ok(())
+}
+
+/// Compares the checksum of the program in `slot` against the checksum the local binary would
+/// have once uploaded.
+async fn check_program(
+    connection: &mut DeviceConnection,
+    slot: Option<u8>,
+    local_binary: Option<&Path>,
+    compress: Option<bool>,
+) -> Result<CheckResult, CliError> {
+    let check = PreflightCheck::Program;
+
+    let Some(slot) = slot else {
+        return Ok(CheckResult::new(
+            check,
+            Outcome::Fail,
+            "no slot configured (pass `--slot` or set `package.metadata.v5.slot`)",
+        ));
+    };
+    let Some(local_binary) = local_binary else {
+        return Ok(CheckResult::new(
+            check,
+            Outcome::Fail,
+            "no program was built",
+        ));
+    };
+
+    let file_name = format!("slot_{slot}.bin");
+    let Some(installed) = brain_file_metadata(
+        connection,
+        FixedString::new(file_name.clone())?,
+        FileVendor::User,
+    )
+    .await?
+    else {
+        return Ok(CheckResult::new(
+            check,
+            Outcome::Fail,
+            format!("slot {slot} is empty"),
+        ));
+    };
+
+    // Differential uploads store a patch in the slot, which can't be compared to the binary.
+    if installed.load_address != USER_PROGRAM_LOAD_ADDR {
+        return Ok(CheckResult::new(
+            check,
+            Outcome::Skipped,
+            format!("`{file_name}` was uploaded differentially and can't be compared"),
+        ));
+    }
+
+    // Uploads are stamped unless `--no-stamp` was passed, so the slot may hold either version.
+    let binary = tokio::fs::read(local_binary).await?;
+    let mut stamped = binary.clone();
+    let commit = read_build_info(local_binary).and_then(|info| info.git);
+    Stamp::new(&binary, commit, "monolith").apply(&mut stamped);
+
+    // Uploads are compressed unless configured otherwise, and the Brain checksums what it stored.
+    let checksum = |mut data: Vec<u8>| {
+        if compress.unwrap_or(true) {
+            gzip_compress(&mut data);
+        }
+        VEX_CRC32.checksum(&data)
+    };
+    let expected = checksum(stamped);
+
+    Ok(
+        if installed.crc32 == expected || installed.crc32 == checksum(binary) {
+            CheckResult::new(
+                check,
+                Outcome::Pass,
+                format!("`{file_name}` matches the local build (CRC32 {expected:08x})"),
+            )
+        } else {
+            CheckResult::new(
+                check,
+                Outcome::Fail,
+                format!(
+                    "`{file_name}` differs from the local build (CRC32 {:08x}, expected {expected:08x})",
+                    installed.crc32
+                ),
+            )
+        },
+    )
+}
+
+async fn check_battery(
+    connection: &mut DeviceConnection,
+    min_battery: u8,
+) -> Result<CheckResult, CliError> {
+    let (battery, charging) = battery_level(connection).await?;
+    let detail = format!(
+        "{battery}%{} (minimum {min_battery}%)",
+        if charging { ", charging" } else { "" }
+    );
+
+    Ok(CheckResult::new(
+        PreflightCheck::Battery,
+        if battery >= u32::from(min_battery) {
+            Outcome::Pass
+        } else {
+            Outcome::Fail
+        },
+        detail,
+    ))
+}
+
+async fn check_radio(connection: &mut DeviceConnection) -> CheckResult {
+    let check = PreflightCheck::Radio;
+
+    let Some(radio) = radio_status(connection).await else {
+        return CheckResult::new(check, Outcome::Fail, "no radio detected");
+    };
+
+    match NON_PIT_CHANNELS
+        .iter()
+        .find(|(channel, _)| *channel == radio.channel)
+    {
+        Some((_, name)) => CheckResult::new(
+            check,
+            Outcome::Fail,
+            format!("on the {name} channel ({})", radio.channel),
+        ),
+        None => CheckResult::new(
+            check,
+            Outcome::Pass,
+            format!("on pit channel {}", radio.channel),
+        ),
+    }
+}
+
+/// Checks that devices of the same type run the same firmware version, which VEXos expects after
+/// an update.
+async fn check_firmware(connection: &mut DeviceConnection) -> Result<CheckResult, CliError> {
+    let devices = list_devices(connection).await?.devices;
+
+    // Device types alongside the firmware versions seen for them.
+    let mut versions = Vec::<(String, Vec<String>)>::new();
+    for device in &devices {
+        let device_type = format!("{:?}", device.device_type);
+        let version = format_device_version(device.version, device.beta_version);
+
+        match versions.iter_mut().find(|(ty, _)| *ty == device_type) {
+            Some((_, seen)) if !seen.contains(&version) => seen.push(version),
+            Some(_) => {}
+            None => versions.push((device_type, vec![version])),
+        }
+    }
+
+    let mismatched = versions
+        .iter()
+        .filter(|(_, seen)| seen.len() > 1)
+        .map(|(ty, seen)| format!("{ty} ({})", seen.join(", ")))
+        .collect::<Vec<_>>();
+
+    Ok(if mismatched.is_empty() {
+        CheckResult::new(
+            PreflightCheck::Firmware,
+            Outcome::Pass,
+            format!("{} devices, no mismatches", devices.len()),
+        )
+    } else {
+        CheckResult::new(
+            PreflightCheck::Firmware,
+            Outcome::Fail,
+            format!("mismatched versions: {}", mismatched.join("; ")),
+        )
+    })
+}
+
+/// Looks for errors logged since the Brain was last turned on.
+async fn check_event_log(connection: &mut DeviceConnection) -> Result<CheckResult, CliError> {
+    let count = log_count(connection).await?.min(RECENT_LOG_ENTRIES);
+    let entries = read_newest_entries(connection, count).await?;
+
+    let since_power_on = entries
+        .iter()
+        .rposition(|entry| entry.description == POWER && entry.code != POWER_OFF)
+        .map_or(&entries[..], |index| &entries[index + 1..]);
+    let errors = since_power_on
+        .iter()
+        .filter(|entry| EntryCategory::of(entry) == EntryCategory::Error)
+        .collect::<Vec<&LogEntry>>();
+
+    Ok(match errors.last() {
+        None => CheckResult::new(
+            PreflightCheck::EventLog,
+            Outcome::Pass,
+            "no errors since power on",
+        ),
+        Some(latest) => CheckResult::new(
+            PreflightCheck::EventLog,
+            Outcome::Fail,
+            format!(
+                "{} error{} since power on, latest: {}",
+                errors.len(),
+                if errors.len() == 1 { "" } else { "s" },
+                EntryMessage(latest)
+            ),
+        ),
+    })
+}
+
+fn print_report(results: &[CheckResult]) {
+    for result in results {
+        let (color, symbol) = match (result.outcome, ascii_output()) {
+            (Outcome::Pass, false) => ("\x1b[1;92m", "✓"),
+            (Outcome::Pass, true) => ("\x1b[1;92m", "ok"),
+            (Outcome::Fail, false) => ("\x1b[1;91m", "✗"),
+            (Outcome::Fail, true) => ("\x1b[1;91m", "FAIL"),
+            (Outcome::Skipped, _) => ("\x1b[2m", "-"),
+        };
+
+        println!(
+            "{color}{symbol:>4}\x1b[0m \x1b[1m{:<9}\x1b[0m {}",
+            result.check.label(),
+            result.detail
+        );
+    }
+}
@@ -0,0 +1,100 @@
+use std::time::Duration;
+
+use clap::ValueEnum;
+use vex_v5_serial::{
+    Connection,
+    protocol::{
+        FixedString,
+        cdc2::{
+            Cdc2Ack,
+            file::{
+                FileLoadAction, FileLoadActionPacket, FileLoadActionPayload,
+                FileLoadActionReplyPacket, FileVendor,
+            },
+            system::{SystemFlagsPacket, SystemFlagsReplyPacket},
+        },
+    },
+};
+
+use crate::{connection::DeviceConnection, errors::CliError};
+
+/// What to do with a running program when `cargo v5 run` is interrupted.
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OnExit {
+    /// Stop the program.
+    #[default]
+    Stop,
+
+    /// Leave the program running and only close the terminal.
+    Detach,
+
+    /// Stop the program only if Ctrl-C is pressed a second time.
+    Prompt,
+}
+
+/// Runs or stops the user program named `file_name`.
+///
+/// When stopping, `file_name` is ignored and may be empty.
+pub async fn load_action(
+    connection: &mut DeviceConnection,
+    action: FileLoadAction,
+    file_name: &str,
+) -> Result<(), CliError> {
+    let reply = connection
+        .handshake::<FileLoadActionReplyPacket>(
+            Duration::from_millis(500),
+            3,
+            FileLoadActionPacket::new(FileLoadActionPayload {
+                vendor: FileVendor::User,
+                action,
+                file_name: FixedString::new(file_name)?,
+            }),
+        )
+        .await?;
+
+    match reply.payload {
+        Ok(()) => Ok(()),
+        Err(Cdc2Ack::NackProgramFile) => Err(CliError::RemoteFileNotFound(file_name.to_string())),
+        Err(nack) => Err(nack.into()),
+    }
+}
+
+/// Returns the slot number of the currently running program, or `None` if no program is running.
+pub async fn current_program(connection: &mut DeviceConnection) -> Result<Option<u8>, CliError> {
+    let flags = connection
+        .handshake::<SystemFlagsReplyPacket>(
+            Duration::from_millis(500),
+            2,
+            SystemFlagsPacket::new(()),
+        )
+        .await?
+        .payload?;
+
+    Ok((flags.current_program != 0).then_some(flags.current_program))
+}
+
+/// Run the program installed in `slot`.
+pub async fn run_slot(connection: &mut DeviceConnection, slot: u8) -> Result<(), CliError> {
+    if !(1..=8).contains(&slot) {
+        return Err(CliError::SlotOutOfRange);
+    }
+
+    let file_name = format!("slot_{slot}.bin");
+    load_action(connection, FileLoadAction::Run, &file_name).await?;
+    eprintln!("     \x1b[1;92mRunning\x1b[0m `{file_name}`");
+
+    Ok(())
+}
+
+/// Stop the currently running program.
+pub async fn stop(connection: &mut DeviceConnection) -> Result<(), CliError> {
+    let Some(program) = current_program(connection).await? else {
+        eprintln!("No program is running.");
+        return Ok(());
+    };
+
+    load_action(connection, FileLoadAction::Stop, "").await?;
+    eprintln!("     \x1b[1;92mStopped\x1b[0m program {program}");
+
+    Ok(())
+}
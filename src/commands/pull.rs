@@ -0,0 +1,59 @@
+use std::path::PathBuf;
+
+use vex_v5_serial::{
+    Connection, commands::file::DownloadFile, protocol::cdc2::file::FileTransferTarget,
+};
+
+use crate::{
+    connection::DeviceConnection,
+    errors::CliError,
+    reporter::{Reporter, Task, TaskKind, progress_callback},
+};
+
+use super::{cat::parse_remote_path, upload::brain_file_metadata};
+
+/// Download a file from flash and save it to the host.
+///
+/// If `local` is not provided, the file is saved to the current directory under its remote name.
+pub async fn pull(
+    connection: &mut DeviceConnection,
+    remote: PathBuf,
+    local: Option<PathBuf>,
+    force: bool,
+    reporter: &dyn Reporter,
+) -> Result<(), CliError> {
+    let (vendor, file_name) = parse_remote_path(&remote)?;
+    let local = local.unwrap_or_else(|| PathBuf::from(file_name.as_str()));
+
+    if !force && tokio::fs::try_exists(&local).await? {
+        return Err(CliError::FileExists(local));
+    }
+
+    let metadata = brain_file_metadata(connection, file_name.clone(), vendor)
+        .await?
+        .ok_or_else(|| CliError::RemoteFileNotFound(remote.display().to_string()))?;
+
+    let progress = reporter.start_task(Task {
+        kind: TaskKind::Download,
+        name: file_name.as_str(),
+        color: "blue",
+    });
+
+    let data = connection
+        .execute_command(DownloadFile {
+            file_name,
+            size: metadata.size,
+            vendor,
+            target: FileTransferTarget::Qspi,
+            address: metadata.load_address,
+            progress_callback: Some(progress_callback(&progress)),
+        })
+        .await?;
+
+    progress.finish();
+
+    tokio::fs::write(&local, &data).await?;
+    reporter.status("Saved", &local.display().to_string());
+
+    Ok(())
+}
@@ -0,0 +1,92 @@
+use std::path::Path;
+
+use vex_v5_serial::{
+    Connection,
+    commands::file::{UploadFile, j2000_timestamp},
+    protocol::{
+        FixedString, Version,
+        cdc2::file::{ExtensionType, FileMetadata, FileTransferTarget},
+    },
+};
+
+use crate::{
+    connection::{DeviceConnection, switch_to_download_channel},
+    errors::CliError,
+    reporter::{Reporter, Task, TaskKind, progress_callback},
+};
+
+use super::{cat::parse_remote_path, upload::AfterUpload};
+
+/// Parses a load address, either in hexadecimal (`0x` prefixed) or decimal.
+pub fn parse_load_address(address: &str) -> Result<u32, String> {
+    let parsed = match address
+        .strip_prefix("0x")
+        .or_else(|| address.strip_prefix("0X"))
+    {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => address.parse(),
+    };
+
+    parsed.map_err(|err| format!("invalid load address `{address}`: {err}"))
+}
+
+/// Upload an arbitrary file from the host to flash.
+///
+/// The remote path may be prefixed with a vendor (e.g. `pros/table.bin`), and defaults to
+/// the user vendor otherwise. If `extension` is not provided, the remote file's extension
+/// is used as the file's type.
+pub async fn push(
+    connection: &mut DeviceConnection,
+    local: &Path,
+    remote: &Path,
+    load_address: u32,
+    extension: Option<String>,
+    after: AfterUpload,
+    reporter: &dyn Reporter,
+) -> Result<(), CliError> {
+    let (vendor, file_name) = parse_remote_path(remote)?;
+    let extension = extension.unwrap_or_else(|| {
+        remote
+            .extension()
+            .map(|ext| ext.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "bin".to_string())
+    });
+
+    let data = tokio::fs::read(local).await?;
+
+    switch_to_download_channel(connection).await?;
+
+    let progress = reporter.start_task(Task {
+        kind: TaskKind::Upload,
+        name: file_name.as_str(),
+        color: "yellow",
+    });
+
+    connection
+        .execute_command(UploadFile {
+            file_name,
+            metadata: FileMetadata {
+                extension: FixedString::new(extension)?,
+                extension_type: ExtensionType::default(),
+                timestamp: j2000_timestamp(),
+                version: Version {
+                    major: 1,
+                    minor: 0,
+                    build: 0,
+                    beta: 0,
+                },
+            },
+            vendor,
+            data: &data,
+            target: FileTransferTarget::Qspi,
+            load_address,
+            linked_file: None,
+            after_upload: after.into(),
+            progress_callback: Some(progress_callback(&progress)),
+        })
+        .await?;
+
+    progress.finish();
+
+    Ok(())
+}
@@ -0,0 +1,110 @@
+//! Configures rust-analyzer to analyze a project the same way `cargo v5 build` builds it.
+
+use std::{io::ErrorKind, path::Path};
+
+use serde_json::{Map, Value, json};
+
+use crate::{
+    errors::CliError,
+    metadata::{manifest_dir, manifest_path},
+    reporter::Reporter,
+};
+
+use super::build::{CargoOpts, DEFAULT_TARGET, cargo_command};
+
+/// Writes the flags `cargo v5 build` would use with `opts` to the project's
+/// `.vscode/settings.json`, keeping any unrelated settings already in it.
+pub async fn ra_config(
+    path: &Path,
+    opts: CargoOpts,
+    reporter: &dyn Reporter,
+) -> Result<(), CliError> {
+    let manifest_path = manifest_path(path);
+    let project_dir = manifest_dir(&manifest_path);
+    let settings_path = project_dir.join(".vscode").join("settings.json");
+
+    let mut settings = match tokio::fs::read_to_string(&settings_path).await {
+        Ok(contents) => match serde_json::from_str::<Map<String, Value>>(&contents) {
+            Ok(settings) => settings,
+            Err(_) => return Err(CliError::MalformedSettings(settings_path)),
+        },
+        Err(err) if err.kind() == ErrorKind::NotFound => Map::new(),
+        Err(err) => return Err(err.into()),
+    };
+
+    // Use the same flags and environment as a build, so the two can't disagree. rust-analyzer
+    // has dedicated settings for the target and features, and finds the manifest itself. The rest
+    // of the arguments are passed along as-is.
+    let cargo = cargo_command(&[], &manifest_path, &opts, reporter).await?;
+
+    let mut target = DEFAULT_TARGET.to_string();
+    let mut features = Vec::new();
+    let mut no_default_features = false;
+    let mut extra_args = Vec::new();
+
+    let mut args = cargo
+        .get_args()
+        .map(|arg| arg.to_string_lossy().into_owned());
+    while let Some(arg) = args.next() {
+        let (flag, value) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag, Some(value.to_string())),
+            _ => (arg.as_str(), None),
+        };
+
+        match flag {
+            "--target" => target = value.or_else(|| args.next()).unwrap_or(target),
+            "--features" => features.extend(
+                value
+                    .or_else(|| args.next())
+                    .iter()
+                    .flat_map(|features| features.split(','))
+                    .map(str::to_string),
+            ),
+            "--no-default-features" => no_default_features = true,
+            "--manifest-path" => {
+                if value.is_none() {
+                    args.next();
+                }
+            }
+            _ => extra_args.push(arg),
+        }
+    }
+
+    let extra_env = cargo
+        .get_envs()
+        .filter_map(|(key, value)| {
+            Some((
+                key.to_string_lossy().into_owned(),
+                value?.to_string_lossy().into_owned().into(),
+            ))
+        })
+        .collect::<Map<String, Value>>();
+
+    settings.insert("rust-analyzer.cargo.target".to_string(), json!(target));
+    settings.insert("rust-analyzer.cargo.features".to_string(), json!(features));
+    settings.insert(
+        "rust-analyzer.cargo.noDefaultFeatures".to_string(),
+        json!(no_default_features),
+    );
+    settings.insert(
+        "rust-analyzer.cargo.extraArgs".to_string(),
+        json!(extra_args),
+    );
+    settings.insert(
+        "rust-analyzer.cargo.extraEnv".to_string(),
+        Value::Object(extra_env),
+    );
+    // Test harnesses need `std`, which the Brain's target doesn't have.
+    settings.insert("rust-analyzer.check.allTargets".to_string(), json!(false));
+
+    tokio::fs::create_dir_all(settings_path.parent().unwrap()).await?;
+    tokio::fs::write(
+        &settings_path,
+        serde_json::to_string_pretty(&settings).unwrap() + "\n",
+    )
+    .await?;
+
+    eprintln!("     \x1b[1;92mUpdated\x1b[0m {}", settings_path.display());
+
+    Ok(())
+}
@@ -0,0 +1,194 @@
+//! VEXnet radio diagnostics.
+//!
+//! `cargo v5 radio` polls the Brain's radio status and keeps track of how often the link drops,
+//! and `cargo v5 radio --test` measures how fast the link to the Brain actually is.
+
+use std::{
+    io::{self, Write},
+    time::Duration,
+};
+
+use humansize::{BINARY, format_size};
+use tabwriter::TabWriter;
+use tokio::time::{Instant, interval};
+use vex_v5_serial::{
+    Connection,
+    protocol::cdc2::system::{RadioStatus, SystemFlagsPacket, SystemFlagsReplyPacket},
+};
+
+use crate::{
+    connection::{DeviceConnection, brain_generation},
+    errors::CliError,
+};
+
+use super::{
+    info::radio_status,
+    screenshot::{capture_buffer_size, capture_screen},
+};
+
+/// How often the radio's status is polled when monitoring.
+const REFRESH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Number of round trips timed by the link test.
+const LATENCY_SAMPLES: u32 = 20;
+
+/// How long a round trip may take before it's counted as lost.
+const LATENCY_TIMEOUT: Duration = Duration::from_millis(1000);
+
+/// Number of screen captures downloaded to measure throughput.
+const THROUGHPUT_SAMPLES: u32 = 3;
+
+/// Radio channel used for file transfers.
+const DOWNLOAD_CHANNEL: u8 = 5;
+
+/// Radio channel reported while the controller is reconnecting.
+const RECONNECTING_CHANNEL: u8 = 9;
+
+/// Radio channel reported when connected over Bluetooth.
+const BLUETOOTH_CHANNEL: u8 = 245;
+
+/// Describes a radio channel number.
+pub fn channel_name(channel: u8) -> &'static str {
+    match channel {
+        DOWNLOAD_CHANNEL => "download",
+        RECONNECTING_CHANNEL => "reconnecting",
+        BLUETOOTH_CHANNEL => "bluetooth",
+        _ => "pit",
+    }
+}
+
+/// Whether the radio has a working link to a controller.
+fn is_linked(status: Option<&RadioStatus>) -> bool {
+    status.is_some_and(|status| status.device != 0 && status.channel != RECONNECTING_CHANNEL)
+}
+
+/// Statistics collected while monitoring the radio.
+#[derive(Debug, Default)]
+struct RadioHistory {
+    samples: u32,
+    quality_sum: u64,
+    min_quality: Option<u16>,
+    drops: u32,
+    linked: bool,
+}
+
+impl RadioHistory {
+    fn record(&mut self, status: Option<&RadioStatus>) {
+        let linked = is_linked(status);
+        if self.linked && !linked {
+            self.drops += 1;
+        }
+        self.linked = linked;
+
+        if let Some(status) = status.filter(|_| linked) {
+            self.samples += 1;
+            self.quality_sum += status.quality as u64;
+            self.min_quality = Some(
+                self.min_quality
+                    .map_or(status.quality, |min| min.min(status.quality)),
+            );
+        }
+    }
+}
+
+/// Continuously displays the radio's status until interrupted.
+pub async fn radio(connection: &mut DeviceConnection) -> Result<(), CliError> {
+    let mut history = RadioHistory::default();
+    let mut refresh = interval(REFRESH_INTERVAL);
+
+    loop {
+        refresh.tick().await;
+
+        let status = radio_status(connection).await;
+        history.record(status.as_ref());
+        draw(status.as_ref(), &history)?;
+    }
+}
+
+fn draw(status: Option<&RadioStatus>, history: &RadioHistory) -> io::Result<()> {
+    let mut tw = TabWriter::new(Vec::new());
+
+    match status {
+        Some(status) => {
+            writeln!(
+                tw,
+                "\x1b[1mChannel\x1b[0m\t{} ({})",
+                status.channel,
+                channel_name(status.channel)
+            )?;
+            writeln!(tw, "\x1b[1mQuality\x1b[0m\t{}%", status.quality)?;
+            writeln!(tw, "\x1b[1mStrength\x1b[0m\t{} dBm", status.strength)?;
+            writeln!(tw, "\x1b[1mTimeslot\x1b[0m\t{}", status.timeslot)?;
+        }
+        None => writeln!(tw, "\x1b[1mRadio\x1b[0m\t\x1b[2mnot responding\x1b[0m")?,
+    }
+
+    if let Some(min_quality) = history.min_quality {
+        writeln!(
+            tw,
+            "\x1b[1mAverage Quality\x1b[0m\t{}% (lowest {min_quality}%)",
+            history.quality_sum / history.samples as u64
+        )?;
+    }
+    writeln!(tw, "\x1b[1mDrops\x1b[0m\t{}", history.drops)?;
+
+    let mut stdout = io::stdout().lock();
+
+    // Clear the screen and move the cursor home before redrawing.
+    write!(stdout, "\x1b[2J\x1b[H")?;
+    stdout.write_all(&tw.into_inner().unwrap())?;
+    stdout.flush()
+}
+
+/// Measures the round-trip latency and download throughput of the link to the Brain.
+pub async fn radio_test(connection: &mut DeviceConnection) -> Result<(), CliError> {
+    eprintln!(
+        "     \x1b[1;92mTesting\x1b[0m {LATENCY_SAMPLES} round trips and {THROUGHPUT_SAMPLES} downloads"
+    );
+
+    let mut round_trips = Vec::new();
+    for _ in 0..LATENCY_SAMPLES {
+        let started = Instant::now();
+        let reply = connection
+            .handshake::<SystemFlagsReplyPacket>(LATENCY_TIMEOUT, 0, SystemFlagsPacket::new(()))
+            .await;
+
+        if reply.is_ok() {
+            round_trips.push(started.elapsed());
+        }
+    }
+
+    let download_size = capture_buffer_size(brain_generation(connection).await?);
+    let started = Instant::now();
+    for _ in 0..THROUGHPUT_SAMPLES {
+        capture_screen(connection, None).await?;
+    }
+    let throughput = (download_size * THROUGHPUT_SAMPLES) as f64 / started.elapsed().as_secs_f64();
+
+    let mut tw = TabWriter::new(io::stdout());
+
+    let lost = LATENCY_SAMPLES - round_trips.len() as u32;
+    match (round_trips.iter().min(), round_trips.iter().max()) {
+        (Some(min), Some(max)) => {
+            let average = round_trips.iter().sum::<Duration>() / round_trips.len() as u32;
+            writeln!(
+                tw,
+                "\x1b[1mLatency\x1b[0m\t{average:.1?} average ({min:.1?} to {max:.1?})"
+            )?;
+        }
+        _ => writeln!(tw, "\x1b[1mLatency\x1b[0m\t\x1b[2mno replies\x1b[0m")?,
+    }
+    writeln!(
+        tw,
+        "\x1b[1mLost\x1b[0m\t{lost} of {LATENCY_SAMPLES} ({:.0}%)",
+        lost as f64 / LATENCY_SAMPLES as f64 * 100.0
+    )?;
+    writeln!(
+        tw,
+        "\x1b[1mThroughput\x1b[0m\t{}/s",
+        format_size(throughput as u64, BINARY)
+    )?;
+
+    tw.flush()?;
+    Ok(())
+}
@@ -1,31 +1,122 @@
-use std::{path::PathBuf, str::FromStr, time::Duration};
+use std::{path::Path, time::Duration};
 
+use glob::Pattern;
 use vex_v5_serial::{
     Connection,
     protocol::{
         FixedString,
         cdc2::file::{
             FileErasePacket, FileErasePayload, FileEraseReplyPacket, FileExitAction,
-            FileTransferExitPacket, FileTransferExitReplyPacket,
+            FileTransferExitPacket, FileTransferExitReplyPacket, FileVendor,
         },
     },
-    serial::{SerialConnection, SerialError},
 };
 
-use crate::errors::CliError;
+use crate::{
+    connection::{DeviceConnection, switch_to_download_channel},
+    errors::CliError,
+    reporter::Reporter,
+};
+
+use super::{
+    cat::vendor_from_prefix,
+    dir::{read_directory, vendor_prefix},
+    upload::brain_file_metadata,
+};
+
+/// Erase a file from flash.
+///
+/// The file name may be a glob pattern like `slot_*.bin`, which erases every matching file in
+/// the vendor's directory.
+pub async fn rm(
+    connection: &mut DeviceConnection,
+    file: &Path,
+    reporter: &dyn Reporter,
+) -> Result<(), CliError> {
+    let vendor = vendor_from_prefix(
+        file.parent()
+            .and_then(|parent| parent.to_str())
+            .unwrap_or(""),
+    );
+    let name = file
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default();
+
+    if !name.contains(['*', '?', '[']) {
+        erase_file(connection, vendor, FixedString::new(name)?).await?;
+        reporter.status("Removed", &file.display().to_string());
+        return Ok(());
+    }
+
+    let pattern = Pattern::new(name)
+        .map_err(|err| CliError::InvalidRemotePattern(name.to_string(), err.msg))?;
+
+    switch_to_download_channel(connection).await?;
+
+    let matches = read_directory(connection, vendor)
+        .await?
+        .into_iter()
+        .filter(|entry| pattern.matches(entry.file_name.as_str()))
+        .collect::<Vec<_>>();
+
+    if matches.is_empty() {
+        return Err(CliError::RemoteFileNotFound(file.display().to_string()));
+    }
 
-use super::cat::vendor_from_prefix;
+    for entry in matches {
+        reporter.status(
+            "Removed",
+            &format!("{}{}", vendor_prefix(vendor), entry.file_name.as_str()),
+        );
+        erase_file(connection, vendor, entry.file_name).await?;
+    }
 
-pub async fn rm(connection: &mut SerialConnection, file: PathBuf) -> Result<(), CliError> {
-    let vendor = vendor_from_prefix(if let Some(parent) = file.parent() {
-        parent.to_str().unwrap()
-    } else {
-        ""
-    });
+    Ok(())
+}
+
+/// Erase everything installed in a program slot: its binary, its metadata, and the base binary
+/// or Python script it links to, if any.
+pub async fn rm_slot(
+    connection: &mut DeviceConnection,
+    slot: u8,
+    reporter: &dyn Reporter,
+) -> Result<(), CliError> {
+    if !(1..=8).contains(&slot) {
+        return Err(CliError::SlotOutOfRange);
+    }
+
+    switch_to_download_channel(connection).await?;
+
+    let mut removed = false;
+    for extension in ["bin", "ini", "base.bin", "py"] {
+        let file_name = FixedString::new(format!("slot_{slot}.{extension}"))?;
 
-    let file_name = FixedString::from_str(file.file_name().unwrap_or_default().to_str().unwrap())
-        .map_err(|err| CliError::SerialError(SerialError::FixedStringSizeError(err)))?;
+        if brain_file_metadata(connection, file_name.clone(), FileVendor::User)
+            .await?
+            .is_none()
+        {
+            continue;
+        }
+
+        erase_file(connection, FileVendor::User, file_name.clone()).await?;
+        reporter.status("Removed", &format!("user/{}", file_name.as_str()));
+        removed = true;
+    }
+
+    if !removed {
+        reporter.warning(&format!("Slot {slot} is already empty."));
+    }
+
+    Ok(())
+}
 
+/// Erases a file from flash.
+pub async fn erase_file(
+    connection: &mut DeviceConnection,
+    vendor: FileVendor,
+    file_name: FixedString<23>,
+) -> Result<(), CliError> {
     connection
         .handshake::<FileEraseReplyPacket>(
             Duration::from_millis(500),
@@ -0,0 +1,311 @@
+//! Live view of a Brain's screen in the terminal.
+//!
+//! Each terminal cell shows two of the screen's pixels stacked on top of each other using a
+//! half-block character, so the terminal needs true color support to look right. Clicking or
+//! dragging on the view taps the Brain's touchscreen.
+//!
+//! The controller viewer is the same view opened on the Brain's controller page. The serial
+//! protocol has no way to read a controller's sticks and buttons, but VEXos draws them there.
+
+use std::{
+    io,
+    time::{Duration, Instant},
+};
+
+use crossterm::{
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyModifiers, MouseButton,
+        MouseEventKind,
+    },
+    execute,
+};
+use image::RgbImage;
+use ratatui::{
+    Frame,
+    buffer::Buffer,
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Stylize},
+    text::Line,
+    widgets::Widget,
+};
+use vex_v5_serial::{
+    Connection,
+    protocol::cdc2::system::{
+        DashScreen, DashSelectPacket, DashSelectPayload, DashSelectReplyPacket, DashTouchPacket,
+        DashTouchPayload, DashTouchReplyPacket,
+    },
+};
+
+use super::{
+    info::{ControllerBatteries, controller_batteries},
+    screenshot::capture_screen,
+    upload::ascii_output,
+};
+use crate::{connection::DeviceConnection, errors::CliError};
+
+/// Draws a screen capture scaled to fit its area, keeping the screen's aspect ratio.
+struct ScreenView<'a> {
+    image: &'a RgbImage,
+}
+
+impl ScreenView<'_> {
+    /// Number of image rows each terminal row shows.
+    fn rows_per_cell() -> u32 {
+        // Plain ASCII can only color a cell's background, which shows a single pixel.
+        if ascii_output() { 1 } else { 2 }
+    }
+
+    /// The part of `area` the image is drawn in.
+    fn image_area(&self, area: Rect) -> Rect {
+        let (width, height) = self.image.dimensions();
+
+        // Terminal cells are about twice as tall as they are wide.
+        let columns = (area.height as u32 * 2 * width / height).min(area.width as u32) as u16;
+        let rows = (columns as u32 * height / width / 2) as u16;
+
+        Rect {
+            x: area.x + (area.width - columns) / 2,
+            y: area.y + (area.height - rows) / 2,
+            width: columns,
+            height: rows,
+        }
+    }
+
+    /// Converts a terminal cell within `area` to the screen coordinate under it.
+    fn screen_position(&self, area: Rect, column: u16, row: u16) -> Option<(u16, u16)> {
+        let view = self.image_area(area);
+        if !view.contains((column, row).into()) {
+            return None;
+        }
+
+        let (width, height) = self.image.dimensions();
+        let x = (column - view.x) as u32 * width / view.width as u32;
+        let y = (row - view.y) as u32 * height / view.height as u32;
+
+        Some((x as u16, y as u16))
+    }
+
+    fn pixel(&self, view: Rect, column: u16, subrow: u32) -> Color {
+        let (width, height) = self.image.dimensions();
+        let x = (column as u32 * width / view.width as u32).min(width - 1);
+        let y = (subrow * height / (view.height as u32 * Self::rows_per_cell())).min(height - 1);
+
+        let [r, g, b] = self.image.get_pixel(x, y).0;
+        Color::Rgb(r, g, b)
+    }
+}
+
+impl Widget for ScreenView<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let view = self.image_area(area);
+        let rows_per_cell = Self::rows_per_cell();
+
+        for row in 0..view.height {
+            for column in 0..view.width {
+                let top = self.pixel(view, column, row as u32 * rows_per_cell);
+                let cell = &mut buf[(view.x + column, view.y + row)];
+
+                if rows_per_cell == 2 {
+                    let bottom = self.pixel(view, column, row as u32 * 2 + 1);
+                    cell.set_symbol("▀").set_fg(top).set_bg(bottom);
+                } else {
+                    cell.set_symbol(" ").set_bg(top);
+                }
+            }
+        }
+    }
+}
+
+/// A change to the simulated touch on the Brain's screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Touch {
+    x: u16,
+    y: u16,
+    pressing: bool,
+}
+
+/// What the user asked for since the last frame.
+#[derive(Debug, Default)]
+struct Input {
+    touches: Vec<Touch>,
+    exit: bool,
+}
+
+/// Collects the terminal events that arrived while a frame was downloading.
+///
+/// Clicks are ignored until there's a frame to click on. `touching` tracks where the screen is
+/// being touched, if anywhere.
+fn read_input(
+    view: Option<&ScreenView>,
+    area: Rect,
+    touching: &mut Option<(u16, u16)>,
+) -> io::Result<Input> {
+    let mut input = Input::default();
+
+    while event::poll(Duration::ZERO)? {
+        match event::read()? {
+            Event::Key(key) => {
+                if matches!(key.code, KeyCode::Char('q') | KeyCode::Esc)
+                    || (key.code == KeyCode::Char('c')
+                        && key.modifiers.contains(KeyModifiers::CONTROL))
+                {
+                    input.exit = true;
+                }
+            }
+            Event::Mouse(mouse) => {
+                let pressing = match mouse.kind {
+                    MouseEventKind::Down(MouseButton::Left)
+                    | MouseEventKind::Drag(MouseButton::Left) => true,
+                    MouseEventKind::Up(MouseButton::Left) => false,
+                    _ => continue,
+                };
+
+                let position =
+                    view.and_then(|view| view.screen_position(area, mouse.column, mouse.row));
+
+                match (position, pressing) {
+                    (Some((x, y)), true) => {
+                        *touching = Some((x, y));
+                        input.touches.push(Touch { x, y, pressing });
+                    }
+                    // Releasing outside the view still has to lift the finger.
+                    (position, false) => {
+                        if let Some((x, y)) = position.or(touching.take()) {
+                            input.touches.push(Touch { x, y, pressing });
+                        }
+                    }
+                    (None, true) => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(input)
+}
+
+async fn send_touch(connection: &mut DeviceConnection, touch: Touch) -> Result<(), CliError> {
+    connection
+        .handshake::<DashTouchReplyPacket>(
+            Duration::from_millis(100),
+            5,
+            DashTouchPacket::new(DashTouchPayload {
+                x: touch.x,
+                y: touch.y,
+                pressing: touch.pressing as u16,
+            }),
+        )
+        .await?
+        .payload?;
+
+    Ok(())
+}
+
+/// Splits the terminal into the screen view and a status line.
+fn layout(area: Rect) -> [Rect; 2] {
+    Layout::vertical([Constraint::Min(1), Constraint::Length(1)]).areas(area)
+}
+
+fn draw(
+    frame: &mut Frame,
+    image: Option<&RgbImage>,
+    frame_time: Option<Duration>,
+    batteries: Option<ControllerBatteries>,
+) {
+    let [view_area, status_area] = layout(frame.area());
+
+    if let Some(image) = image {
+        frame.render_widget(ScreenView { image }, view_area);
+    }
+
+    let rate = frame_time
+        .map(|time| format!("{:.1} fps", 1.0 / time.as_secs_f32()))
+        .unwrap_or_else(|| "waiting for the first frame".to_string());
+    let batteries = batteries
+        .map(|batteries| {
+            let partner = batteries
+                .partner
+                .map(|partner| format!(", partner {partner}%"))
+                .unwrap_or_default();
+            format!(" controller {}%{partner} |", batteries.primary)
+        })
+        .unwrap_or_default();
+    frame.render_widget(
+        Line::from(format!(
+            "{batteries} {rate} | click to touch the screen, q to quit"
+        ))
+        .dim(),
+        status_area,
+    );
+}
+
+/// Mirrors the Brain's screen in the terminal until the user quits.
+///
+/// The screen is refreshed as fast as the link allows, which is roughly every second over USB and
+/// much slower over a controller. Touches are forwarded between frames.
+pub async fn mirror_screen(connection: &mut DeviceConnection) -> Result<(), CliError> {
+    mirror(connection, false).await
+}
+
+/// Opens the Brain's page for the primary controller and mirrors it until the user quits, showing
+/// the controllers' battery levels below it.
+///
+/// The page shows the controller's stick positions and pressed buttons, which makes drift and
+/// broken buttons easy to spot without a test program.
+pub async fn view_controller(connection: &mut DeviceConnection) -> Result<(), CliError> {
+    connection
+        .handshake::<DashSelectReplyPacket>(
+            Duration::from_millis(500),
+            3,
+            DashSelectPacket::new(DashSelectPayload {
+                screen: DashScreen::Controller1,
+                port: 0,
+            }),
+        )
+        .await?
+        .payload?;
+
+    mirror(connection, true).await
+}
+
+/// Mirrors the Brain's screen, also polling the controllers' batteries if `controller` is set.
+async fn mirror(connection: &mut DeviceConnection, controller: bool) -> Result<(), CliError> {
+    let mut terminal = ratatui::init();
+    execute!(io::stdout(), EnableMouseCapture)?;
+
+    let result = async {
+        let mut image = None;
+        let mut frame_time = None;
+        let mut touching = None;
+        let mut batteries = None;
+
+        loop {
+            let area = terminal.get_frame().area();
+            let view = image.as_ref().map(|image| ScreenView { image });
+            let input = read_input(view.as_ref(), layout(area)[0], &mut touching)?;
+            if input.exit {
+                return Ok(());
+            }
+
+            for touch in input.touches {
+                send_touch(connection, touch).await?;
+            }
+
+            terminal.draw(|frame| draw(frame, image.as_ref(), frame_time, batteries))?;
+
+            let started = Instant::now();
+            image = Some(capture_screen(connection, None).await?);
+            frame_time = Some(started.elapsed());
+
+            if controller {
+                batteries = Some(controller_batteries(connection).await?);
+            }
+        }
+    }
+    .await;
+
+    _ = execute!(io::stdout(), DisableMouseCapture);
+    ratatui::restore();
+
+    result
+}
@@ -1,13 +1,14 @@
 use std::{
-    path::Path,
-    sync::Arc,
+    fs::File,
+    io::{self, BufWriter, Cursor, Write},
+    num::NonZeroU32,
+    path::{Path, PathBuf},
     time::{Duration, Instant},
 };
 
-use image::GenericImageView;
-use indicatif::{ProgressBar, ProgressStyle};
-use log::info;
-use tokio::sync::Mutex;
+use image::{GenericImageView, ImageFormat, RgbImage};
+use indicatif::ProgressBar;
+use tokio::{select, time::sleep_until};
 use vex_v5_serial::{
     Connection,
     commands::file::DownloadFile,
@@ -18,26 +19,56 @@ use vex_v5_serial::{
             system::{ScreenCapturePacket, ScreenCapturePayload, ScreenCaptureReplyPacket},
         },
     },
-    serial::SerialConnection,
 };
 
-use crate::errors::CliError;
+use crate::{
+    connection::{BrainGeneration, DeviceConnection, brain_generation},
+    errors::CliError,
+    reporter::{Reporter, Task, TaskKind, progress_callback},
+};
 
-use super::upload::PROGRESS_CHARS;
+/// Layout of a Brain's screen capture buffer.
+struct ScreenGeometry {
+    /// Visible width of the screen in pixels.
+    width: u32,
+    /// Height of the screen in pixels.
+    height: u32,
+    /// Width of each row in the capture buffer, which may be padded past the visible width.
+    stride: u32,
+}
 
-pub async fn screenshot(connection: &mut SerialConnection) -> Result<(), CliError> {
-    let timestamp = Arc::new(Mutex::new(None));
-    let progress = Arc::new(Mutex::new(
-        ProgressBar::new(10000)
-            .with_style(
-                ProgressStyle::with_template(
-                    "{msg:4} {percent_precise:>7}% {bar:40.blue} {prefix}",
-                )
-                .unwrap() // Okay to unwrap, since this just validates style formatting.
-                .progress_chars(PROGRESS_CHARS),
-            )
-            .with_message("CBUF"),
-    ));
+impl ScreenGeometry {
+    fn of(generation: BrainGeneration) -> Self {
+        match generation {
+            BrainGeneration::V5 => Self {
+                width: 480,
+                height: 272,
+                stride: 512,
+            },
+            BrainGeneration::Exp => Self {
+                width: 160,
+                height: 108,
+                stride: 160,
+            },
+        }
+    }
+}
+
+/// Number of bytes downloaded for each capture of a Brain's screen.
+pub fn capture_buffer_size(generation: BrainGeneration) -> u32 {
+    let geometry = ScreenGeometry::of(generation);
+    geometry.stride * geometry.height * 4
+}
+
+/// Captures the Brain's screen.
+///
+/// `progress_callback` is called with the percentage of the capture that has been downloaded.
+pub async fn capture_screen(
+    connection: &mut DeviceConnection,
+    progress_callback: Option<Box<dyn FnMut(f32) + Send>>,
+) -> Result<RgbImage, CliError> {
+    let generation = brain_generation(connection).await?;
+    let geometry = ScreenGeometry::of(generation);
 
     // Tell the brain we want to take a screenshot
     connection
@@ -56,30 +87,10 @@ pub async fn screenshot(connection: &mut SerialConnection) -> Result<(), CliErro
             vendor: FileVendor::Sys,
             target: FileTransferTarget::Cbuf,
             address: 0,
-            size: 512 * 272 * 4,
-            progress_callback: Some({
-                let progress = progress.clone();
-                let timestamp = timestamp.clone();
-
-                Box::new(move |percent| {
-                    let progress = progress.try_lock().unwrap();
-                    let mut timestamp = timestamp.try_lock().unwrap();
-
-                    if timestamp.is_none() {
-                        *timestamp = Some(Instant::now());
-                    }
-
-                    progress.set_prefix(format!("{:.2?}", timestamp.unwrap().elapsed()));
-                    progress.set_position((percent * 100.0) as u64);
-                })
-            }),
+            size: capture_buffer_size(generation),
+            progress_callback,
         })
-        .await
-        .unwrap();
-
-    progress.lock().await.finish();
-
-    info!("Creating image file...");
+        .await?;
 
     let colors = cap
         .chunks(4)
@@ -95,14 +106,277 @@ pub async fn screenshot(connection: &mut SerialConnection) -> Result<(), CliErro
         .flatten()
         .collect::<Vec<_>>();
 
-    let image = image::RgbImage::from_vec(512, 272, colors).unwrap();
+    let image = RgbImage::from_vec(geometry.stride, geometry.height, colors).unwrap();
+
+    Ok(GenericImageView::view(&image, 0, 0, geometry.width, geometry.height).to_image())
+}
+
+/// Where a screenshot should be written.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum ScreenshotOutput {
+    /// `screen.png` in the current directory, numbered if it already exists.
+    #[default]
+    Default,
+
+    /// An image file, in the format given by its extension.
+    File(PathBuf),
+
+    /// A PNG image written to stdout.
+    Stdout,
+
+    /// Raw 8-bit RGB pixels written to stdout, row by row.
+    Raw,
+}
+
+/// Image formats screenshots can be saved as.
+const SUPPORTED_FORMATS: [ImageFormat; 2] = [ImageFormat::Png, ImageFormat::Bmp];
+
+pub async fn screenshot(
+    connection: &mut DeviceConnection,
+    output: ScreenshotOutput,
+    reporter: &dyn Reporter,
+) -> Result<(), CliError> {
+    // Check the format before capturing so a typo doesn't cost a slow download.
+    let file = match &output {
+        ScreenshotOutput::Default => {
+            Some((next_free_path(Path::new("screen.png")), ImageFormat::Png))
+        }
+        ScreenshotOutput::File(path) => Some((path.clone(), image_format(path)?)),
+        ScreenshotOutput::Stdout | ScreenshotOutput::Raw => None,
+    };
 
-    let path = Path::new("./screen.png");
-    GenericImageView::view(&image, 0, 0, 480, 272)
-        .to_image()
-        .save(path)?;
+    let progress = reporter.start_task(Task {
+        kind: TaskKind::Capture,
+        name: "screen",
+        color: "blue",
+    });
+    let image = capture_screen(connection, Some(progress_callback(&progress))).await?;
+    progress.finish();
 
-    info!("Saved screenshot to {}", path.canonicalize()?.display());
+    match file {
+        Some((path, format)) => {
+            image.save_with_format(&path, format)?;
+            reporter.status("Saved", &path.display().to_string());
+        }
+        None => write_to_stdout(&image, output == ScreenshotOutput::Raw)?,
+    }
 
     Ok(())
 }
+
+/// Writes an image to stdout, either as a PNG or as raw 8-bit RGB pixels.
+fn write_to_stdout(image: &RgbImage, raw: bool) -> Result<(), CliError> {
+    let mut stdout = io::stdout().lock();
+
+    if raw {
+        stdout.write_all(image.as_raw())?;
+    } else {
+        // PNG encoding needs to seek, so encode into memory first.
+        let mut png = Cursor::new(Vec::new());
+        image.write_to(&mut png, ImageFormat::Png)?;
+        stdout.write_all(png.get_ref())?;
+    }
+
+    stdout.flush()?;
+    Ok(())
+}
+
+/// Where frames captured by [`record_screen`] go.
+enum FrameSink {
+    /// Numbered image files (`screen-001.png`, `screen-002.png`, ...).
+    Sequence {
+        path: PathBuf,
+        format: ImageFormat,
+        next: u32,
+    },
+
+    /// An animated PNG, written once recording stops. Each frame is kept with how long it's shown.
+    Animation {
+        path: PathBuf,
+        frames: Vec<(RgbImage, Duration)>,
+    },
+
+    /// Images written back-to-back to stdout, as PNGs or raw pixels.
+    Stdout { raw: bool },
+}
+
+/// Repeatedly captures the Brain's screen every `interval` until `max_frames` have been captured
+/// or the command is interrupted.
+///
+/// Frames are saved as a numbered image sequence, unless `output` is a `.apng` file, in which case
+/// they're encoded as an animation. Writing to stdout streams one image per frame, which tools like
+/// `ffmpeg` can read as a video.
+pub async fn record_screen(
+    connection: &mut DeviceConnection,
+    output: ScreenshotOutput,
+    interval: Duration,
+    max_frames: Option<NonZeroU32>,
+    reporter: &dyn Reporter,
+) -> Result<(), CliError> {
+    let mut sink = match output {
+        ScreenshotOutput::Default => FrameSink::Sequence {
+            next: first_free_number(Path::new("screen.png")),
+            path: PathBuf::from("screen.png"),
+            format: ImageFormat::Png,
+        },
+        ScreenshotOutput::File(path) if path.extension().is_some_and(|ext| ext == "apng") => {
+            FrameSink::Animation {
+                path,
+                frames: Vec::new(),
+            }
+        }
+        ScreenshotOutput::File(path) => FrameSink::Sequence {
+            format: image_format(&path)?,
+            next: first_free_number(&path),
+            path,
+        },
+        ScreenshotOutput::Stdout => FrameSink::Stdout { raw: false },
+        ScreenshotOutput::Raw => FrameSink::Stdout { raw: true },
+    };
+
+    let progress = ProgressBar::new_spinner().with_message("Waiting for the first frame");
+    progress.enable_steady_tick(Duration::from_millis(100));
+
+    let interrupted = tokio::signal::ctrl_c();
+    tokio::pin!(interrupted);
+
+    let mut captured = 0;
+    let mut last_capture: Option<Instant> = None;
+
+    loop {
+        let started = Instant::now();
+
+        let image = select! {
+            image = capture_screen(connection, None) => image?,
+            _ = &mut interrupted => break,
+        };
+
+        match &mut sink {
+            FrameSink::Sequence { path, format, next } => {
+                image.save_with_format(numbered_path(path, *next), *format)?;
+                *next += 1;
+            }
+            FrameSink::Animation { frames, .. } => {
+                // The previous frame is shown until this one was captured.
+                if let (Some(last_capture), Some((_, delay))) = (last_capture, frames.last_mut()) {
+                    *delay = started - last_capture;
+                }
+                frames.push((image, interval));
+            }
+            FrameSink::Stdout { raw } => write_to_stdout(&image, *raw)?,
+        }
+
+        captured += 1;
+        last_capture = Some(started);
+        progress.set_message(format!(
+            "Captured {captured} frame{}",
+            if captured == 1 { "" } else { "s" }
+        ));
+
+        if max_frames.is_some_and(|max| captured >= max.get()) {
+            break;
+        }
+
+        select! {
+            () = sleep_until((started + interval).into()) => {}
+            _ = &mut interrupted => break,
+        }
+    }
+
+    progress.finish_and_clear();
+
+    match sink {
+        FrameSink::Sequence { path, next, .. } => {
+            if captured > 0 {
+                reporter.status(
+                    "Recorded",
+                    &format!(
+                        "{captured} frames ({} to {})",
+                        numbered_path(&path, next - captured).display(),
+                        numbered_path(&path, next - 1).display()
+                    ),
+                );
+            }
+        }
+        FrameSink::Animation { path, frames } => {
+            if !frames.is_empty() {
+                write_animation(&path, &frames)?;
+                reporter.status(
+                    "Recorded",
+                    &format!("{captured} frames to {}", path.display()),
+                );
+            }
+        }
+        FrameSink::Stdout { .. } => {}
+    }
+
+    Ok(())
+}
+
+/// Encodes frames as an animated PNG that loops forever.
+fn write_animation(path: &Path, frames: &[(RgbImage, Duration)]) -> Result<(), CliError> {
+    let (width, height) = frames[0].0.dimensions();
+
+    let mut encoder = png::Encoder::new(BufWriter::new(File::create(path)?), width, height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .set_animated(frames.len() as u32, 0)
+        .map_err(io::Error::other)?;
+
+    let mut writer = encoder.write_header().map_err(io::Error::other)?;
+    for (image, delay) in frames {
+        // Delays are stored as a fraction of a second, here in milliseconds.
+        let delay = delay.as_millis().min(u16::MAX as u128) as u16;
+        writer
+            .set_frame_delay(delay, 1000)
+            .map_err(io::Error::other)?;
+        writer
+            .write_image_data(image.as_raw())
+            .map_err(io::Error::other)?;
+    }
+    writer.finish().map_err(io::Error::other)?;
+
+    Ok(())
+}
+
+/// Infers the format to save a screenshot in from the extension of `path`.
+fn image_format(path: &Path) -> Result<ImageFormat, CliError> {
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    ImageFormat::from_extension(&extension)
+        .filter(|format| SUPPORTED_FORMATS.contains(format))
+        .ok_or(CliError::UnsupportedImageFormat(extension))
+}
+
+/// Returns `path` if nothing exists there, or the first free numbered variant of it
+/// (`screen-001.png`, `screen-002.png`, ...).
+fn next_free_path(path: &Path) -> PathBuf {
+    if !path.exists() {
+        return path.to_path_buf();
+    }
+
+    numbered_path(path, first_free_number(path))
+}
+
+/// Returns the smallest number `n` for which `numbered_path(path, n)` doesn't exist yet.
+fn first_free_number(path: &Path) -> u32 {
+    (1..).find(|&n| !numbered_path(path, n).exists()).unwrap()
+}
+
+/// Numbers `path` by appending `-{n:03}` to its file stem.
+fn numbered_path(path: &Path, n: u32) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let extension = path
+        .extension()
+        .map(|extension| format!(".{}", extension.to_string_lossy()))
+        .unwrap_or_default();
+
+    path.with_file_name(format!("{stem}-{n:03}{extension}"))
+}
@@ -0,0 +1,301 @@
+//! Forwards a Brain's serial channels to plain TCP sockets, so that tools without VEX CDC2
+//! support can talk to a running program.
+//!
+//! Clients of the user channel receive the program's output byte for byte, and anything they send
+//! is written to the program's input. Clients of the system channel exchange raw CDC2 packets with
+//! the Brain, one client at a time. Clients of either channel can control the robot, so unless both
+//! are only reachable from this machine, clients must first send a token followed by a newline.
+
+use std::{net::SocketAddr, time::Duration};
+
+use futures::FutureExt;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    time::{Instant, timeout},
+};
+use vex_v5_serial::{
+    Connection, ConnectionType,
+    protocol::{
+        COMMAND_HEADER,
+        cdc::cmds::{CON_CDC, USER_CDC},
+    },
+};
+
+use crate::{
+    connection::DeviceConnection,
+    errors::CliError,
+    remote::{AnyPacket, token_matches},
+};
+
+use super::{serve::is_timeout, terminal::TerminalHub};
+
+/// The longest each channel is waited on before checking the other.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// How long replies are waited for after a system channel client sends a packet.
+const REPLY_WINDOW: Duration = Duration::from_secs(2);
+
+/// How long a system channel client has to send its token.
+const AUTH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Checks that a channel on `addr` can only be reached by trusted clients, either because it's
+/// only reachable from this machine or because clients need `token`.
+fn check_addr(addr: SocketAddr, token: Option<&str>) -> Result<(), CliError> {
+    if !addr.ip().is_loopback() && token.is_none() {
+        return Err(CliError::UnauthenticatedSerialBridge(addr));
+    }
+
+    Ok(())
+}
+
+/// Forwards the program's I/O to clients connecting to `addr`, and the system channel to clients
+/// connecting to `system_addr` if one is given, until the command is interrupted. Clients of
+/// either channel must send `token` first if one is given.
+///
+/// Program output is also shared with `cargo v5 terminal`, which can attach while the bridge is
+/// running.
+pub async fn serial_bridge(
+    connection: &mut DeviceConnection,
+    addr: SocketAddr,
+    system_addr: Option<SocketAddr>,
+    token: Option<String>,
+) -> Result<(), CliError> {
+    check_addr(addr, token.as_deref())?;
+    if let Some(system_addr) = system_addr {
+        check_addr(system_addr, token.as_deref())?;
+    }
+
+    let user = TerminalHub::listen(addr, token.clone()).await?;
+    eprintln!("    \x1b[1;92mBridging\x1b[0m program I/O to tcp://{addr}");
+
+    let mut system = match system_addr {
+        Some(addr) => {
+            let bridge = SystemBridge::bind(addr, token).await?;
+            eprintln!("    \x1b[1;92mBridging\x1b[0m system channel to tcp://{addr}");
+            Some(bridge)
+        }
+        None => None,
+    };
+
+    let mut hubs = vec![user];
+    hubs.extend(TerminalHub::bind().await);
+
+    // Program output is polled over the system channel when connected through a controller, so
+    // it's left alone while a system client is waiting on replies.
+    let shares_system_channel = connection.connection_type() == ConnectionType::Controller;
+    let mut output = [0; 2048];
+
+    loop {
+        if !(shares_system_channel && system.as_ref().is_some_and(SystemBridge::awaiting_reply))
+            && let Ok(Ok(size @ 1..)) =
+                timeout(POLL_INTERVAL, connection.read_user(&mut output)).await
+        {
+            for hub in &hubs {
+                hub.publish(&output[..size]);
+            }
+        }
+
+        for hub in &mut hubs {
+            while let Some(input) = hub.try_recv_input() {
+                write_input(connection, &input).await?;
+            }
+        }
+
+        if let Some(system) = &mut system {
+            system.poll(connection).await?;
+        }
+    }
+}
+
+/// Writes input from a client to the program.
+async fn write_input(connection: &mut DeviceConnection, input: &[u8]) -> Result<(), CliError> {
+    // VEXos only carries text between a controller and the Brain's program.
+    if connection.connection_type() == ConnectionType::Controller
+        && std::str::from_utf8(input).is_err()
+    {
+        log::warn!("Dropping input that isn't text, which can't be sent through a controller");
+        return Ok(());
+    }
+
+    connection.write_user(input).await?;
+    Ok(())
+}
+
+/// Splits a stream of CDC2 command packets at packet boundaries.
+#[derive(Debug, Default)]
+struct PacketSplitter {
+    buffer: Vec<u8>,
+}
+
+impl PacketSplitter {
+    /// Adds data received from a client.
+    fn feed(&mut self, data: &[u8]) {
+        self.buffer.extend_from_slice(data);
+    }
+
+    /// Removes the next complete packet, skipping anything before it that isn't a packet.
+    fn next_packet(&mut self) -> Option<Vec<u8>> {
+        let start = self
+            .buffer
+            .windows(COMMAND_HEADER.len())
+            .position(|window| window == COMMAND_HEADER);
+        let discard = start.unwrap_or(self.buffer.len().saturating_sub(COMMAND_HEADER.len() - 1));
+        if discard > 0 {
+            log::warn!(
+                "Dropping {discard} bytes from the system channel client that aren't a packet"
+            );
+            self.buffer.drain(..discard);
+        }
+
+        let len = self.packet_len()?;
+        (self.buffer.len() >= len).then(|| self.buffer.drain(..len).collect())
+    }
+
+    /// The length of the packet at the start of the buffer, if enough of it has arrived to tell.
+    fn packet_len(&self) -> Option<usize> {
+        let header = COMMAND_HEADER.len();
+        let command = *self.buffer.get(header)?;
+
+        // Simple commands are just the header and the command. Extended ones are followed by
+        // the extended command, a variable-width payload length, the payload, and a CRC16.
+        if command != USER_CDC && command != CON_CDC {
+            return Some(header + 1);
+        }
+
+        let size = *self.buffer.get(header + 2)?;
+        let (payload, size_len) = if size & 0x80 == 0 {
+            (usize::from(size), 1)
+        } else {
+            let low = *self.buffer.get(header + 3)?;
+            (usize::from(size & 0x7F) << 8 | usize::from(low), 2)
+        };
+
+        Some(header + 2 + size_len + payload + 2)
+    }
+}
+
+/// Passes raw packets between a TCP client and the Brain's system channel.
+struct SystemBridge {
+    listener: TcpListener,
+    /// The token clients must send before any packets, if any.
+    token: Option<String>,
+    client: Option<(TcpStream, SocketAddr)>,
+    packets: PacketSplitter,
+    /// When the Brain is no longer expected to reply to the packets sent so far.
+    reply_deadline: Option<Instant>,
+}
+
+impl SystemBridge {
+    async fn bind(addr: SocketAddr, token: Option<String>) -> Result<Self, CliError> {
+        Ok(Self {
+            listener: TcpListener::bind(addr).await?,
+            token,
+            client: None,
+            packets: PacketSplitter::default(),
+            reply_deadline: None,
+        })
+    }
+
+    /// Reads the token a client sends before its packets, and checks it.
+    ///
+    /// This holds up the bridge until the client sends its token or [`AUTH_TIMEOUT`] passes, which
+    /// only delays program output, since the Brain buffers it.
+    async fn authenticate(&self, client: &mut TcpStream) -> bool {
+        let Some(token) = &self.token else {
+            return true;
+        };
+
+        let read_line = async {
+            let mut line = Vec::new();
+            loop {
+                match client.read_u8().await? {
+                    b'\n' => return Ok(line),
+                    // Anything longer than the token is wrong anyway.
+                    _ if line.len() > token.len() => return Ok(line),
+                    byte => line.push(byte),
+                }
+            }
+        };
+
+        match timeout(AUTH_TIMEOUT, read_line).await {
+            Ok(Ok::<_, std::io::Error>(line)) => {
+                let line = String::from_utf8_lossy(&line);
+                token_matches(token, line.trim_end_matches('\r'))
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether packets were sent that the Brain may still reply to.
+    fn awaiting_reply(&self) -> bool {
+        self.reply_deadline
+            .is_some_and(|deadline| Instant::now() < deadline)
+    }
+
+    /// Accepts a client if there isn't one, then passes on any packets waiting in either
+    /// direction.
+    async fn poll(&mut self, connection: &mut DeviceConnection) -> Result<(), CliError> {
+        let Some((client, _)) = &mut self.client else {
+            if let Some(Ok((mut client, peer))) = self.listener.accept().now_or_never() {
+                if !self.authenticate(&mut client).await {
+                    log::warn!("A system channel client from {peer} sent the wrong token");
+                    return Ok(());
+                }
+
+                eprintln!("   \x1b[1;92mConnected\x1b[0m to {peer}");
+                client.set_nodelay(true)?;
+                self.client = Some((client, peer));
+            }
+            return Ok(());
+        };
+
+        let mut data = [0; 4096];
+        match client.try_read(&mut data) {
+            Ok(0) => {
+                self.disconnect();
+                return Ok(());
+            }
+            Ok(size) => {
+                self.packets.feed(&data[..size]);
+                while let Some(packet) = self.packets.next_packet() {
+                    connection.send(packet).await?;
+                    self.reply_deadline = Some(Instant::now() + REPLY_WINDOW);
+                }
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => {}
+            Err(err) => {
+                log::warn!("Lost the system channel client: {err}");
+                self.disconnect();
+                return Ok(());
+            }
+        }
+
+        if !self.awaiting_reply() {
+            return Ok(());
+        }
+
+        let packet = match connection.recv::<AnyPacket>(POLL_INTERVAL).await {
+            Ok(AnyPacket(packet)) => packet,
+            Err(err) if is_timeout(&err) => return Ok(()),
+            Err(err) => return Err(err.into()),
+        };
+
+        if let Some((client, _)) = &mut self.client
+            && let Err(err) = client.write_all(&packet).await
+        {
+            log::warn!("Lost the system channel client: {err}");
+            self.disconnect();
+        }
+
+        Ok(())
+    }
+
+    fn disconnect(&mut self) {
+        if let Some((_, peer)) = self.client.take() {
+            eprintln!("\x1b[1;92mDisconnected\x1b[0m from {peer}");
+        }
+        self.packets = PacketSplitter::default();
+        self.reply_deadline = None;
+    }
+}
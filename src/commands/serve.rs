@@ -0,0 +1,140 @@
+//! Shares a device plugged into this machine with other machines on the network, which use it by
+//! passing `--remote <HOST:PORT>` to any command.
+//!
+//! See [`crate::remote`] for how the serial protocol is tunnelled.
+
+use std::{io, net::SocketAddr, time::Duration};
+
+use tokio::{
+    net::{TcpListener, TcpStream},
+    time::timeout,
+};
+use vex_v5_serial::{Connection, serial::SerialError};
+
+use crate::{
+    connection::{ConnectionError, DeviceConnection},
+    errors::CliError,
+    remote::{
+        AnyPacket, FRAME_ERROR, FRAME_HELLO, FRAME_READ_USER, FRAME_RECV, FRAME_SEND,
+        FRAME_WRITE_USER, encode_connection_type, nonce, read_frame, verify_nonce, write_frame,
+    },
+};
+
+/// The longest a client waits for program output before asking again.
+const READ_USER_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// How long a client has to authenticate. Clients are served one at a time, so one that never
+/// answers would otherwise block everyone else.
+const AUTH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Accepts clients on `addr` and passes their packets to `connection` until the command is
+/// interrupted.
+///
+/// Clients are served one at a time. Others connecting in the meantime wait until the current
+/// client disconnects.
+pub async fn serve(
+    connection: &mut DeviceConnection,
+    addr: SocketAddr,
+    token: &str,
+) -> Result<(), CliError> {
+    let listener = TcpListener::bind(addr).await?;
+    eprintln!("     \x1b[1;92mServing\x1b[0m device on {addr}");
+
+    loop {
+        let (client, peer) = listener.accept().await?;
+        eprintln!("   \x1b[1;92mConnected\x1b[0m to {peer}");
+
+        match serve_client(connection, client, token).await {
+            Ok(()) => eprintln!("\x1b[1;92mDisconnected\x1b[0m from {peer}"),
+            Err(err) => log::warn!("Lost the connection to {peer}: {err}"),
+        }
+    }
+}
+
+/// Authenticates a client, then handles its requests until it disconnects.
+async fn serve_client(
+    connection: &mut DeviceConnection,
+    client: TcpStream,
+    token: &str,
+) -> io::Result<()> {
+    client.set_nodelay(true)?;
+    let (mut reader, mut writer) = client.into_split();
+
+    let nonce = nonce();
+    write_frame(&mut writer, FRAME_HELLO, &nonce).await?;
+    let Ok(reply) = timeout(AUTH_TIMEOUT, read_frame(&mut reader)).await else {
+        log::warn!("A client didn't authenticate in time");
+        return write_frame(&mut writer, FRAME_ERROR, b"authentication timed out").await;
+    };
+    let (kind, signature) = reply?;
+    if kind != FRAME_HELLO || !verify_nonce(token, &nonce, &signature) {
+        log::warn!("A client tried to connect with the wrong token");
+        return write_frame(&mut writer, FRAME_ERROR, b"wrong token").await;
+    }
+
+    let connection_type = encode_connection_type(connection.connection_type());
+    write_frame(&mut writer, FRAME_HELLO, &[connection_type]).await?;
+
+    loop {
+        let (kind, payload) = match read_frame(&mut reader).await {
+            Ok(frame) => frame,
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(err) => return Err(err),
+        };
+
+        let result = match kind {
+            FRAME_SEND => connection.send(payload).await.map(|()| None),
+            FRAME_RECV if payload.len() >= 8 => {
+                let (id, timeout_ms) = payload.split_at(4);
+                let timeout_ms = u32::from_le_bytes(timeout_ms[..4].try_into().unwrap());
+
+                match connection
+                    .recv::<AnyPacket>(Duration::from_millis(timeout_ms.into()))
+                    .await
+                {
+                    Ok(AnyPacket(packet)) => Ok(Some((id.to_vec(), packet))),
+                    Err(err) if is_timeout(&err) => Ok(Some((id.to_vec(), Vec::new()))),
+                    Err(err) => Err(err),
+                }
+            }
+            FRAME_READ_USER if payload.len() >= 4 => {
+                let mut output = [0; 2048];
+                match timeout(READ_USER_TIMEOUT, connection.read_user(&mut output)).await {
+                    Ok(Ok(size)) => Ok(Some((payload, output[..size].to_vec()))),
+                    // Usually means no program is running.
+                    Ok(Err(err)) if is_timeout(&err) => Ok(Some((payload, Vec::new()))),
+                    Ok(Err(err)) => Err(err),
+                    Err(_) => Ok(Some((payload, Vec::new()))),
+                }
+            }
+            FRAME_WRITE_USER => connection.write_user(&payload).await.map(|_| None),
+            _ => {
+                log::debug!("Ignoring unknown frame of kind {kind}");
+                Ok(None)
+            }
+        };
+
+        match result {
+            Ok(Some((id, mut reply))) => {
+                let mut frame = id;
+                frame.append(&mut reply);
+                write_frame(&mut writer, kind, &frame).await?;
+            }
+            Ok(None) => {}
+            Err(err) => {
+                log::warn!("Device error: {err}");
+                write_frame(&mut writer, FRAME_ERROR, err.to_string().as_bytes()).await?;
+            }
+        }
+    }
+}
+
+/// Whether `err` means that the device didn't reply in time.
+pub fn is_timeout(err: &ConnectionError) -> bool {
+    match err {
+        ConnectionError::Serial(SerialError::Timeout) | ConnectionError::RemoteTimeout => true,
+        #[cfg(feature = "bluetooth")]
+        ConnectionError::Bluetooth(vex_v5_serial::bluetooth::BluetoothError::Timeout) => true,
+        _ => false,
+    }
+}
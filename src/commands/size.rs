@@ -0,0 +1,198 @@
+//! Size breakdowns of built programs, shown by `cargo v5 size` and `cargo v5 build --size`.
+
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    io::{self, Write},
+    path::Path,
+};
+
+use humansize::{BINARY, format_size};
+use object::{
+    Object, ObjectSection, ObjectSymbol, SectionFlags, SectionKind, SymbolKind,
+    elf::{SHF_ALLOC, SHF_WRITE},
+};
+use tabwriter::TabWriter;
+
+use super::upload::DIFFERENTIAL_UPLOAD_MAX_SIZE;
+use crate::errors::CliError;
+
+/// Number of crates listed before the rest are grouped together.
+const TOP_CRATES: usize = 10;
+
+/// How much of a program's memory each kind of section and each crate takes up.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SizeReport {
+    /// Code and read-only data.
+    pub text: u64,
+    /// Initialized writable data.
+    pub data: u64,
+    /// Zero-initialized data, which takes up memory but not space in the binary.
+    pub bss: u64,
+
+    /// Total size of the symbols from each crate, largest first.
+    pub crates: Vec<(String, u64)>,
+}
+
+impl SizeReport {
+    /// Measures the sections and symbols of an ELF.
+    pub fn of(elf: &[u8]) -> Result<Self, CliError> {
+        let elf = object::File::parse(elf)?;
+        let mut report = Self::default();
+
+        for section in elf.sections() {
+            let SectionFlags::Elf { sh_flags } = section.flags() else {
+                continue;
+            };
+            if sh_flags & u64::from(SHF_ALLOC) == 0 {
+                continue;
+            }
+
+            if matches!(
+                section.kind(),
+                SectionKind::UninitializedData | SectionKind::UninitializedTls
+            ) {
+                report.bss += section.size();
+            } else if sh_flags & u64::from(SHF_WRITE) != 0 {
+                report.data += section.size();
+            } else {
+                report.text += section.size();
+            }
+        }
+
+        let mut crates = HashMap::<String, u64>::new();
+        for (name, size) in symbol_sizes(&elf) {
+            *crates.entry(crate_name(&name).to_string()).or_default() += size;
+        }
+
+        report.crates = crates.into_iter().collect();
+        report
+            .crates
+            .sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+        Ok(report)
+    }
+}
+
+/// Returns the demangled name and size of every function and data symbol in an ELF, largest first.
+///
+/// Symbol hashes are left out of the names so that the same symbol can be matched up between two
+/// builds. Symbols that end up with the same name are combined.
+pub fn symbol_sizes(elf: &object::File) -> Vec<(String, u64)> {
+    let mut sizes = HashMap::<String, u64>::new();
+
+    for symbol in elf.symbols() {
+        if symbol.size() == 0 || !matches!(symbol.kind(), SymbolKind::Text | SymbolKind::Data) {
+            continue;
+        }
+        let Ok(name) = symbol.name() else {
+            continue;
+        };
+
+        let name = addr2line::demangle_auto(Cow::from(name), None);
+        *sizes.entry(strip_hash(&name).to_string()).or_default() += symbol.size();
+    }
+
+    let mut sizes = sizes.into_iter().collect::<Vec<_>>();
+    sizes.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    sizes
+}
+
+/// Removes the `::h0123456789abcdef` hash that ends legacy-mangled Rust symbols.
+fn strip_hash(name: &str) -> &str {
+    match name.rsplit_once("::h") {
+        Some((path, hash)) if hash.len() == 16 && hash.chars().all(|c| c.is_ascii_hexdigit()) => {
+            path
+        }
+        _ => name,
+    }
+}
+
+/// Guesses which crate a demangled symbol came from by its first path segment.
+///
+/// Symbols without a Rust path, like those from C libraries or the linker, are grouped as
+/// `[other]`.
+pub fn crate_name(symbol: &str) -> &str {
+    // Trait impls look like `<alloc::vec::Vec<T> as core::ops::Drop>::drop`.
+    let path = symbol.trim_start_matches(['<', '&', '*']);
+    let path = ["mut ", "const ", "dyn "]
+        .iter()
+        .fold(path, |path, prefix| path.trim_start_matches(prefix));
+
+    match path.split_once("::") {
+        Some((name, _))
+            if !name.is_empty()
+                && name
+                    .chars()
+                    .all(|char| char.is_ascii_alphanumeric() || char == '_') =>
+        {
+            name
+        }
+        _ => "[other]",
+    }
+}
+
+/// Prints a size breakdown of the ELF at `elf_path` and how its BIN compares to the differential
+/// upload limit.
+pub fn print_size_report(elf_path: &Path, bin_path: &Path) -> Result<(), CliError> {
+    let report = SizeReport::of(&fs_err::read(elf_path)?)?;
+    let bin_size = fs_err::metadata(bin_path)?.len();
+
+    let mut tw = TabWriter::new(io::stdout());
+
+    writeln!(tw, "\x1b[1mSection\tSize\x1b[0m")?;
+    for (name, size) in [
+        ("text", report.text),
+        ("data", report.data),
+        ("bss", report.bss),
+    ] {
+        writeln!(tw, "{name}\t{}", format_size(size, BINARY))?;
+    }
+
+    let total = report.crates.iter().map(|(_, size)| size).sum::<u64>();
+    if total > 0 {
+        writeln!(tw)?;
+        writeln!(tw, "\x1b[1mCrate\tSize\tShare\x1b[0m")?;
+
+        let share = |size: u64| size as f64 / total as f64 * 100.0;
+        for (name, size) in report.crates.iter().take(TOP_CRATES) {
+            writeln!(
+                tw,
+                "{name}\t{}\t{:.1}%",
+                format_size(*size, BINARY),
+                share(*size)
+            )?;
+        }
+
+        let rest = &report.crates[report.crates.len().min(TOP_CRATES)..];
+        if !rest.is_empty() {
+            let size = rest.iter().map(|(_, size)| size).sum::<u64>();
+            writeln!(
+                tw,
+                "{} more\t{}\t{:.1}%",
+                rest.len(),
+                format_size(size, BINARY),
+                share(size)
+            )?;
+        }
+    }
+
+    writeln!(tw)?;
+    let limit = DIFFERENTIAL_UPLOAD_MAX_SIZE as u64;
+    let color = if bin_size > limit {
+        "\x1b[1;91m"
+    } else {
+        "\x1b[1m"
+    };
+    writeln!(
+        tw,
+        "{color}BIN\x1b[0m\t{}\t{:.1}% of the {} differential upload limit",
+        format_size(bin_size, BINARY),
+        bin_size as f64 / limit as f64 * 100.0,
+        format_size(limit, BINARY)
+    )?;
+
+    tw.flush()?;
+
+    Ok(())
+}
@@ -0,0 +1,128 @@
+use std::io::{self, Write};
+
+use chrono::{TimeZone, Utc};
+use humansize::{BINARY, format_size};
+use tabwriter::TabWriter;
+use vex_v5_serial::{
+    Connection,
+    commands::file::{DownloadFile, J2000_EPOCH},
+    protocol::{
+        FixedString,
+        cdc2::file::{FileTransferTarget, FileVendor},
+    },
+};
+
+use crate::{connection::DeviceConnection, errors::CliError};
+
+use super::upload::brain_file_metadata;
+
+/// Returns the value of `key` in `section` of an ini file.
+pub fn ini_value<'a>(ini: &'a str, section: &str, key: &str) -> Option<&'a str> {
+    let mut current_section = "";
+
+    for line in ini.lines().map(str::trim) {
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            current_section = name;
+        } else if current_section == section
+            && let Some((k, v)) = line.split_once('=')
+            && k.trim() == key
+        {
+            return Some(v.trim());
+        }
+    }
+
+    None
+}
+
+/// Maps the `project.ide` field of a slot's ini file to a human-readable toolchain name.
+pub fn language_from_ide(ide: &str) -> &str {
+    let lowercase = ide.to_lowercase();
+
+    if lowercase.contains("python") {
+        "Python"
+    } else if lowercase.contains("pros") {
+        "PROS"
+    } else if lowercase.contains("vexcode") {
+        "VEXcode C++"
+    } else if lowercase.contains("rust") || lowercase.contains("vexide") {
+        "Rust"
+    } else {
+        ide
+    }
+}
+
+/// List the programs installed in each of the Brain's eight slots.
+pub async fn slots(connection: &mut DeviceConnection) -> Result<(), CliError> {
+    let mut tw = TabWriter::new(io::stdout());
+
+    writeln!(
+        &mut tw,
+        "\x1B[1mSlot\tName\tLanguage\tSize\tTimestamp\x1B[0m"
+    )
+    .unwrap();
+
+    for slot in 1..=8u8 {
+        // Python programs are stored as a `.py` file instead of a `.bin`.
+        let mut program_metadata = None;
+        for extension in ["bin", "py"] {
+            program_metadata = brain_file_metadata(
+                connection,
+                FixedString::new(format!("slot_{slot}.{extension}")).unwrap(),
+                FileVendor::User,
+            )
+            .await?;
+            if program_metadata.is_some() {
+                break;
+            }
+        }
+
+        let ini_name = FixedString::new(format!("slot_{slot}.ini")).unwrap();
+        let ini = match brain_file_metadata(connection, ini_name.clone(), FileVendor::User).await? {
+            Some(ini_metadata) => {
+                let data = connection
+                    .execute_command(DownloadFile {
+                        file_name: ini_name,
+                        size: ini_metadata.size,
+                        vendor: FileVendor::User,
+                        target: FileTransferTarget::Qspi,
+                        address: 0,
+                        progress_callback: None,
+                    })
+                    .await?;
+                Some(String::from_utf8_lossy(&data).into_owned())
+            }
+            None => None,
+        };
+
+        if program_metadata.is_none() && ini.is_none() {
+            writeln!(&mut tw, "{slot}\t-\t-\t-\t-").unwrap();
+            continue;
+        }
+
+        let ini = ini.unwrap_or_default();
+        let (size, timestamp) = match &program_metadata {
+            Some(metadata) => (
+                format_size(metadata.size, BINARY),
+                Utc.timestamp_opt(J2000_EPOCH as i64 + metadata.metadata.timestamp as i64, 0)
+                    .unwrap()
+                    .format("%Y-%m-%d %H:%M:%S")
+                    .to_string(),
+            ),
+            None => ("-".to_string(), "-".to_string()),
+        };
+
+        writeln!(
+            &mut tw,
+            "{slot}\t{}\t{}\t{size}\t{timestamp}",
+            ini_value(&ini, "program", "name").unwrap_or("-"),
+            ini_value(&ini, "project", "ide")
+                .map(language_from_ide)
+                .unwrap_or("-"),
+        )
+        .unwrap();
+    }
+
+    tw.flush().unwrap();
+
+    Ok(())
+}
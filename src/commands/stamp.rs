@@ -0,0 +1,192 @@
+//! Build information stamped onto uploaded programs, read back by `cargo v5 stamp`.
+//!
+//! The stamp is a JSON object appended after the end of the program image, followed by its
+//! length and [`STAMP_MAGIC`]. VEXos loads it past the program's initialized data, into memory
+//! that vexide zeroes before the program starts, so stamped programs run unchanged. (A `.note`
+//! section in the ELF wouldn't reach the Brain, since `objcopy` only keeps loadable sections.)
+//!
+//! Everything in the stamp is derived from the program itself and how it was built, so uploading
+//! the same build again gives the same bytes, and unchanged uploads can still be skipped.
+
+use std::io::{self, Read, Write};
+
+use flate2::read::GzDecoder;
+use ring::digest::{SHA256, digest};
+use serde_json::{Value, json};
+use tabwriter::TabWriter;
+use vex_v5_serial::{
+    Connection,
+    commands::file::{DownloadFile, USER_PROGRAM_LOAD_ADDR},
+    protocol::{
+        FixedString,
+        cdc2::file::{FileTransferTarget, FileVendor},
+    },
+};
+
+use crate::{
+    connection::DeviceConnection,
+    errors::CliError,
+    reporter::{Reporter, Task, TaskKind, progress_callback},
+};
+
+use super::upload::{apply_patch, brain_file_metadata};
+
+/// Marks the end of a stamped program.
+pub const STAMP_MAGIC: &[u8; 8] = b"V5STAMP\x01";
+
+/// What a program was built from and how it was uploaded.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stamp {
+    /// The start of the SHA-256 hash of the program image, without the stamp.
+    pub image: Option<String>,
+    /// The git revision the program was built from.
+    pub commit: Option<String>,
+    /// The version of cargo-v5 that uploaded the program.
+    pub cargo_v5: String,
+    /// The upload strategy used, like `monolith` or `differential`.
+    pub strategy: String,
+}
+
+impl Stamp {
+    /// A stamp for the program image `binary`, built from `commit` and uploaded by this version of
+    /// cargo-v5.
+    pub fn new(binary: &[u8], commit: Option<String>, strategy: &str) -> Self {
+        let image = digest(&SHA256, binary).as_ref()[..8]
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect();
+
+        Self {
+            image: Some(image),
+            commit,
+            cargo_v5: env!("CARGO_PKG_VERSION").to_string(),
+            strategy: strategy.to_string(),
+        }
+    }
+
+    /// Appends the stamp to the end of `binary`.
+    pub fn apply(&self, binary: &mut Vec<u8>) {
+        let stamp = json!({
+            "image": self.image,
+            "commit": self.commit,
+            "cargo_v5": self.cargo_v5,
+            "strategy": self.strategy,
+        })
+        .to_string();
+
+        binary.extend_from_slice(stamp.as_bytes());
+        binary.extend_from_slice(&(stamp.len() as u32).to_le_bytes());
+        binary.extend_from_slice(STAMP_MAGIC);
+    }
+
+    /// Reads the stamp from the end of `binary`, if it has one.
+    pub fn find(binary: &[u8]) -> Option<Self> {
+        let rest = binary.strip_suffix(STAMP_MAGIC)?;
+        let (rest, len) = rest.split_at_checked(rest.len().checked_sub(4)?)?;
+        let len = u32::from_le_bytes(len.try_into().ok()?) as usize;
+        let stamp = rest.get(rest.len().checked_sub(len)?..)?;
+
+        let stamp = serde_json::from_slice::<Value>(stamp).ok()?;
+        let string = |key: &str| stamp[key].as_str().map(str::to_string);
+
+        Some(Self {
+            image: string("image"),
+            commit: string("commit"),
+            cargo_v5: string("cargo_v5")?,
+            strategy: string("strategy").unwrap_or_default(),
+        })
+    }
+}
+
+/// Prints the stamp of the program installed in `slot`.
+pub async fn stamp(
+    connection: &mut DeviceConnection,
+    slot: u8,
+    reporter: &dyn Reporter,
+) -> Result<(), CliError> {
+    if !(1..=8).contains(&slot) {
+        return Err(CliError::SlotOutOfRange);
+    }
+
+    let program = download_program(connection, slot, reporter).await?;
+    let stamp = Stamp::find(&program).ok_or(CliError::NoStamp(slot))?;
+
+    let mut tw = TabWriter::new(io::stdout());
+    for (label, value) in [
+        ("Image", stamp.image.as_deref()),
+        ("Commit", stamp.commit.as_deref()),
+        ("cargo-v5", Some(stamp.cargo_v5.as_str())),
+        ("Strategy", Some(stamp.strategy.as_str())),
+    ] {
+        match value {
+            Some(value) => writeln!(tw, "\x1b[1m{label}\x1b[0m\t{value}")?,
+            None => writeln!(tw, "\x1b[1m{label}\x1b[0m\t\x1b[2munknown\x1b[0m")?,
+        }
+    }
+    tw.flush()?;
+
+    Ok(())
+}
+
+/// Downloads the program installed in `slot`, as it's loaded when the program runs.
+///
+/// Differential uploads leave a patch in the slot's file, which is applied to the slot's base.
+async fn download_program(
+    connection: &mut DeviceConnection,
+    slot: u8,
+    reporter: &dyn Reporter,
+) -> Result<Vec<u8>, CliError> {
+    let slot_file_name = format!("slot_{slot}.bin");
+    let (data, load_address) = download(connection, &slot_file_name, reporter).await?;
+    if load_address == USER_PROGRAM_LOAD_ADDR {
+        return Ok(data);
+    }
+
+    let base_file_name = format!("slot_{slot}.base.bin");
+    let (base, _) = download(connection, &base_file_name, reporter).await?;
+
+    // A cold differential upload leaves a stub instead of a patch, so the base runs as-is.
+    if data[..] == u32::to_le_bytes(0xB2DF) {
+        return Ok(base);
+    }
+
+    apply_patch(&base, &data).ok_or(CliError::NoStamp(slot))
+}
+
+/// Downloads `file_name` from the Brain, decompressing it if it was gzipped, and returns it along
+/// with its load address.
+async fn download(
+    connection: &mut DeviceConnection,
+    file_name: &str,
+    reporter: &dyn Reporter,
+) -> Result<(Vec<u8>, u32), CliError> {
+    let fixed_name = FixedString::new(file_name).unwrap();
+    let metadata = brain_file_metadata(connection, fixed_name.clone(), FileVendor::User)
+        .await?
+        .ok_or_else(|| CliError::RemoteFileNotFound(file_name.to_string()))?;
+
+    let progress = reporter.start_task(Task {
+        kind: TaskKind::Download,
+        name: file_name,
+        color: "blue",
+    });
+    let data = connection
+        .execute_command(DownloadFile {
+            file_name: fixed_name,
+            size: metadata.size,
+            vendor: FileVendor::User,
+            target: FileTransferTarget::Qspi,
+            address: metadata.load_address,
+            progress_callback: Some(progress_callback(&progress)),
+        })
+        .await?;
+    progress.finish();
+
+    if !data.starts_with(&[0x1f, 0x8b]) {
+        return Ok((data, metadata.load_address));
+    }
+
+    let mut decompressed = Vec::new();
+    GzDecoder::new(&data[..]).read_to_end(&mut decompressed)?;
+    Ok((decompressed, metadata.load_address))
+}
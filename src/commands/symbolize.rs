@@ -0,0 +1,242 @@
+//! Symbolization of the backtraces vexide programs print when they panic.
+//!
+//! vexide only knows the raw addresses of a backtrace's frames, so it prints them like this:
+//!
+//! ```text
+//! stack backtrace:
+//!   0: 0x3800a2b4
+//!   1: 0x38001f08
+//! ```
+//!
+//! [`BacktraceAnnotator`] watches a program's output for those frames and follows each of them
+//! with the functions and source locations it resolves to in the program's ELF.
+
+use std::{
+    borrow::Cow,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use addr2line::Context;
+use gimli::{EndianArcSlice, RunTimeEndian};
+use object::{Object, ObjectSection, ObjectSymbol};
+
+use crate::errors::CliError;
+
+type Reader = EndianArcSlice<RunTimeEndian>;
+
+/// Lines longer than this can't be backtrace frames, so only this much of a line is buffered.
+const MAX_FRAME_LINE_LEN: usize = 64;
+
+/// A function a backtrace frame resolved to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SymbolizedFrame {
+    /// Demangled name of the function.
+    pub function: Option<String>,
+
+    /// Source location formatted as `file:line:column`, if debug info is available.
+    pub location: Option<String>,
+}
+
+/// Resolves program addresses to functions and source locations using an ELF's debug info.
+pub struct Symbolizer {
+    context: Context<Reader>,
+
+    /// Function symbols sorted by address, used when an address has no debug info.
+    symbols: Vec<(u64, u64, String)>,
+}
+
+impl Symbolizer {
+    /// Loads the debug info and symbol table of the ELF at `path`.
+    pub fn load(path: &Path) -> Result<Self, CliError> {
+        let data = std::fs::read(path)?;
+        let elf = object::File::parse(&*data)?;
+
+        let endian = if elf.is_little_endian() {
+            RunTimeEndian::Little
+        } else {
+            RunTimeEndian::Big
+        };
+        let dwarf = gimli::Dwarf::load(|id| {
+            let data = elf
+                .section_by_name(id.name())
+                .and_then(|section| section.uncompressed_data().ok())
+                .unwrap_or_default();
+
+            Ok::<_, gimli::Error>(EndianArcSlice::new(Arc::from(&*data), endian))
+        })
+        .and_then(Context::from_dwarf)
+        .map_err(|err| CliError::DebugInfoError(path.to_path_buf(), err))?;
+
+        let mut symbols = elf
+            .symbols()
+            .filter(|symbol| symbol.kind() == object::SymbolKind::Text)
+            .filter_map(|symbol| {
+                let name = symbol.name().ok()?;
+                // Thumb functions have their lowest address bit set.
+                Some((symbol.address() & !1, symbol.size(), name.to_string()))
+            })
+            .collect::<Vec<_>>();
+        symbols.sort_by_key(|(address, ..)| *address);
+
+        Ok(Self {
+            context: dwarf,
+            symbols,
+        })
+    }
+
+    /// Resolves `address` to the functions it's in, innermost (inlined) function first.
+    pub fn symbolize(&self, address: u64) -> Vec<SymbolizedFrame> {
+        let mut frames = Vec::new();
+
+        if let Ok(mut iter) = self.context.find_frames(address).skip_all_loads() {
+            while let Ok(Some(frame)) = iter.next() {
+                frames.push(SymbolizedFrame {
+                    function: frame
+                        .function
+                        .as_ref()
+                        .and_then(|function| function.demangle().ok())
+                        .map(Cow::into_owned),
+                    location: frame.location.and_then(|location| {
+                        let mut formatted = location.file?.to_string();
+                        if let Some(line) = location.line {
+                            formatted += &format!(":{line}");
+                            if let Some(column) = location.column.filter(|column| *column != 0) {
+                                formatted += &format!(":{column}");
+                            }
+                        }
+                        Some(formatted)
+                    }),
+                });
+            }
+        }
+
+        // Without debug info, the symbol table can still tell us which function we're in.
+        if frames.iter().all(|frame| frame.function.is_none())
+            && let Some(function) = self.symbol_at(address)
+        {
+            frames = vec![SymbolizedFrame {
+                function: Some(function),
+                location: frames.into_iter().find_map(|frame| frame.location),
+            }];
+        }
+
+        frames
+    }
+
+    fn symbol_at(&self, address: u64) -> Option<String> {
+        let index = self
+            .symbols
+            .partition_point(|(start, ..)| *start <= address)
+            .checked_sub(1)?;
+        let (start, size, name) = &self.symbols[index];
+
+        (*size == 0 || address < start + size)
+            .then(|| addr2line::demangle_auto(Cow::from(name.as_str()), None).into_owned())
+    }
+}
+
+/// Parses a backtrace frame line like `  3: 0x3800a2b4`, returning the frame's index and address.
+fn parse_frame(line: &str) -> Option<(usize, u64)> {
+    let (index, address) = line.trim().split_once(':')?;
+    let index = index.parse().ok()?;
+    let address = address.trim().strip_prefix("0x")?;
+
+    Some((index, u64::from_str_radix(address, 16).ok()?))
+}
+
+/// Adds symbol information to the backtraces in a stream of program output.
+pub struct BacktraceAnnotator {
+    symbolizer: Symbolizer,
+
+    /// The line currently being received.
+    line: Vec<u8>,
+
+    /// Whether the lines being received are part of a backtrace, which starts after a
+    /// `stack backtrace:` line and ends at the first line that isn't a frame.
+    in_backtrace: bool,
+}
+
+impl BacktraceAnnotator {
+    pub fn new(symbolizer: Symbolizer) -> Self {
+        Self {
+            symbolizer,
+            line: Vec::new(),
+            in_backtrace: false,
+        }
+    }
+
+    /// Loads the ELF at `path`, logging a warning and returning `None` if that fails so that
+    /// output is still shown without symbols.
+    pub fn for_elf(path: Option<PathBuf>) -> Option<Self> {
+        let path = path?;
+
+        match Symbolizer::load(&path) {
+            Ok(symbolizer) => Some(Self::new(symbolizer)),
+            Err(err) => {
+                log::warn!("Backtraces won't be symbolized: {err}");
+                None
+            }
+        }
+    }
+
+    /// Passes `data` through, inserting the symbols of each backtrace frame after its line. Only
+    /// frames following a `stack backtrace:` line are symbolized, so other output that looks like
+    /// a frame is left alone.
+    ///
+    /// Output is never held back, so frames are annotated once the newline ending them arrives.
+    pub fn annotate(&mut self, data: &[u8]) -> Vec<u8> {
+        let mut output = Vec::with_capacity(data.len());
+
+        for &byte in data {
+            output.push(byte);
+
+            if byte != b'\n' {
+                if self.line.len() <= MAX_FRAME_LINE_LEN {
+                    self.line.push(byte);
+                }
+                continue;
+            }
+
+            let line = String::from_utf8_lossy(&self.line).into_owned();
+            self.line.clear();
+
+            if line.trim_end().ends_with("stack backtrace:") {
+                self.in_backtrace = true;
+                continue;
+            }
+
+            let Some((index, address)) = (self.in_backtrace && line.len() <= MAX_FRAME_LINE_LEN)
+                .then(|| parse_frame(&line))
+                .flatten()
+            else {
+                self.in_backtrace = false;
+                continue;
+            };
+
+            // Frames other than the first hold return addresses, which point to the instruction
+            // after the call.
+            let probe = if index == 0 {
+                address
+            } else {
+                address.saturating_sub(1)
+            };
+            for frame in self.symbolizer.symbolize(probe) {
+                output.extend_from_slice(
+                    format!(
+                        "        \x1b[1m{}\x1b[0m\n",
+                        frame.function.as_deref().unwrap_or("<unknown>")
+                    )
+                    .as_bytes(),
+                );
+                if let Some(location) = frame.location {
+                    output.extend_from_slice(
+                        format!("            \x1b[2mat {location}\x1b[0m\n").as_bytes(),
+                    );
+                }
+            }
+        }
+
+        output
+    }
+}
@@ -0,0 +1,115 @@
+//! Team number, robot name, and owner stored in a Brain's system configuration.
+
+use std::io::{self, Write};
+
+use tabwriter::TabWriter;
+
+use crate::{connection::DeviceConnection, errors::CliError};
+
+use super::key_value::{kv_get, kv_set};
+
+/// Key/value key holding the team number.
+const TEAM_NUMBER_KEY: &str = "teamnumber";
+
+/// Key/value key holding the robot's name.
+const ROBOT_NAME_KEY: &str = "robotname";
+
+/// Key/value key holding the name of the robot's owner.
+const OWNER_KEY: &str = "owner";
+
+/// Every key/value key holding team information.
+pub const TEAM_KEYS: &[&str] = &[TEAM_NUMBER_KEY, ROBOT_NAME_KEY, OWNER_KEY];
+
+/// Identifying information stored on a Brain. Fields are `None` if they aren't set.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TeamInfo {
+    pub team: Option<String>,
+    pub robot: Option<String>,
+    pub owner: Option<String>,
+}
+
+/// Parses a team number like `1234A` (VRC and VIQRC) or `BLRS` (VEX U), normalizing it to
+/// uppercase.
+pub fn parse_team_number(team: &str) -> Result<String, String> {
+    let team = team.trim().to_ascii_uppercase();
+
+    let digits = team.bytes().take_while(u8::is_ascii_digit).count();
+    let letters = team.len() - digits;
+    let valid = if digits > 0 {
+        // Competition teams: up to five digits followed by an optional letter.
+        digits <= 5 && letters <= 1 && team.bytes().skip(digits).all(|b| b.is_ascii_uppercase())
+    } else {
+        // VEX U teams: two to five letters followed by an optional digit.
+        let letters = team.bytes().take_while(u8::is_ascii_uppercase).count();
+        (2..=5).contains(&letters)
+            && team.len() - letters <= 1
+            && team.bytes().skip(letters).all(|b| b.is_ascii_digit())
+    };
+
+    if valid {
+        Ok(team)
+    } else {
+        Err(format!(
+            "`{team}` is not a valid team number (expected something like `1234A` or `BLRS`)"
+        ))
+    }
+}
+
+/// Parses a robot or owner name, which must be non-empty and can't contain control characters.
+pub fn parse_display_name(name: &str) -> Result<String, String> {
+    let name = name.trim();
+
+    if name.is_empty() {
+        Err("names can't be empty".to_string())
+    } else if name.chars().any(char::is_control) {
+        Err("names can't contain control characters".to_string())
+    } else {
+        Ok(name.to_string())
+    }
+}
+
+/// Reads the team information stored on a Brain.
+pub async fn team_info(connection: &mut DeviceConnection) -> Result<TeamInfo, CliError> {
+    Ok(TeamInfo {
+        team: kv_get(connection, TEAM_NUMBER_KEY).await?,
+        robot: kv_get(connection, ROBOT_NAME_KEY).await?,
+        owner: kv_get(connection, OWNER_KEY).await?,
+    })
+}
+
+/// Stores the given fields on a Brain, leaving the fields that are `None` unchanged.
+pub async fn set_team_info(
+    connection: &mut DeviceConnection,
+    info: &TeamInfo,
+) -> Result<(), CliError> {
+    for (key, value) in [
+        (TEAM_NUMBER_KEY, &info.team),
+        (ROBOT_NAME_KEY, &info.robot),
+        (OWNER_KEY, &info.owner),
+    ] {
+        if let Some(value) = value {
+            kv_set(connection, key, value).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Prints team information as a table.
+pub fn print_team_info(info: &TeamInfo) -> Result<(), CliError> {
+    let mut tw = TabWriter::new(io::stdout());
+
+    for (label, value) in [
+        ("Team", &info.team),
+        ("Robot", &info.robot),
+        ("Owner", &info.owner),
+    ] {
+        match value {
+            Some(value) => writeln!(tw, "\x1b[1m{label}\x1b[0m\t{value}")?,
+            None => writeln!(tw, "\x1b[1m{label}\x1b[0m\t\x1b[2mnot set\x1b[0m")?,
+        }
+    }
+
+    tw.flush()?;
+    Ok(())
+}
@@ -0,0 +1,162 @@
+//! Capturing structured program output to a CSV file.
+//!
+//! Lines of program output that are a JSON object, like `{"kp": 1.2, "error": 0.4}`, or that
+//! consist only of `key=value` pairs, like `kp=1.2 error=0.4`, are written to the file as rows,
+//! with a column for each key and the time each line was received. Other output is ignored.
+//!
+//! Columns are added as new keys appear, rewriting the file so that every row has a value (or an
+//! empty cell) for every column.
+
+use std::{
+    collections::BTreeMap,
+    fs::{File, OpenOptions},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use chrono::Utc;
+use serde_json::Value;
+
+use crate::errors::CliError;
+
+/// Name of the column holding when each row was received.
+const TIME_COLUMN: &str = "host_time";
+
+/// Parses a structured line of output into its keys and values, returning `None` if the line
+/// isn't structured.
+pub fn parse_structured_line(line: &str) -> Option<BTreeMap<String, String>> {
+    let line = line.trim();
+
+    if line.starts_with('{') {
+        let object = serde_json::from_str::<Value>(line).ok()?;
+        return Some(
+            object
+                .as_object()?
+                .iter()
+                .map(|(key, value)| {
+                    let value = match value {
+                        Value::String(string) => string.clone(),
+                        value => value.to_string(),
+                    };
+                    (key.clone(), value)
+                })
+                .collect(),
+        );
+    }
+
+    let fields = line
+        .split_whitespace()
+        .map(|field| {
+            let (key, value) = field.split_once('=')?;
+            (!key.is_empty()).then(|| (key.to_string(), value.to_string()))
+        })
+        .collect::<Option<BTreeMap<_, _>>>()?;
+
+    (!fields.is_empty()).then_some(fields)
+}
+
+/// Writes structured lines of program output to a CSV file as they arrive.
+#[derive(Debug)]
+pub struct TelemetryCapture {
+    path: PathBuf,
+    columns: Vec<String>,
+    rows: Vec<BTreeMap<String, String>>,
+    partial_line: Vec<u8>,
+}
+
+impl TelemetryCapture {
+    /// Creates the capture file at `path`.
+    pub fn create(path: &Path) -> Result<Self, CliError> {
+        if path.extension().is_some_and(|extension| extension != "csv") {
+            return Err(CliError::UnsupportedCaptureFormat(path.to_path_buf()));
+        }
+
+        let capture = Self {
+            path: path.to_path_buf(),
+            columns: vec![TIME_COLUMN.to_string()],
+            rows: Vec::new(),
+            partial_line: Vec::new(),
+        };
+        capture.rewrite()?;
+
+        Ok(capture)
+    }
+
+    /// Records the structured lines in a chunk of program output.
+    pub fn feed(&mut self, output: &[u8]) -> io::Result<()> {
+        self.partial_line.extend_from_slice(output);
+
+        while let Some(newline) = self.partial_line.iter().position(|&b| b == b'\n') {
+            let line = String::from_utf8_lossy(&self.partial_line[..newline]).to_string();
+            self.partial_line.drain(..=newline);
+
+            if let Some(mut row) = parse_structured_line(&line) {
+                row.insert(
+                    TIME_COLUMN.to_string(),
+                    Utc::now().format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string(),
+                );
+                self.record(row)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn record(&mut self, row: BTreeMap<String, String>) -> io::Result<()> {
+        let mut new_columns = row
+            .keys()
+            .filter(|key| !self.columns.contains(key))
+            .cloned()
+            .peekable();
+        let has_new_columns = new_columns.peek().is_some();
+        self.columns.extend(new_columns.collect::<Vec<_>>());
+        self.rows.push(row);
+
+        if has_new_columns {
+            return self.rewrite();
+        }
+
+        let mut file = OpenOptions::new().append(true).open(&self.path)?;
+        self.write_row(&mut file, self.rows.last().unwrap())
+    }
+
+    /// Writes the whole file again, after the columns change.
+    fn rewrite(&self) -> io::Result<()> {
+        let mut file = BufWriter::new(File::create(&self.path)?);
+
+        let header = self
+            .columns
+            .iter()
+            .map(|column| escape(column))
+            .collect::<Vec<_>>();
+        writeln!(file, "{}", header.join(","))?;
+
+        for row in &self.rows {
+            self.write_row(&mut file, row)?;
+        }
+
+        file.flush()
+    }
+
+    fn write_row(&self, out: &mut impl Write, row: &BTreeMap<String, String>) -> io::Result<()> {
+        let cells = self
+            .columns
+            .iter()
+            .map(|column| {
+                row.get(column)
+                    .map(|value| escape(value))
+                    .unwrap_or_default()
+            })
+            .collect::<Vec<_>>();
+        writeln!(out, "{}", cells.join(","))
+    }
+}
+
+/// Quotes a CSV cell if it contains characters that would break the row.
+fn escape(cell: &str) -> String {
+    if cell.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", cell.replace('"', "\"\""))
+    } else {
+        cell.to_string()
+    }
+}
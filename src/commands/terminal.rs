@@ -1,15 +1,47 @@
-use std::time::Duration;
+use std::{
+    borrow::Cow,
+    io,
+    net::{Ipv4Addr, SocketAddr},
+    path::PathBuf,
+    time::Duration,
+};
 
 use flexi_logger::{LogSpecification, LoggerHandle};
 use log::info;
 use tokio::{
     io::{AsyncReadExt, AsyncWriteExt, stdin, stdout},
+    net::{TcpListener, TcpStream},
     select,
-    time::sleep,
+    sync::{broadcast, mpsc},
+    time::{sleep, timeout},
+};
+use vex_v5_serial::Connection;
+
+use crate::{
+    config::{runtime_dir, write_private_file},
+    connection::DeviceConnection,
+    remote::{generate_token, token_matches},
 };
-use vex_v5_serial::{Connection, serial::SerialConnection};
 
-pub async fn terminal(connection: &mut SerialConnection, logger: &mut LoggerHandle) -> ! {
+use super::{symbolize::BacktraceAnnotator, telemetry::TelemetryCapture};
+
+/// File in [`runtime_dir`] holding the address of the running [`TerminalHub`] and the token its
+/// clients must send, one per line.
+const TERMINAL_HUB_FILE: &str = "terminal-hub";
+
+/// How long a terminal has to send the hub's token after connecting.
+const AUTH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Shows a program's output and forwards input to it, forever.
+///
+/// If `backtraces` is given, panic backtraces in the output are symbolized. If `capture` is
+/// given, structured lines of output are also recorded to it.
+pub async fn terminal(
+    connection: &mut DeviceConnection,
+    logger: &mut LoggerHandle,
+    mut backtraces: Option<BacktraceAnnotator>,
+    mut capture: Option<TelemetryCapture>,
+) -> ! {
     info!("Started terminal.");
 
     logger.push_temp_spec(LogSpecification::off());
@@ -22,7 +54,9 @@ pub async fn terminal(connection: &mut SerialConnection, logger: &mut LoggerHand
         select! {
             read = connection.read_user(&mut program_output) => {
                 if let Ok(size) = read {
-                    stdout().write_all(&program_output[..size]).await.unwrap();
+                    record(&mut capture, &program_output[..size]);
+                    let output = annotate(&mut backtraces, &program_output[..size]);
+                    stdout().write_all(&output).await.unwrap();
                 }
             },
             read = stdin.read(&mut program_input) => {
@@ -35,3 +69,229 @@ pub async fn terminal(connection: &mut SerialConnection, logger: &mut LoggerHand
         sleep(Duration::from_millis(10)).await;
     }
 }
+
+/// Symbolizes the backtraces in `output` if a [`BacktraceAnnotator`] is given.
+fn annotate<'a>(backtraces: &mut Option<BacktraceAnnotator>, output: &'a [u8]) -> Cow<'a, [u8]> {
+    match backtraces {
+        Some(backtraces) => Cow::Owned(backtraces.annotate(output)),
+        None => Cow::Borrowed(output),
+    }
+}
+
+/// Records structured lines of output if a [`TelemetryCapture`] is given.
+fn record(capture: &mut Option<TelemetryCapture>, output: &[u8]) {
+    if let Some(capture) = capture
+        && let Err(err) = capture.feed(output)
+    {
+        log::warn!("Failed to capture program output: {err}");
+    }
+}
+
+/// Shares a program's terminal I/O with other `cargo v5 terminal` processes.
+///
+/// Only one process can poll a device's stdio channel at a time, and output read by one is lost
+/// to the other. A process that owns the connection (such as the field control TUI) can host a hub
+/// so that a standalone terminal attaches to it with [`attach_terminal`] rather than opening the
+/// device itself.
+///
+/// The hub listens on a random local port. Its address and a token that terminals must send
+/// before anything else are kept in a file in [`runtime_dir`] that only the current user can read,
+/// so other users on the machine can't attach.
+pub struct TerminalHub {
+    output: broadcast::Sender<Vec<u8>>,
+    input: mpsc::UnboundedReceiver<Vec<u8>>,
+    /// The file advertising this hub to terminals, if it's shared that way.
+    advertisement: Option<PathBuf>,
+}
+
+impl TerminalHub {
+    /// Starts accepting terminal clients, or returns `None` if another hub is already running.
+    pub async fn bind() -> Option<Self> {
+        let Some(path) = runtime_dir().map(|dir| dir.join(TERMINAL_HUB_FILE)) else {
+            log::debug!("Not sharing terminal: there's no runtime directory");
+            return None;
+        };
+
+        if attach_terminal().await.is_some() {
+            log::debug!("Not sharing terminal: another process already is");
+            return None;
+        }
+
+        let token = generate_token();
+        let hub = async {
+            let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await?;
+            write_private_file(&path, &format!("{}\n{token}\n", listener.local_addr()?))?;
+            io::Result::Ok(Self::serve(listener, Some(token)))
+        };
+
+        match hub.await {
+            Ok(mut hub) => {
+                hub.advertisement = Some(path);
+                Some(hub)
+            }
+            Err(err) => {
+                log::debug!("Not sharing terminal: {err}");
+                None
+            }
+        }
+    }
+
+    /// Starts accepting clients on `addr`, which receive the raw program output and can send it
+    /// input after sending `token` followed by a newline, if one is given.
+    pub async fn listen(addr: SocketAddr, token: Option<String>) -> io::Result<Self> {
+        Ok(Self::serve(TcpListener::bind(addr).await?, token))
+    }
+
+    /// Accepts clients from `listener`, which must first send `token` followed by a newline if one
+    /// is given.
+    fn serve(listener: TcpListener, token: Option<String>) -> Self {
+        let (output, _) = broadcast::channel::<Vec<u8>>(256);
+        let (input_tx, input) = mpsc::unbounded_channel();
+
+        tokio::spawn({
+            let output = output.clone();
+            async move {
+                while let Ok((mut client, peer)) = listener.accept().await {
+                    let mut output = output.subscribe();
+                    let input = input_tx.clone();
+                    let token = token.clone();
+
+                    tokio::spawn(async move {
+                        if let Some(token) = &token
+                            && !authenticate(&mut client, token).await
+                        {
+                            log::warn!("A terminal from {peer} sent the wrong token");
+                            return;
+                        }
+
+                        let (mut reader, mut writer) = client.into_split();
+                        tokio::spawn(async move {
+                            let mut buf = [0; 1024];
+                            while let Ok(size @ 1..) = reader.read(&mut buf).await {
+                                if input.send(buf[..size].to_vec()).is_err() {
+                                    break;
+                                }
+                            }
+                        });
+
+                        loop {
+                            let data = match output.recv().await {
+                                Ok(data) => data,
+                                // A slow terminal misses some output, but stays attached.
+                                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                                Err(broadcast::error::RecvError::Closed) => break,
+                            };
+
+                            if writer.write_all(&data).await.is_err() {
+                                break;
+                            }
+                        }
+                    });
+                }
+            }
+        });
+
+        Self {
+            output,
+            input,
+            advertisement: None,
+        }
+    }
+
+    /// Sends program output to every attached terminal.
+    pub fn publish(&self, data: &[u8]) {
+        // Sending only fails if no terminals are attached.
+        _ = self.output.send(data.to_vec());
+    }
+
+    /// Returns input typed into an attached terminal, if there is any.
+    pub fn try_recv_input(&mut self) -> Option<Vec<u8>> {
+        self.input.try_recv().ok()
+    }
+}
+
+impl Drop for TerminalHub {
+    fn drop(&mut self) {
+        if let Some(path) = &self.advertisement {
+            _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+/// Reads the token a terminal sends before anything else, and checks it.
+async fn authenticate(client: &mut TcpStream, token: &str) -> bool {
+    let read_line = async {
+        let mut line = Vec::new();
+        loop {
+            match client.read_u8().await? {
+                b'\n' => return io::Result::Ok(line),
+                // Anything longer than the token is wrong anyway.
+                _ if line.len() > token.len() => return Ok(line),
+                byte => line.push(byte),
+            }
+        }
+    };
+
+    match timeout(AUTH_TIMEOUT, read_line).await {
+        Ok(Ok(line)) => token_matches(token, &String::from_utf8_lossy(&line)),
+        _ => false,
+    }
+}
+
+/// Connects to a running [`TerminalHub`], returning `None` if there isn't one.
+pub async fn attach_terminal() -> Option<TcpStream> {
+    let hub = tokio::fs::read_to_string(runtime_dir()?.join(TERMINAL_HUB_FILE))
+        .await
+        .ok()?;
+    let (addr, token) = hub.split_once('\n')?;
+
+    let mut stream = TcpStream::connect(addr.parse::<SocketAddr>().ok()?)
+        .await
+        .ok()?;
+    stream
+        .write_all(format!("{}\n", token.trim_end()).as_bytes())
+        .await
+        .ok()?;
+    Some(stream)
+}
+
+/// Runs a terminal over a connection to a [`TerminalHub`] until the hub closes.
+pub async fn shared_terminal(
+    hub: TcpStream,
+    logger: &mut LoggerHandle,
+    mut backtraces: Option<BacktraceAnnotator>,
+    mut capture: Option<TelemetryCapture>,
+) {
+    info!("Attached to shared terminal.");
+
+    logger.push_temp_spec(LogSpecification::off());
+
+    let (mut reader, mut writer) = hub.into_split();
+    let mut stdin = stdin();
+    let mut program_output = [0; 2048];
+    let mut program_input = [0; 4096];
+
+    loop {
+        select! {
+            read = reader.read(&mut program_output) => {
+                match read {
+                    Ok(0) | Err(_) => break,
+                    Ok(size) => {
+                        record(&mut capture, &program_output[..size]);
+                        let output = annotate(&mut backtraces, &program_output[..size]);
+                        stdout().write_all(&output).await.unwrap();
+                    }
+                }
+            },
+            read = stdin.read(&mut program_input) => {
+                if let Ok(size) = read
+                    && writer.write_all(&program_input[..size]).await.is_err()
+                {
+                    break;
+                }
+            }
+        }
+    }
+
+    logger.pop_temp_spec();
+}
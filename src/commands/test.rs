@@ -0,0 +1,287 @@
+//! On-device tests, for running a project's test suite on a real Brain from a CI rig.
+//!
+//! Each test harness is built for the Brain, uploaded to a scratch slot, and run while its output
+//! is echoed to stdout. Results are read from the libtest-style lines the harness prints
+//! (`test name ... ok`, then a final `test result:` summary).
+
+use std::{io::Write, path::Path, time::Duration};
+
+use clap::Args;
+use tokio::time::{Instant, timeout};
+use vex_v5_serial::{Connection, protocol::cdc2::file::FileLoadAction};
+
+use crate::{
+    connection::{ConnectionOpts, DeviceConnection, open_connection, switch_to_download_channel},
+    errors::CliError,
+    reporter::{Reporter, TerminalReporter},
+};
+
+use super::{
+    build::{BuildOutput, CargoOpts, build_tests},
+    patch_bases::PatchBases,
+    program::{current_program, load_action},
+    transfer::default_window_size,
+    upload::{
+        AfterUpload, DEFAULT_PROGRAM_TYPE, DEFAULT_PROGRAM_VERSION, ProgramIcon, UploadStrategy,
+        upload_program,
+    },
+};
+
+/// Slot test harnesses are uploaded to unless another is chosen.
+pub const DEFAULT_TEST_SLOT: u8 = 8;
+
+/// How long a harness may run before it's stopped, unless another limit is chosen.
+pub const DEFAULT_TEST_TIMEOUT_SECS: u64 = 60;
+
+/// How long to wait for output before checking whether the harness is still running.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How long a harness has to start running after it's uploaded.
+const START_GRACE_PERIOD: Duration = Duration::from_secs(3);
+
+/// Options for `cargo v5 test`.
+#[derive(Args, Debug)]
+pub struct TestOpts {
+    /// Scratch slot to upload test harnesses to. The program installed there is overwritten.
+    #[arg(long, default_value_t = DEFAULT_TEST_SLOT)]
+    pub slot: u8,
+
+    /// Seconds each test harness may run before it's stopped and counted as failed.
+    #[arg(long, value_name = "SECONDS", default_value_t = DEFAULT_TEST_TIMEOUT_SECS)]
+    pub timeout: u64,
+
+    /// Arguments forwarded to `cargo`.
+    #[clap(flatten)]
+    pub cargo_opts: CargoOpts,
+}
+
+/// Test results read from a harness's output.
+#[derive(Debug, Default)]
+struct HarnessResults {
+    passed: usize,
+    failed: Vec<String>,
+    ignored: usize,
+
+    /// Whether the harness printed its final `test result:` line.
+    finished: bool,
+}
+
+impl HarnessResults {
+    /// Updates the results from a line of harness output.
+    fn parse_line(&mut self, line: &str) {
+        let line = line.trim();
+
+        if let Some(summary) = line.strip_prefix("test result:") {
+            self.finished = true;
+
+            // Trust the harness's own counts over the lines we happened to see.
+            for count in summary.split([';', '.']) {
+                match count.trim().split_once(' ') {
+                    Some((n, "passed")) => self.passed = n.parse().unwrap_or(self.passed),
+                    Some((n, "ignored")) => self.ignored = n.parse().unwrap_or(self.ignored),
+                    _ => {}
+                }
+            }
+            return;
+        }
+
+        let Some((name, outcome)) = line
+            .strip_prefix("test ")
+            .and_then(|test| test.rsplit_once(" ... "))
+        else {
+            return;
+        };
+
+        match outcome.trim() {
+            "ok" => self.passed += 1,
+            "FAILED" => self.failed.push(name.to_string()),
+            outcome if outcome.starts_with("ignored") => self.ignored += 1,
+            _ => {}
+        }
+    }
+}
+
+/// How a harness run ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum HarnessEnd {
+    /// The harness reported its results.
+    Finished,
+
+    /// The harness stopped running without reporting results, for example because it crashed.
+    Exited,
+
+    /// The harness was still running when the timeout ran out.
+    TimedOut,
+}
+
+/// Builds the project's tests, runs each test harness on the Brain, and fails if any test fails.
+pub async fn test(
+    path: &Path,
+    opts: TestOpts,
+    connection_opts: &ConnectionOpts,
+) -> miette::Result<()> {
+    if !(1..=8).contains(&opts.slot) {
+        Err(CliError::SlotOutOfRange)?;
+    }
+
+    let reporter = TerminalReporter::new();
+
+    // Connect in the background while the tests build.
+    let (mut connection, harnesses) = tokio::try_join!(
+        async {
+            let mut connection = open_connection(connection_opts).await?;
+            switch_to_download_channel(&mut connection).await?;
+            Ok::<DeviceConnection, CliError>(connection)
+        },
+        build_tests(path, opts.cargo_opts, &reporter),
+    )?;
+
+    if harnesses.is_empty() {
+        reporter.warning("No test harnesses were built, so no tests ran.");
+        return Ok(());
+    }
+
+    let mut passed = 0;
+    let mut ignored = 0;
+    let mut failures = Vec::new();
+
+    for harness in harnesses {
+        let (results, end) = run_harness(
+            &mut connection,
+            &harness,
+            opts.slot,
+            Duration::from_secs(opts.timeout),
+            &reporter,
+        )
+        .await?;
+
+        passed += results.passed;
+        ignored += results.ignored;
+        failures.extend(
+            results
+                .failed
+                .into_iter()
+                .map(|test| format!("{}: {test}", harness.target)),
+        );
+
+        match end {
+            HarnessEnd::Finished => {}
+            HarnessEnd::Exited => {
+                reporter.warning(&format!(
+                    "`{}` stopped without reporting its results",
+                    harness.target
+                ));
+                failures.push(format!("{} (exited early)", harness.target));
+            }
+            HarnessEnd::TimedOut => {
+                reporter.warning(&format!(
+                    "`{}` didn't finish within {} seconds",
+                    harness.target, opts.timeout
+                ));
+                failures.push(format!("{} (timed out)", harness.target));
+            }
+        }
+    }
+
+    reporter.status(
+        "Finished",
+        &format!(
+            "{passed} passed; {} failed; {ignored} ignored",
+            failures.len()
+        ),
+    );
+
+    if !failures.is_empty() {
+        eprintln!("\nfailures:");
+        for failure in &failures {
+            eprintln!("    {failure}");
+        }
+        eprintln!();
+
+        Err(CliError::TestsFailed(failures.len()))?;
+    }
+
+    Ok(())
+}
+
+/// Uploads and runs a single test harness, echoing its output until it finishes.
+async fn run_harness(
+    connection: &mut DeviceConnection,
+    harness: &BuildOutput,
+    slot: u8,
+    time_limit: Duration,
+    reporter: &dyn Reporter,
+) -> Result<(HarnessResults, HarnessEnd), CliError> {
+    let binary = tokio::fs::read(&harness.bin_artifact).await?;
+    let window_size = default_window_size(connection);
+
+    upload_program(
+        connection,
+        binary,
+        &PatchBases::for_artifact(None, &harness.bin_artifact),
+        None,
+        AfterUpload::Run,
+        slot,
+        format!("test {}", harness.target),
+        "Test harness uploaded by cargo v5 test.".to_string(),
+        ProgramIcon::default(),
+        DEFAULT_PROGRAM_TYPE.to_string(),
+        true,
+        false,
+        UploadStrategy::Monolith,
+        window_size,
+        false,
+        false,
+        DEFAULT_PROGRAM_VERSION,
+        reporter,
+    )
+    .await?;
+
+    let started = Instant::now();
+    let mut results = HarnessResults::default();
+    let mut line = Vec::new();
+    let mut output = [0; 2048];
+    let mut seen_running = false;
+
+    let end = loop {
+        if started.elapsed() >= time_limit {
+            break HarnessEnd::TimedOut;
+        }
+
+        match timeout(POLL_INTERVAL, connection.read_user(&mut output)).await {
+            Ok(Ok(size @ 1..)) => {
+                let output = &output[..size];
+                let mut stdout = std::io::stdout().lock();
+                stdout.write_all(output)?;
+                stdout.flush()?;
+
+                for &byte in output {
+                    if byte == b'\n' {
+                        results.parse_line(&String::from_utf8_lossy(&line));
+                        line.clear();
+                    } else {
+                        line.push(byte);
+                    }
+                }
+
+                if results.finished {
+                    break HarnessEnd::Finished;
+                }
+            }
+            _ => match current_program(connection).await? {
+                Some(_) => seen_running = true,
+                None if seen_running || started.elapsed() > START_GRACE_PERIOD => {
+                    break HarnessEnd::Exited;
+                }
+                None => {}
+            },
+        }
+    };
+
+    // Don't leave a finished or hung harness running.
+    if current_program(connection).await?.is_some() {
+        load_action(connection, FileLoadAction::Stop, "").await?;
+    }
+
+    Ok((results, end))
+}
@@ -0,0 +1,200 @@
+//! Live resource usage monitor.
+//!
+//! VEXos doesn't report user program memory usage or CPU load over the serial protocol, so these
+//! statistics are provided by the program itself. A cooperating program periodically prints a
+//! line of the form:
+//!
+//! ```text
+//! @v5top heap=<used bytes>/<total bytes> cpu=<percent>
+//! ```
+//!
+//! to its standard output. Either field may be omitted, and unknown fields are ignored. All other
+//! output is shown below the statistics.
+
+use std::{
+    collections::VecDeque,
+    io::{self, Write},
+    time::Duration,
+};
+
+use humansize::{BINARY, format_size};
+use tabwriter::TabWriter;
+use tokio::{
+    select,
+    time::{Instant, interval, sleep},
+};
+use vex_v5_serial::Connection;
+
+use crate::{connection::DeviceConnection, errors::CliError};
+
+use super::program::current_program;
+
+/// Prefix of lines containing statistics reported by the program.
+pub const STATS_PREFIX: &str = "@v5top";
+
+/// How often the display is redrawn and the Brain is polled.
+const REFRESH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Number of lines of regular program output kept on screen.
+const OUTPUT_LINES: usize = 10;
+
+/// Resource usage most recently reported by the program.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct ProgramStats {
+    pub heap_used: Option<u64>,
+    pub heap_total: Option<u64>,
+    pub cpu: Option<f32>,
+}
+
+impl ProgramStats {
+    /// Parses a statistics line, returning `None` if the line isn't one.
+    pub fn parse(line: &str) -> Option<Self> {
+        let fields = line.trim().strip_prefix(STATS_PREFIX)?;
+        let mut stats = Self::default();
+
+        for field in fields.split_whitespace() {
+            match field.split_once('=') {
+                Some(("heap", value)) => {
+                    let (used, total) = value.split_once('/').unwrap_or((value, ""));
+                    stats.heap_used = used.parse().ok();
+                    stats.heap_total = total.parse().ok();
+                }
+                Some(("cpu", value)) => {
+                    stats.cpu = value.trim_end_matches('%').parse().ok();
+                }
+                _ => {}
+            }
+        }
+
+        Some(stats)
+    }
+}
+
+/// Continuously displays the running program's resource usage until interrupted.
+pub async fn top(connection: &mut DeviceConnection) -> Result<(), CliError> {
+    let mut stats = None::<ProgramStats>;
+    let mut peak_heap = 0;
+    let mut last_report = None::<Instant>;
+    let mut running = None;
+
+    let mut output = VecDeque::with_capacity(OUTPUT_LINES);
+    let mut partial_line = Vec::new();
+    let mut buf = [0; 2048];
+
+    let mut refresh = interval(REFRESH_INTERVAL);
+
+    loop {
+        select! {
+            read = connection.read_user(&mut buf) => {
+                // Reads fail or come back empty while no program is running.
+                let Ok(size @ 1..) = read else {
+                    sleep(Duration::from_millis(10)).await;
+                    continue;
+                };
+
+                partial_line.extend_from_slice(&buf[..size]);
+
+                while let Some(newline) = partial_line.iter().position(|&b| b == b'\n') {
+                    let line = String::from_utf8_lossy(&partial_line[..newline])
+                        .trim_end()
+                        .to_string();
+                    partial_line.drain(..=newline);
+
+                    if let Some(new_stats) = ProgramStats::parse(&line) {
+                        peak_heap = peak_heap.max(new_stats.heap_used.unwrap_or_default());
+                        stats = Some(new_stats);
+                        last_report = Some(Instant::now());
+                    } else {
+                        if output.len() == OUTPUT_LINES {
+                            output.pop_front();
+                        }
+                        output.push_back(line);
+                    }
+                }
+            },
+            _ = refresh.tick() => {
+                match current_program(connection).await {
+                    Ok(program) => running = program,
+                    Err(err) => log::debug!("Failed to poll program status: {err}"),
+                }
+
+                draw(running, stats, peak_heap, last_report, &output)?;
+            }
+        }
+    }
+}
+
+fn draw(
+    running: Option<u8>,
+    stats: Option<ProgramStats>,
+    peak_heap: u64,
+    last_report: Option<Instant>,
+    output: &VecDeque<String>,
+) -> io::Result<()> {
+    let mut tw = TabWriter::new(Vec::new());
+
+    writeln!(
+        tw,
+        "\x1b[1mProgram\x1b[0m\t{}",
+        match running {
+            Some(slot) => format!("slot {slot}"),
+            None => "not running".to_string(),
+        }
+    )?;
+
+    match stats {
+        Some(stats) => {
+            let heap = match (stats.heap_used, stats.heap_total) {
+                (Some(used), Some(total)) if total > 0 => format!(
+                    "{} / {} ({:.1}%)",
+                    format_size(used, BINARY),
+                    format_size(total, BINARY),
+                    used as f64 / total as f64 * 100.0
+                ),
+                (Some(used), _) => format_size(used, BINARY),
+                _ => "-".to_string(),
+            };
+
+            writeln!(tw, "\x1b[1mHeap\x1b[0m\t{heap}")?;
+            writeln!(
+                tw,
+                "\x1b[1mPeak Heap\x1b[0m\t{}",
+                format_size(peak_heap, BINARY)
+            )?;
+            writeln!(
+                tw,
+                "\x1b[1mCPU\x1b[0m\t{}",
+                stats
+                    .cpu
+                    .map(|cpu| format!("{cpu:.1}%"))
+                    .unwrap_or_else(|| "-".to_string())
+            )?;
+            writeln!(
+                tw,
+                "\x1b[1mLast Report\x1b[0m\t{:.1?} ago",
+                last_report.map(|time| time.elapsed()).unwrap_or_default()
+            )?;
+        }
+        None => {
+            writeln!(
+                tw,
+                "\x1b[1mHeap\x1b[0m\twaiting for `{STATS_PREFIX} heap=<used>/<total> cpu=<percent>` from the program"
+            )?;
+        }
+    }
+
+    let mut stdout = io::stdout().lock();
+
+    // Clear the screen and move the cursor home before redrawing.
+    write!(stdout, "\x1b[2J\x1b[H")?;
+    stdout.write_all(&tw.into_inner().unwrap())?;
+
+    if !output.is_empty() {
+        writeln!(stdout)?;
+        for line in output {
+            writeln!(stdout, "{line}")?;
+        }
+    }
+
+    stdout.flush()
+}
@@ -0,0 +1,248 @@
+//! Pipelined file uploads.
+//!
+//! [`UploadFile`] waits for the Brain to acknowledge each chunk before sending the next one.
+//! That's fine over USB, but over a controller's radio every round trip is slow, so most of an
+//! upload is spent waiting. [`upload_file`] instead keeps a window of several write packets in
+//! flight, resending from the first unacknowledged chunk if one is rejected or times out.
+//!
+//! Since a windowed upload owns the connection for its whole duration, it also polls the Brain's
+//! stdio FIFO every so often so that output from the running program isn't lost during the
+//! transfer.
+
+use std::time::{Duration, Instant};
+
+use vex_v5_serial::{
+    Connection, ConnectionType,
+    commands::file::UploadFile,
+    protocol::{
+        VEX_CRC32,
+        cdc2::{
+            controller::{UserDataPacket, UserDataPayload, UserDataReplyPacket},
+            file::{
+                FileDataWritePacket, FileDataWritePayload, FileDataWriteReplyPacket,
+                FileInitOption, FileLinkPacket, FileLinkPayload, FileLinkReplyPacket,
+                FileTransferExitPacket, FileTransferExitReplyPacket, FileTransferInitializePacket,
+                FileTransferInitializePayload, FileTransferInitializeReplyPacket,
+                FileTransferOperation,
+            },
+        },
+    },
+};
+
+use crate::connection::{ConnectionError, DeviceConnection};
+
+/// Number of write packets kept in flight over a controller's radio by default.
+pub const DEFAULT_WIRELESS_WINDOW: usize = 4;
+
+/// Largest chunk the Brain accepts in a single write packet.
+const MAX_CHUNK_SIZE: usize = 4096;
+
+/// How long to wait for a chunk to be acknowledged before resending it.
+const WRITE_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// How many times a chunk may be resent before giving up.
+const MAX_RETRIES: usize = 5;
+
+/// How often the running program's output is read during an upload.
+const OUTPUT_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Returns the window size to upload with if the user didn't provide one.
+pub fn default_window_size(connection: &DeviceConnection) -> usize {
+    match connection.connection_type() {
+        ConnectionType::Controller => DEFAULT_WIRELESS_WINDOW,
+        _ => 1,
+    }
+}
+
+/// Uploads a file, keeping up to `window_size` write packets in flight at once.
+///
+/// The packets in flight never add up to more than the Brain says it can buffer, so larger
+/// windows are sent as smaller chunks.
+///
+/// A window size of 1 behaves exactly like executing [`UploadFile`] directly. Bluetooth
+/// connections always use [`UploadFile`], since it already doesn't wait for replies there.
+///
+/// When `program_output` is given, output from the program running on the Brain is read
+/// between chunks of a windowed upload and passed to it as it arrives.
+pub async fn upload_file(
+    connection: &mut DeviceConnection,
+    upload: UploadFile<'_>,
+    window_size: usize,
+    mut program_output: Option<&mut (dyn FnMut(&str) + '_)>,
+) -> Result<(), ConnectionError> {
+    if window_size <= 1 || connection.connection_type() == ConnectionType::Bluetooth {
+        return connection.execute_command(upload).await;
+    }
+
+    let UploadFile {
+        file_name,
+        metadata,
+        vendor,
+        data,
+        target,
+        load_address,
+        linked_file,
+        after_upload,
+        mut progress_callback,
+    } = upload;
+
+    log::debug!("Uploading file with a window of {window_size}: {file_name}");
+
+    let transfer = connection
+        .handshake::<FileTransferInitializeReplyPacket>(
+            Duration::from_millis(500),
+            5,
+            FileTransferInitializePacket::new(FileTransferInitializePayload {
+                operation: FileTransferOperation::Write,
+                target,
+                vendor,
+                options: FileInitOption::Overwrite,
+                file_size: data.len() as u32,
+                load_address,
+                write_file_crc: VEX_CRC32.checksum(data),
+                metadata,
+                file_name: file_name.clone(),
+            }),
+        )
+        .await?
+        .payload?;
+
+    if let Some(linked_file) = linked_file {
+        connection
+            .handshake::<FileLinkReplyPacket>(
+                Duration::from_millis(500),
+                5,
+                FileLinkPacket::new(FileLinkPayload {
+                    vendor: linked_file.vendor,
+                    reserved: 0,
+                    required_file: linked_file.file_name,
+                }),
+            )
+            .await?
+            .payload?;
+    }
+
+    // The Brain only has room for `transfer.window_size` bytes of unacknowledged writes, so the
+    // chunks in flight have to share it. If the Brain doesn't say, one chunk of the largest size
+    // is assumed to fit.
+    let buffer_size = match transfer.window_size as usize {
+        0 => MAX_CHUNK_SIZE,
+        size => size.max(4),
+    };
+    let window_size = window_size.min(buffer_size / 4);
+    let chunk_size = (buffer_size / window_size).min(MAX_CHUNK_SIZE) / 4 * 4;
+    log::debug!("Keeping {window_size} chunks of {chunk_size} bytes in flight");
+
+    // Split the file into write packets up front so that any of them can be resent.
+    let mut offset = 0;
+    let packets = data
+        .chunks(chunk_size)
+        .map(|chunk| {
+            let mut chunk = chunk.to_vec();
+            // Writes must be 4-byte aligned.
+            chunk.resize(chunk.len().next_multiple_of(4), 0);
+
+            let packet = FileDataWritePacket::new(FileDataWritePayload {
+                address: (load_address + offset) as _,
+                chunk_data: chunk,
+            });
+            offset += chunk_size as u32;
+            packet
+        })
+        .collect::<Vec<_>>();
+
+    let mut acknowledged = 0;
+    let mut next = 0;
+    let mut retries = 0;
+    let mut last_output_poll = Instant::now();
+
+    while acknowledged < packets.len() {
+        if let Some(output) = &mut program_output
+            && last_output_poll.elapsed() >= OUTPUT_POLL_INTERVAL
+        {
+            poll_program_output(connection, output).await?;
+            last_output_poll = Instant::now();
+        }
+
+        while next < packets.len() && next - acknowledged < window_size {
+            connection.send(packets[next].clone()).await?;
+            next += 1;
+        }
+
+        let error = match connection
+            .recv::<FileDataWriteReplyPacket>(WRITE_TIMEOUT)
+            .await
+        {
+            Ok(reply) => match reply.payload {
+                Ok(()) => {
+                    acknowledged += 1;
+                    retries = 0;
+
+                    if let Some(callback) = &mut progress_callback {
+                        callback(acknowledged as f32 / packets.len() as f32 * 100.0);
+                    }
+                    continue;
+                }
+                Err(nack) => ConnectionError::Nack(nack),
+            },
+            Err(err) => err,
+        };
+
+        retries += 1;
+        if retries > MAX_RETRIES {
+            return Err(error);
+        }
+        log::warn!("Chunk {acknowledged} of {file_name} failed ({error}). Resending...");
+
+        // Let replies to packets that are still in flight arrive, so that they aren't mistaken
+        // for acknowledgements of the resent chunks.
+        while connection
+            .recv::<FileDataWriteReplyPacket>(Duration::from_millis(100))
+            .await
+            .is_ok()
+        {}
+        next = acknowledged;
+    }
+
+    if let Some(callback) = &mut progress_callback {
+        callback(100.0);
+    }
+
+    connection
+        .handshake::<FileTransferExitReplyPacket>(
+            Duration::from_millis(1000),
+            5,
+            FileTransferExitPacket::new(after_upload),
+        )
+        .await?
+        .payload?;
+
+    log::debug!("Successfully uploaded file: {file_name}");
+    Ok(())
+}
+
+/// Reads whatever is waiting in the Brain's stdio FIFO without blocking the upload for long.
+async fn poll_program_output(
+    connection: &mut DeviceConnection,
+    output: &mut dyn FnMut(&str),
+) -> Result<(), ConnectionError> {
+    connection
+        .send(UserDataPacket::new(UserDataPayload {
+            channel: 1, // stdio
+            write: None,
+        }))
+        .await?;
+
+    // Output is best-effort, so a slow reply shouldn't hold up the transfer.
+    if let Ok(reply) = connection
+        .recv::<UserDataReplyPacket>(Duration::from_millis(100))
+        .await
+        && let Ok(fifo) = reply.payload
+        && let Some(data) = fifo.data
+        && !data.is_empty()
+    {
+        output(&data);
+    }
+
+    Ok(())
+}
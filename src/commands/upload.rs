@@ -1,18 +1,22 @@
+use cargo_metadata::{Package, PackageId};
 use clap::{Args, ValueEnum};
 use flate2::{Compression, GzBuilder};
-use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use futures::FutureExt;
+use humansize::{BINARY, format_size};
 use inquire::{
     CustomType,
     validator::{ErrorMessage, Validation},
 };
-use tokio::{fs::File, io::AsyncWriteExt, sync::Mutex, task::block_in_place, time::Instant};
+use tokio::task::{JoinHandle, block_in_place};
 
 use std::{
+    borrow::BorrowMut,
     ffi::OsStr,
     io::{ErrorKind, Write},
     path::{Path, PathBuf},
-    sync::Arc,
-    time::Duration,
+    str::FromStr,
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant},
 };
 
 use vex_v5_serial::{
@@ -23,25 +27,40 @@ use vex_v5_serial::{
         cdc2::{
             Cdc2Ack,
             file::{
-                ExtensionType, FileExitAction, FileMetadata, FileMetadataPacket,
+                ExtensionType, FileExitAction, FileLoadAction, FileMetadata, FileMetadataPacket,
                 FileMetadataPayload, FileMetadataReplyPacket, FileMetadataReplyPayload,
                 FileTransferTarget, FileVendor,
             },
         },
     },
-    serial::{SerialConnection, SerialError},
 };
 
 use crate::{
-    connection::{open_connection, switch_to_download_channel},
+    build_info::read_build_info,
+    config::user_config,
+    connection::{
+        ConnectionError, ConnectionOpts, DeviceConnection, is_connection_wireless, open_connection,
+        open_serial_or_remote_connection, opens_without_prompt, switch_to_download_channel,
+    },
     errors::CliError,
-    metadata::Metadata,
+    interactive::is_interactive,
+    metadata::{Metadata, manifest_dir, manifest_path},
+    provenance::verify_provenance,
+    reporter::{Reporter, Task, TaskKind, progress_callback},
 };
 
-use super::build::{CargoOpts, build, objcopy};
+use super::{
+    build::{CargoOpts, build, objcopy, prepare_linked_files},
+    firmware::format_version,
+    history::{UploadHistoryEntry, record_upload_history},
+    patch_bases::PatchBases,
+    program::{load_action, run_slot},
+    stamp::Stamp,
+    transfer::{default_window_size, upload_file},
+};
 
 /// Options used to control the behavior of a program upload
-#[derive(Args, Debug)]
+#[derive(Args, Debug, Default)]
 pub struct UploadOpts {
     /// Program slot.
     #[arg(short, long)]
@@ -59,6 +78,10 @@ pub struct UploadOpts {
     #[arg(short, long)]
     pub icon: Option<ProgramIcon>,
 
+    /// The IDE the program was made with, as shown on the Brain's program info screen.
+    #[arg(long, value_name = "NAME", conflicts_with = "python")]
+    pub ide: Option<String>,
+
     /// Skip gzip compression before uploading. Will result in longer upload times.
     #[arg(short, long)]
     pub uncompressed: Option<bool>,
@@ -67,6 +90,15 @@ pub struct UploadOpts {
     #[arg(long)]
     pub file: Option<PathBuf>,
 
+    /// An ELF to upload, converted to a BIN in memory rather than written next to it. Useful
+    /// when the ELF is in a read-only location.
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["file", "python"])]
+    pub elf: Option<PathBuf>,
+
+    /// A VEXcode Python script to upload instead of building a Rust program.
+    #[arg(long, value_name = "FILE.py", conflicts_with = "file")]
+    pub python: Option<PathBuf>,
+
     /// Method to use when uploading binaries.
     #[arg(long)]
     pub upload_strategy: Option<UploadStrategy>,
@@ -75,11 +107,72 @@ pub struct UploadOpts {
     #[arg(long)]
     pub cold: bool,
 
+    /// Number of chunks to send before waiting for the Brain to acknowledge them. Larger windows
+    /// speed up wireless uploads, but may cause retries on a poor connection. Chunks get smaller
+    /// as the window grows, so that they fit in the Brain's buffer.
+    #[arg(long)]
+    pub window_size: Option<usize>,
+
+    /// Read back the uploaded program's checksum from the Brain and fail if it doesn't match.
+    #[arg(long)]
+    pub verify: bool,
+
+    /// Output format for progress messages. `json` writes one JSON object per line to stdout.
+    #[arg(long, value_enum, default_value = "human")]
+    pub message_format: MessageFormat,
+
+    /// Append a record of this upload to a log file (Markdown, or JSON if the path ends in `.json`).
+    #[arg(long)]
+    pub upload_log: Option<PathBuf>,
+
+    /// Don't stamp the program with its image hash, commit, and upload strategy, which
+    /// `cargo v5 stamp` reads back.
+    #[arg(long)]
+    pub no_stamp: bool,
+
+    /// Skip transferring the program if the Brain already has an identical copy in the slot.
+    #[arg(skip)]
+    pub skip_unchanged: bool,
+
     /// Arguments forwarded to `cargo`.
     #[clap(flatten)]
     pub cargo_opts: CargoOpts,
 }
 
+/// Format of the progress messages printed while uploading.
+#[derive(ValueEnum, Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum MessageFormat {
+    /// Progress bars and status lines for humans.
+    #[default]
+    Human,
+
+    /// Line-delimited JSON events on stdout, for editors and other tools.
+    Json,
+}
+
+/// Writes a JSON progress event as a single line to stdout.
+pub fn emit_message(message: serde_json::Value) {
+    let mut stdout = std::io::stdout().lock();
+    _ = writeln!(stdout, "{message}");
+    _ = stdout.flush();
+}
+
+/// Returns a sink for output from the program running on the Brain during an upload.
+///
+/// Output is passed to the reporter a line at a time.
+fn program_output_printer(reporter: &dyn Reporter) -> impl FnMut(&str) + '_ {
+    let mut buffer = String::new();
+
+    move |data| {
+        buffer.push_str(data);
+
+        while let Some(end) = buffer.find('\n') {
+            let line = buffer.drain(..=end).collect::<String>();
+            reporter.program_output(line.trim_end_matches(['\r', '\n']));
+        }
+    }
+}
+
 /// Method used for uploading binaries
 #[derive(ValueEnum, Debug, Clone, Copy, Default, Eq, PartialEq)]
 pub enum UploadStrategy {
@@ -91,6 +184,52 @@ pub enum UploadStrategy {
     Differential,
 }
 
+/// Version recorded in the metadata of uploaded programs.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ProgramVersion {
+    /// Use the crate's version, with the beta number counting uploads of that version.
+    Auto,
+
+    /// Use a fixed version.
+    Fixed(Version),
+}
+
+impl FromStr for ProgramVersion {
+    type Err = CliError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "auto" {
+            return Ok(Self::Auto);
+        }
+
+        let invalid = || CliError::InvalidProgramVersion(s.to_string());
+        let mut parts = s
+            .split('.')
+            .map(|part| part.parse::<u8>().map_err(|_| invalid()));
+
+        let version = Version {
+            major: parts.next().ok_or_else(invalid)??,
+            minor: parts.next().unwrap_or(Ok(0))?,
+            build: parts.next().unwrap_or(Ok(0))?,
+            beta: 0,
+        };
+
+        if parts.next().is_some() {
+            return Err(invalid());
+        }
+
+        Ok(Self::Fixed(version))
+    }
+}
+
+/// Version given to uploaded programs when none is configured.
+pub const DEFAULT_PROGRAM_VERSION: Version = Version {
+    major: 1,
+    minor: 0,
+    build: 0,
+    beta: 0,
+};
+
 /// An action to perform after uploading a program.
 #[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub enum AfterUpload {
@@ -153,12 +292,60 @@ pub enum ProgramIcon {
 
 pub const PROGRESS_CHARS: &str = "⣿⣦⣀";
 
-const DIFFERENTIAL_UPLOAD_MAX_SIZE: usize = 0x200000;
+/// Progress bar characters used when output is restricted to ASCII.
+pub const ASCII_PROGRESS_CHARS: &str = "#>-";
+
+static ASCII_OUTPUT: AtomicBool = AtomicBool::new(false);
 
-/// Upload a program to the brain.
+/// Restricts progress bars and TUIs to plain ASCII, for terminals and screen readers that can't
+/// render the usual box-drawing and braille characters.
+pub fn set_ascii_output(ascii: bool) {
+    ASCII_OUTPUT.store(ascii, Ordering::Relaxed);
+}
+
+/// Returns whether output is restricted to plain ASCII.
+pub fn ascii_output() -> bool {
+    ASCII_OUTPUT.load(Ordering::Relaxed)
+}
+
+/// Returns the characters progress bars should be drawn with.
+pub fn progress_chars() -> &'static str {
+    if ascii_output() {
+        ASCII_PROGRESS_CHARS
+    } else {
+        PROGRESS_CHARS
+    }
+}
+
+pub const DIFFERENTIAL_UPLOAD_MAX_SIZE: usize = 0x200000;
+
+/// Name of the Python VM that VEXcode installs on the Brain.
+const PYTHON_VM_FILE_NAME: &str = "python_vm.bin";
+
+/// Address that Python scripts are loaded at for the Python VM to run.
+const PYTHON_SCRIPT_LOAD_ADDR: u32 = 0x07800000;
+
+/// IDE recorded for uploaded programs when none is configured.
+pub const DEFAULT_PROGRAM_TYPE: &str = "Rust";
+
+/// Binary size in bytes above which wireless uploads default to the differential strategy.
+pub const DEFAULT_DIFFERENTIAL_THRESHOLD: u64 = 128 * 1024;
+
+/// Upload a program binary to the brain.
+///
+/// Differential uploads keep a copy of the base binary last uploaded to the slot in `bases`.
+/// Monolith uploads link the program to `linked_file` on the Brain, if given.
+/// If `skip_unchanged` is set and the slot already holds exactly the data that would be
+/// uploaded, only the ini file is updated.
+///
+/// Returns the number of bytes of program data sent to the Brain, which is zero if the upload
+/// was skipped.
+#[allow(clippy::too_many_arguments)]
 pub async fn upload_program(
-    connection: &mut SerialConnection,
-    path: &Path,
+    connection: &mut DeviceConnection,
+    binary: Vec<u8>,
+    bases: &PatchBases,
+    linked_file: Option<&str>,
     after: AfterUpload,
     slot: u8,
     name: String,
@@ -168,160 +355,129 @@ pub async fn upload_program(
     compress: bool,
     cold: bool,
     upload_strategy: UploadStrategy,
-) -> Result<(), CliError> {
-    let multi_progress = MultiProgress::new();
+    window_size: usize,
+    verify: bool,
+    skip_unchanged: bool,
+    version: Version,
+    reporter: &dyn Reporter,
+) -> Result<u64, CliError> {
+    let mut program_output = program_output_printer(reporter);
 
     let slot_file_name = format!("slot_{slot}.bin");
-    let ini_file_name = format!("slot_{slot}.ini");
+    // Compressing a monolith binary can take a noticeable amount of time, so we prepare it in
+    // the background while the ini file is checked and transferred.
+    let mut binary = Some(binary);
+    let monolith_data = (upload_strategy == UploadStrategy::Monolith).then(|| {
+        let mut data = binary.take().unwrap();
+        tokio::task::spawn_blocking(move || {
+            if compress {
+                gzip_compress(&mut data);
+            }
+
+            data
+        })
+    });
 
-    let ini = format!(
-        "[project]
-ide={}
-[program]
-name={}
-slot={}
-icon=USER{:03}x.bmp
-iconalt=
-description={}",
-        program_type,
-        name,
-        slot - 1,
-        icon as u16,
-        description
-    );
+    // Building a patch can also take a while, so it's built against our base in the background
+    // too, and only used if the Brain turns out to still have that base.
+    let base = match upload_strategy {
+        UploadStrategy::Differential => bases.read(slot).await,
+        UploadStrategy::Monolith => None,
+    };
+    let mut patch_task = match (&base, &binary) {
+        (Some(base), Some(new))
+            if !cold
+                && base.data.len() <= DIFFERENTIAL_UPLOAD_MAX_SIZE
+                && new.len() <= DIFFERENTIAL_UPLOAD_MAX_SIZE =>
+        {
+            let (old, new) = (base.data.clone(), new.clone());
+            Some(tokio::task::spawn_blocking(move || build_patch(&old, &new)))
+        }
+        _ => None,
+    };
 
-    let needs_ini_upload = if let Some(brain_metadata) = brain_file_metadata(
+    upload_ini(
         connection,
-        FixedString::new(ini_file_name.clone()).unwrap(),
-        FileVendor::User,
+        slot,
+        &name,
+        &description,
+        icon,
+        &program_type,
+        window_size,
+        reporter,
     )
-    .await?
-    {
-        brain_metadata.crc32 != VEX_CRC32.checksum(ini.as_bytes())
-    } else {
-        true
-    };
+    .await?;
 
-    if needs_ini_upload {
-        let ini_timestamp = Arc::new(Mutex::new(None));
-        // Progress bars
-        let ini_progress = Arc::new(Mutex::new(
-            multi_progress
-                .add(ProgressBar::new(10000))
-                .with_style(
-                    ProgressStyle::with_template(
-                        "   \x1b[1;96mUploading\x1b[0m {percent_precise:>7}% {bar:40.green} {msg} ({prefix})",
-                    )
-                    .unwrap() // Okay to unwrap, since this just validates style formatting.
-                    .progress_chars(PROGRESS_CHARS),
-                )
-                .with_message(ini_file_name.clone()),
-        ));
-
-        connection
-            .execute_command(UploadFile {
-                file_name: FixedString::new(ini_file_name).unwrap(),
-                metadata: FileMetadata {
-                    extension: FixedString::new("ini").unwrap(),
-                    extension_type: ExtensionType::default(),
-                    timestamp: j2000_timestamp(),
-                    version: Version {
-                        major: 1,
-                        minor: 0,
-                        build: 0,
-                        beta: 0,
-                    },
-                },
-                vendor: FileVendor::User,
-                data: ini.as_bytes(),
-                target: FileTransferTarget::Qspi,
-                load_address: USER_PROGRAM_LOAD_ADDR,
-                linked_file: None,
-                after_upload: FileExitAction::DoNothing,
-                progress_callback: Some(build_progress_callback(
-                    ini_progress.clone(),
-                    ini_timestamp.clone(),
-                )),
-            })
-            .await?;
-
-        ini_progress.lock().await.finish();
-    }
+    // Checksum of the data written to the slot's binary, compared against the Brain's copy when
+    // verifying.
+    let slot_crc;
+    let transferred;
 
     match upload_strategy {
         UploadStrategy::Monolith => {
-            // indicatif is a little dumb with timestamp handling, so we're going to do this all custom,
-            // which unfortunately requires us to juggle timestamps across threads.
-            let bin_timestamp = Arc::new(Mutex::new(None));
-
-            let bin_progress = Arc::new(Mutex::new(
-                multi_progress
-                    .add(ProgressBar::new(10000))
-                    .with_style(
-                        ProgressStyle::with_template(
-                            "   \x1b[1;96mUploading\x1b[0m {percent_precise:>7}% {bar:40.red} {msg} ({prefix})",
-                        )
-                        .unwrap() // Okay to unwrap, since this just validates style formatting.
-                        .progress_chars(PROGRESS_CHARS),
-                    )
-                    .with_message(slot_file_name.clone()),
-            ));
+            let data = monolith_data
+                .expect("monolith data is prepared for monolith uploads")
+                .await
+                .unwrap();
+            slot_crc = VEX_CRC32.checksum(&data);
+
+            if skip_unchanged
+                && skip_unchanged_upload(connection, &slot_file_name, slot_crc, after, reporter)
+                    .await?
+            {
+                return Ok(0);
+            }
+            transferred = data.len();
+
+            let bin_progress = reporter.start_task(Task {
+                kind: TaskKind::Upload,
+                name: &slot_file_name,
+                color: "red",
+            });
 
             // Upload the program.
-            connection
-                .execute_command(UploadFile {
+            upload_file(
+                connection,
+                UploadFile {
                     file_name: FixedString::new(slot_file_name.clone()).unwrap(),
                     metadata: FileMetadata {
                         extension: FixedString::new("bin").unwrap(),
                         extension_type: ExtensionType::default(),
                         timestamp: j2000_timestamp(),
-                        version: Version {
-                            major: 1,
-                            minor: 0,
-                            build: 0,
-                            beta: 0,
-                        },
+                        version,
                     },
                     vendor: FileVendor::User,
-                    data: &{
-                        let mut data = tokio::fs::read(path).await?;
-
-                        if compress {
-                            gzip_compress(&mut data);
-                        }
-
-                        data
-                    },
+                    data: &data,
                     target: FileTransferTarget::Qspi,
                     load_address: USER_PROGRAM_LOAD_ADDR,
-                    linked_file: None,
+                    linked_file: linked_file.map(|file_name| LinkedFile {
+                        file_name: FixedString::new(file_name).unwrap(),
+                        vendor: FileVendor::User,
+                    }),
                     after_upload: match after {
                         AfterUpload::None => FileExitAction::DoNothing,
                         AfterUpload::ShowScreen => FileExitAction::ShowRunScreen,
                         AfterUpload::Run => FileExitAction::RunProgram,
                     },
-                    progress_callback: Some(build_progress_callback(
-                        bin_progress.clone(),
-                        bin_timestamp.clone(),
-                    )),
-                })
-                .await?;
+                    progress_callback: Some(progress_callback(&bin_progress)),
+                },
+                window_size,
+                Some(&mut program_output),
+            )
+            .await?;
 
             // Tell the progressbars that we're done once uploading is complete, allowing further messages to be printed to stdout.
-            bin_progress.lock().await.finish();
+            bin_progress.finish();
         }
         UploadStrategy::Differential => {
             let base_file_name = format!("slot_{slot}.base.bin");
-
-            let mut base = match tokio::fs::read(&path.with_file_name(&base_file_name)).await {
-                Ok(contents) => Some(contents),
-                Err(e) if e.kind() == ErrorKind::NotFound => None,
-                _ => None,
-            };
+            let new = binary
+                .take()
+                .expect("binary is kept for differential uploads");
 
             let needs_cold_upload = cold
                 || 'check: {
-                    let Some(base) = base.as_mut() else {
+                    let Some(base) = &base else {
                         break 'check true;
                     };
 
@@ -335,62 +491,67 @@ description={}",
                         break 'check true;
                     };
 
-                    if base.len() >= 4 {
-                        let crc_metadata =
-                            u32::from_le_bytes(base.split_off(base.len() - 4).try_into().unwrap());
-
-                        // last four bytes of base file contain the crc32 at time of upload
-                        brain_metadata.crc32 != crc_metadata
-                    } else {
-                        true
-                    }
+                    // The base on the Brain was replaced since ours was uploaded, possibly from
+                    // another machine or branch.
+                    brain_metadata.crc32 != base.crc32
                 };
 
             if !needs_cold_upload {
                 let base = base.unwrap();
-                let patch_timestamp = Arc::new(Mutex::new(None));
-                let patch_progress = Arc::new(Mutex::new(
-                    multi_progress
-                        .add(ProgressBar::new(10000))
-                        .with_style(
-                            ProgressStyle::with_template(
-                                "    \x1b[1;96mPatching\x1b[0m {percent_precise:>7}% {bar:40.red} {msg} ({prefix})",
-                            )
-                            .unwrap() // Okay to unwrap, since this just validates style formatting.
-                            .progress_chars(PROGRESS_CHARS),
-                        )
-                        .with_message(slot_file_name.clone()),
-                ));
-
-                let new = tokio::fs::read(path).await?;
-
-                if base.len() > DIFFERENTIAL_UPLOAD_MAX_SIZE {
-                    return Err(CliError::ProgramTooLarge(base.len()));
+                if base.data.len() > DIFFERENTIAL_UPLOAD_MAX_SIZE {
+                    return Err(CliError::ProgramTooLarge(base.data.len()));
                 } else if new.len() > DIFFERENTIAL_UPLOAD_MAX_SIZE {
                     return Err(CliError::ProgramTooLarge(new.len()));
                 }
 
-                let mut patch = build_patch(&base, &new);
+                let mut patch = match patch_task.take() {
+                    Some(task) => task.await.unwrap(),
+                    None => build_patch(&base.data, &new),
+                };
 
                 if patch.len() > DIFFERENTIAL_UPLOAD_MAX_SIZE {
                     return Err(CliError::PatchTooLarge(patch.len()));
                 }
 
                 gzip_compress(&mut patch);
+                slot_crc = VEX_CRC32.checksum(&patch);
+                bases.touch(slot).await;
+
+                reporter.status(
+                    "Diffed",
+                    &format!(
+                        "against the base from {}, sending {} ({:.1}% of the program)",
+                        base.commit.as_deref().unwrap_or("an unknown commit"),
+                        format_size(patch.len(), BINARY),
+                        patch.len() as f64 / new.len().max(1) as f64 * 100.0,
+                    ),
+                );
+
+                // Patches are built deterministically against the same base, so an identical
+                // patch means an identical program.
+                if skip_unchanged
+                    && skip_unchanged_upload(connection, &slot_file_name, slot_crc, after, reporter)
+                        .await?
+                {
+                    return Ok(0);
+                }
+                transferred = patch.len();
+
+                let patch_progress = reporter.start_task(Task {
+                    kind: TaskKind::Patch,
+                    name: &slot_file_name,
+                    color: "red",
+                });
 
-                connection
-                    .execute_command(UploadFile {
+                upload_file(
+                    connection,
+                    UploadFile {
                         file_name: FixedString::new(slot_file_name.clone()).unwrap(),
                         metadata: FileMetadata {
                             extension: FixedString::new("bin").unwrap(),
                             extension_type: ExtensionType::default(),
                             timestamp: j2000_timestamp(),
-                            version: Version {
-                                major: 1,
-                                minor: 0,
-                                build: 0,
-                                beta: 0,
-                            },
+                            version,
                         },
                         vendor: FileVendor::User,
                         data: &patch,
@@ -405,96 +566,75 @@ description={}",
                             AfterUpload::ShowScreen => FileExitAction::ShowRunScreen,
                             AfterUpload::Run => FileExitAction::RunProgram,
                         },
-                        progress_callback: Some(build_progress_callback(
-                            patch_progress.clone(),
-                            patch_timestamp.clone(),
-                        )),
-                    })
-                    .await?;
+                        progress_callback: Some(progress_callback(&patch_progress)),
+                    },
+                    window_size,
+                    Some(&mut program_output),
+                )
+                .await?;
 
-                patch_progress.lock().await.finish();
+                patch_progress.finish();
             } else {
-                // indicatif is a little dumb with timestamp handling, so we're going to do this all custom,
-                // which unfortunately requires us to juggle timestamps across threads.
-                let base_timestamp = Arc::new(Mutex::new(None));
-
-                let base_progress = Arc::new(Mutex::new(
-                    multi_progress
-                        .add(ProgressBar::new(10000))
-                        .with_style(
-                            ProgressStyle::with_template(
-                                "   \x1b[1;96mUploading\x1b[0m {percent_precise:>7}% {bar:40.blue} {msg} ({prefix})",
-                            )
-                            .unwrap() // Okay to unwrap, since this just validates style formatting.
-                            .progress_chars(PROGRESS_CHARS),
-                        )
-                        .with_message(base_file_name.clone()),
-                ));
+                let base_progress = reporter.start_task(Task {
+                    kind: TaskKind::Upload,
+                    name: &base_file_name,
+                    color: "blue",
+                });
 
-                let mut base_data = tokio::fs::read(path).await?;
+                let mut base_data = new.clone();
 
                 if base_data.len() > DIFFERENTIAL_UPLOAD_MAX_SIZE {
                     return Err(CliError::ProgramTooLarge(base_data.len()));
                 }
 
-                connection
-                    .execute_command(UploadFile {
+                if compress {
+                    gzip_compress(&mut base_data);
+                }
+
+                upload_file(
+                    connection,
+                    UploadFile {
                         file_name: FixedString::new(base_file_name.clone()).unwrap(),
                         metadata: FileMetadata {
                             extension: FixedString::new("bin").unwrap(),
                             extension_type: ExtensionType::default(),
                             timestamp: j2000_timestamp(),
-                            version: Version {
-                                major: 1,
-                                minor: 0,
-                                build: 0,
-                                beta: 0,
-                            },
+                            version,
                         },
                         vendor: FileVendor::User,
-                        data: {
-                            let mut base_file =
-                                File::create(path.with_file_name(&base_file_name)).await?;
-                            base_file.write_all(&base_data).await?;
-
-                            if compress {
-                                gzip_compress(&mut base_data);
-                            }
-
-                            base_file
-                                .write_all(&VEX_CRC32.checksum(&base_data).to_le_bytes())
-                                .await?;
-
-                            &base_data
-                        },
+                        data: &base_data,
                         target: FileTransferTarget::Qspi,
                         load_address: USER_PROGRAM_LOAD_ADDR,
                         linked_file: None,
                         after_upload: FileExitAction::DoNothing,
-                        progress_callback: Some(build_progress_callback(
-                            base_progress.clone(),
-                            base_timestamp.clone(),
-                        )),
-                    })
+                        progress_callback: Some(progress_callback(&base_progress)),
+                    },
+                    window_size,
+                    Some(&mut program_output),
+                )
+                .await?;
+                base_progress.finish();
+
+                bases
+                    .write(slot, &new, VEX_CRC32.checksum(&base_data))
                     .await?;
-                base_progress.lock().await.finish();
 
-                connection
-                    .execute_command(UploadFile {
+                let stub = u32::to_le_bytes(0xB2DF);
+                slot_crc = VEX_CRC32.checksum(&stub);
+                transferred = base_data.len() + stub.len();
+
+                upload_file(
+                    connection,
+                    UploadFile {
                         file_name: FixedString::new(slot_file_name.clone()).unwrap(),
                         metadata: FileMetadata {
                             extension: FixedString::new("bin").unwrap(),
                             extension_type: ExtensionType::default(),
                             timestamp: j2000_timestamp(),
-                            version: Version {
-                                major: 1,
-                                minor: 0,
-                                build: 0,
-                                beta: 0,
-                            },
+                            version,
                         },
                         vendor: FileVendor::User,
-                        data: &u32::to_le_bytes(0xB2DF),
+                        data: &stub,
                         target: FileTransferTarget::Qspi,
                         load_address: 0x07A00000,
                         linked_file: Some(LinkedFile {
@@ -507,20 +647,422 @@ description={}",
                             AfterUpload::Run => FileExitAction::RunProgram,
                         },
                         progress_callback: None,
-                    })
-                    .await?;
+                    },
+                    window_size,
+                    None,
+                )
+                .await?;
             };
         }
     }
 
+    if verify {
+        let brain_crc = brain_file_metadata(
+            connection,
+            FixedString::new(slot_file_name.clone()).unwrap(),
+            FileVendor::User,
+        )
+        .await?
+        .map(|metadata| metadata.crc32);
+
+        if brain_crc != Some(slot_crc) {
+            return Err(CliError::UploadVerificationFailed {
+                file_name: slot_file_name,
+                expected: slot_crc,
+                actual: brain_crc,
+            });
+        }
+
+        reporter.status(
+            "Verified",
+            &format!("`{slot_file_name}` (CRC32 {slot_crc:08x})"),
+        );
+    }
+
     if after == AfterUpload::Run {
-        eprintln!("     \x1b[1;92mRunning\x1b[0m `{slot_file_name}`");
+        reporter.status("Running", &format!("`{slot_file_name}`"));
+    }
+
+    Ok(transferred as u64)
+}
+
+/// Uploads the linked file at `blob` for the program in `slot`, unless the Brain already has it,
+/// returning its name on the Brain.
+#[allow(clippy::too_many_arguments)]
+async fn upload_linked_file(
+    connection: &mut DeviceConnection,
+    slot: u8,
+    blob: &Path,
+    load_address: u32,
+    compress: bool,
+    window_size: usize,
+    version: Version,
+    reporter: &dyn Reporter,
+) -> Result<String, CliError> {
+    let file_name = format!("slot_{slot}.link.bin");
+
+    let mut data = tokio::fs::read(blob).await?;
+    if compress {
+        gzip_compress(&mut data);
+    }
+
+    let installed = brain_file_metadata(
+        connection,
+        FixedString::new(file_name.clone()).unwrap(),
+        FileVendor::User,
+    )
+    .await?;
+    if installed.is_some_and(|metadata| {
+        metadata.crc32 == VEX_CRC32.checksum(&data) && metadata.load_address == load_address
+    }) {
+        reporter.status(
+            "Unchanged",
+            &format!("`{file_name}` is already on the Brain, skipping upload"),
+        );
+        return Ok(file_name);
+    }
+
+    let progress = reporter.start_task(Task {
+        kind: TaskKind::Upload,
+        name: &file_name,
+        color: "blue",
+    });
+    upload_file(
+        connection,
+        UploadFile {
+            file_name: FixedString::new(file_name.clone()).unwrap(),
+            metadata: FileMetadata {
+                extension: FixedString::new("bin").unwrap(),
+                extension_type: ExtensionType::default(),
+                timestamp: j2000_timestamp(),
+                version,
+            },
+            vendor: FileVendor::User,
+            data: &data,
+            target: FileTransferTarget::Qspi,
+            load_address,
+            linked_file: None,
+            after_upload: FileExitAction::DoNothing,
+            progress_callback: Some(progress_callback(&progress)),
+        },
+        window_size,
+        None,
+    )
+    .await?;
+    progress.finish();
+
+    Ok(file_name)
+}
+
+/// Skips uploading `slot_file_name` if the Brain's copy has the checksum `crc`, starting the
+/// installed program instead if `after` asks for it.
+///
+/// Returns whether the upload was skipped.
+async fn skip_unchanged_upload(
+    connection: &mut DeviceConnection,
+    slot_file_name: &str,
+    crc: u32,
+    after: AfterUpload,
+    reporter: &dyn Reporter,
+) -> Result<bool, CliError> {
+    let installed = brain_file_metadata(
+        connection,
+        FixedString::new(slot_file_name).unwrap(),
+        FileVendor::User,
+    )
+    .await?;
+    if installed.is_none_or(|metadata| metadata.crc32 != crc) {
+        return Ok(false);
+    }
+
+    reporter.status(
+        "Unchanged",
+        &format!("`{slot_file_name}` is already on the Brain, skipping upload"),
+    );
+
+    if after == AfterUpload::Run {
+        load_action(connection, FileLoadAction::Run, slot_file_name).await?;
+        reporter.status("Running", &format!("`{slot_file_name}`"));
+    }
+
+    Ok(true)
+}
+
+/// Derives the version of a program being uploaded to `slot` from its crate version.
+///
+/// The beta number counts how many times this version has been uploaded, based on the version of
+/// the program currently in the slot.
+async fn auto_version(
+    connection: &mut DeviceConnection,
+    slot: u8,
+    crate_version: Option<&cargo_metadata::semver::Version>,
+    reporter: &dyn Reporter,
+) -> Result<Version, CliError> {
+    let mut version = crate_version
+        .map(|version| Version {
+            major: version.major.min(u8::MAX as u64) as u8,
+            minor: version.minor.min(u8::MAX as u64) as u8,
+            build: version.patch.min(u8::MAX as u64) as u8,
+            beta: 0,
+        })
+        .unwrap_or(DEFAULT_PROGRAM_VERSION);
+
+    if let Some(existing) = brain_file_metadata(
+        connection,
+        FixedString::new(format!("slot_{slot}.bin")).unwrap(),
+        FileVendor::User,
+    )
+    .await?
+    .map(|metadata| metadata.metadata.version)
+        && (existing.major, existing.minor, existing.build)
+            == (version.major, version.minor, version.build)
+    {
+        version.beta = existing.beta.saturating_add(1);
+    }
+
+    reporter.status("Version", &format_version(version));
+
+    Ok(version)
+}
+
+/// Picks an upload strategy when none was configured.
+///
+/// Over USB, full uploads are fast enough that differential uploads aren't worth the extra base
+/// file. Over a controller's radio, large vexide programs are uploaded differentially, since
+/// usually only a small part of them changes between uploads.
+async fn default_upload_strategy(
+    connection: &mut DeviceConnection,
+    size: u64,
+    package: Option<&Package>,
+    threshold: u64,
+    reporter: &dyn Reporter,
+) -> Result<UploadStrategy, CliError> {
+    // Only vexide's startup code knows how to apply patches.
+    let uses_vexide = package.is_some_and(|package| {
+        package
+            .dependencies
+            .iter()
+            .any(|dependency| dependency.name == "vexide" || dependency.name.starts_with("vexide-"))
+    });
+    if !uses_vexide {
+        return Ok(UploadStrategy::Monolith);
+    }
+
+    if size <= threshold || !is_connection_wireless(connection).await? {
+        return Ok(UploadStrategy::Monolith);
+    }
+
+    reporter.note(&format!(
+        "Using differential upload for {} program over a wireless connection",
+        format_size(size, BINARY)
+    ));
+    Ok(UploadStrategy::Differential)
+}
+
+/// Uploads a VEXcode Python script to `slot`.
+///
+/// Scripts are run by the Python VM that VEXcode installs on the Brain, which must already be
+/// present. Returns the size of the script.
+#[allow(clippy::too_many_arguments)]
+pub async fn upload_python_program(
+    connection: &mut DeviceConnection,
+    path: &Path,
+    after: AfterUpload,
+    slot: u8,
+    name: &str,
+    description: &str,
+    icon: ProgramIcon,
+    window_size: usize,
+    verify: bool,
+    reporter: &dyn Reporter,
+) -> Result<u64, CliError> {
+    let script_file_name = format!("slot_{slot}.py");
+    let script = tokio::fs::read(path).await?;
+
+    if brain_file_metadata(
+        connection,
+        FixedString::new(PYTHON_VM_FILE_NAME).unwrap(),
+        FileVendor::VexVm,
+    )
+    .await?
+    .is_none()
+    {
+        return Err(CliError::PythonVmMissing);
+    }
+
+    let mut program_output = program_output_printer(reporter);
+
+    upload_ini(
+        connection,
+        slot,
+        name,
+        description,
+        icon,
+        "Python",
+        window_size,
+        reporter,
+    )
+    .await?;
+
+    let script_progress = reporter.start_task(Task {
+        kind: TaskKind::Upload,
+        name: &script_file_name,
+        color: "red",
+    });
+
+    upload_file(
+        connection,
+        UploadFile {
+            file_name: FixedString::new(script_file_name.clone()).unwrap(),
+            metadata: FileMetadata {
+                extension: FixedString::new("py").unwrap(),
+                extension_type: ExtensionType::Vm,
+                timestamp: j2000_timestamp(),
+                version: Version {
+                    major: 1,
+                    minor: 0,
+                    build: 0,
+                    beta: 0,
+                },
+            },
+            vendor: FileVendor::User,
+            data: &script,
+            target: FileTransferTarget::Qspi,
+            load_address: PYTHON_SCRIPT_LOAD_ADDR,
+            linked_file: Some(LinkedFile {
+                file_name: FixedString::new(PYTHON_VM_FILE_NAME).unwrap(),
+                vendor: FileVendor::VexVm,
+            }),
+            after_upload: match after {
+                AfterUpload::None => FileExitAction::DoNothing,
+                AfterUpload::ShowScreen => FileExitAction::ShowRunScreen,
+                AfterUpload::Run => FileExitAction::RunProgram,
+            },
+            progress_callback: Some(progress_callback(&script_progress)),
+        },
+        window_size,
+        Some(&mut program_output),
+    )
+    .await?;
+
+    script_progress.finish();
+
+    if verify {
+        let expected = VEX_CRC32.checksum(&script);
+        let brain_crc = brain_file_metadata(
+            connection,
+            FixedString::new(script_file_name.clone()).unwrap(),
+            FileVendor::User,
+        )
+        .await?
+        .map(|metadata| metadata.crc32);
+
+        if brain_crc != Some(expected) {
+            return Err(CliError::UploadVerificationFailed {
+                file_name: script_file_name,
+                expected,
+                actual: brain_crc,
+            });
+        }
+
+        reporter.status(
+            "Verified",
+            &format!("`{script_file_name}` (CRC32 {expected:08x})"),
+        );
+    }
+
+    if after == AfterUpload::Run {
+        reporter.status("Running", &format!("`{script_file_name}`"));
+    }
+
+    Ok(script.len() as u64)
+}
+
+/// Uploads the ini file describing the program in `slot`, unless the Brain already has it.
+#[allow(clippy::too_many_arguments)]
+async fn upload_ini(
+    connection: &mut DeviceConnection,
+    slot: u8,
+    name: &str,
+    description: &str,
+    icon: ProgramIcon,
+    program_type: &str,
+    window_size: usize,
+    reporter: &dyn Reporter,
+) -> Result<(), CliError> {
+    let ini_file_name = format!("slot_{slot}.ini");
+
+    let ini = format!(
+        "[project]
+ide={}
+[program]
+name={}
+slot={}
+icon=USER{:03}x.bmp
+iconalt=
+description={}",
+        program_type,
+        name,
+        slot - 1,
+        icon as u16,
+        description
+    );
+
+    let needs_ini_upload = if let Some(brain_metadata) = brain_file_metadata(
+        connection,
+        FixedString::new(ini_file_name.clone()).unwrap(),
+        FileVendor::User,
+    )
+    .await?
+    {
+        brain_metadata.crc32 != VEX_CRC32.checksum(ini.as_bytes())
+    } else {
+        true
+    };
+
+    if needs_ini_upload {
+        let ini_progress = reporter.start_task(Task {
+            kind: TaskKind::Upload,
+            name: &ini_file_name,
+            color: "green",
+        });
+
+        upload_file(
+            connection,
+            UploadFile {
+                file_name: FixedString::new(ini_file_name.clone()).unwrap(),
+                metadata: FileMetadata {
+                    extension: FixedString::new("ini").unwrap(),
+                    extension_type: ExtensionType::default(),
+                    timestamp: j2000_timestamp(),
+                    version: Version {
+                        major: 1,
+                        minor: 0,
+                        build: 0,
+                        beta: 0,
+                    },
+                },
+                vendor: FileVendor::User,
+                data: ini.as_bytes(),
+                target: FileTransferTarget::Qspi,
+                load_address: USER_PROGRAM_LOAD_ADDR,
+                linked_file: None,
+                after_upload: FileExitAction::DoNothing,
+                progress_callback: Some(progress_callback(&ini_progress)),
+            },
+            window_size,
+            None,
+        )
+        .await?;
+
+        ini_progress.finish();
     }
 
     Ok(())
 }
 
-fn build_patch(old: &[u8], new: &[u8]) -> Vec<u8> {
+/// Builds the patch sent by differential uploads to turn `old` into `new`.
+pub fn build_patch(old: &[u8], new: &[u8]) -> Vec<u8> {
     let mut patch = Vec::new();
 
     bidiff::simple_diff(old, new, &mut patch).unwrap();
@@ -534,11 +1076,66 @@ fn build_patch(old: &[u8], new: &[u8]) -> Vec<u8> {
     patch
 }
 
-async fn brain_file_metadata(
-    connection: &mut SerialConnection,
+/// Applies a patch made by [`build_patch`] to `old`, returning `None` if the patch is malformed or
+/// wasn't made against `old`.
+pub fn apply_patch(old: &[u8], patch: &[u8]) -> Option<Vec<u8>> {
+    fn read_u32(data: &[u8], offset: usize) -> Option<usize> {
+        Some(u32::from_le_bytes(data.get(offset..offset + 4)?.try_into().ok()?) as usize)
+    }
+
+    fn read_varint(data: &mut &[u8]) -> Option<u64> {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let (&byte, rest) = data.split_first()?;
+            *data = rest;
+            value |= u64::from(byte & 0x7F) << shift;
+            if byte & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+        None
+    }
+
+    fn take<'a>(data: &mut &'a [u8], len: u64) -> Option<&'a [u8]> {
+        let (taken, rest) = data.split_at_checked(usize::try_from(len).ok()?)?;
+        *data = rest;
+        Some(taken)
+    }
+
+    if read_u32(patch, 0)? != 0xB1DF || read_u32(patch, 12)? != old.len() {
+        return None;
+    }
+    let new_len = read_u32(patch, 16)?;
+
+    let mut controls = patch.get(20..)?;
+    let mut new = Vec::with_capacity(new_len);
+    let mut old_pos = 0_usize;
+
+    while !controls.is_empty() {
+        let add_len = read_varint(&mut controls)?;
+        for &byte in take(&mut controls, add_len)? {
+            new.push(old.get(old_pos)?.wrapping_add(byte));
+            old_pos += 1;
+        }
+
+        let copy_len = read_varint(&mut controls)?;
+        new.extend_from_slice(take(&mut controls, copy_len)?);
+
+        // Seeks are zigzag-encoded, since they can go backwards.
+        let seek = read_varint(&mut controls)?;
+        let seek = (seek >> 1) as i64 ^ -((seek & 1) as i64);
+        old_pos = old_pos.checked_add_signed(isize::try_from(seek).ok()?)?;
+    }
+
+    (new.len() == new_len).then_some(new)
+}
+
+/// Fetches the metadata of a file on the Brain's flash, or `None` if it doesn't exist.
+pub async fn brain_file_metadata(
+    connection: &mut DeviceConnection,
     file_name: FixedString<23>,
     vendor: FileVendor,
-) -> Result<Option<FileMetadataReplyPayload>, SerialError> {
+) -> Result<Option<FileMetadataReplyPayload>, ConnectionError> {
     let reply = connection
         .handshake::<FileMetadataReplyPacket>(
             Duration::from_millis(1000),
@@ -554,34 +1151,73 @@ async fn brain_file_metadata(
     match reply.payload {
         Ok(payload) => Ok(payload),
         Err(Cdc2Ack::NackProgramFile) => Ok(None),
-        Err(nack) => Err(SerialError::Nack(nack)),
+        Err(nack) => Err(ConnectionError::Nack(nack)),
     }
 }
 
-fn build_progress_callback(
-    progress: Arc<Mutex<ProgressBar>>,
-    timestamp: Arc<Mutex<Option<Instant>>>,
-) -> Box<dyn FnMut(f32) + Send> {
-    Box::new(move |percent| {
-        let progress = progress.try_lock().unwrap();
-        let mut timestamp = timestamp.try_lock().unwrap();
-
-        if timestamp.is_none() {
-            *timestamp = Some(Instant::now());
-        }
-        progress.set_prefix(format!("{:.2?}", timestamp.unwrap().elapsed()));
-        progress.set_position((percent * 100.0) as u64);
-    })
-}
-
 /// Apply gzip compression to the given data
-fn gzip_compress(data: &mut Vec<u8>) {
+pub fn gzip_compress(data: &mut Vec<u8>) {
     let mut encoder = GzBuilder::new().write(Vec::new(), Compression::best());
     encoder.write_all(data).unwrap();
     *data = encoder.finish().unwrap();
 }
 
+/// The result of a successful [`upload`].
+pub struct UploadOutcome<C = DeviceConnection> {
+    /// The connection the program was uploaded over, left open for follow-up commands.
+    pub connection: C,
+    pub slot: u8,
+    pub name: String,
+    /// The file that was uploaded.
+    pub artifact: PathBuf,
+    /// The ELF the uploaded program was built from, if known.
+    pub elf_artifact: Option<PathBuf>,
+    pub strategy: UploadStrategy,
+}
+
+/// Builds (unless a file is given) and uploads a program.
 pub async fn upload(
+    path: &Path,
+    opts: UploadOpts,
+    after: AfterUpload,
+    connection_opts: &ConnectionOpts,
+) -> miette::Result<UploadOutcome> {
+    let reporter = opts.message_format.reporter();
+
+    // The build blocks the task it runs on, so the connection is opened on its own task to make
+    // progress during the build. If opening it may ask the user something, like which device to
+    // use, it's opened before building instead, so the prompt isn't drawn over Cargo's output.
+    let connect = if opens_without_prompt(connection_opts) {
+        let connection_opts = connection_opts.clone();
+        let mut connecting = AbortOnDrop(tokio::spawn(async move {
+            open_serial_or_remote_connection(&connection_opts).await
+        }));
+
+        async move { (&mut connecting.0).await.expect("connection task panicked") }.boxed()
+    } else {
+        let connection = open_connection(connection_opts).await?;
+        async move { Ok(connection) }.boxed()
+    };
+
+    upload_with(path, opts, after, connect, reporter.as_ref()).await
+}
+
+/// Aborts a task when dropped, such as when the build fails while the connection is opening.
+struct AbortOnDrop<T>(JoinHandle<T>);
+
+impl<T> Drop for AbortOnDrop<T> {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// Like [`upload`], but uploads over the connection returned by `connect`, which runs while the
+/// program builds, and reports progress to `reporter` instead of the one chosen by
+/// `--message-format`.
+///
+/// `connect` may return a borrowed connection, so that callers holding a connection open can
+/// upload over it.
+pub async fn upload_with<C: BorrowMut<DeviceConnection>>(
     path: &Path,
     UploadOpts {
         file,
@@ -589,31 +1225,46 @@ pub async fn upload(
         name,
         description,
         icon,
+        ide,
         uncompressed,
         cargo_opts,
         upload_strategy,
         cold,
+        upload_log,
+        window_size,
+        verify,
+        elf,
+        python,
+        message_format: _,
+        no_stamp,
+        skip_unchanged,
     }: UploadOpts,
     after: AfterUpload,
-) -> miette::Result<SerialConnection> {
-    // Try to open a serialport in the background while we build.
-    let (mut connection, (artifact, package_id)) = tokio::try_join!(
-        async {
-            let mut connection = open_connection().await?;
-
-            // Switch the radio to the download channel if the controller is wireless.
-            switch_to_download_channel(&mut connection).await?;
+    connect: impl Future<Output = Result<C, CliError>>,
+    reporter: &dyn Reporter,
+) -> miette::Result<UploadOutcome<C>> {
+    let is_python = python.is_some();
 
-            Ok::<SerialConnection, CliError>(connection)
-        },
-        async {
+    // Try to open a serialport in the background while we build.
+    let (mut connection, (artifact, elf_artifact, in_memory_binary, package_id)) =
+        tokio::try_join!(connect, async {
             // Get the build artifact we'll be uploading with.
             //
             // The user either directly passed an file through the `--file` argument, or they didn't and we need to run
-            // `cargo build`.
-            Ok(if let Some(file) = file {
+            // `cargo build`. Python scripts are uploaded as-is. ELFs passed through `--elf` are only
+            // converted in memory.
+            Ok(if let Some(script) = python {
+                (script, None, None, None)
+            } else if let Some(elf) = elf {
+                let binary = objcopy(&tokio::fs::read(&elf).await.map_err(CliError::IoError)?)?;
+                reporter.status("Objcopy", &format!("{} (in memory)", elf.display()));
+
+                (elf.clone(), Some(elf), Some(binary), None)
+            } else if let Some(file) = file {
                 if file.extension() == Some(OsStr::new("bin")) {
-                    (file, None)
+                    // Binaries built by cargo-v5 sit next to the ELF they were copied from.
+                    let elf = file.with_extension("");
+                    (file, elf.is_file().then_some(elf), None, None)
                 } else {
                     // If a BIN file wasn't provided, we'll attempt to objcopy it as if it were an ELF.
                     let binary =
@@ -624,24 +1275,96 @@ pub async fn upload(
                     tokio::fs::write(&binary_path, binary)
                         .await
                         .map_err(CliError::IoError)?;
-                    eprintln!("     \x1b[1;92mObjcopy\x1b[0m {}", binary_path.display());
+                    reporter.status("Objcopy", &binary_path.display().to_string());
 
-                    (binary_path, None)
+                    (binary_path, Some(file), None, None)
                 }
             } else {
                 // Run cargo build, then objcopy.
-                build(path, cargo_opts)
+                reporter.event("build-started", serde_json::json!({}));
+
+                build(path, cargo_opts, reporter)
                     .await?
-                    .map(|output| (output.bin_artifact, Some(output.package_id)))
+                    .map(|output| {
+                        (
+                            output.bin_artifact,
+                            Some(output.elf_artifact),
+                            None,
+                            Some(output.package_id),
+                        )
+                    })
                     .ok_or(CliError::NoArtifact)?
             })
-        }
-    )?;
+        })?;
+
+    // Switch the radio to the download channel if the controller is wireless. This waits for the
+    // build, since switching takes several seconds and there's no reason to if the build fails.
+    switch_to_download_channel(connection.borrow_mut()).await?;
+
+    reporter.event("artifact", serde_json::json!({ "path": artifact }));
+
+    // Refuse to upload an artifact that was changed after its provenance manifest was written.
+    if !is_python && let Some(manifest) = verify_provenance(&artifact)? {
+        let commit = manifest["git"]["commit"]
+            .as_str()
+            .unwrap_or("unknown commit");
+        let dirty = manifest["git"]["dirty"].as_bool().unwrap_or_default();
+
+        reporter.status(
+            "Verified",
+            &format!(
+                "{} (built from {commit}{})",
+                artifact.display(),
+                if dirty {
+                    " with uncommitted changes"
+                } else {
+                    ""
+                }
+            ),
+        );
+    }
+
+    // Programs passed with `--file` or `--elf` weren't built here, so what they were built from
+    // comes from the manifest written next to them when they were.
+    let build_info = (!is_python).then(|| read_build_info(&artifact)).flatten();
+    let package_id = package_id.or_else(|| {
+        build_info.as_ref().map(|info| PackageId {
+            repr: info.package_id.clone(),
+        })
+    });
+
+    // Python scripts are read by `upload_python_program` instead.
+    let binary = match in_memory_binary {
+        Some(binary) => Some(binary),
+        None if is_python => None,
+        None => Some(
+            tokio::fs::read(&artifact)
+                .await
+                .map_err(CliError::IoError)?,
+        ),
+    };
+    let size = match &binary {
+        Some(binary) => binary.len() as u64,
+        None => tokio::fs::metadata(&artifact)
+            .await
+            .map_err(CliError::IoError)?
+            .len(),
+    };
 
     // We'll use `cargo-metadata` to parse the output of `cargo metadata` and find valid `Cargo.toml`
     // files in the workspace directory.
-    let cargo_metadata =
-        block_in_place(|| cargo_metadata::MetadataCommand::new().no_deps().exec()).ok();
+    let manifest_path = manifest_path(path);
+    let cargo_metadata = block_in_place(|| {
+        cargo_metadata::MetadataCommand::new()
+            .manifest_path(&manifest_path)
+            .no_deps()
+            .exec()
+    })
+    .ok();
+
+    let target_dir = cargo_metadata
+        .as_ref()
+        .map(|metadata| metadata.target_directory.clone().into_std_path_buf());
 
     // Find which package we're being built from, if we're being built from a package at all.
     let package = cargo_metadata.and_then(|metadata| {
@@ -660,10 +1383,17 @@ pub async fn upload(
     // The program's slot number is absolutely required for uploading. If the slot argument isn't directly provided:
     //
     // - Check for the `package.metadata.v5.slot` field in Cargo.toml.
-    // - If that doesn't exist, directly prompt the user asking what slot to upload to.
+    // - Check for the `upload.slot` field in the user config.
+    // - If that doesn't exist, directly prompt the user asking what slot to upload to, unless
+    //   prompts are disabled.
     let slot = slot
-        .or(metadata.and_then(|m| m.slot))
+        .or(metadata.as_ref().and_then(|m| m.slot))
+        .or(user_config().upload.slot)
         .or_else(|| {
+            if !is_interactive() {
+                return None;
+            }
+
             CustomType::<u8>::new("Choose a program slot to upload to:")
                 .with_validator(|slot: &u8| {
                     Ok(if (1..=8).contains(slot) {
@@ -683,32 +1413,357 @@ pub async fn upload(
         Err(CliError::SlotOutOfRange)?;
     }
 
+    let name = name
+        .or_else(|| {
+            if is_python {
+                artifact
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().to_string())
+            } else {
+                build_info
+                    .as_ref()
+                    .map(|info| info.name.clone())
+                    .or_else(|| package.as_ref().map(|pkg| pkg.name.to_string()))
+            }
+        })
+        .unwrap_or("cargo-v5".to_string());
+    let description = description.unwrap_or_else(|| {
+        let description = build_info
+            .as_ref()
+            .and_then(|info| info.description.clone())
+            .or(package.as_ref().and_then(|pkg| pkg.description.clone()))
+            .unwrap_or("Uploaded with cargo-v5.".to_string());
+
+        // Shows which revision is on the robot in the program's info on the Brain.
+        match build_info.as_ref().and_then(|info| info.git.as_deref()) {
+            Some(git) => format!("{description} ({git})"),
+            None => description,
+        }
+    });
+    // Linked files are packed during the build, so they're only known for packages.
+    let linked_files = match &package {
+        Some(package) if !is_python => {
+            prepare_linked_files(&manifest_path, Some(package.name.as_str()), reporter).await?
+        }
+        _ => None,
+    };
+
+    let upload_strategy = match upload_strategy
+        .or(metadata
+            .as_ref()
+            .and_then(|metadata| metadata.upload_strategy))
+        .or(user_config().upload.upload_strategy)
+    {
+        Some(UploadStrategy::Differential) if linked_files.is_some() => {
+            return Err(CliError::DifferentialWithLinkedFiles.into());
+        }
+        Some(strategy) => strategy,
+        // Differential uploads need the program's only linked file for the patch base.
+        None if linked_files.is_some() => UploadStrategy::Monolith,
+        None => {
+            default_upload_strategy(
+                connection.borrow_mut(),
+                size,
+                package.as_ref(),
+                metadata
+                    .as_ref()
+                    .and_then(|metadata| metadata.differential_threshold)
+                    .unwrap_or(DEFAULT_DIFFERENTIAL_THRESHOLD),
+                reporter,
+            )
+            .await?
+        }
+    };
+
+    let version = match metadata.as_ref().and_then(|metadata| metadata.version) {
+        Some(ProgramVersion::Auto) => {
+            auto_version(
+                connection.borrow_mut(),
+                slot,
+                package.as_ref().map(|package| &package.version),
+                reporter,
+            )
+            .await?
+        }
+        Some(ProgramVersion::Fixed(version)) => version,
+        None => DEFAULT_PROGRAM_VERSION,
+    };
+
+    // Differential uploads keep the slot's base binary under the target directory, keyed by the
+    // artifact's target and profile.
+    let mut bases = PatchBases::for_artifact(target_dir.as_deref(), &artifact);
+    if upload_strategy == UploadStrategy::Differential {
+        bases.collect_garbage(artifact.parent()).await;
+        bases = bases.with_commit(git_describe(path).await);
+    }
+
+    let binary = binary.map(|mut binary| {
+        if !no_stamp {
+            let commit = build_info.as_ref().and_then(|info| info.git.clone());
+            let strategy = match upload_strategy {
+                UploadStrategy::Monolith => "monolith",
+                UploadStrategy::Differential => "differential",
+            };
+            Stamp::new(&binary, commit, strategy).apply(&mut binary);
+        }
+
+        binary
+    });
+
     // Pass information to the upload routine.
-    upload_program(
-        &mut connection,
-        &artifact,
-        after,
+    let window_size = window_size.unwrap_or_else(|| default_window_size(connection.borrow()));
+    let compress = match uncompressed {
+        Some(val) => !val,
+        None => metadata
+            .as_ref()
+            .and_then(|metadata| metadata.compress)
+            .unwrap_or(true),
+    };
+    let started = Instant::now();
+
+    let linked_file = match &linked_files {
+        Some(linked) => Some(
+            upload_linked_file(
+                connection.borrow_mut(),
+                slot,
+                &linked.blob,
+                linked.load_address,
+                compress,
+                window_size,
+                version,
+                reporter,
+            )
+            .await?,
+        ),
+        None => None,
+    };
+
+    let transferred = if is_python {
+        upload_python_program(
+            connection.borrow_mut(),
+            &artifact,
+            after,
+            slot,
+            &name,
+            &description,
+            icon.unwrap_or(ProgramIcon::VexcodePython),
+            window_size,
+            verify,
+            reporter,
+        )
+        .await?
+    } else {
+        upload_program(
+            connection.borrow_mut(),
+            binary.expect("programs are read before uploading"),
+            &bases,
+            linked_file.as_deref(),
+            after,
+            slot,
+            name.clone(),
+            description,
+            icon.or(metadata.as_ref().and_then(|metadata| metadata.icon))
+                .or(user_config().upload.icon)
+                .unwrap_or_default(),
+            ide.or(metadata.as_ref().and_then(|metadata| metadata.ide.clone()))
+                .unwrap_or_else(|| DEFAULT_PROGRAM_TYPE.to_string()),
+            compress,
+            cold,
+            upload_strategy,
+            window_size,
+            verify,
+            skip_unchanged,
+            version,
+            reporter,
+        )
+        .await?
+    };
+
+    record_upload_history(UploadHistoryEntry {
         slot,
-        name.or(package.as_ref().map(|pkg| pkg.name.to_string()))
-            .unwrap_or("cargo-v5".to_string()),
-        description
-            .or(package.as_ref().and_then(|pkg| pkg.description.clone()))
-            .unwrap_or("Uploaded with cargo-v5.".to_string()),
-        icon.or(metadata.and_then(|metadata| metadata.icon))
-            .unwrap_or_default(),
-        "Rust".to_string(), // `program_type` hardcoded for now, maybe configurable in the future.
-        match uncompressed {
-            Some(val) => !val,
-            None => metadata
-                .and_then(|metadata| metadata.compress)
-                .unwrap_or(true),
+        name: name.clone(),
+        size,
+        transferred,
+        strategy: match upload_strategy {
+            _ if is_python => "python",
+            UploadStrategy::Monolith => "monolith",
+            UploadStrategy::Differential => "differential",
         },
-        cold,
-        upload_strategy
-            .or(metadata.and_then(|metadata| metadata.upload_strategy))
-            .unwrap_or_default(),
-    )
-    .await?;
+        link: connection.borrow().connection_type(),
+        duration: started.elapsed(),
+    });
+
+    reporter.event(
+        "upload-finished",
+        serde_json::json!({
+            "slot": slot,
+            "name": name,
+        }),
+    );
+
+    if let Some(log_path) = upload_log.or(metadata.and_then(|metadata| metadata.upload_log)) {
+        record_upload(
+            &log_path,
+            manifest_dir(&manifest_path),
+            UploadRecord {
+                slot,
+                name: name.clone(),
+                size,
+                strategy: upload_strategy,
+                cold,
+            },
+            reporter,
+        )
+        .await?;
+    }
+
+    Ok(UploadOutcome {
+        connection,
+        slot,
+        name,
+        artifact,
+        elf_artifact,
+        strategy: upload_strategy,
+    })
+}
+
+/// Information about a completed upload, written to an upload log.
+struct UploadRecord {
+    slot: u8,
+    name: String,
+    size: u64,
+    strategy: UploadStrategy,
+    cold: bool,
+}
+
+/// Starts the program already installed in the slot that [`upload`] would upload to, without
+/// building or uploading anything.
+pub async fn run_installed(
+    path: &Path,
+    slot: Option<u8>,
+    package: Option<&str>,
+    connection_opts: &ConnectionOpts,
+) -> miette::Result<DeviceConnection> {
+    let manifest_path = manifest_path(path);
+    let package = block_in_place(|| {
+        cargo_metadata::MetadataCommand::new()
+            .manifest_path(&manifest_path)
+            .no_deps()
+            .exec()
+    })
+    .ok()
+    .and_then(|metadata| match package {
+        Some(spec) => metadata
+            .packages
+            .iter()
+            .find(|p| p.name.as_str() == spec)
+            .cloned(),
+        None => metadata.root_package().cloned(),
+    });
+    let metadata = package.as_ref().map(Metadata::new).transpose()?;
+
+    let slot = slot
+        .or(metadata.and_then(|metadata| metadata.slot))
+        .or(user_config().upload.slot)
+        .ok_or(CliError::NoSlot)?;
+
+    let mut connection = open_connection(connection_opts).await?;
+    run_slot(&mut connection, slot).await?;
 
     Ok(connection)
 }
+
+/// Describes the current commit of the repository at `path`, if there is one.
+async fn git_describe(path: &Path) -> Option<String> {
+    let output = tokio::process::Command::new("git")
+        .args(["describe", "--always", "--dirty", "--tags"])
+        .current_dir(path)
+        .output()
+        .await
+        .ok()?;
+
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Escapes `text` for a cell of a Markdown table, so that pipes and line breaks in it can't add
+/// columns or rows.
+fn markdown_cell(text: &str) -> String {
+    text.replace('|', "\\|")
+        .replace("\r\n", " ")
+        .replace(['\r', '\n'], " ")
+}
+
+/// Appends an entry to the upload log at `log_path`.
+///
+/// Logs ending in `.json` are stored as a JSON array of entries. Any other path is treated
+/// as a Markdown file containing a table with one row per upload.
+async fn record_upload(
+    log_path: &Path,
+    project_path: &Path,
+    record: UploadRecord,
+    reporter: &dyn Reporter,
+) -> Result<(), CliError> {
+    let date = chrono::Utc::now()
+        .format("%Y-%m-%d %H:%M:%S UTC")
+        .to_string();
+    let git = git_describe(project_path).await;
+    let strategy = match (record.strategy, record.cold) {
+        (UploadStrategy::Monolith, _) => "monolith",
+        (UploadStrategy::Differential, false) => "differential",
+        (UploadStrategy::Differential, true) => "differential (cold)",
+    };
+
+    let existing = match tokio::fs::read_to_string(log_path).await {
+        Ok(contents) => Some(contents),
+        Err(e) if e.kind() == ErrorKind::NotFound => None,
+        Err(e) => return Err(CliError::IoError(e)),
+    };
+
+    let contents = if log_path.extension() == Some(OsStr::new("json")) {
+        // Leave a log that can't be read alone rather than replacing it with this entry.
+        let mut entries = match existing {
+            Some(contents) => serde_json::from_str::<Vec<serde_json::Value>>(&contents)
+                .map_err(|_| CliError::MalformedUploadLog(log_path.to_path_buf()))?,
+            None => Vec::new(),
+        };
+
+        entries.push(serde_json::json!({
+            "date": date,
+            "slot": record.slot,
+            "name": record.name,
+            "git": git,
+            "size": record.size,
+            "strategy": strategy,
+        }));
+
+        serde_json::to_string_pretty(&entries).unwrap() + "\n"
+    } else {
+        let mut contents = existing.unwrap_or_else(|| {
+            "# Uploads\n\n| Date | Slot | Program | Version | Size | Strategy |\n| --- | --- | --- | --- | --- | --- |\n".to_string()
+        });
+
+        let row = [
+            date,
+            record.slot.to_string(),
+            record.name,
+            git.unwrap_or_else(|| "-".to_string()),
+            format_size(record.size, BINARY),
+            strategy.to_string(),
+        ]
+        .iter()
+        .map(|cell| markdown_cell(cell))
+        .collect::<Vec<_>>()
+        .join(" | ");
+        contents.push_str(&format!("| {row} |\n"));
+
+        contents
+    };
+
+    tokio::fs::write(log_path, contents).await?;
+    reporter.status("Logged", &format!("upload to {}", log_path.display()));
+
+    Ok(())
+}
@@ -0,0 +1,176 @@
+//! The small subset of the WebSocket protocol (RFC 6455) that cargo-v5's servers need: accepting
+//! a client's handshake, sending unfragmented frames, and reading the frames clients send.
+
+use std::io;
+
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use ring::digest::{SHA1_FOR_LEGACY_USE_ONLY, digest};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{
+        TcpStream,
+        tcp::{OwnedReadHalf, OwnedWriteHalf},
+    },
+};
+
+/// GUID that servers append to a client's key to accept a WebSocket handshake.
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// Longest line of a handshake request that's accepted. Browsers keep headers well under this.
+const MAX_HEADER_LEN: u64 = 8 * 1024;
+
+/// Most lines of a handshake request that are accepted, including the request line.
+const MAX_HEADERS: usize = 64;
+
+pub const OPCODE_TEXT: u8 = 0x1;
+pub const OPCODE_CLOSE: u8 = 0x8;
+pub const OPCODE_PING: u8 = 0x9;
+pub const OPCODE_PONG: u8 = 0xA;
+
+/// Headers of a client's request to open a WebSocket, checked before accepting it.
+#[derive(Debug, Default)]
+pub struct HandshakeRequest {
+    /// The page that opened the WebSocket. Browsers always send this, and other clients usually
+    /// don't.
+    pub origin: Option<String>,
+    /// The `Authorization` header, if any.
+    pub authorization: Option<String>,
+}
+
+/// Completes a client's WebSocket handshake if `authorize` allows its request, returning the two
+/// halves of the connection.
+///
+/// Returns `None` if the client disconnected, didn't ask for a WebSocket, sent a request larger
+/// than [`MAX_HEADER_LEN`] and [`MAX_HEADERS`] allow, or wasn't allowed.
+pub async fn accept(
+    client: TcpStream,
+    authorize: impl FnOnce(&HandshakeRequest) -> bool,
+) -> io::Result<Option<(BufReader<OwnedReadHalf>, OwnedWriteHalf)>> {
+    let (reader, mut writer) = client.into_split();
+    let mut reader = BufReader::new(reader);
+
+    let mut request = HandshakeRequest::default();
+    let mut key = None;
+    let mut lines = 0;
+    loop {
+        let mut header = String::new();
+        if (&mut reader)
+            .take(MAX_HEADER_LEN)
+            .read_line(&mut header)
+            .await?
+            == 0
+        {
+            return Ok(None);
+        }
+
+        lines += 1;
+        if !header.ends_with('\n') || lines > MAX_HEADERS {
+            writer
+                .write_all(
+                    b"HTTP/1.1 431 Request Header Fields Too Large\r\nContent-Length: 0\r\n\r\n",
+                )
+                .await?;
+            return Ok(None);
+        }
+
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+
+        if let Some((name, value)) = header.split_once(':') {
+            let value = Some(value.trim().to_string());
+            match name.to_ascii_lowercase().as_str() {
+                "sec-websocket-key" => key = value,
+                "origin" => request.origin = value,
+                "authorization" => request.authorization = value,
+                _ => {}
+            }
+        }
+    }
+
+    if !authorize(&request) {
+        writer
+            .write_all(b"HTTP/1.1 403 Forbidden\r\nContent-Length: 0\r\n\r\n")
+            .await?;
+        return Ok(None);
+    }
+
+    let Some(key) = key else {
+        writer
+            .write_all(b"HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\n\r\n")
+            .await?;
+        return Ok(None);
+    };
+
+    let accept = BASE64.encode(digest(
+        &SHA1_FOR_LEGACY_USE_ONLY,
+        format!("{key}{WEBSOCKET_GUID}").as_bytes(),
+    ));
+    writer
+        .write_all(
+            format!(
+                "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {accept}\r\n\r\n"
+            )
+            .as_bytes(),
+        )
+        .await?;
+
+    Ok(Some((reader, writer)))
+}
+
+/// Encodes a single unfragmented, unmasked frame, as sent by servers.
+pub fn frame(opcode: u8, payload: &[u8]) -> Vec<u8> {
+    let mut frame = vec![0x80 | opcode];
+
+    match payload.len() {
+        len @ 0..=125 => frame.push(len as u8),
+        len @ 126..=0xFFFF => {
+            frame.push(126);
+            frame.extend((len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(127);
+            frame.extend((len as u64).to_be_bytes());
+        }
+    }
+
+    frame.extend(payload);
+    frame
+}
+
+/// Reads a frame sent by a client, returning its opcode and unmasked payload.
+pub async fn read_frame(reader: &mut (impl AsyncReadExt + Unpin)) -> io::Result<(u8, Vec<u8>)> {
+    let mut header = [0; 2];
+    reader.read_exact(&mut header).await?;
+
+    let opcode = header[0] & 0x0F;
+    let masked = header[1] & 0x80 != 0;
+    let len = match header[1] & 0x7F {
+        126 => reader.read_u16().await? as u64,
+        127 => reader.read_u64().await?,
+        len => len as u64,
+    };
+
+    let mut mask = [0; 4];
+    if masked {
+        reader.read_exact(&mut mask).await?;
+    }
+
+    // Control frames are small, and clients have no reason to send large messages.
+    if len > 0xFFFF {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "WebSocket frame is too large",
+        ));
+    }
+    let mut payload = vec![0; len as usize];
+    reader.read_exact(&mut payload).await?;
+    if masked {
+        for (i, byte) in payload.iter_mut().enumerate() {
+            *byte ^= mask[i % 4];
+        }
+    }
+
+    Ok((opcode, payload))
+}
@@ -0,0 +1,244 @@
+//! User-level defaults for command-line options.
+//!
+//! Settings are read from `~/.config/cargo-v5/config.toml` (or `$XDG_CONFIG_HOME/cargo-v5`, or
+//! the file named by `CARGO_V5_CONFIG`). They have the lowest precedence: flags passed on the
+//! command line win over `package.metadata.v5`, which wins over the user config.
+//!
+//! ```toml
+//! ascii = false
+//! device = "/dev/ttyACM0"
+//!
+//! [upload]
+//! slot = 1
+//! icon = "cool-x"
+//! upload-strategy = "differential"
+//!
+//! [terminal]
+//! on-exit = "detach"
+//!
+//! [new]
+//! template = "~/templates/team-template.tar.gz"
+//! ```
+
+use std::{
+    env, fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use clap::ValueEnum;
+use toml_edit::{DocumentMut, Item};
+
+use crate::{
+    commands::{
+        program::OnExit,
+        upload::{ProgramIcon, UploadStrategy},
+    },
+    errors::CliError,
+};
+
+/// Defaults for `cargo v5 upload` and the commands built on it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UploadDefaults {
+    pub slot: Option<u8>,
+    pub icon: Option<ProgramIcon>,
+    pub upload_strategy: Option<UploadStrategy>,
+}
+
+/// Defaults for commands that show a program's output.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TerminalDefaults {
+    /// What `cargo v5 run` does with the program when the terminal is closed.
+    pub on_exit: Option<OnExit>,
+}
+
+/// Defaults for `cargo v5 new`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NewDefaults {
+    /// A directory or `.tar.gz` archive to create projects from instead of vexide-template.
+    pub template: Option<PathBuf>,
+}
+
+/// Settings from the user's config file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UserConfig {
+    /// Only use plain ASCII characters in output.
+    pub ascii: Option<bool>,
+
+    /// Serial port or Brain ID of the device to connect to.
+    pub device: Option<String>,
+
+    pub upload: UploadDefaults,
+    pub terminal: TerminalDefaults,
+    pub new: NewDefaults,
+}
+
+static USER_CONFIG: OnceLock<UserConfig> = OnceLock::new();
+
+/// Returns the path of the user config file, whether or not it exists.
+pub fn config_path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("CARGO_V5_CONFIG") {
+        return Some(PathBuf::from(path));
+    }
+
+    let config_dir = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| env::home_dir().map(|home| home.join(".config")))?;
+
+    Some(config_dir.join("cargo-v5").join("config.toml"))
+}
+
+/// Reads the user config file, making it available through [`user_config`].
+///
+/// A missing config file is the same as an empty one.
+pub fn load_user_config() -> Result<&'static UserConfig, CliError> {
+    if let Some(config) = USER_CONFIG.get() {
+        return Ok(config);
+    }
+
+    let config = match config_path() {
+        Some(path) if path.is_file() => UserConfig::parse(&path, &std::fs::read_to_string(&path)?)?,
+        _ => UserConfig::default(),
+    };
+
+    Ok(USER_CONFIG.get_or_init(|| config))
+}
+
+/// Returns the user config loaded by [`load_user_config`], or the defaults if it hasn't been
+/// loaded.
+pub fn user_config() -> &'static UserConfig {
+    USER_CONFIG.get_or_init(UserConfig::default)
+}
+
+/// Returns the directory for files that only last as long as a running command, such as the
+/// daemon's token, whether or not it exists.
+pub fn runtime_dir() -> Option<PathBuf> {
+    env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| env::home_dir().map(|home| home.join(".config")))
+        .map(|dir| dir.join("cargo-v5"))
+}
+
+/// Writes `contents` to `path` so that only the current user can read it, creating its directory
+/// if needed.
+pub fn write_private_file(path: &Path, contents: &str) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(path)?;
+
+    // The mode only applies to new files, so an existing one is locked down too.
+    #[cfg(unix)]
+    file.set_permissions(std::os::unix::fs::PermissionsExt::from_mode(0o600))?;
+
+    file.write_all(contents.as_bytes())
+}
+
+/// Reads values out of a parsed config file, reporting errors against its path.
+struct ConfigReader<'a> {
+    path: &'a Path,
+    document: DocumentMut,
+}
+
+impl ConfigReader<'_> {
+    fn malformed(&self, message: String) -> CliError {
+        CliError::MalformedConfig(self.path.to_path_buf(), message)
+    }
+
+    /// Looks up `key`, which is either a top-level key or a `table.key` pair.
+    fn get(&self, key: &str) -> Option<&Item> {
+        match key.split_once('.') {
+            Some((table, key)) => self.document.get(table)?.get(key),
+            None => self.document.get(key),
+        }
+    }
+
+    fn bool(&self, key: &str) -> Result<Option<bool>, CliError> {
+        self.get(key)
+            .map(|item| {
+                item.as_bool()
+                    .ok_or_else(|| self.malformed(format!("`{key}` should be a boolean")))
+            })
+            .transpose()
+    }
+
+    fn string(&self, key: &str) -> Result<Option<String>, CliError> {
+        self.get(key)
+            .map(|item| {
+                item.as_str()
+                    .map(str::to_string)
+                    .ok_or_else(|| self.malformed(format!("`{key}` should be a string")))
+            })
+            .transpose()
+    }
+
+    fn value_enum<T: ValueEnum>(&self, key: &str) -> Result<Option<T>, CliError> {
+        self.string(key)?
+            .map(|value| {
+                T::from_str(&value, false).map_err(|_| {
+                    self.malformed(format!("`{value}` is not a valid value for `{key}`"))
+                })
+            })
+            .transpose()
+    }
+
+    fn slot(&self, key: &str) -> Result<Option<u8>, CliError> {
+        self.get(key)
+            .map(|item| {
+                item.as_integer()
+                    .filter(|slot| (1..=8).contains(slot))
+                    .map(|slot| slot as u8)
+                    .ok_or_else(|| {
+                        self.malformed(format!("`{key}` should be a number from 1 to 8"))
+                    })
+            })
+            .transpose()
+    }
+
+    /// Reads a path, expanding a leading `~/` to the home directory. Relative paths are relative
+    /// to the config file.
+    fn path(&self, key: &str) -> Result<Option<PathBuf>, CliError> {
+        Ok(self.string(key)?.map(|path| {
+            let path = match path.strip_prefix("~/") {
+                Some(rest) => env::home_dir().unwrap_or_default().join(rest),
+                None => PathBuf::from(path),
+            };
+
+            self.path.parent().unwrap_or(Path::new(".")).join(path)
+        }))
+    }
+}
+
+impl UserConfig {
+    /// Parses the contents of the config file at `path`.
+    pub fn parse(path: &Path, contents: &str) -> Result<Self, CliError> {
+        let document = contents.parse::<DocumentMut>().map_err(|err| {
+            CliError::MalformedConfig(path.to_path_buf(), err.message().to_string())
+        })?;
+        let config = ConfigReader { path, document };
+
+        Ok(Self {
+            ascii: config.bool("ascii")?,
+            device: config.string("device")?,
+            upload: UploadDefaults {
+                slot: config.slot("upload.slot")?,
+                icon: config.value_enum("upload.icon")?,
+                upload_strategy: config.value_enum("upload.upload-strategy")?,
+            },
+            terminal: TerminalDefaults {
+                on_exit: config.value_enum("terminal.on-exit")?,
+            },
+            new: NewDefaults {
+                template: config.path("new.template")?,
+            },
+        })
+    }
+}
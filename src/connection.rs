@@ -1,29 +1,371 @@
+use clap::Args;
 use core::fmt;
+use futures::future::join_all;
 use inquire::Select;
 use log::info;
+use miette::Diagnostic;
 use std::time::Duration;
+use thiserror::Error;
 use tokio::{task::spawn_blocking, time::sleep};
 use vex_v5_serial::{
-    Connection,
+    CheckHeader, Connection, ConnectionType,
     protocol::{
+        Decode, DecodeError, Encode, FixedStringSizeError,
         cdc::{ProductType, SystemVersionPacket, SystemVersionReplyPacket},
         cdc2::{
+            Cdc2Ack,
             file::{FileControlGroup, FileControlPacket, FileControlReplyPacket, RadioChannel},
             system::{
                 RadioStatusPacket, RadioStatusReplyPacket, SystemFlagsPacket,
-                SystemFlagsReplyPacket,
+                SystemFlagsReplyPacket, SystemStatusPacket, SystemStatusReplyPacket,
             },
         },
     },
-    serial::{self, SerialConnection, SerialDevice},
+    serial::{self, SerialConnection, SerialDevice, SerialError},
 };
 
-use crate::errors::CliError;
+#[cfg(feature = "bluetooth")]
+use vex_v5_serial::bluetooth::{self, BluetoothConnection, BluetoothError};
+
+use crate::{
+    capture::{self, Captured, Direction, ReplayConnection},
+    commands::{firmware::format_version, key_value::kv_get},
+    errors::CliError,
+    interactive::ensure_interactive,
+    remote::{RemoteConnection, TOKEN_ENV_VAR},
+};
+
+/// Options controlling which device `cargo-v5` connects to.
+#[derive(Args, Debug, Clone, Default)]
+pub struct ConnectionOpts {
+    /// Connect to a Brain over Bluetooth rather than USB.
+    #[cfg_attr(feature = "bluetooth", arg(long, global = true))]
+    #[cfg_attr(not(feature = "bluetooth"), arg(skip = false))]
+    pub bluetooth: bool,
+
+    /// Bluetooth address of the Brain to connect to. Implies `--bluetooth`.
+    #[cfg_attr(
+        feature = "bluetooth",
+        arg(long, global = true, value_name = "ADDRESS")
+    )]
+    #[cfg_attr(not(feature = "bluetooth"), arg(skip))]
+    pub mac: Option<String>,
+
+    /// Serial port or Brain ID of the device to connect to (see `cargo v5 devices`).
+    #[arg(long, global = true, value_name = "ID")]
+    pub device: Option<String>,
+
+    /// Use the device attached to another machine running `cargo v5 serve`.
+    #[arg(long, global = true, value_name = "HOST:PORT")]
+    pub remote: Option<String>,
+
+    /// Token printed by `cargo v5 serve`. Defaults to the `CARGO_V5_REMOTE_TOKEN` environment
+    /// variable.
+    #[arg(long, global = true, value_name = "TOKEN", requires = "remote")]
+    pub remote_token: Option<String>,
+}
+
+/// An open connection to a V5 device over any supported transport.
+pub enum DeviceConnection {
+    Serial(SerialConnection),
+    #[cfg(feature = "bluetooth")]
+    Bluetooth(BluetoothConnection),
+    /// Replies recorded in a capture file, played back by `cargo v5 replay`.
+    Replay(ReplayConnection),
+    /// A device attached to another machine, tunnelled over TCP by `cargo v5 serve`.
+    Remote(RemoteConnection),
+}
+
+impl DeviceConnection {
+    async fn recv_packet<P: Decode + CheckHeader>(
+        &mut self,
+        timeout: Duration,
+    ) -> Result<P, ConnectionError> {
+        Ok(match self {
+            Self::Serial(connection) => connection.recv(timeout).await?,
+            #[cfg(feature = "bluetooth")]
+            Self::Bluetooth(connection) => connection.recv(timeout).await?,
+            Self::Replay(connection) => connection.recv(timeout).await?,
+            Self::Remote(connection) => connection.recv(timeout).await?,
+        })
+    }
+}
+
+impl Connection for DeviceConnection {
+    type Error = ConnectionError;
+
+    fn connection_type(&self) -> ConnectionType {
+        match self {
+            Self::Serial(connection) => connection.connection_type(),
+            #[cfg(feature = "bluetooth")]
+            Self::Bluetooth(connection) => connection.connection_type(),
+            Self::Replay(connection) => connection.connection_type(),
+            Self::Remote(connection) => connection.connection_type(),
+        }
+    }
+
+    async fn send(&mut self, packet: impl Encode) -> Result<(), ConnectionError> {
+        capture::record_packet(&packet);
+
+        match self {
+            Self::Serial(connection) => connection.send(packet).await?,
+            #[cfg(feature = "bluetooth")]
+            Self::Bluetooth(connection) => connection.send(packet).await?,
+            Self::Replay(connection) => connection.send(packet).await?,
+            Self::Remote(connection) => connection.send(packet).await?,
+        }
+        Ok(())
+    }
+
+    async fn recv<P: Decode + CheckHeader>(
+        &mut self,
+        timeout: Duration,
+    ) -> Result<P, ConnectionError> {
+        if !capture::is_capturing() {
+            return self.recv_packet(timeout).await;
+        }
+
+        let Captured { bytes, packet } = self.recv_packet::<Captured<P>>(timeout).await?;
+        capture::record(Direction::Rx, &bytes);
+
+        Ok(packet)
+    }
+
+    async fn read_user(&mut self, buf: &mut [u8]) -> Result<usize, ConnectionError> {
+        let size = match self {
+            Self::Serial(connection) => connection.read_user(buf).await?,
+            #[cfg(feature = "bluetooth")]
+            Self::Bluetooth(connection) => connection.read_user(buf).await?,
+            Self::Replay(connection) => connection.read_user(buf).await?,
+            Self::Remote(connection) => connection.read_user(buf).await?,
+        };
+
+        if size > 0 {
+            capture::record(Direction::UserRx, &buf[..size]);
+        }
+        Ok(size)
+    }
+
+    async fn write_user(&mut self, buf: &[u8]) -> Result<usize, ConnectionError> {
+        let size = match self {
+            Self::Serial(connection) => connection.write_user(buf).await?,
+            #[cfg(feature = "bluetooth")]
+            Self::Bluetooth(connection) => connection.write_user(buf).await?,
+            Self::Replay(connection) => connection.write_user(buf).await?,
+            Self::Remote(connection) => connection.write_user(buf).await?,
+        };
+
+        capture::record(Direction::UserTx, &buf[..size]);
+        Ok(size)
+    }
+
+    /// Sends a packet and waits for a response, allowing slower links more time and more retries
+    /// (see [`LinkTiming`]).
+    async fn handshake<D: Decode + CheckHeader>(
+        &mut self,
+        timeout: Duration,
+        retries: usize,
+        packet: impl Encode + Clone,
+    ) -> Result<D, ConnectionError> {
+        let timing = LinkTiming::of(self.connection_type());
+        let timeout = timing.timeout(timeout);
+        let retries = timing.retries(retries);
+
+        let mut last_error = None;
+        for attempt in 0..=retries {
+            self.send(packet.clone()).await?;
+            match self.recv::<D>(timeout).await {
+                Ok(decoded) => return Ok(decoded),
+                Err(err) => {
+                    log::debug!(
+                        "Attempt {} of {} waiting for {} failed: {err}",
+                        attempt + 1,
+                        retries + 1,
+                        std::any::type_name::<D>()
+                    );
+                    last_error = Some(err);
+                }
+            }
+        }
+
+        Err(last_error.unwrap())
+    }
+}
+
+/// The longest a handshake waits for a reply, however slow the link.
+///
+/// Unclaimed replies are discarded after two seconds, so waiting any longer wouldn't help.
+const MAX_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How much slack handshakes get on a particular kind of link.
+///
+/// Timeouts throughout cargo-v5 are tuned for a Brain plugged in over USB. Replies relayed by a
+/// controller's radio or over Bluetooth routinely take several times longer, so those links scale
+/// up every handshake's timeout and retry budget rather than spuriously failing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LinkTiming {
+    /// Factor applied to handshake timeouts.
+    pub timeout_scale: u32,
+
+    /// Retries added to every handshake.
+    pub extra_retries: usize,
+}
+
+impl LinkTiming {
+    pub fn of(connection_type: ConnectionType) -> Self {
+        match connection_type {
+            ConnectionType::Wired => Self {
+                timeout_scale: 1,
+                extra_retries: 0,
+            },
+            ConnectionType::Controller => Self {
+                timeout_scale: 3,
+                extra_retries: 1,
+            },
+            ConnectionType::Bluetooth => Self {
+                timeout_scale: 4,
+                extra_retries: 2,
+            },
+        }
+    }
+
+    /// Scales a timeout tuned for USB to this link.
+    pub fn timeout(&self, timeout: Duration) -> Duration {
+        (timeout * self.timeout_scale).clamp(timeout, MAX_HANDSHAKE_TIMEOUT.max(timeout))
+    }
+
+    /// Scales a retry budget tuned for USB to this link.
+    pub fn retries(&self, retries: usize) -> usize {
+        retries + self.extra_retries
+    }
+}
+
+impl From<SerialConnection> for DeviceConnection {
+    fn from(connection: SerialConnection) -> Self {
+        Self::Serial(connection)
+    }
+}
+
+#[cfg(feature = "bluetooth")]
+impl From<BluetoothConnection> for DeviceConnection {
+    fn from(connection: BluetoothConnection) -> Self {
+        Self::Bluetooth(connection)
+    }
+}
+
+/// An error that occurred while communicating with a device over a [`DeviceConnection`].
+#[derive(Error, Diagnostic, Debug)]
+pub enum ConnectionError {
+    #[error(transparent)]
+    #[diagnostic(code(cargo_v5::serial_error))]
+    Serial(#[from] SerialError),
+
+    #[cfg(feature = "bluetooth")]
+    #[error(transparent)]
+    #[diagnostic(code(cargo_v5::bluetooth_error))]
+    Bluetooth(#[from] BluetoothError),
+
+    #[error("Packet decoding error: {0}")]
+    #[diagnostic(code(cargo_v5::decode_error))]
+    Decode(#[from] DecodeError),
+
+    #[error("NACK received: {0:?}")]
+    #[diagnostic(code(cargo_v5::cdc2_nack))]
+    Nack(#[from] Cdc2Ack),
+
+    #[error(transparent)]
+    #[diagnostic(code(cargo_v5::fixed_string_size_error))]
+    FixedStringSizeError(#[from] FixedStringSizeError),
+
+    #[error("The capture being replayed has no more replies.")]
+    #[diagnostic(
+        code(cargo_v5::replay_exhausted),
+        help(
+            "The replayed command asked for a reply that wasn't recorded, so it's running differently than when it was captured."
+        )
+    )]
+    ReplayExhausted,
+
+    #[error("Timed out waiting for the remote device to reply.")]
+    #[diagnostic(code(cargo_v5::remote_timeout))]
+    RemoteTimeout,
+
+    #[error("The remote device failed: {0}")]
+    #[diagnostic(code(cargo_v5::remote_error))]
+    Remote(String),
+
+    #[error("Lost the connection to the remote device: {0}")]
+    #[diagnostic(code(cargo_v5::remote_disconnected))]
+    RemoteDisconnected(#[from] std::io::Error),
+}
+
+/// Whether [`open_connection`] can open a connection without asking the user anything, such as
+/// which of several devices to use or a Bluetooth PIN.
+pub fn opens_without_prompt(opts: &ConnectionOpts) -> bool {
+    if opts.bluetooth || opts.mac.is_some() {
+        return false;
+    }
+
+    opts.remote.is_some()
+        || opts.device.is_some()
+        || serial::find_devices().is_ok_and(|devices| devices.len() <= 1)
+}
+
+/// Opens a connection to a V5 device, prompting the user to pick one if several are available.
+///
+/// While a capture is being replayed, this returns the replay connection instead.
+pub async fn open_connection(opts: &ConnectionOpts) -> Result<DeviceConnection, CliError> {
+    #[cfg(feature = "bluetooth")]
+    if opts.bluetooth || opts.mac.is_some() {
+        return open_with(open_bluetooth_connection(opts.mac.as_deref())).await;
+    }
+
+    open_serial_or_remote_connection(opts).await
+}
+
+/// Like [`open_connection`], but ignores the Bluetooth options.
+///
+/// Opening a Bluetooth connection can't move between tasks, but this can, so it can be spawned to
+/// open the connection in the background.
+pub async fn open_serial_or_remote_connection(
+    opts: &ConnectionOpts,
+) -> Result<DeviceConnection, CliError> {
+    open_with(open_device_connection(opts)).await
+}
+
+/// Runs `open`, unless a capture is being replayed, and records the connection it opens.
+async fn open_with(
+    open: impl Future<Output = Result<DeviceConnection, CliError>>,
+) -> Result<DeviceConnection, CliError> {
+    if let Some(replay) = capture::take_replay()? {
+        return Ok(DeviceConnection::Replay(replay));
+    }
+
+    let connection = open.await?;
+    capture::record_open(connection.connection_type());
+
+    Ok(connection)
+}
+
+async fn open_device_connection(opts: &ConnectionOpts) -> Result<DeviceConnection, CliError> {
+    if let Some(addr) = &opts.remote {
+        let token = match &opts.remote_token {
+            Some(token) => token.clone(),
+            None => std::env::var(TOKEN_ENV_VAR).map_err(|_| CliError::RemoteTokenRequired)?,
+        };
+
+        return Ok(DeviceConnection::Remote(
+            RemoteConnection::connect(addr, &token).await?,
+        ));
+    }
 
-pub async fn open_connection() -> Result<SerialConnection, CliError> {
     // Find all vex devices on serial ports.
     let devices = serial::find_devices().map_err(CliError::SerialError)?;
 
+    if let Some(id) = &opts.device {
+        return open_device_by_id(devices, id).await;
+    }
+
     let device = match devices.len() {
         // No devices connected
         0 => return Err(CliError::NoDevice),
@@ -33,9 +375,16 @@ pub async fn open_connection() -> Result<SerialConnection, CliError> {
 
         // Multiple devices connected at once. Prompt the user asking which one they want.
         _ => {
+            ensure_interactive(
+                "choose which of the connected devices to use",
+                "Pass `--device` with a serial port or Brain ID from `cargo v5 devices`.",
+            )?;
+
             /// Wrapper around SerialDevice to provide a Display implementation for the prompt choices.
             struct SerialDeviceChoice {
                 inner: SerialDevice,
+                summary: DeviceSummary,
+                connection: Option<SerialConnection>,
             }
 
             impl fmt::Display for SerialDeviceChoice {
@@ -45,10 +394,10 @@ pub async fn open_connection() -> Result<SerialConnection, CliError> {
                             user_port,
                             system_port,
                         } => {
-                            write!(f, "Brain on {user_port}, {system_port}")
+                            write!(f, "Brain{} on {user_port}, {system_port}", self.summary)
                         }
                         SerialDevice::Controller { system_port } => {
-                            write!(f, "Controller on {system_port}")
+                            write!(f, "Controller{} on {system_port}", self.summary)
                         }
                         SerialDevice::Unknown { system_port } => {
                             write!(f, "<unknown> on {system_port}")
@@ -57,11 +406,247 @@ pub async fn open_connection() -> Result<SerialConnection, CliError> {
                 }
             }
 
+            // Connect to every device at once so that they can be described in the prompt. The
+            // chosen device's connection is reused afterwards.
+            let tasks = devices
+                .into_iter()
+                .map(|device| async move {
+                    let mut connection = spawn_blocking({
+                        let device = device.clone();
+                        move || device.connect(Duration::from_secs(5))
+                    })
+                    .await
+                    .unwrap()
+                    .ok();
+
+                    let summary = match &mut connection {
+                        Some(connection) => device_summary(connection).await,
+                        None => DeviceSummary::default(),
+                    };
+
+                    SerialDeviceChoice {
+                        inner: device,
+                        summary,
+                        connection,
+                    }
+                })
+                .collect::<Vec<_>>();
+            let choices = join_all(tasks).await;
+
+            let choice = Select::new("Choose a device to connect to", choices).prompt()?;
+
+            if let Some(connection) = choice.connection {
+                return Ok(DeviceConnection::Serial(connection));
+            }
+
+            choice.inner
+        }
+    };
+
+    // Open a connection to the device.
+    spawn_blocking(move || {
+        device
+            .connect(Duration::from_secs(5))
+            .map(DeviceConnection::Serial)
+            .map_err(CliError::SerialError)
+    })
+    .await
+    .unwrap()
+}
+
+/// Identifying details of a Brain, shown when choosing between several devices.
+#[derive(Debug, Default, Clone)]
+struct DeviceSummary {
+    name: Option<String>,
+    team: Option<String>,
+    version: Option<String>,
+}
+
+impl fmt::Display for DeviceSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(name) = &self.name {
+            write!(f, " '{name}'")?;
+        }
+
+        let details = [
+            self.team.as_ref().map(|team| format!("team {team}")),
+            self.version
+                .as_ref()
+                .map(|version| format!("VEXos {version}")),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+
+        if !details.is_empty() {
+            write!(f, " ({})", details.join(", "))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Reads a device's name, team number, and VEXos version, leaving out any that don't respond
+/// quickly.
+async fn device_summary(connection: &mut SerialConnection) -> DeviceSummary {
+    let summary = async {
+        let mut summary = DeviceSummary {
+            name: kv_get(connection, "robotname").await.ok().flatten(),
+            team: kv_get(connection, "teamnumber").await.ok().flatten(),
+            version: None,
+        };
+
+        if let Ok(reply) = connection
+            .handshake::<SystemStatusReplyPacket>(
+                Duration::from_millis(500),
+                1,
+                SystemStatusPacket::new(()),
+            )
+            .await
+            && let Ok(status) = reply.payload
+        {
+            summary.version = status.system_version.map(format_version);
+        }
+
+        summary
+    };
+
+    tokio::time::timeout(Duration::from_secs(2), summary)
+        .await
+        .unwrap_or_default()
+}
+
+/// Opens the serial device identified by `id`, which is either one of its serial ports or the
+/// ID of the Brain (as reported by [`brain_id`]).
+async fn open_device_by_id(
+    devices: Vec<SerialDevice>,
+    id: &str,
+) -> Result<DeviceConnection, CliError> {
+    let by_port = devices
+        .iter()
+        .find(|device| {
+            device.system_port().eq_ignore_ascii_case(id)
+                || device
+                    .user_port()
+                    .is_some_and(|port| port.eq_ignore_ascii_case(id))
+        })
+        .cloned();
+    let matches_port = by_port.is_some();
+
+    // Devices can be picked by port without connecting to them, but finding a Brain by its ID
+    // requires asking each one.
+    let candidates = match by_port {
+        Some(device) => vec![device],
+        None => devices,
+    };
+
+    for device in candidates {
+        let mut connection = match spawn_blocking(move || device.connect(Duration::from_secs(5)))
+            .await
+            .unwrap()
+        {
+            Ok(connection) => connection,
+            Err(err) if !matches_port => {
+                log::debug!("Skipping device that failed to open: {err}");
+                continue;
+            }
+            Err(err) => return Err(CliError::SerialError(err)),
+        };
+
+        if matches_port {
+            return Ok(DeviceConnection::Serial(connection));
+        }
+
+        match brain_id(&mut connection).await {
+            Ok(Some(brain_id)) if brain_id_matches(brain_id, id) => {
+                return Ok(DeviceConnection::Serial(connection));
+            }
+            Ok(_) => {}
+            Err(err) => log::debug!("Failed to read Brain ID: {err}"),
+        }
+    }
+
+    Err(CliError::DeviceNotFound(id.to_string()))
+}
+
+fn brain_id_matches(brain_id: u32, id: &str) -> bool {
+    let id = id
+        .strip_prefix("0x")
+        .or_else(|| id.strip_prefix("0X"))
+        .unwrap_or(id);
+
+    u32::from_str_radix(id, 16) == Ok(brain_id)
+}
+
+/// Reads the unique ID of the connected Brain.
+///
+/// Returns `None` when the ID isn't available, such as when connected through a controller.
+pub async fn brain_id<C: Connection>(connection: &mut C) -> Result<Option<u32>, CliError>
+where
+    CliError: From<C::Error>,
+{
+    let status = connection
+        .handshake::<SystemStatusReplyPacket>(
+            Duration::from_millis(500),
+            3,
+            SystemStatusPacket::new(()),
+        )
+        .await?
+        .payload?;
+
+    Ok(status.details.map(|details| details.ssn))
+}
+
+/// Scans for Brains advertising over Bluetooth and connects to one, pairing with it if needed.
+///
+/// If `address` is provided, only the Brain with that Bluetooth address is considered.
+#[cfg(feature = "bluetooth")]
+async fn open_bluetooth_connection(address: Option<&str>) -> Result<DeviceConnection, CliError> {
+    use btleplug::api::Peripheral;
+    use inquire::{
+        CustomType,
+        validator::{ErrorMessage, Validation},
+    };
+
+    info!("Scanning for Bluetooth devices...");
+
+    // Scan for the whole timeout rather than stopping at the first Brain found, so that every
+    // nearby Brain can be offered, or the one with `address` found among them.
+    let devices = bluetooth::find_devices(Duration::from_secs(10), None)
+        .await
+        .map_err(ConnectionError::from)?
+        .into_iter()
+        .filter(|device| {
+            address
+                .is_none_or(|address| device.0.address().to_string().eq_ignore_ascii_case(address))
+        })
+        .collect::<Vec<_>>();
+
+    let device = match devices.len() {
+        0 => return Err(CliError::NoDevice),
+        1 => devices.into_iter().next().unwrap(),
+        _ => {
+            ensure_interactive(
+                "choose which of the nearby Brains to connect to",
+                "Pass `--mac` with the Bluetooth address of the Brain to connect to.",
+            )?;
+
+            /// Wrapper around BluetoothDevice to provide a Display implementation for the prompt choices.
+            struct BluetoothDeviceChoice {
+                inner: bluetooth::BluetoothDevice,
+            }
+
+            impl fmt::Display for BluetoothDeviceChoice {
+                fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                    write!(f, "Brain at {}", self.inner.0.address())
+                }
+            }
+
             Select::new(
                 "Choose a device to connect to",
                 devices
                     .into_iter()
-                    .map(|device| SerialDeviceChoice { inner: device })
+                    .map(|device| BluetoothDeviceChoice { inner: device })
                     .collect::<Vec<_>>(),
             )
             .prompt()?
@@ -69,17 +654,81 @@ pub async fn open_connection() -> Result<SerialConnection, CliError> {
         }
     };
 
-    // Open a connection to the device.
-    spawn_blocking(move || {
-        device
-            .connect(Duration::from_secs(5))
-            .map_err(CliError::SerialError)
+    let mut connection = device.connect().await.map_err(ConnectionError::from)?;
+
+    if !connection
+        .is_paired()
+        .await
+        .map_err(ConnectionError::from)?
+    {
+        connection
+            .request_pairing()
+            .await
+            .map_err(ConnectionError::from)?;
+
+        ensure_interactive(
+            "enter the Brain's pairing code",
+            "Pair with the Brain once from an interactive terminal.",
+        )?;
+        let pin = CustomType::<String>::new("Enter the 4-digit code shown on the Brain's screen:")
+            .with_validator(|pin: &String| {
+                Ok(
+                    if pin.len() == 4 && pin.chars().all(|c| c.is_ascii_digit()) {
+                        Validation::Valid
+                    } else {
+                        Validation::Invalid(ErrorMessage::Custom(
+                            "The code should be four digits".to_string(),
+                        ))
+                    },
+                )
+            })
+            .prompt()?;
+
+        let mut digits = [0; 4];
+        for (digit, c) in digits.iter_mut().zip(pin.chars()) {
+            *digit = c.to_digit(10).unwrap() as u8;
+        }
+
+        connection
+            .authenticate_pairing(digits)
+            .await
+            .map_err(ConnectionError::from)?;
+    }
+
+    Ok(DeviceConnection::Bluetooth(connection))
+}
+
+/// The kind of Brain at the other end of a connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BrainGeneration {
+    /// A V5 Brain, either connected directly or through a controller.
+    V5,
+
+    /// A VEX EXP Brain.
+    Exp,
+}
+
+/// Detects which kind of Brain the connection leads to.
+pub async fn brain_generation(
+    connection: &mut DeviceConnection,
+) -> Result<BrainGeneration, CliError> {
+    let version = connection
+        .handshake::<SystemVersionReplyPacket>(
+            Duration::from_millis(500),
+            1,
+            SystemVersionPacket::new(()),
+        )
+        .await?;
+
+    Ok(match version.payload.product_type {
+        ProductType::ExpBrain => BrainGeneration::Exp,
+        // Controllers only pair with V5 Brains.
+        ProductType::V5Brain | ProductType::Controller => BrainGeneration::V5,
     })
-    .await
-    .unwrap()
 }
 
-async fn is_connection_wireless(connection: &mut SerialConnection) -> Result<bool, CliError> {
+/// Returns whether the connection goes through a controller that isn't tethered to the Brain.
+pub async fn is_connection_wireless(connection: &mut DeviceConnection) -> Result<bool, CliError> {
     let version = connection
         .handshake::<SystemVersionReplyPacket>(
             Duration::from_millis(500),
@@ -101,7 +750,12 @@ async fn is_connection_wireless(connection: &mut SerialConnection) -> Result<boo
     Ok(!tethered && controller)
 }
 
-pub async fn switch_to_download_channel(connection: &mut SerialConnection) -> Result<(), CliError> {
+pub async fn switch_to_download_channel(connection: &mut DeviceConnection) -> Result<(), CliError> {
+    // EXP Brains don't have a VEXnet radio.
+    if brain_generation(connection).await? == BrainGeneration::Exp {
+        return Ok(());
+    }
+
     let radio_status = connection
         .handshake::<RadioStatusReplyPacket>(Duration::from_secs(2), 3, RadioStatusPacket::new(()))
         .await?
@@ -181,7 +835,7 @@ pub async fn switch_to_download_channel(connection: &mut SerialConnection) -> Re
                     Ok(payload) if payload.channel == 5 => return Ok(()),
 
                     // The radio/controller reconnected, but failed to report its status.
-                    Err(error) => return Err(CliError::Nack(error)),
+                    Err(error) => return Err(CliError::from(error)),
 
                     // Still reconnecting.
                     _ => {
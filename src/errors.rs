@@ -7,7 +7,7 @@ use miette::Diagnostic;
 use thiserror::Error;
 use vex_v5_serial::protocol::{FixedStringSizeError, cdc2::Cdc2Ack};
 
-use crate::commands::migrate::MigrateError;
+use crate::{commands::migrate::MigrateError, connection::ConnectionError};
 
 #[non_exhaustive]
 #[derive(Error, Diagnostic, Debug)]
@@ -21,19 +21,39 @@ pub enum CliError {
     SerialError(#[from] vex_v5_serial::serial::SerialError),
 
     #[error(transparent)]
+    #[diagnostic(transparent)]
+    ConnectionError(ConnectionError),
+
+    #[error("{0}")]
     #[diagnostic(code(cargo_v5::cdc2_nack))]
-    Nack(#[from] Cdc2Ack),
+    Nack(Cdc2Ack, #[help] Option<&'static str>),
+
+    #[error("The Brain's flash storage is full.")]
+    #[diagnostic(
+        code(cargo_v5::storage_full),
+        help(
+            "Run `cargo v5 df` to see which files are using space, then remove old programs with `cargo v5 rm`."
+        )
+    )]
+    StorageFull,
+
+    #[error("The Brain has reached its limit on the number of user files.")]
+    #[diagnostic(
+        code(cargo_v5::max_user_files),
+        help("Remove old programs or files with `cargo v5 rm`, then try again.")
+    )]
+    MaxUserFiles,
 
     #[error(transparent)]
     #[diagnostic(transparent)]
     MigrateError(#[from] MigrateError),
 
-    #[cfg(feature = "fetch-template")]
+    #[cfg(any(feature = "fetch-template", feature = "firmware"))]
     #[error(transparent)]
     #[diagnostic(code(cargo_v5::bad_response))]
     ReqwestError(#[from] reqwest::Error),
 
-    #[cfg(feature = "fetch-template")]
+    #[cfg(any(feature = "fetch-template", feature = "firmware"))]
     #[error("Received a malformed HTTP response")]
     #[diagnostic(code(cargo_v5::malformed_response))]
     MalformedResponse,
@@ -92,6 +112,67 @@ pub enum CliError {
     )]
     InvalidUploadStrategy(String),
 
+    #[error("Verification of `{file_name}` failed: expected CRC32 {expected:08x}, but the Brain reported {}.", actual.map(|crc| format!("{crc:08x}")).unwrap_or_else(|| "no file".to_string()))]
+    #[diagnostic(
+        code(cargo_v5::upload_verification_failed),
+        help("The program may have been corrupted in transit. Try uploading it again.")
+    )]
+    UploadVerificationFailed {
+        file_name: String,
+        expected: u32,
+        actual: Option<u32>,
+    },
+
+    #[error("The Brain doesn't have the Python VM installed.")]
+    #[diagnostic(
+        code(cargo_v5::python_vm_missing),
+        help("Upload any Python program to the Brain with VEXcode once to install the Python VM.")
+    )]
+    PythonVmMissing,
+
+    #[error("Could not parse {}.", .0.display())]
+    #[diagnostic(
+        code(cargo_v5::malformed_settings),
+        help(
+            "Only plain JSON is supported. Remove any comments or trailing commas and try again."
+        )
+    )]
+    MalformedSettings(PathBuf),
+
+    #[error("The upload log {} is not a JSON array, so the upload wasn't logged.", .0.display())]
+    #[diagnostic(
+        code(cargo_v5::malformed_upload_log),
+        help(
+            "The program was uploaded, and the log was left unchanged. Fix or move the log file."
+        )
+    )]
+    MalformedUploadLog(PathBuf),
+
+    #[error("{0} is not a valid program version.")]
+    #[diagnostic(
+        code(cargo_v5::invalid_program_version),
+        help(
+            "Use `\"auto\"` or a version of the form `\"major.minor.build\"`, where each part is at most 255."
+        )
+    )]
+    InvalidProgramVersion(String),
+
+    #[error("No deploy profiles are configured.")]
+    #[diagnostic(
+        code(cargo_v5::no_deploy_profiles),
+        help(
+            "Add a profile to your Cargo.toml under `[package.metadata.v5.deploy.<name>]`, with a `slot` and optionally `features`, `name`, `description`, and `icon`."
+        )
+    )]
+    NoDeployProfiles,
+
+    #[error("There is no deploy profile named `{name}`.")]
+    #[diagnostic(
+        code(cargo_v5::unknown_deploy_profile),
+        help("Available profiles: {available}")
+    )]
+    UnknownDeployProfile { name: String, available: String },
+
     #[error("No slot number was provided.")]
     #[diagnostic(
         code(cargo_v5::no_slot),
@@ -119,6 +200,40 @@ pub enum CliError {
     )]
     NoDevice,
 
+    #[error("Couldn't reach the device server at {0}.")]
+    #[diagnostic(
+        code(cargo_v5::remote_unreachable),
+        help(
+            "Check that `cargo v5 serve` is running on that machine and that the port is reachable."
+        )
+    )]
+    RemoteUnreachable(String, #[source] std::io::Error),
+
+    #[error("The device server at {0} refused the connection: {1}")]
+    #[diagnostic(
+        code(cargo_v5::remote_rejected),
+        help(
+            "Pass the token printed by `cargo v5 serve` with `--remote-token` or the `CARGO_V5_REMOTE_TOKEN` environment variable."
+        )
+    )]
+    RemoteRejected(String, String),
+
+    #[error("A token is needed to use a remote device.")]
+    #[diagnostic(
+        code(cargo_v5::remote_token_required),
+        help(
+            "Pass the token printed by `cargo v5 serve` with `--remote-token` or the `CARGO_V5_REMOTE_TOKEN` environment variable."
+        )
+    )]
+    RemoteTokenRequired,
+
+    #[error("No device matching `{0}` was found.")]
+    #[diagnostic(
+        code(cargo_v5::device_not_found),
+        help("Pass `--device` either a serial port or the Brain ID shown by `cargo v5 devices`.")
+    )]
+    DeviceNotFound(String),
+
     #[error("cargo-v5 requires Nightly Rust features, but you're using stable.")]
     #[diagnostic(
         code(cargo_v5::unsupported_release_channel),
@@ -130,6 +245,43 @@ pub enum CliError {
     #[diagnostic(code(cargo_v5::elf_parse_error))]
     ElfParseError(#[from] object::Error),
 
+    #[error("Can't prompt to {action} in non-interactive mode.")]
+    #[diagnostic(code(cargo_v5::prompt_required))]
+    PromptRequired {
+        action: String,
+        #[help]
+        help: String,
+    },
+
+    #[error("Invalid capture file {}: {}", .0.display(), .1)]
+    #[diagnostic(
+        code(cargo_v5::malformed_capture),
+        help("Capture files are written by passing `--capture <FILE>` to another command.")
+    )]
+    MalformedCapture(PathBuf, String),
+
+    #[error("Can't replay `cargo {0}`, since {1}.")]
+    #[diagnostic(
+        code(cargo_v5::unreplayable_capture),
+        help(
+            "Replays only run commands that talk to a device, so that replaying a capture from someone else can't change your files. Run the command yourself instead."
+        )
+    )]
+    UnreplayableCapture(String, &'static str),
+
+    #[error("Couldn't read the debug info in {}: {}", .0.display(), .1)]
+    #[diagnostic(code(cargo_v5::debug_info_error))]
+    DebugInfoError(PathBuf, gimli::Error),
+
+    #[error("Build artifact was compiled for the wrong platform (found {0}, expected ARM).")]
+    #[diagnostic(
+        code(cargo_v5::incompatible_artifact),
+        help(
+            "The V5 Brain can only run 32-bit ARM binaries. Make sure you're building for the `armv7a-vex-v5` target rather than your host platform."
+        )
+    )]
+    IncompatibleArtifact(String),
+
     #[error("Controller is stuck in radio channel 9.")]
     #[diagnostic(
         code(cargo_v5::radio_channel_stuck),
@@ -167,6 +319,24 @@ pub enum CliError {
     )]
     NoController,
 
+    #[cfg(feature = "field-control")]
+    #[error("No V5 controller found on {0}.")]
+    #[diagnostic(
+        code(cargo_v5::controller_not_found),
+        help("Run `cargo v5 devices` to list the serial ports of the connected controllers.")
+    )]
+    ControllerNotFound(String),
+
+    #[cfg(feature = "field-control")]
+    #[error("Invalid match script {}: {}", .0.display(), .1)]
+    #[diagnostic(
+        code(cargo_v5::malformed_match_script),
+        help(
+            "Each step needs a `mode` and `duration`, a `delay`, or a nested `step` list to `repeat`."
+        )
+    )]
+    MalformedMatchScript(PathBuf, String),
+
     #[cfg(feature = "field-control")]
     #[error("Attempted to change the match mode over a direct Brain connection.")]
     #[diagnostic(
@@ -184,6 +354,184 @@ pub enum CliError {
     )]
     ProjectDirFull(PathBuf),
 
+    #[error("`{0}` isn't a template directory, archive, Git URL, or GitHub repository")]
+    #[diagnostic(
+        code(cargo_v5::invalid_template_source),
+        help(
+            "Pass a directory or `.tar.gz` archive that exists, a Git URL such as `https://example.com/team/template.git`, or a GitHub repository such as `org/repo` or `org/repo@v1`."
+        )
+    )]
+    InvalidTemplateSource(String),
+
+    #[error("Couldn't fetch the template `{0}`: {1}")]
+    #[diagnostic(
+        code(cargo_v5::template_fetch_failed),
+        help("Check that the template exists and that you're connected to the internet.")
+    )]
+    TemplateFetchFailed(String, String),
+
+    #[error("The template `{0}` hasn't been downloaded yet")]
+    #[diagnostic(
+        code(cargo_v5::template_not_cached),
+        help("Run the command without `--offline` to download the template.")
+    )]
+    TemplateNotCached(String),
+
+    #[error("Invalid bundle pattern `{0}`: {1}")]
+    #[diagnostic(
+        code(cargo_v5::invalid_bundle_pattern),
+        help(
+            "Entries in `package.metadata.v5.bundle` should be glob patterns such as `assets/**/*.png`."
+        )
+    )]
+    InvalidBundlePattern(String, &'static str),
+
+    #[error("{} already exists.", .0.display())]
+    #[diagnostic(
+        code(cargo_v5::file_exists),
+        help("Pass `--force` to overwrite the existing file.")
+    )]
+    FileExists(PathBuf),
+
+    #[error("`{0}` does not exist on the Brain.")]
+    #[diagnostic(
+        code(cargo_v5::remote_file_not_found),
+        help("Use `cargo v5 dir` to list the files stored on the Brain.")
+    )]
+    RemoteFileNotFound(String),
+
+    #[error("`{0}` is linked to another file, so it can't be copied.")]
+    #[diagnostic(
+        code(cargo_v5::linked_file_copy),
+        help(
+            "The Brain doesn't report which file a file is linked to, so a copy would lose the link. Upload the program again under the new name instead."
+        )
+    )]
+    LinkedFileCopy(String),
+
+    #[error("Invalid file pattern `{0}`: {1}")]
+    #[diagnostic(
+        code(cargo_v5::invalid_remote_pattern),
+        help("File patterns may use `*`, `?`, and `[...]`, such as `slot_*.bin`.")
+    )]
+    InvalidRemotePattern(String, &'static str),
+
+    #[error("There is no log file for run {0}.")]
+    #[diagnostic(
+        code(cargo_v5::no_log_file),
+        help("Use `cargo v5 logs list` to see which log files are available.")
+    )]
+    NoLogFile(usize),
+
+    #[error("Invalid config file {}: {}", .0.display(), .1)]
+    #[diagnostic(
+        code(cargo_v5::malformed_config),
+        help(
+            "Fix or remove the config file. Options passed on the command line take precedence over it."
+        )
+    )]
+    MalformedConfig(PathBuf, String),
+
+    #[error("{0} preflight check{s} failed.", s = if *.0 == 1 { "" } else { "s" })]
+    #[diagnostic(
+        code(cargo_v5::preflight_failed),
+        help("Fix the failing checks above, or skip checks that don't apply with `--skip`.")
+    )]
+    PreflightFailed(usize),
+
+    #[error("{0} test{s} failed.", s = if *.0 == 1 { "" } else { "s" })]
+    #[diagnostic(
+        code(cargo_v5::tests_failed),
+        help("The output of the failing tests is shown above.")
+    )]
+    TestsFailed(usize),
+
+    #[error("Cargo exited with status {0} while building.")]
+    #[diagnostic(
+        code(cargo_v5::build_failed),
+        help("The compiler's errors are shown above.")
+    )]
+    BuildFailed(i32),
+
+    #[error("`{0}` already exists on the Brain.")]
+    #[diagnostic(
+        code(cargo_v5::remote_file_exists),
+        help("Pass `--force` to overwrite the existing file, or choose another name.")
+    )]
+    RemoteFileExists(String),
+
+    #[error("Invalid key/value dump {}: {}", .0.display(), .1)]
+    #[diagnostic(
+        code(cargo_v5::malformed_key_value_dump),
+        help(
+            "Dumps map each key to a string value, like the output of `cargo v5 kv dump`. Files ending in `.json` are read as JSON, and anything else as TOML."
+        )
+    )]
+    MalformedKeyValueDump(PathBuf, String),
+
+    #[error("Couldn't set `{key}`, so the import stopped. Keys already set: {applied}.")]
+    #[diagnostic(
+        code(cargo_v5::partial_key_value_import),
+        help(
+            "The Brain keeps the keys that were already set. Fix the problem and run the import again to set the rest."
+        )
+    )]
+    PartialKeyValueImport {
+        key: String,
+        applied: String,
+        #[source]
+        source: Box<CliError>,
+    },
+
+    #[error("No device is connected to port {0}.")]
+    #[diagnostic(
+        code(cargo_v5::no_device_on_port),
+        help("Run `cargo v5 devices` to see which ports have devices connected.")
+    )]
+    NoDeviceOnPort(u8),
+
+    #[error("Can't capture program output to {}.", .0.display())]
+    #[diagnostic(
+        code(cargo_v5::unsupported_capture_format),
+        help("Captures are written as CSV. Use a file ending in `.csv`.")
+    )]
+    UnsupportedCaptureFormat(PathBuf),
+
+    #[error("Binary passthrough isn't supported over a controller.")]
+    #[diagnostic(
+        code(cargo_v5::passthrough_over_controller),
+        help(
+            "VEXos only carries text between a controller and the Brain's program. Connect to the Brain directly over USB to use `--binary`, `--cobs`, or `--pty`."
+        )
+    )]
+    PassthroughOverController,
+
+    #[error("The serial bridge would be reachable from other machines on {0} without a token.")]
+    #[diagnostic(
+        code(cargo_v5::unauthenticated_serial_bridge),
+        help(
+            "Clients can send input to the program, and the system channel can do anything to the Brain. Pass `--token <TOKEN>` so clients must send it before anything else, or listen on a loopback address like `127.0.0.1`."
+        )
+    )]
+    UnauthenticatedSerialBridge(std::net::SocketAddr),
+
+    #[cfg(not(unix))]
+    #[error("Virtual serial ports aren't supported on this platform.")]
+    #[diagnostic(
+        code(cargo_v5::pty_unsupported),
+        help(
+            "`--pty` is only available on Linux and macOS. Use `--binary` to pipe the program's I/O through another tool instead."
+        )
+    )]
+    PtyUnsupported,
+
+    #[error("The key `{0}` is not set on the Brain.")]
+    #[diagnostic(
+        code(cargo_v5::key_not_found),
+        help("Pass `--default <VALUE>` to use a fallback value when the key isn't set.")
+    )]
+    KeyNotFound(String),
+
     #[error("Program exceeded the maximum differential upload size of 2MiB (program was {}).", format_size(*.0, BINARY))]
     #[diagnostic(
         code(cargo_v5::program_too_large),
@@ -199,4 +547,96 @@ pub enum CliError {
         help("Try running a cold upload using `cargo v5 upload --cold`.")
     )]
     PatchTooLarge(usize),
+
+    #[error("Screenshots can't be saved as `{0}` files.")]
+    #[diagnostic(
+        code(cargo_v5::unsupported_image_format),
+        help(
+            "Use a path ending in `.png` or `.bmp` (or `.apng` with `--watch`), or pass `--stdout` for raw RGB pixels."
+        )
+    )]
+    UnsupportedImageFormat(String),
+
+    #[error("`{}` doesn't match its provenance manifest.", .artifact.display())]
+    #[diagnostic(
+        code(cargo_v5::provenance_mismatch),
+        help(
+            "The artifact changed after `{}` was written. Rebuild with `--provenance` to record the new artifact, or delete the manifest.",
+            .manifest.display()
+        )
+    )]
+    ProvenanceMismatch {
+        artifact: PathBuf,
+        manifest: PathBuf,
+    },
+
+    #[error("Provenance manifest `{}` is malformed.", .0.display())]
+    #[diagnostic(
+        code(cargo_v5::malformed_provenance),
+        help("Rebuild with `--provenance` to regenerate it, or delete it.")
+    )]
+    MalformedProvenance(PathBuf),
+
+    #[error("The program in slot {0} has no build stamp.")]
+    #[diagnostic(
+        code(cargo_v5::no_stamp),
+        help(
+            "Programs are stamped when cargo-v5 uploads them without `--no-stamp`. Upload it again to stamp it."
+        )
+    )]
+    NoStamp(u8),
+
+    #[error("Differential uploads can't be used with `linked-files`.")]
+    #[diagnostic(
+        code(cargo_v5::differential_with_linked_files),
+        help(
+            "A program can only have one linked file, which differential uploads use for the patch base. Upload with `--upload-strategy monolith` instead."
+        )
+    )]
+    DifferentialWithLinkedFiles,
+}
+
+impl From<Cdc2Ack> for CliError {
+    /// Translates NACKs with a known cause into errors that explain how to resolve them.
+    fn from(ack: Cdc2Ack) -> Self {
+        match ack {
+            // A general NACK has many causes, but a common one is VEXos refusing requests while a
+            // modal screen is open.
+            Cdc2Ack::Nack => Self::Nack(
+                ack,
+                Some(
+                    "The Brain may be showing a menu or dialog, such as the program selection screen or a firmware update prompt. Return the Brain to its home screen, stop any running program, and try again.",
+                ),
+            ),
+            Cdc2Ack::NackFileStorageFull => Self::StorageFull,
+            Cdc2Ack::NackMaxUserFiles => Self::MaxUserFiles,
+            ack => Self::Nack(ack, None),
+        }
+    }
+}
+
+impl From<ConnectionError> for CliError {
+    fn from(err: ConnectionError) -> Self {
+        match err {
+            ConnectionError::Nack(ack) => ack.into(),
+            err => Self::ConnectionError(err),
+        }
+    }
+}
+
+impl CliError {
+    /// Process exit code to report this error with.
+    ///
+    /// Most errors exit with `1`, but some use a distinct code so that scripts can tell them
+    /// apart from transport failures.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::KeyNotFound(_) => 2,
+            // Matches `cargo test`, so CI scripts can treat both the same way.
+            Self::TestsFailed(_) => 101,
+            // Passes on Cargo's own exit code.
+            Self::BuildFailed(code) => *code,
+            _ => 1,
+        }
+    }
 }
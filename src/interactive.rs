@@ -0,0 +1,44 @@
+//! Whether cargo-v5 may stop to ask the user questions.
+//!
+//! Prompts hang CI jobs and other unattended runs, so they're disabled by `--non-interactive` or
+//! when the `CI` environment variable is set. Disabled prompts become errors that explain how to
+//! provide the answer up front instead.
+
+use std::{
+    env,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use crate::errors::CliError;
+
+static NON_INTERACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Disables all prompts for the rest of the run.
+pub fn set_non_interactive(non_interactive: bool) {
+    NON_INTERACTIVE.store(non_interactive, Ordering::Relaxed);
+}
+
+/// Whether running in a CI environment, going by the `CI` variable most CI services set.
+fn is_ci() -> bool {
+    env::var("CI").is_ok_and(|ci| !matches!(ci.as_str(), "" | "0" | "false"))
+}
+
+/// Whether prompts may be shown.
+pub fn is_interactive() -> bool {
+    !NON_INTERACTIVE.load(Ordering::Relaxed) && !is_ci()
+}
+
+/// Fails with [`CliError::PromptRequired`] if prompts are disabled.
+///
+/// `action` describes what the prompt would ask for (as in "Can't prompt to {action}"), and
+/// `help` explains how to answer it without a prompt.
+pub fn ensure_interactive(action: &str, help: &str) -> Result<(), CliError> {
+    if is_interactive() {
+        Ok(())
+    } else {
+        Err(CliError::PromptRequired {
+            action: action.to_string(),
+            help: help.to_string(),
+        })
+    }
+}
@@ -1,5 +1,13 @@
+pub mod build_info;
+pub mod bundle;
+pub mod capture;
 pub mod commands;
+pub mod config;
 pub mod connection;
 pub mod errors;
+pub mod interactive;
 pub mod metadata;
+pub mod provenance;
+pub mod remote;
+pub mod reporter;
 pub mod self_update;
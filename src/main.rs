@@ -1,39 +1,87 @@
 use cargo_v5::{
+    capture::{ReplayConnection, read_capture, set_replay, start_capture},
     commands::{
-        build::{CargoOpts, build},
+        analyze::{self, BROWNOUT_VOLTAGE},
+        battery::{BatteryThresholds, battery},
+        bloat::{BloatOpts, bloat},
+        bridge::bridge,
+        build::{CargoOpts, build, check},
         cat::cat,
-        devices::devices,
+        copy::{cp, mv},
+        daemon::daemon,
+        devices::{device_firmware, devices},
+        df::df,
+        deploy::{DeploySelection, deploy},
         dir::dir,
-        key_value::{kv_get, kv_set},
-        log::log,
-        new::new,
-        rm::rm,
-        screenshot::screenshot,
-        terminal::terminal,
-        migrate,
-        upload::{AfterUpload, UploadOpts, upload},
-    },
-    connection::{open_connection, switch_to_download_channel},
+        firmware,
+        history::history,
+        info::info,
+        key_value::{DumpFormat, kv_dump, kv_get, kv_import, kv_set, parse_dump},
+        kiosk::kiosk,
+        log::{log, parse_log_time},
+        logs::{
+            LogRetention, clean_up_log_files, list_logs, log_file_spec, open_log, purge_logs,
+        },
+        new::{ProjectOptions, TemplateSource, new, prompt_project_options},
+        passthrough::passthrough,
+        patch::patch,
+        portcheck::portcheck,
+        preflight::{PreflightOpts, preflight},
+        program::{OnExit, run_slot, stop},
+        pull::pull,
+        push::{parse_load_address, push},
+        ra_config::ra_config,
+        radio::{radio, radio_test},
+        rm::{rm, rm_slot},
+        screenshot::{ScreenshotOutput, record_screen, screenshot},
+        serial_bridge::serial_bridge,
+        serve::serve,
+        size::print_size_report,
+        slots::slots,
+        stamp::stamp,
+        symbolize::{BacktraceAnnotator, Symbolizer},
+        team::{
+            TeamInfo, parse_display_name, parse_team_number, print_team_info, set_team_info,
+            team_info,
+        },
+        telemetry::TelemetryCapture,
+        terminal::{attach_terminal, shared_terminal, terminal},
+        test::{TestOpts, test},
+        top::top,
+        migrate::{self, MigrateOpts},
+        upload::{AfterUpload, MessageFormat, UploadOpts, run_installed, set_ascii_output, upload},
+    },
+    config::{load_user_config, user_config},
+    connection::{ConnectionOpts, open_connection, switch_to_download_channel},
     errors::CliError,
+    interactive::{is_interactive, set_non_interactive},
+    metadata::{ascii_output_configured, manifest_path},
+    remote,
+    reporter::TerminalReporter,
     self_update::{self, SelfUpdateMode},
 };
-use chrono::Utc;
-use clap::{Args, Parser, Subcommand};
-use flexi_logger::{AdaptiveFormat, FileSpec, LogfileSelector, LoggerHandle};
-use std::{env, num::NonZeroU32, panic, path::PathBuf};
+use clap::{ArgGroup, Args, Parser, Subcommand, ValueEnum};
+use flexi_logger::{AdaptiveFormat, Duplicate, LogfileSelector, LoggerHandle};
+use std::{net::SocketAddr, num::NonZeroU32, panic, path::PathBuf, time::Duration};
 use vex_v5_serial::{
     Connection,
     protocol::{
         FixedString,
         cdc2::file::{FileLoadAction, FileLoadActionPacket, FileLoadActionPayload, FileVendor},
     },
-    serial::{self, SerialConnection, SerialDevice},
 };
 
 #[cfg(feature = "field-control")]
-use cargo_v5::commands::field_control::run_field_control_tui;
-#[cfg(feature = "field-control")]
-use std::time::Duration;
+use cargo_v5::commands::field_control::{
+    MatchCues, MatchLog, MatchScript, MatchTemplate, connect_controllers, run_field_control_tui,
+    run_match_script,
+};
+#[cfg(feature = "plot")]
+use cargo_v5::commands::plot::plot;
+#[cfg(unix)]
+use cargo_v5::commands::passthrough::Pty;
+#[cfg(feature = "screen")]
+use cargo_v5::commands::screen::{mirror_screen, view_controller};
 #[cfg(feature = "field-control")]
 
 cargo_subcommand_metadata::description!("Manage vexide projects");
@@ -48,20 +96,169 @@ enum Cargo {
         #[command(subcommand)]
         command: Command,
 
+        /// Path to the project, either as a directory or a `Cargo.toml` file.
         #[arg(long, default_value = ".", global = true)]
         path: PathBuf,
+
+        /// How errors should be reported.
+        #[arg(long, value_enum, default_value = "human", global = true)]
+        error_format: ErrorFormat,
+
+        /// Only use plain ASCII characters in progress bars, reports, and TUIs.
+        #[arg(long, global = true)]
+        ascii: bool,
+
+        /// Record all data exchanged with the device to a file, for attaching to bug reports.
+        #[arg(long, global = true, value_name = "FILE")]
+        capture: Option<PathBuf>,
+
+        /// Fail instead of prompting for input. Implied when the `CI` environment variable is set.
+        #[arg(long, global = true)]
+        non_interactive: bool,
+
+        #[command(flatten)]
+        connection_opts: ConnectionOpts,
     },
 }
 
+/// Tools that `cargo v5 migrate` can set up vexide projects from.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum MigrateFrom {
+    /// A PROS C++ project, with a `project.pros` file.
+    Pros,
+}
+
+/// Output format for diagnostics.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum ErrorFormat {
+    /// Human-readable, graphical reports.
+    Human,
+
+    /// One JSON object per error, written to stderr. Intended for editor integrations.
+    Json,
+}
+
 /// Access a Brain's system key/value configuration.
 #[derive(Subcommand, Debug)]
 #[clap(name = "kv")]
 enum KeyValue {
     /// Get the value of a system variable on a Brain.
-    Get { key: String },
+    ///
+    /// Exits with code 2 if the key isn't set and no default is provided.
+    Get {
+        key: String,
+
+        /// Value to print if the key isn't set.
+        #[arg(long)]
+        default: Option<String>,
+
+        /// Print the value without a trailing newline.
+        #[arg(long)]
+        raw: bool,
+    },
 
     /// Set a system variable on a Brain.
     Set { key: String, value: String },
+
+    /// Print the team information set on a Brain, for backing it up.
+    ///
+    /// VEXos can't list its variables, so this only covers the team number, robot name, and
+    /// owner, the same ones as `cargo v5 team`.
+    Dump {
+        /// Format to print the variables in.
+        #[arg(long, value_enum, default_value_t)]
+        format: DumpFormat,
+    },
+
+    /// Set every system variable in a file written by `cargo v5 kv dump`.
+    ///
+    /// Files ending in `.json` are read as JSON, and anything else as TOML. Keys are set in order,
+    /// and if one fails, the error lists the keys that were already set.
+    Import { file: PathBuf },
+}
+
+/// Manage the devices connected to a Brain.
+#[derive(Subcommand, Debug)]
+enum Devices {
+    /// Show the firmware of connected devices, and how to update it.
+    ///
+    /// VEXos updates devices itself from the firmware bundled with it, so cargo-v5 can't install
+    /// device firmware.
+    Firmware {
+        /// Only show the device on this port.
+        #[arg(long)]
+        port: Option<u8>,
+    },
+}
+
+/// Manage the team information stored on a Brain.
+#[derive(Subcommand, Debug)]
+enum Team {
+    /// Show the team number, robot name, and owner stored on a Brain.
+    Show,
+
+    /// Change the team number, robot name, or owner stored on a Brain.
+    #[command(group(ArgGroup::new("fields").required(true).multiple(true)))]
+    Set {
+        /// Team number, such as `1234A`.
+        #[arg(group = "fields", value_parser = parse_team_number)]
+        team: Option<String>,
+
+        /// Name of the robot.
+        #[arg(long, group = "fields", value_parser = parse_display_name)]
+        robot: Option<String>,
+
+        /// Name of the robot's owner.
+        #[arg(long, group = "fields", value_parser = parse_display_name)]
+        owner: Option<String>,
+    },
+}
+
+/// Analyze data recorded by a Brain.
+#[derive(Subcommand, Debug)]
+enum Analyze {
+    /// Correlate battery events in the event log with program runs to find likely brownouts.
+    Brownouts,
+}
+
+/// Manage cargo-v5's own log files.
+#[derive(Subcommand, Debug)]
+enum Logs {
+    /// List log files from previous runs, newest first.
+    List,
+
+    /// Open a log file in the default application.
+    Open {
+        /// Which run to open the log of, counting back from the most recent.
+        #[arg(default_value_t = 1)]
+        run: usize,
+    },
+
+    /// Delete all log files.
+    Purge,
+}
+
+/// Manage a Brain's VEXos firmware.
+#[derive(Subcommand, Debug)]
+enum Firmware {
+    /// Show the firmware versions installed on a Brain.
+    Status,
+
+    /// Check whether a newer version of VEXos is available.
+    #[cfg(feature = "firmware")]
+    Check,
+}
+
+/// Field control without the TUI.
+#[cfg(feature = "field-control")]
+#[derive(Subcommand, Debug)]
+enum FieldControlAction {
+    /// Run a match timeline from a script, logging each mode change.
+    Run {
+        /// TOML file listing the match's steps.
+        #[arg(long, value_name = "FILE")]
+        script: PathBuf,
+    },
 }
 
 /// A possible `cargo v5` subcommand.
@@ -70,11 +267,85 @@ enum Command {
     /// Build a project for the V5 Brain.
     #[clap(visible_alias = "b")]
     Build {
+        /// Show how much space each section and crate takes up in the built program.
+        #[arg(long)]
+        size: bool,
+
+        /// Arguments forwarded to `cargo`.
+        #[clap(flatten)]
+        cargo_opts: CargoOpts,
+    },
+
+    /// Check a project for errors, targeting the V5 Brain.
+    Check {
+        /// Arguments forwarded to `cargo`.
+        #[clap(flatten)]
+        cargo_opts: CargoOpts,
+    },
+
+    /// Lint a project with Clippy, targeting the V5 Brain.
+    Clippy {
+        /// Arguments forwarded to `cargo`.
+        #[clap(flatten)]
+        cargo_opts: CargoOpts,
+    },
+
+    /// Build the project and show what's taking up space in the program.
+    ///
+    /// Same as `cargo v5 build --size`.
+    Size {
+        /// Arguments forwarded to `cargo`.
+        #[clap(flatten)]
+        cargo_opts: CargoOpts,
+    },
+
+    /// List the largest functions and data in the program, or compare them with an older build.
+    Bloat {
+        /// ELF to analyze instead of building the project.
+        #[arg(long, value_name = "FILE")]
+        elf: Option<PathBuf>,
+
+        /// An older build's ELF to compare against, listing what grew or shrank.
+        #[arg(long, value_name = "OLD")]
+        compare: Option<PathBuf>,
+
+        /// List crates instead of individual symbols.
+        #[arg(long)]
+        crates: bool,
+
+        /// Number of entries to list.
+        #[arg(short = 'n', long, default_value_t = 20)]
+        count: usize,
+
         /// Arguments forwarded to `cargo`.
         #[clap(flatten)]
         cargo_opts: CargoOpts,
     },
     
+    /// Build the patch a differential upload would send between two binaries.
+    Patch {
+        /// Binary currently on the Brain.
+        old: PathBuf,
+
+        /// Binary to patch it to.
+        new: PathBuf,
+
+        /// Where to write the patch. Defaults to NEW with a `.patch` extension.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Write the patch without gzip compression.
+        #[arg(long)]
+        uncompressed: bool,
+    },
+
+    /// Configure rust-analyzer in `.vscode/settings.json` to match `cargo v5 build`.
+    RaConfig {
+        /// Arguments forwarded to `cargo`.
+        #[clap(flatten)]
+        cargo_opts: CargoOpts,
+    },
+
     /// Upload a project or file to a Brain.
     #[clap(visible_alias = "u")]
     Upload {
@@ -85,14 +356,119 @@ enum Command {
         upload_opts: UploadOpts,
     },
     
+    /// Run a checklist on a robot before a match.
+    ///
+    /// Checks that the program in the competition slot matches the local build, the battery is
+    /// charged, the radio is on a pit channel, device firmware is consistent, and no errors were
+    /// logged since the Brain was turned on.
+    Preflight {
+        #[clap(flatten)]
+        opts: PreflightOpts,
+    },
+
+    /// Build and upload the programs configured in `package.metadata.v5.deploy`.
+    Deploy {
+        /// Name of the profile to deploy.
+        profile: Option<String>,
+
+        /// Deploy every configured profile.
+        #[arg(long, conflicts_with = "profile")]
+        all: bool,
+
+        #[arg(long, default_value = "none")]
+        after: AfterUpload,
+
+        #[clap(flatten)]
+        cargo_opts: CargoOpts,
+    },
+
     /// Access a Brain's remote terminal I/O.
     #[clap(visible_alias = "t")]
-    Terminal,
+    Terminal {
+        /// The running program's ELF, used to show function names and source locations in panic
+        /// backtraces.
+        #[arg(long)]
+        elf: Option<PathBuf>,
+
+        /// Record structured lines of output (JSON objects or `key=value` pairs) to this CSV
+        /// file, with the time each line was received.
+        #[arg(long, value_name = "FILE")]
+        record: Option<PathBuf>,
+
+        /// Pass the program's I/O through byte for byte, for programs that speak a binary
+        /// protocol. Requires a USB connection to the Brain.
+        #[arg(long, conflicts_with_all = ["elf", "record"])]
+        binary: bool,
+
+        /// Pass the program's I/O on as whole COBS frames, dropping any that are malformed. Input
+        /// must already be COBS frames, each ending in a zero byte. Implies `--binary`.
+        #[arg(long, conflicts_with_all = ["elf", "record"])]
+        cobs: bool,
+
+        /// Expose the program's I/O as a pseudoterminal that other tools can open like a serial
+        /// port, rather than using stdin and stdout. Implies `--binary`.
+        #[arg(long, conflicts_with_all = ["elf", "record"])]
+        pty: bool,
+    },
     
+    /// Build the project's tests and run them on a V5 Brain.
+    ///
+    /// Exits with code 101 if any test fails, like `cargo test`.
+    Test {
+        #[clap(flatten)]
+        opts: TestOpts,
+    },
+
     /// Build, upload, and run a program on a V5 Brain, showing its output in the terminal.
     #[clap(visible_alias = "r")]
-    Run(UploadOpts),
+    Run {
+        /// What to do with the program when the terminal is closed with Ctrl-C. Defaults to
+        /// `terminal.on-exit` in the user config, or `stop`.
+        #[arg(long)]
+        on_exit: Option<OnExit>,
+
+        /// Upload the program even if the Brain already has an identical copy in the slot.
+        #[arg(long)]
+        force_upload: bool,
+
+        /// Don't build or upload anything, just run the program already installed in the slot.
+        #[arg(long, conflicts_with_all = ["force_upload", "file", "elf", "python"])]
+        no_upload: bool,
+
+        /// Record structured lines of output (JSON objects or `key=value` pairs) to this CSV
+        /// file, with the time each line was received.
+        #[arg(long, value_name = "FILE")]
+        record: Option<PathBuf>,
+
+        #[clap(flatten)]
+        upload_opts: UploadOpts,
+    },
     
+    /// Run a program that is already installed on a Brain.
+    #[clap(visible_alias = "start")]
+    RunSlot {
+        /// The slot of the program to run.
+        slot: u8,
+    },
+
+    /// Stop the currently running program.
+    Stop,
+
+    /// Run a program, restarting it whenever it exits. Useful for unattended demo robots.
+    Kiosk {
+        /// The slot of the program to run.
+        #[arg(short, long)]
+        slot: u8,
+
+        /// Restart the program each time it exits.
+        #[arg(long = "loop")]
+        looping: bool,
+
+        /// Stop the program after it has been running for this many seconds.
+        #[arg(long, value_name = "SECONDS")]
+        max_runtime: Option<u64>,
+    },
+
     /// Create a new vexide project with a given name.
     #[clap(visible_alias = "n")]
     New {
@@ -101,57 +477,392 @@ enum Command {
 
         #[clap(flatten)]
         download_opts: DownloadOpts,
+
+        /// Set up the project with the default answers instead of asking.
+        #[arg(long, short)]
+        yes: bool,
     },
     
     /// Create a new vexide project in the current directory.
     Init {
         #[clap(flatten)]
         download_opts: DownloadOpts,
+
+        /// Set up the project with the default answers instead of asking.
+        #[arg(long, short)]
+        yes: bool,
     },
     
     /// List files on flash.
     #[clap(visible_alias = "ls")]
     Dir,
     
+    /// List the programs installed in each slot.
+    Slots,
+
+    /// Show when and from which commit the program in a slot was built, and how it was uploaded.
+    Stamp {
+        /// The slot of the program.
+        slot: u8,
+    },
+
+    /// Show a summary of the Brain's versions, battery, radio, and storage.
+    Info {
+        /// Output format. `json` writes the summary as a single JSON object to stdout.
+        #[arg(long, value_enum, default_value = "human")]
+        message_format: MessageFormat,
+    },
+
+    /// Show the battery's charge, last logged voltage, and firmware version.
+    Battery {
+        /// Keep refreshing until interrupted.
+        #[arg(long)]
+        watch: bool,
+
+        /// Time between refreshes when watching, in milliseconds.
+        #[arg(long, value_name = "MS", default_value_t = 2000, requires = "watch")]
+        interval: u64,
+
+        /// Highlight the charge when it's below this percentage.
+        #[arg(long, value_name = "PERCENT", default_value_t = 30)]
+        warn_charge: u32,
+
+        /// Highlight the voltage when it's below this many volts.
+        #[arg(long, value_name = "VOLTS", default_value_t = BROWNOUT_VOLTAGE)]
+        warn_voltage: f32,
+    },
+
+    /// Monitor the VEXnet radio's channel, signal quality, and dropped connections.
+    Radio {
+        /// Measure the link's round-trip latency and throughput instead.
+        #[arg(long)]
+        test: bool,
+    },
+
+    /// Show how much flash is used by each vendor's files.
+    Df {
+        /// Number of largest files to list.
+        #[arg(short = 'n', long, default_value_t = 5)]
+        largest: usize,
+    },
+
     /// Read a file from flash, then write its contents to stdout.
     Cat {
         file: PathBuf,
     },
 
+    /// Download a file from flash and save it to the host.
+    Pull {
+        /// The file to download, optionally prefixed with its vendor (e.g. `user/slot_1.ini`).
+        remote: PathBuf,
+
+        /// Where to save the file. Defaults to the file's name in the current directory.
+        local: Option<PathBuf>,
+
+        /// Overwrite the local file if it already exists.
+        #[arg(long, short)]
+        force: bool,
+    },
+
+    /// Upload an arbitrary file from the host to flash.
+    Push {
+        /// The file to upload.
+        local: PathBuf,
+
+        /// Where to save the file, optionally prefixed with its vendor (e.g. `user/table.bin`).
+        remote: PathBuf,
+
+        /// Address the file is loaded at, in decimal or `0x`-prefixed hexadecimal.
+        #[arg(long, default_value = "0x03800000", value_parser = parse_load_address)]
+        load_address: u32,
+
+        /// File type reported to the Brain. Defaults to the remote file's extension.
+        #[arg(long)]
+        extension: Option<String>,
+
+        /// Action to perform once the upload completes.
+        #[arg(long, default_value = "none")]
+        after: AfterUpload,
+    },
+
     /// Erase a file from flash.
     Rm {
-        file: PathBuf,
+        /// The file to erase, optionally prefixed with its vendor. The file name may be a glob
+        /// pattern like `slot_*.bin`.
+        #[arg(required_unless_present = "slot")]
+        file: Option<PathBuf>,
+
+        /// Erase the binary, metadata, and linked files of a program slot instead.
+        #[arg(long, conflicts_with = "file")]
+        slot: Option<u8>,
+    },
+
+    /// Copy a file in flash to another name.
+    Cp {
+        /// The file to copy, optionally prefixed with its vendor (e.g. `user/slot_1.bin`).
+        source: PathBuf,
+
+        /// Name of the copy, optionally prefixed with its vendor.
+        destination: PathBuf,
+
+        /// Overwrite the destination if it already exists.
+        #[arg(long, short)]
+        force: bool,
+    },
+
+    /// Rename a file in flash.
+    Mv {
+        /// The file to rename, optionally prefixed with its vendor (e.g. `user/slot_1.bin`).
+        source: PathBuf,
+
+        /// New name of the file, optionally prefixed with its vendor.
+        destination: PathBuf,
+
+        /// Overwrite the destination if it already exists.
+        #[arg(long, short)]
+        force: bool,
     },
     
     /// Read a Brain's event log.
     Log {
         #[arg(long, short, default_value = "1")]
         page: NonZeroU32,
+
+        /// Keep printing new entries as they are recorded.
+        #[arg(long, short)]
+        follow: bool,
+
+        /// Only show entries recorded at or after this time on the log's clock (HH:MM:SS).
+        #[arg(long, value_parser = parse_log_time)]
+        since: Option<u32>,
+
+        /// Only show the newest N entries.
+        #[arg(long, short = 'n')]
+        count: Option<u32>,
     },
     
+    /// Manage a Brain's VEXos firmware.
+    #[command(subcommand)]
+    Firmware(Firmware),
+
+    /// Analyze data recorded by a Brain.
+    #[command(subcommand)]
+    Analyze(Analyze),
+
+    /// Manage cargo-v5's own log files.
+    #[command(subcommand)]
+    Logs(Logs),
+
+    /// Show recent uploads and the average transfer speed over each kind of link.
+    History {
+        /// Number of uploads to show.
+        #[arg(short = 'n', long, default_value_t = 20)]
+        count: usize,
+    },
+
     /// List devices connected to a Brain.
-    #[clap(visible_alias = "lsdev")]
-    Devices,
+    #[clap(visible_alias = "lsdev", args_conflicts_with_subcommands = true)]
+    Devices {
+        #[command(subcommand)]
+        action: Option<Devices>,
+
+        /// Keep refreshing the list, highlighting ports whose devices are plugged in, unplugged,
+        /// or change status. Useful for finding loose cables.
+        #[arg(long)]
+        watch: bool,
+
+        /// Time between refreshes when watching, in milliseconds.
+        #[arg(long, value_name = "MS", default_value_t = 250, requires = "watch")]
+        interval: u64,
+    },
+
+    /// Monitor smart ports for a period of time and report flaky connections.
+    Portcheck {
+        /// How long to monitor ports for, in seconds.
+        #[arg(long, short, default_value = "30")]
+        duration: u64,
+    },
+
+    /// Show the running program's memory and CPU usage, as reported by the program.
+    Top,
+
+    /// Republish a running program's output to external dashboards.
+    ///
+    /// Each line of output is sent to WebSocket clients as a JSON message, with lines that are
+    /// JSON objects or arrays forwarded as structured `data`.
+    Bridge {
+        /// Address to accept WebSocket connections on, such as `0.0.0.0:9001`.
+        #[arg(long, value_name = "ADDR")]
+        websocket: SocketAddr,
+
+        /// Origin of a web page allowed to connect, like `http://localhost:5173`. Can be given
+        /// more than once. Clients outside a browser can always connect.
+        #[arg(long, value_name = "ORIGIN")]
+        allow_origin: Vec<String>,
+    },
+
+    /// Forward the program's I/O to a TCP socket.
+    ///
+    /// Tools like Foxglove, PlotJuggler, or custom dashboards can connect to read the program's
+    /// output and send it input without speaking the VEX serial protocol.
+    SerialBridge {
+        /// Address to accept connections for the program's I/O on.
+        #[arg(long, value_name = "ADDR", default_value = "127.0.0.1:7375")]
+        listen: SocketAddr,
+
+        /// Also forward the Brain's system channel, which carries raw CDC2 packets, to clients
+        /// connecting to this address.
+        #[arg(long, value_name = "ADDR")]
+        system: Option<SocketAddr>,
+
+        /// Token clients must send, followed by a newline, before anything else. Required unless
+        /// both channels are on loopback addresses.
+        #[arg(long, value_name = "TOKEN")]
+        token: Option<String>,
+    },
+
+    /// Keep a connection to the Brain open and serve a local control API.
+    ///
+    /// Editors and scripts connect over WebSocket and send JSON-RPC requests to upload, run, and
+    /// stop programs, take screenshots, and stream the terminal.
+    Daemon {
+        /// Address to accept WebSocket connections on.
+        #[arg(long, value_name = "ADDR", default_value = "127.0.0.1:7373")]
+        listen: SocketAddr,
+    },
+
+    /// Share the connected device with other machines on the network.
+    ///
+    /// Other machines pass `--remote <HOST:PORT>` to any command to use the device, such as a
+    /// laptop uploading through a Raspberry Pi tethered to the robot. The connection isn't
+    /// encrypted, so only use this on trusted networks.
+    Serve {
+        /// Address to accept connections on, like `0.0.0.0:7374` to accept them from any machine.
+        #[arg(long, value_name = "ADDR")]
+        listen: SocketAddr,
+
+        /// Token clients must present. Defaults to the `CARGO_V5_REMOTE_TOKEN` environment
+        /// variable, or a random token that's printed on startup.
+        #[arg(long, value_name = "TOKEN")]
+        token: Option<String>,
+    },
 
     /// Take a screen capture of the brain, saving the file to the current directory.
     #[clap(visible_alias = "sc")]
-    Screenshot,
+    Screenshot {
+        /// File to save the screenshot to, or `-` to write a PNG to stdout. The format is inferred
+        /// from the extension (`.png` or `.bmp`). Defaults to `screen.png`, numbered if it exists.
+        #[arg(short, long, value_name = "PATH|-")]
+        output: Option<PathBuf>,
+
+        /// Write raw 8-bit RGB pixels to stdout instead of an image file.
+        #[arg(long, conflicts_with = "output")]
+        stdout: bool,
+
+        /// Keep capturing the screen until interrupted. Frames are saved as numbered images, or
+        /// as an animation if the output ends in `.apng`.
+        #[arg(short, long)]
+        watch: bool,
+
+        /// Time between captures when watching, in milliseconds.
+        #[arg(long, value_name = "MS", default_value_t = 1000, requires = "watch")]
+        interval: u64,
+
+        /// Stop watching after capturing this many frames.
+        #[arg(long, value_name = "COUNT", requires = "watch")]
+        frames: Option<NonZeroU32>,
+    },
     
+    /// Plot values printed by the running program live, as lines like `#plot speed=1.5 error=0.2`.
+    #[cfg(feature = "plot")]
+    Plot {
+        /// Number of seconds of data to show.
+        #[arg(long, value_name = "SECONDS", default_value_t = 10)]
+        window: u64,
+
+        /// Also write every value to this CSV file, as `time,series,value` rows.
+        #[arg(long, value_name = "FILE")]
+        csv: Option<PathBuf>,
+    },
+
+    /// Show a live view of the Brain's screen, forwarding clicks as touches.
+    #[cfg(feature = "screen")]
+    Screen,
+
+    /// Show a controller's sticks, buttons, and battery live, for diagnosing drift and broken
+    /// buttons. This opens the Brain's controller page and mirrors it in the terminal.
+    #[cfg(feature = "screen")]
+    Controller,
+
     /// Access a Brain's system key/value configuration.
     #[command(subcommand, visible_alias = "kv")]
     KeyValue(KeyValue),
+
+    /// Manage the team number, robot name, and owner stored on a Brain.
+    #[command(subcommand)]
+    Team(Team),
     
     /// Run a field control TUI.
     #[cfg(feature = "field-control")]
     #[clap(visible_aliases = ["fc", "comp-control"])]
-    FieldControl,
+    FieldControl {
+        #[command(subcommand)]
+        action: Option<FieldControlAction>,
+
+        /// Serial port of a controller to run the match with. Repeat to control several robots.
+        #[arg(long = "controller", value_name = "PORT", global = true)]
+        controllers: Vec<String>,
+
+        /// Run the match with every controller plugged into USB.
+        #[arg(long, conflicts_with = "controllers", global = true)]
+        all: bool,
+
+        /// Match timings to start with. Templates can also be switched from the TUI.
+        #[arg(long, value_enum, default_value_t)]
+        template: MatchTemplate,
+
+        /// Play sounds when periods start and end, and 30 seconds before driver control ends.
+        #[arg(long)]
+        sounds: bool,
+
+        /// Show desktop notifications at the same points in the match as `--sounds`.
+        #[arg(long)]
+        notifications: bool,
+
+        /// Save a JSON log of the session's mode changes and program output to this file.
+        #[arg(long, value_name = "FILE", global = true)]
+        export: Option<PathBuf>,
+    },
     
     /// Update cargo-v5 to the latest version.
     #[clap(hide = matches!(*self_update::CURRENT_MODE, SelfUpdateMode::Unmanaged(_)))]
     SelfUpdate,
 
     /// Migrate an older project to vexide 0.8.0.
-    Migrate,
+    Migrate {
+        /// Set up a vexide project from a project made with another tool.
+        #[arg(long, value_enum)]
+        from: Option<MigrateFrom>,
+
+        #[clap(flatten)]
+        opts: MigrateOpts,
+    },
+
+    /// Run the command recorded in a `--capture` file again, answering it with the recorded
+    /// replies instead of a real device. Used to reproduce protocol bugs.
+    Replay {
+        /// The capture file to replay.
+        file: PathBuf,
+    },
+}
+
+/// Asks how to set up a new project, unless told to use the defaults or prompts are disabled.
+fn project_options(yes: bool) -> Result<ProjectOptions, CliError> {
+    if yes || !is_interactive() {
+        Ok(ProjectOptions::default())
+    } else {
+        prompt_project_options()
+    }
 }
 
 #[derive(Args, Debug)]
@@ -160,65 +871,512 @@ struct DownloadOpts {
     #[cfg_attr(feature = "fetch-template", arg(long, default_value = "false"))]
     #[cfg_attr(not(feature = "fetch-template"), arg(skip = false))]
     offline: bool,
+
+    /// Create the project from a directory, `.tar.gz` archive, Git URL, or GitHub repository
+    /// (`org/repo[@rev]`) instead of vexide-template.
+    #[arg(long, value_name = "TEMPLATE")]
+    template: Option<TemplateSource>,
 }
 
 #[tokio::main]
 async fn main() -> miette::Result<()> {
     // Parse CLI arguments
-    let Cargo::V5 { command, path } = Cargo::parse();
+    let Cargo::V5 {
+        command,
+        path,
+        error_format,
+        ascii,
+        capture,
+        non_interactive,
+        mut connection_opts,
+    } = Cargo::parse();
+
+    set_non_interactive(non_interactive);
+
+    let config = load_user_config()?;
+    connection_opts.device = connection_opts.device.or(config.device.clone());
+
+    if ascii || config.ascii == Some(true) || ascii_output_configured(&manifest_path(&path)) {
+        set_ascii_output(true);
+        _ = miette::set_hook(Box::new(|_| {
+            Box::new(miette::MietteHandlerOpts::new().unicode(false).build())
+        }));
+    }
 
     let mut logger = flexi_logger::Logger::try_with_env()
         .unwrap()
-        .log_to_file(
-            FileSpec::default()
-                .directory(env::temp_dir())
-                .use_timestamp(false)
-                .basename(format!(
-                    "cargo-v5-{}",
-                    Utc::now().format("%Y-%m-%d_%H-%M-%S")
-                )),
-        )
-        .log_to_stderr()
+        .log_to_file(log_file_spec())
+        .duplicate_to_stderr(Duplicate::All)
         .adaptive_format_for_stderr(AdaptiveFormat::Default)
         .start()
         .unwrap();
 
-    if let Err(err) = app(command, path, &mut logger).await {
+    let current_log_files = logger
+        .existing_log_files(&LogfileSelector::default())
+        .unwrap_or_default();
+    if let Err(err) = clean_up_log_files(LogRetention::from_env(), &current_log_files) {
+        log::debug!("Couldn't clean up old log files: {err}");
+    }
+
+    if let Some(capture) = &capture {
+        start_capture(capture, &std::env::args().skip(1).collect::<Vec<_>>())?;
+    }
+
+    if let Err(err) = app(command, path, &connection_opts, &mut logger).await {
         log::debug!("cargo-v5 is exiting due to an error: {err}");
+
+        let exit_code = err
+            .downcast_ref::<CliError>()
+            .map_or(1, CliError::exit_code);
+
+        if error_format == ErrorFormat::Json {
+            let mut report = String::new();
+            if miette::JSONReportHandler::new()
+                .render_report(&mut report, err.as_ref())
+                .is_ok()
+            {
+                eprintln!("{report}");
+                std::process::exit(exit_code);
+            }
+        }
+
         if let Ok(files) = logger.existing_log_files(&LogfileSelector::default()) {
             for file in files {
                 eprintln!("A log file is available at {}.", file.display());
             }
         }
+
+        if exit_code != 1 {
+            eprintln!("{err:?}");
+            std::process::exit(exit_code);
+        }
         return Err(err);
     }
     Ok(())
 }
 
-async fn app(command: Command, path: PathBuf, logger: &mut LoggerHandle) -> miette::Result<()> {
+async fn app(
+    command: Command,
+    path: PathBuf,
+    connection_opts: &ConnectionOpts,
+    logger: &mut LoggerHandle,
+) -> miette::Result<()> {
     match command {
-        Command::Build { cargo_opts } => {
-            build(&path, cargo_opts).await?;
+        Command::Build { size, cargo_opts } => {
+            let output = build(&path, cargo_opts, &TerminalReporter::new()).await?;
+
+            if size && let Some(output) = output {
+                print_size_report(&output.elf_artifact, &output.bin_artifact)?;
+            }
+        }
+        Command::Check { cargo_opts } => {
+            check(&path, cargo_opts, false, &TerminalReporter::new()).await?;
+        }
+        Command::Clippy { cargo_opts } => {
+            check(&path, cargo_opts, true, &TerminalReporter::new()).await?;
+        }
+        Command::Size { cargo_opts } => {
+            if let Some(output) = build(&path, cargo_opts, &TerminalReporter::new()).await? {
+                print_size_report(&output.elf_artifact, &output.bin_artifact)?;
+            }
+        }
+        Command::Bloat {
+            elf,
+            compare,
+            crates,
+            count,
+            cargo_opts,
+        } => {
+            let elf = match elf {
+                Some(elf) => Some(elf),
+                None => build(&path, cargo_opts, &TerminalReporter::new())
+                    .await?
+                    .map(|output| output.elf_artifact),
+            };
+
+            if let Some(elf) = elf {
+                bloat(
+                    &elf,
+                    BloatOpts {
+                        compare,
+                        crates,
+                        count,
+                    },
+                )?;
+            }
+        }
+        Command::Patch {
+            old,
+            new,
+            output,
+            uncompressed,
+        } => {
+            patch(&old, &new, output, uncompressed).await?;
+        }
+        Command::RaConfig { cargo_opts } => {
+            ra_config(&path, cargo_opts, &TerminalReporter::new()).await?
         }
         Command::Upload { upload_opts, after } => {
-            upload(&path, upload_opts, after).await?;
+            upload(&path, upload_opts, after, connection_opts).await?;
+        }
+        Command::Deploy {
+            profile,
+            all,
+            after,
+            cargo_opts,
+        } => {
+            let selection = match (profile, all) {
+                (Some(profile), _) => DeploySelection::Profile(profile),
+                (None, true) => DeploySelection::All,
+                (None, false) => DeploySelection::Prompt,
+            };
+
+            deploy(&path, selection, cargo_opts, after, connection_opts).await?;
+        }
+        Command::Preflight { opts } => preflight(&path, opts, connection_opts).await?,
+        Command::Dir => dir(&mut open_connection(connection_opts).await?).await?,
+        Command::Slots => slots(&mut open_connection(connection_opts).await?).await?,
+        Command::Stamp { slot } => {
+            stamp(
+                &mut open_connection(connection_opts).await?,
+                slot,
+                &TerminalReporter::new(),
+            )
+            .await?
+        }
+        Command::Info { message_format } => {
+            info(&mut open_connection(connection_opts).await?, message_format).await?
+        }
+        Command::Battery {
+            watch,
+            interval,
+            warn_charge,
+            warn_voltage,
+        } => {
+            battery(
+                &mut open_connection(connection_opts).await?,
+                watch.then(|| Duration::from_millis(interval)),
+                BatteryThresholds {
+                    charge: warn_charge,
+                    voltage: warn_voltage,
+                },
+            )
+            .await?
+        }
+        Command::Radio { test } => {
+            let mut connection = open_connection(connection_opts).await?;
+            if test {
+                radio_test(&mut connection).await?;
+            } else {
+                radio(&mut connection).await?;
+            }
+        }
+        Command::Df { largest } => df(&mut open_connection(connection_opts).await?, largest).await?,
+        Command::Devices {
+            action: Some(Devices::Firmware { port }),
+            ..
+        } => device_firmware(&mut open_connection(connection_opts).await?, port).await?,
+        Command::Devices {
+            action: None,
+            watch,
+            interval,
+        } => {
+            devices(
+                &mut open_connection(connection_opts).await?,
+                watch.then(|| Duration::from_millis(interval)),
+            )
+            .await?
+        }
+        Command::Cat { file } => cat(&mut open_connection(connection_opts).await?, file).await?,
+        Command::Pull {
+            remote,
+            local,
+            force,
+        } => {
+            pull(
+                &mut open_connection(connection_opts).await?,
+                remote,
+                local,
+                force,
+                &TerminalReporter::new(),
+            )
+            .await?
+        }
+        Command::Push {
+            local,
+            remote,
+            load_address,
+            extension,
+            after,
+        } => {
+            push(
+                &mut open_connection(connection_opts).await?,
+                &local,
+                &remote,
+                load_address,
+                extension,
+                after,
+                &TerminalReporter::new(),
+            )
+            .await?
         }
-        Command::Dir => dir(&mut open_connection().await?).await?,
-        Command::Devices => devices(&mut open_connection().await?).await?,
-        Command::Cat { file } => cat(&mut open_connection().await?, file).await?,
-        Command::Rm { file } => rm(&mut open_connection().await?, file).await?,
-        Command::Log { page } => log(&mut open_connection().await?, page).await?,
-        Command::Screenshot => screenshot(&mut open_connection().await?).await?,
-        Command::Run(opts) => {
-            let mut connection = upload(&path, opts, AfterUpload::Run).await?;
+        Command::Rm { file, slot } => {
+            let mut connection = open_connection(connection_opts).await?;
+            let reporter = TerminalReporter::new();
+
+            match (file, slot) {
+                (_, Some(slot)) => rm_slot(&mut connection, slot, &reporter).await?,
+                (Some(file), None) => rm(&mut connection, &file, &reporter).await?,
+                (None, None) => unreachable!("clap requires a file or slot"),
+            }
+        }
+        Command::Cp {
+            source,
+            destination,
+            force,
+        } => {
+            cp(
+                &mut open_connection(connection_opts).await?,
+                &source,
+                &destination,
+                force,
+                &TerminalReporter::new(),
+            )
+            .await?
+        }
+        Command::Mv {
+            source,
+            destination,
+            force,
+        } => {
+            mv(
+                &mut open_connection(connection_opts).await?,
+                &source,
+                &destination,
+                force,
+                &TerminalReporter::new(),
+            )
+            .await?
+        }
+        Command::Log {
+            page,
+            follow,
+            since,
+            count,
+        } => {
+            log(
+                &mut open_connection(connection_opts).await?,
+                page,
+                follow,
+                since,
+                count,
+            )
+            .await?
+        }
+        Command::Firmware(subcommand) => {
+            let mut connection = open_connection(connection_opts).await?;
+            match subcommand {
+                Firmware::Status => firmware::status(&mut connection).await?,
+                #[cfg(feature = "firmware")]
+                Firmware::Check => firmware::check(&mut connection).await?,
+            }
+        }
+        Command::Logs(subcommand) => {
+            let current = logger
+                .existing_log_files(&LogfileSelector::default())
+                .unwrap_or_default();
+
+            match subcommand {
+                Logs::List => list_logs(&current)?,
+                Logs::Open { run } => open_log(run, &current)?,
+                Logs::Purge => purge_logs(&current)?,
+            }
+        }
+        Command::History { count } => history(count)?,
+        Command::Analyze(Analyze::Brownouts) => {
+            analyze::brownouts(&mut open_connection(connection_opts).await?).await?
+        }
+        Command::Portcheck { duration } => {
+            portcheck(
+                &mut open_connection(connection_opts).await?,
+                Duration::from_secs(duration),
+            )
+            .await?
+        }
+        Command::RunSlot { slot } => {
+            run_slot(&mut open_connection(connection_opts).await?, slot).await?
+        }
+        Command::Stop => stop(&mut open_connection(connection_opts).await?).await?,
+        Command::Top => top(&mut open_connection(connection_opts).await?).await?,
+        Command::Bridge {
+            websocket,
+            allow_origin,
+        } => {
+            bridge(
+                &mut open_connection(connection_opts).await?,
+                websocket,
+                allow_origin,
+            )
+            .await?
+        }
+        Command::SerialBridge {
+            listen,
+            system,
+            token,
+        } => {
+            let mut connection = open_connection(connection_opts).await?;
+            switch_to_download_channel(&mut connection).await?;
+
+            serial_bridge(&mut connection, listen, system, token).await?;
+        }
+        Command::Daemon { listen } => {
+            let mut connection = open_connection(connection_opts).await?;
+            switch_to_download_channel(&mut connection).await?;
+
+            daemon(&mut connection, &path, listen).await?;
+        }
+        Command::Serve { listen, token } => {
+            let token = match token.or_else(|| std::env::var(remote::TOKEN_ENV_VAR).ok()) {
+                Some(token) => token,
+                None => {
+                    let token = remote::generate_token();
+                    eprintln!(
+                        "Clients can connect with `--remote-token {token}` or by setting {}.",
+                        remote::TOKEN_ENV_VAR
+                    );
+                    token
+                }
+            };
+
+            let mut connection = open_connection(connection_opts).await?;
+            serve(&mut connection, listen, &token).await?;
+        }
+        Command::Screenshot {
+            output,
+            stdout,
+            watch,
+            interval,
+            frames,
+        } => {
+            let output = match output {
+                _ if stdout => ScreenshotOutput::Raw,
+                Some(path) if path.as_os_str() == "-" => ScreenshotOutput::Stdout,
+                Some(path) => ScreenshotOutput::File(path),
+                None => ScreenshotOutput::Default,
+            };
+
+            let mut connection = open_connection(connection_opts).await?;
+            if watch {
+                record_screen(
+                    &mut connection,
+                    output,
+                    Duration::from_millis(interval),
+                    frames,
+                    &TerminalReporter::new(),
+                )
+                .await?
+            } else {
+                screenshot(&mut connection, output, &TerminalReporter::new()).await?
+            }
+        }
+        #[cfg(feature = "plot")]
+        Command::Plot { window, csv } => {
+            plot(
+                &mut open_connection(connection_opts).await?,
+                Duration::from_secs(window),
+                csv.as_deref(),
+            )
+            .await?
+        }
+        #[cfg(feature = "screen")]
+        Command::Screen => {
+            let mut connection = open_connection(connection_opts).await?;
+            mirror_screen(&mut connection).await?;
+        }
+        #[cfg(feature = "screen")]
+        Command::Controller => {
+            let mut connection = open_connection(connection_opts).await?;
+            view_controller(&mut connection).await?;
+        }
+        Command::Test { opts } => test(&path, opts, connection_opts).await?,
+        Command::Run {
+            on_exit,
+            force_upload,
+            no_upload,
+            record,
+            mut upload_opts,
+        } => {
+            // Create the CSV file first so that a bad path is reported before uploading.
+            let capture = record
+                .map(|path| TelemetryCapture::create(&path))
+                .transpose()?;
+
+            let (mut connection, elf_artifact) = if no_upload {
+                let connection = run_installed(
+                    &path,
+                    upload_opts.slot,
+                    upload_opts.cargo_opts.package.as_deref(),
+                    connection_opts,
+                )
+                .await?;
+                (connection, None)
+            } else {
+                upload_opts.skip_unchanged = !force_upload;
+                let outcome = upload(&path, upload_opts, AfterUpload::Run, connection_opts).await?;
+                (outcome.connection, outcome.elf_artifact)
+            };
+            let backtraces = BacktraceAnnotator::for_elf(elf_artifact);
 
             tokio::select! {
-                () = terminal(&mut connection, logger) => {}
+                () = terminal(&mut connection, logger, backtraces, capture) => {}
+                _ = tokio::signal::ctrl_c() => {
+                    let on_exit = on_exit
+                        .or(user_config().terminal.on_exit)
+                        .unwrap_or_default();
+                    let stop_program = match on_exit {
+                        OnExit::Stop => true,
+                        OnExit::Detach => false,
+                        OnExit::Prompt => {
+                            eprintln!(
+                                "\n        \x1b[1;96mNote\x1b[0m The program is still running. Press Ctrl-C again within 3 seconds to stop it."
+                            );
+                            tokio::select! {
+                                _ = tokio::signal::ctrl_c() => true,
+                                () = tokio::time::sleep(Duration::from_secs(3)) => false,
+                            }
+                        }
+                    };
+
+                    if stop_program {
+                        // Try to quit program.
+                        //
+                        // Don't bother waiting for a response, since the brain could
+                        // be locked up and prevent the program from exiting.
+                        _ = connection.send(
+                            FileLoadActionPacket::new(FileLoadActionPayload {
+                                vendor: FileVendor::User,
+                                action: FileLoadAction::Stop,
+                                file_name: FixedString::default(),
+                            })
+                        ).await;
+                    } else {
+                        eprintln!("    \x1b[1;96mDetached\x1b[0m The program was left running.");
+                    }
+
+                    std::process::exit(0);
+                }
+            }
+        }
+        Command::Kiosk {
+            slot,
+            looping,
+            max_runtime,
+        } => {
+            let mut connection = open_connection(connection_opts).await?;
+
+            tokio::select! {
+                res = kiosk(&mut connection, slot, looping, max_runtime.map(Duration::from_secs)) => res?,
                 _ = tokio::signal::ctrl_c() => {
-                    // Try to quit program.
-                    //
-                    // Don't bother waiting for a response, since the brain could
-                    // be locked up and prevent the program from exiting.
                     _ = connection.send(
                         FileLoadActionPacket::new(FileLoadActionPayload {
                             vendor: FileVendor::User,
@@ -226,66 +1384,279 @@ async fn app(command: Command, path: PathBuf, logger: &mut LoggerHandle) -> miet
                             file_name: FixedString::default(),
                         })
                     ).await;
-
-                    std::process::exit(0);
                 }
             }
         }
+        Command::Team(subcommand) => {
+            let mut connection = open_connection(connection_opts).await?;
+            if let Team::Set { team, robot, owner } = subcommand {
+                set_team_info(&mut connection, &TeamInfo { team, robot, owner }).await?;
+            }
+
+            print_team_info(&team_info(&mut connection).await?)?;
+        }
         Command::KeyValue(subcommand) => {
-            let mut connection = open_connection().await?;
+            let mut connection = open_connection(connection_opts).await?;
             match subcommand {
-                KeyValue::Get { key } => {
-                    println!("{}", kv_get(&mut connection, &key).await?);
+                KeyValue::Get { key, default, raw } => {
+                    let value = kv_get(&mut connection, &key)
+                        .await?
+                        .or(default)
+                        .ok_or(CliError::KeyNotFound(key))?;
+
+                    if raw {
+                        print!("{value}");
+                    } else {
+                        println!("{value}");
+                    }
                 }
                 KeyValue::Set { key, value } => {
                     kv_set(&mut connection, &key, &value).await?;
-                    println!("{key} = {}", kv_get(&mut connection, &key).await?);
+                    println!(
+                        "{key} = {}",
+                        kv_get(&mut connection, &key).await?.unwrap_or_default()
+                    );
+                }
+                KeyValue::Dump { format } => {
+                    print!("{}", kv_dump(&mut connection, format).await?);
+                }
+                KeyValue::Import { file } => {
+                    let contents = std::fs::read_to_string(&file).map_err(CliError::from)?;
+                    kv_import(&mut connection, parse_dump(&file, &contents)?).await?;
                 }
             }
         }
-        Command::Terminal => {
-            let mut connection = open_connection().await?;
+        Command::Terminal {
+            elf,
+            record,
+            binary,
+            cobs,
+            pty,
+        } => {
+            if binary || cobs || pty {
+                #[cfg(not(unix))]
+                if pty {
+                    Err(CliError::PtyUnsupported)?;
+                }
+
+                let mut connection = open_connection(connection_opts).await?;
+                switch_to_download_channel(&mut connection).await?;
+
+                #[cfg(unix)]
+                if pty {
+                    let mut pty = Pty::open().map_err(CliError::IoError)?;
+                    eprintln!(
+                        "     \x1b[1;92mServing\x1b[0m program I/O on {}",
+                        pty.path.display()
+                    );
+                    passthrough(&mut connection, &mut pty.reader, &mut pty.writer, cobs).await?;
+                    return Ok(());
+                }
+
+                passthrough(
+                    &mut connection,
+                    tokio::io::stdin(),
+                    tokio::io::stdout(),
+                    cobs,
+                )
+                .await?;
+                return Ok(());
+            }
+
+            let backtraces = elf
+                .map(|elf| Symbolizer::load(&elf))
+                .transpose()?
+                .map(BacktraceAnnotator::new);
+            let capture = record
+                .map(|path| TelemetryCapture::create(&path))
+                .transpose()?;
+
+            // If field control is already polling a device's output, share its session.
+            if connection_opts.device.is_none()
+                && !connection_opts.bluetooth
+                && let Some(hub) = attach_terminal().await
+            {
+                shared_terminal(hub, logger, backtraces, capture).await;
+                return Ok(());
+            }
+
+            let mut connection = open_connection(connection_opts).await?;
             switch_to_download_channel(&mut connection).await?;
-            terminal(&mut connection, logger).await;
+            terminal(&mut connection, logger, backtraces, capture).await;
         }
         #[cfg(feature = "field-control")]
-        Command::FieldControl => {
-            // Not using open_connection since we need to filter for controllers only here.
-            let mut connection = {
-                let devices = serial::find_devices().map_err(CliError::SerialError)?;
-
-                tokio::task::spawn_blocking::<_, Result<SerialConnection, CliError>>(move || {
-                    devices
-                        .into_iter()
-                        .find(|device| {
-                            matches!(device, SerialDevice::Controller { system_port: _ })
-                        })
-                        .ok_or(CliError::NoController)?
-                        .connect(Duration::from_secs(5))
-                        .map_err(CliError::SerialError)
-                })
-                .await
-                .unwrap()?
+        Command::FieldControl {
+            action,
+            controllers,
+            all,
+            template,
+            sounds,
+            notifications,
+            export,
+        } => {
+            // Read the script first so that mistakes in it are reported before connecting.
+            let script = match action {
+                Some(FieldControlAction::Run { script }) => Some(MatchScript::load(&script)?),
+                None => None,
             };
 
-            run_field_control_tui(&mut connection).await?;
+            let mut controllers =
+                connect_controllers(controllers, all, connection_opts.device.clone()).await?;
+            let mut session = export.as_ref().map(|_| MatchLog::new(&controllers));
+
+            let result = match script {
+                Some(script) => run_match_script(&mut controllers, &script, session.as_mut()).await,
+                None => {
+                    run_field_control_tui(
+                        &mut controllers,
+                        template,
+                        MatchCues::new(sounds, notifications),
+                        session.as_mut(),
+                    )
+                    .await
+                }
+            };
+
+            // Export even if the session ended in an error, since that's when it's most useful.
+            if let (Some(session), Some(export)) = (session, &export) {
+                session.export(export)?;
+            }
+            result?;
         }
         Command::New {
             name,
             download_opts,
+            yes,
         } => {
-            new(path, Some(name), !download_opts.offline).await?;
+            new(
+                path,
+                Some(name),
+                download_opts.template,
+                !download_opts.offline,
+                project_options(yes)?,
+            )
+            .await?;
         }
-        Command::Init { download_opts } => {
-            new(path, None, !download_opts.offline).await?;
+        Command::Init { download_opts, yes } => {
+            new(
+                path,
+                None,
+                download_opts.template,
+                !download_opts.offline,
+                project_options(yes)?,
+            )
+            .await?;
         }
         Command::SelfUpdate => {
             self_update::self_update().await?;
         }
-        Command::Migrate => {
-            migrate::migrate_workspace(&path).await?;
+        Command::Migrate { from, opts } => match from {
+            Some(MigrateFrom::Pros) => migrate::migrate_pros(&path, &opts).await?,
+            None => migrate::migrate_workspace(&path, &opts).await?,
+        },
+        Command::Replay { file } => {
+            let capture = read_capture(&file)?;
+            if capture.version != env!("CARGO_PKG_VERSION") {
+                log::warn!(
+                    "This capture was recorded by cargo-v5 {}, so it may not replay faithfully.",
+                    capture.version
+                );
+            }
+
+            let Cargo::V5 {
+                command,
+                path,
+                connection_opts,
+                ..
+            } = Cargo::try_parse_from(
+                std::iter::once("cargo".to_string()).chain(capture.args.clone()),
+            )
+            .map_err(|err| CliError::MalformedCapture(file.clone(), err.to_string()))?;
+            if matches!(command, Command::Replay { .. }) {
+                Err(CliError::MalformedCapture(
+                    file,
+                    "a capture can't replay another capture".to_string(),
+                ))?;
+            }
+            if let Some(reason) = replay_refusal(&command) {
+                Err(CliError::UnreplayableCapture(
+                    capture.args.join(" "),
+                    reason,
+                ))?;
+            }
+
+            eprintln!(
+                "   \x1b[1;92mReplaying\x1b[0m `cargo {}`",
+                capture.args.join(" ")
+            );
+            set_replay(ReplayConnection::new(capture));
+            Box::pin(app(command, path, &connection_opts, logger)).await?;
         }
     }
 
     Ok(())
 }
+
+/// Why a recorded command can't be replayed, if it builds the project or writes local files.
+fn replay_refusal(command: &Command) -> Option<&'static str> {
+    const BUILDS: &str = "it builds the project";
+    const WRITES: &str = "it writes local files";
+
+    match command {
+        Command::Build { .. }
+        | Command::Check { .. }
+        | Command::Clippy { .. }
+        | Command::Size { .. }
+        | Command::RaConfig { .. }
+        | Command::Upload { .. }
+        | Command::Deploy { .. }
+        | Command::Test { .. }
+        | Command::Run { .. }
+        | Command::Bloat { elf: None, .. } => Some(BUILDS),
+        Command::Preflight { opts } if opts.file.is_none() => Some(BUILDS),
+        Command::Patch { .. }
+        | Command::New { .. }
+        | Command::Init { .. }
+        | Command::Pull { .. }
+        | Command::SelfUpdate
+        | Command::Migrate { .. }
+        | Command::Logs(Logs::Purge)
+        | Command::Screenshot { stdout: false, .. }
+        | Command::Terminal {
+            record: Some(_), ..
+        } => Some(WRITES),
+        #[cfg(feature = "plot")]
+        Command::Plot { csv: Some(_), .. } => Some(WRITES),
+        #[cfg(feature = "field-control")]
+        Command::FieldControl {
+            export: Some(_), ..
+        } => Some(WRITES),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_is_separate_from_traffic_capture() {
+        let Cargo::V5 {
+            command, capture, ..
+        } = Cargo::try_parse_from(["cargo", "v5", "terminal", "--capture", "x.csv"]).unwrap();
+        assert_eq!(capture, Some(PathBuf::from("x.csv")));
+        assert!(matches!(command, Command::Terminal { record: None, .. }));
+
+        let Cargo::V5 {
+            command, capture, ..
+        } = Cargo::try_parse_from(["cargo", "v5", "run", "--record", "x.csv"]).unwrap();
+        assert_eq!(capture, None);
+        assert!(matches!(
+            command,
+            Command::Run {
+                record: Some(record),
+                ..
+            } if record == PathBuf::from("x.csv")
+        ));
+    }
+}
@@ -1,12 +1,67 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
 use cargo_metadata::Package;
 use clap::ValueEnum;
 use serde_json::Value;
 
 use crate::{
-    commands::upload::{ProgramIcon, UploadStrategy},
+    commands::{
+        preflight::{PreflightCheck, PreflightConfig},
+        upload::{ProgramIcon, ProgramVersion, UploadStrategy},
+    },
     errors::CliError,
 };
 
+/// Resolves the global `--path` argument to the `Cargo.toml` of the project it refers to.
+///
+/// This mirrors cargo's own manifest discovery: `path` may either point directly at a manifest
+/// file, or at a directory, in which case it and its ancestors are searched for a `Cargo.toml`.
+pub fn manifest_path(path: &Path) -> PathBuf {
+    if !path.is_dir() {
+        return path.to_path_buf();
+    }
+
+    let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+
+    path.ancestors()
+        .map(|dir| dir.join("Cargo.toml"))
+        .find(|manifest| manifest.is_file())
+        .unwrap_or_else(|| path.join("Cargo.toml"))
+}
+
+/// Returns the directory containing the manifest at `manifest_path`.
+///
+/// Cargo commands should be run from here so that `.cargo/config.toml` files are picked up
+/// regardless of where `cargo v5` was invoked from.
+pub fn manifest_dir(manifest_path: &Path) -> &Path {
+    match manifest_path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    }
+}
+
+/// Returns whether `package.metadata.v5.ascii` is enabled in the manifest at `manifest_path`.
+///
+/// This is read straight from the manifest rather than through `cargo metadata`, since it's
+/// needed before any command runs.
+pub fn ascii_output_configured(manifest_path: &Path) -> bool {
+    std::fs::read_to_string(manifest_path)
+        .ok()
+        .and_then(|manifest| manifest.parse::<toml_edit::DocumentMut>().ok())
+        .and_then(|manifest| {
+            manifest
+                .get("package")?
+                .get("metadata")?
+                .get("v5")?
+                .get("ascii")?
+                .as_bool()
+        })
+        .unwrap_or(false)
+}
+
 fn field_type(field: &Value) -> &'static str {
     match field {
         Value::Array(_) => "array",
@@ -18,12 +73,195 @@ fn field_type(field: &Value) -> &'static str {
     }
 }
 
-#[derive(Default, Debug, Clone, Copy, Eq, PartialEq)]
+/// A named build and upload configuration from `package.metadata.v5.deploy`.
+#[derive(Default, Debug, Clone, Eq, PartialEq)]
+pub struct DeployProfile {
+    pub slot: Option<u8>,
+    pub features: Vec<String>,
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub icon: Option<ProgramIcon>,
+}
+
+impl DeployProfile {
+    fn new(profile_name: &str, profile: &Value) -> Result<Self, CliError> {
+        let bad_type = |field: &str, expected: &str, found: &Value| CliError::BadFieldType {
+            field: format!("deploy.{profile_name}.{field}"),
+            expected: expected.to_string(),
+            found: field_type(found).to_string(),
+        };
+        let profile = profile.as_object().ok_or_else(|| CliError::BadFieldType {
+            field: format!("deploy.{profile_name}"),
+            expected: "table".to_string(),
+            found: field_type(profile).to_string(),
+        })?;
+        let string = |field: &str| -> Result<Option<String>, CliError> {
+            profile
+                .get(field)
+                .map(|value| {
+                    value
+                        .as_str()
+                        .map(str::to_string)
+                        .ok_or_else(|| bad_type(field, "string", value))
+                })
+                .transpose()
+        };
+
+        Ok(Self {
+            slot: profile
+                .get("slot")
+                .map(|value| {
+                    value
+                        .as_u64()
+                        .map(|slot| slot as u8) // NOTE: range validation is done at a later step
+                        .ok_or_else(|| bad_type("slot", "integer", value))
+                })
+                .transpose()?,
+            features: match profile.get("features") {
+                Some(value) => value
+                    .as_array()
+                    .ok_or_else(|| bad_type("features", "array of strings", value))?
+                    .iter()
+                    .map(|feature| {
+                        feature
+                            .as_str()
+                            .map(str::to_string)
+                            .ok_or_else(|| bad_type("features", "array of strings", value))
+                    })
+                    .collect::<Result<_, _>>()?,
+                None => Vec::new(),
+            },
+            name: string("name")?,
+            description: string("description")?,
+            icon: string("icon")?
+                .map(|icon| {
+                    ProgramIcon::from_str(&icon, false).map_err(|_| CliError::InvalidIcon(icon))
+                })
+                .transpose()?,
+        })
+    }
+}
+
+/// Parses the `package.metadata.v5.preflight` table.
+fn preflight_config(field: &Value) -> Result<PreflightConfig, CliError> {
+    let bad_type = |name: &str, expected: &str, found: &Value| CliError::BadFieldType {
+        field: format!("preflight.{name}"),
+        expected: expected.to_string(),
+        found: field_type(found).to_string(),
+    };
+    let table = field.as_object().ok_or_else(|| CliError::BadFieldType {
+        field: "preflight".to_string(),
+        expected: "table".to_string(),
+        found: field_type(field).to_string(),
+    })?;
+
+    Ok(PreflightConfig {
+        slot: table
+            .get("slot")
+            .map(|value| {
+                value
+                    .as_u64()
+                    .map(|slot| slot as u8) // NOTE: range validation is done at a later step
+                    .ok_or_else(|| bad_type("slot", "integer", value))
+            })
+            .transpose()?,
+        min_battery: table
+            .get("min-battery")
+            .map(|value| {
+                value
+                    .as_u64()
+                    .filter(|percent| *percent <= 100)
+                    .map(|percent| percent as u8)
+                    .ok_or_else(|| bad_type("min-battery", "integer from 0 to 100", value))
+            })
+            .transpose()?,
+        skip: match table.get("skip") {
+            Some(value) => {
+                let bad_skip = || bad_type("skip", "array of check names", value);
+
+                value
+                    .as_array()
+                    .ok_or_else(bad_skip)?
+                    .iter()
+                    .map(|check| {
+                        check
+                            .as_str()
+                            .and_then(|check| PreflightCheck::from_str(check, false).ok())
+                            .ok_or_else(bad_skip)
+                    })
+                    .collect::<Result<_, _>>()?
+            }
+            None => Vec::new(),
+        },
+    })
+}
+
+/// Assets uploaded as the program's linked file, from `package.metadata.v5.linked-files`.
+#[derive(Default, Debug, Clone, Eq, PartialEq)]
+pub struct LinkedFilesConfig {
+    /// Glob patterns of the files to link.
+    pub files: Vec<String>,
+    /// Address VEXos loads the linked file at.
+    pub load_address: u32,
+}
+
+/// Parses the `package.metadata.v5.linked-files` table.
+fn linked_files_config(field: &Value) -> Result<LinkedFilesConfig, CliError> {
+    let bad_type = |name: &str, expected: &str, found: &Value| CliError::BadFieldType {
+        field: format!("linked-files.{name}"),
+        expected: expected.to_string(),
+        found: field_type(found).to_string(),
+    };
+    let table = field.as_object().ok_or_else(|| CliError::BadFieldType {
+        field: "linked-files".to_string(),
+        expected: "table".to_string(),
+        found: field_type(field).to_string(),
+    })?;
+
+    let load_address = table.get("load-address").unwrap_or(&Value::Null);
+
+    Ok(LinkedFilesConfig {
+        files: match table.get("files") {
+            Some(value) => {
+                let bad_files = || bad_type("files", "array of strings", value);
+
+                value
+                    .as_array()
+                    .ok_or_else(bad_files)?
+                    .iter()
+                    .map(|pattern| pattern.as_str().map(str::to_string).ok_or_else(bad_files))
+                    .collect::<Result<_, _>>()?
+            }
+            None => Vec::new(),
+        },
+        load_address: load_address
+            .as_u64()
+            .and_then(|address| u32::try_from(address).ok())
+            .ok_or_else(|| bad_type("load-address", "32-bit address", load_address))?,
+    })
+}
+
+#[derive(Default, Debug, Clone, Eq, PartialEq)]
 pub struct Metadata {
     pub slot: Option<u8>,
     pub icon: Option<ProgramIcon>,
+    /// IDE shown on the Brain's program info screen.
+    pub ide: Option<String>,
     pub compress: Option<bool>,
     pub upload_strategy: Option<UploadStrategy>,
+    /// Binary size in bytes above which wireless uploads default to the differential strategy.
+    pub differential_threshold: Option<u64>,
+    /// Path to an upload log, relative to the package's manifest directory.
+    pub upload_log: Option<PathBuf>,
+    pub version: Option<ProgramVersion>,
+    /// Glob patterns of asset files to bundle into the program.
+    pub bundle: Vec<String>,
+    /// Asset files uploaded as the program's linked file.
+    pub linked_files: Option<LinkedFilesConfig>,
+    /// Named profiles for `cargo v5 deploy`.
+    pub deploy: BTreeMap<String, DeployProfile>,
+    /// Settings for `cargo v5 preflight`.
+    pub preflight: PreflightConfig,
 }
 
 impl Metadata {
@@ -57,6 +295,20 @@ impl Metadata {
                 } else {
                     None
                 },
+                ide: if let Some(field) = v5_metadata.get("ide") {
+                    Some(
+                        field
+                            .as_str()
+                            .ok_or(CliError::BadFieldType {
+                                field: "ide".to_string(),
+                                expected: "string".to_string(),
+                                found: field_type(field).to_string(),
+                            })?
+                            .to_string(),
+                    )
+                } else {
+                    None
+                },
                 compress: if let Some(compress) = v5_metadata.get("compress") {
                     let compress = compress.as_bool().ok_or(CliError::BadFieldType {
                         field: "compress".to_string(),
@@ -82,6 +334,85 @@ impl Metadata {
                 } else {
                     None
                 },
+                differential_threshold: if let Some(field) =
+                    v5_metadata.get("differential-threshold")
+                {
+                    Some(field.as_u64().ok_or(CliError::BadFieldType {
+                        field: "differential-threshold".to_string(),
+                        expected: "integer".to_string(),
+                        found: field_type(field).to_string(),
+                    })?)
+                } else {
+                    None
+                },
+                upload_log: if let Some(field) = v5_metadata.get("upload-log") {
+                    let upload_log = field.as_str().ok_or(CliError::BadFieldType {
+                        field: "upload-log".to_string(),
+                        expected: "string".to_string(),
+                        found: field_type(field).to_string(),
+                    })?;
+
+                    Some(
+                        pkg.manifest_path
+                            .parent()
+                            .map(|dir| dir.as_std_path().join(upload_log))
+                            .unwrap_or_else(|| PathBuf::from(upload_log)),
+                    )
+                } else {
+                    None
+                },
+                version: if let Some(field) = v5_metadata.get("version") {
+                    let version = field.as_str().ok_or(CliError::BadFieldType {
+                        field: "version".to_string(),
+                        expected: "string".to_string(),
+                        found: field_type(field).to_string(),
+                    })?;
+
+                    Some(version.parse()?)
+                } else {
+                    None
+                },
+                bundle: if let Some(field) = v5_metadata.get("bundle") {
+                    let bad_type = || CliError::BadFieldType {
+                        field: "bundle".to_string(),
+                        expected: "array of strings".to_string(),
+                        found: field_type(field).to_string(),
+                    };
+
+                    field
+                        .as_array()
+                        .ok_or_else(bad_type)?
+                        .iter()
+                        .map(|pattern| pattern.as_str().map(str::to_string).ok_or_else(bad_type))
+                        .collect::<Result<_, _>>()?
+                } else {
+                    Vec::new()
+                },
+                linked_files: v5_metadata
+                    .get("linked-files")
+                    .map(linked_files_config)
+                    .transpose()?,
+                deploy: if let Some(field) = v5_metadata.get("deploy") {
+                    field
+                        .as_object()
+                        .ok_or(CliError::BadFieldType {
+                            field: "deploy".to_string(),
+                            expected: "table".to_string(),
+                            found: field_type(field).to_string(),
+                        })?
+                        .iter()
+                        .map(|(name, profile)| {
+                            Ok((name.clone(), DeployProfile::new(name, profile)?))
+                        })
+                        .collect::<Result<_, CliError>>()?
+                } else {
+                    BTreeMap::new()
+                },
+                preflight: if let Some(field) = v5_metadata.get("preflight") {
+                    preflight_config(field)?
+                } else {
+                    PreflightConfig::default()
+                },
             });
         }
 
@@ -0,0 +1,140 @@
+//! Provenance manifests, recording exactly what source and toolchain built an artifact.
+//!
+//! A manifest is written next to the artifact as `<name>.provenance.json` when building with
+//! `--provenance`. Uploads check any manifest they find against the file being uploaded, so a
+//! team can show that the program on their robot is the one their manifest describes.
+
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use ring::digest::{SHA256, digest};
+use serde_json::{Value, json};
+
+use crate::{commands::build::cargo_bin, errors::CliError};
+
+/// Extension given to provenance manifests, in place of the artifact's own extension.
+pub const PROVENANCE_EXTENSION: &str = "provenance.json";
+
+/// Returns where the provenance manifest for `artifact` is stored.
+///
+/// The ELF and BIN built from the same program share a manifest.
+pub fn provenance_path(artifact: &Path) -> PathBuf {
+    artifact.with_extension(PROVENANCE_EXTENSION)
+}
+
+/// Hex-encoded SHA-256 digest of `data`.
+pub fn sha256_hex(data: &[u8]) -> String {
+    digest(&SHA256, data)
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Output of running a command in `dir`, if it ran successfully.
+fn command_output(
+    dir: &Path,
+    program: impl AsRef<std::ffi::OsStr>,
+    args: &[&str],
+) -> Option<String> {
+    let output = Command::new(program)
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .ok()?;
+
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Writes the provenance manifest for a program built in `project_dir`.
+///
+/// `build` describes the options the program was built with.
+pub fn write_provenance(
+    project_dir: &Path,
+    elf: &Path,
+    bin: &Path,
+    build: Value,
+) -> Result<PathBuf, CliError> {
+    let commit = command_output(project_dir, "git", &["rev-parse", "HEAD"]);
+    let dirty = command_output(project_dir, "git", &["status", "--porcelain"])
+        .is_some_and(|status| !status.is_empty());
+
+    let manifest = json!({
+        "artifacts": {
+            "elf": {
+                "path": elf,
+                "sha256": sha256_hex(&std::fs::read(elf)?),
+            },
+            "bin": {
+                "path": bin,
+                "sha256": sha256_hex(&std::fs::read(bin)?),
+            },
+        },
+        "git": commit.map(|commit| json!({
+            "commit": commit,
+            "dirty": dirty,
+        })),
+        "toolchain": {
+            // Run from the project so that `rust-toolchain.toml` overrides apply.
+            "rustc": command_output(project_dir, "rustc", &["--version", "--verbose"]),
+            "cargo": command_output(project_dir, cargo_bin(), &["--version"]),
+            "cargo-v5": env!("CARGO_PKG_VERSION"),
+        },
+        "build": build,
+    });
+
+    let path = provenance_path(bin);
+    std::fs::write(
+        &path,
+        serde_json::to_string_pretty(&manifest).unwrap() + "\n",
+    )?;
+
+    Ok(path)
+}
+
+/// Removes a manifest left over from an earlier build of `artifact`, which would no longer match.
+pub fn remove_stale_provenance(artifact: &Path) -> Result<(), CliError> {
+    match std::fs::remove_file(provenance_path(artifact)) {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+        _ => Ok(()),
+    }
+}
+
+/// Checks `artifact` against its provenance manifest, if it has one.
+///
+/// Returns the manifest if it matches, `None` if there isn't one, or an error if the artifact
+/// isn't the one the manifest describes.
+pub fn verify_provenance(artifact: &Path) -> Result<Option<Value>, CliError> {
+    let manifest_path = provenance_path(artifact);
+    let manifest = match std::fs::read_to_string(&manifest_path) {
+        Ok(manifest) => manifest,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+
+    let manifest = serde_json::from_str::<Value>(&manifest)
+        .map_err(|_| CliError::MalformedProvenance(manifest_path.clone()))?;
+
+    let kind = if artifact.extension().is_some_and(|ext| ext == "bin") {
+        "bin"
+    } else {
+        "elf"
+    };
+    let expected = manifest["artifacts"][kind]["sha256"]
+        .as_str()
+        .ok_or_else(|| CliError::MalformedProvenance(manifest_path.clone()))?;
+
+    if sha256_hex(&std::fs::read(artifact)?) != expected {
+        return Err(CliError::ProvenanceMismatch {
+            artifact: artifact.to_path_buf(),
+            manifest: manifest_path,
+        });
+    }
+
+    Ok(Some(manifest))
+}
@@ -0,0 +1,348 @@
+//! Tunnels the serial protocol over TCP, so a device plugged into one machine can be used from
+//! another (see `cargo v5 serve` and `--remote`).
+//!
+//! Both sides exchange frames made of a one-byte kind, a little-endian `u32` length, and a
+//! payload. The server opens with a `HELLO` frame holding a random nonce, and the client answers
+//! with the nonce's HMAC-SHA256 under the shared token, so the token itself is never sent. The
+//! tunnel isn't encrypted, so it should only be used on trusted networks.
+//!
+//! After the handshake, the client sends packets to the device with `SEND` frames and asks for
+//! replies with `RECV` frames. The server only reads from the device while the client is waiting
+//! for a reply, the same way a local connection does.
+
+use std::{
+    collections::VecDeque,
+    io,
+    time::{Duration, Instant},
+};
+
+use ring::{
+    hmac,
+    rand::{SecureRandom, SystemRandom},
+};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::{TcpStream, tcp::OwnedWriteHalf},
+    sync::mpsc,
+    time::timeout_at,
+};
+use vex_v5_serial::{
+    CheckHeader, Connection, ConnectionType,
+    protocol::{Decode, DecodeError, Encode},
+};
+
+use crate::{connection::ConnectionError, errors::CliError};
+
+/// Environment variable holding the token shared by `cargo v5 serve` and `--remote`.
+pub const TOKEN_ENV_VAR: &str = "CARGO_V5_REMOTE_TOKEN";
+
+/// Server: a nonce to authenticate with. Client: the nonce's HMAC. Server: the device's
+/// connection type, once authenticated.
+pub const FRAME_HELLO: u8 = 0;
+/// Client: a packet to send to the device.
+pub const FRAME_SEND: u8 = 1;
+/// Client: a request ID and a timeout in milliseconds. Server: the request ID and a packet from
+/// the device, or no packet if none arrived in time.
+pub const FRAME_RECV: u8 = 2;
+/// Client: a request ID. Server: the request ID and any program output.
+pub const FRAME_READ_USER: u8 = 3;
+/// Client: input for the program.
+pub const FRAME_WRITE_USER: u8 = 4;
+/// Server: an error message.
+pub const FRAME_ERROR: u8 = 5;
+
+/// Frames larger than this are rejected. Packets are at most a few kilobytes.
+const MAX_FRAME_LEN: usize = 1024 * 1024;
+
+/// Extra time given to the server to answer a request over the network.
+const NETWORK_GRACE: Duration = Duration::from_secs(2);
+
+/// Unclaimed packets are dropped after this long, like on a local connection.
+const PACKET_LIFETIME: Duration = Duration::from_secs(2);
+
+/// Writes a single frame.
+pub async fn write_frame(
+    writer: &mut (impl AsyncWrite + Unpin),
+    kind: u8,
+    payload: &[u8],
+) -> io::Result<()> {
+    let mut frame = Vec::with_capacity(payload.len() + 5);
+    frame.push(kind);
+    frame.extend((payload.len() as u32).to_le_bytes());
+    frame.extend(payload);
+
+    writer.write_all(&frame).await
+}
+
+/// Reads a single frame, returning its kind and payload.
+pub async fn read_frame(reader: &mut (impl AsyncRead + Unpin)) -> io::Result<(u8, Vec<u8>)> {
+    let kind = reader.read_u8().await?;
+    let len = reader.read_u32_le().await? as usize;
+    if len > MAX_FRAME_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("frame of {len} bytes is too large"),
+        ));
+    }
+
+    let mut payload = vec![0; len];
+    reader.read_exact(&mut payload).await?;
+
+    Ok((kind, payload))
+}
+
+/// Generates a random nonce for a client to authenticate with.
+pub fn nonce() -> [u8; 32] {
+    let mut nonce = [0; 32];
+    SystemRandom::new()
+        .fill(&mut nonce)
+        .expect("the system random number generator should be available");
+    nonce
+}
+
+/// Generates a random token for `cargo v5 serve` to use when none is given.
+pub fn generate_token() -> String {
+    nonce()[..16]
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+fn token_key(token: &str) -> hmac::Key {
+    hmac::Key::new(hmac::HMAC_SHA256, token.as_bytes())
+}
+
+/// Signs a server's nonce with the shared token.
+pub fn sign_nonce(token: &str, nonce: &[u8]) -> Vec<u8> {
+    hmac::sign(&token_key(token), nonce).as_ref().to_vec()
+}
+
+/// Checks a client's answer to a nonce against the shared token.
+pub fn verify_nonce(token: &str, nonce: &[u8], signature: &[u8]) -> bool {
+    hmac::verify(&token_key(token), nonce, signature).is_ok()
+}
+
+/// Compares a token a client presented against the expected one in constant time.
+pub fn token_matches(expected: &str, presented: &str) -> bool {
+    expected.len() == presented.len()
+        && expected
+            .bytes()
+            .zip(presented.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+pub fn encode_connection_type(connection_type: ConnectionType) -> u8 {
+    match connection_type {
+        ConnectionType::Wired => 0,
+        ConnectionType::Controller => 1,
+        ConnectionType::Bluetooth => 2,
+    }
+}
+
+pub fn decode_connection_type(byte: u8) -> Option<ConnectionType> {
+    match byte {
+        0 => Some(ConnectionType::Wired),
+        1 => Some(ConnectionType::Controller),
+        2 => Some(ConnectionType::Bluetooth),
+        _ => None,
+    }
+}
+
+/// Any packet received from a device, kept as raw bytes.
+pub struct AnyPacket(pub Vec<u8>);
+
+impl CheckHeader for AnyPacket {
+    fn has_valid_header(_data: &[u8]) -> bool {
+        true
+    }
+}
+
+impl Decode for AnyPacket {
+    fn decode(data: &mut &[u8]) -> Result<Self, DecodeError> {
+        let packet = data.to_vec();
+        *data = &[];
+
+        Ok(Self(packet))
+    }
+}
+
+/// A connection to a device attached to another machine running `cargo v5 serve`.
+pub struct RemoteConnection {
+    connection_type: ConnectionType,
+    writer: OwnedWriteHalf,
+
+    /// Frames from the server, read on a separate task so that waiting for one can time out.
+    frames: mpsc::Receiver<io::Result<(u8, Vec<u8>)>>,
+    next_request: u32,
+
+    /// Packets the server passed on that haven't been claimed yet.
+    incoming: Vec<(Instant, Vec<u8>)>,
+    user_output: VecDeque<u8>,
+}
+
+impl RemoteConnection {
+    /// Connects to the server at `addr` and authenticates with `token`.
+    pub async fn connect(addr: &str, token: &str) -> Result<Self, CliError> {
+        let unreachable = |err| CliError::RemoteUnreachable(addr.to_string(), err);
+
+        let stream = TcpStream::connect(addr).await.map_err(unreachable)?;
+        stream.set_nodelay(true)?;
+        let (mut reader, mut writer) = stream.into_split();
+
+        let (_, nonce) = read_frame(&mut reader).await.map_err(unreachable)?;
+        write_frame(&mut writer, FRAME_HELLO, &sign_nonce(token, &nonce)).await?;
+
+        let connection_type = match read_frame(&mut reader).await.map_err(unreachable)? {
+            (FRAME_HELLO, payload) => payload.first().copied().and_then(decode_connection_type),
+            (FRAME_ERROR, message) => {
+                return Err(CliError::RemoteRejected(
+                    addr.to_string(),
+                    String::from_utf8_lossy(&message).into_owned(),
+                ));
+            }
+            _ => None,
+        }
+        .ok_or_else(|| {
+            CliError::RemoteRejected(addr.to_string(), "unexpected handshake reply".to_string())
+        })?;
+
+        let (frames_tx, frames) = mpsc::channel(64);
+        tokio::spawn(async move {
+            loop {
+                let frame = read_frame(&mut reader).await;
+                let closed = frame.is_err();
+                if frames_tx.send(frame).await.is_err() || closed {
+                    break;
+                }
+            }
+        });
+
+        Ok(Self {
+            connection_type,
+            writer,
+            frames,
+            next_request: 0,
+            incoming: Vec::new(),
+            user_output: VecDeque::new(),
+        })
+    }
+
+    async fn request(&mut self, kind: u8, payload: &[u8]) -> Result<u32, ConnectionError> {
+        let id = self.next_request;
+        self.next_request = self.next_request.wrapping_add(1);
+
+        let mut frame = id.to_le_bytes().to_vec();
+        frame.extend(payload);
+        write_frame(&mut self.writer, kind, &frame).await?;
+
+        Ok(id)
+    }
+
+    /// Waits for the reply to request `id`, storing the packets and program output in it and any
+    /// other replies that arrive in the meantime.
+    async fn reply(
+        &mut self,
+        kind: u8,
+        id: u32,
+        deadline: tokio::time::Instant,
+    ) -> Result<(), ConnectionError> {
+        loop {
+            let (frame_kind, mut payload) = timeout_at(deadline, self.frames.recv())
+                .await
+                .map_err(|_| ConnectionError::RemoteTimeout)?
+                .ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))??;
+
+            if frame_kind == FRAME_ERROR {
+                return Err(ConnectionError::Remote(
+                    String::from_utf8_lossy(&payload).into_owned(),
+                ));
+            }
+            if payload.len() < 4 {
+                continue;
+            }
+
+            let data = payload.split_off(4);
+            let reply_id = u32::from_le_bytes(payload.try_into().unwrap());
+
+            // Replies to earlier requests that gave up waiting are still kept.
+            match frame_kind {
+                FRAME_RECV if !data.is_empty() => self.incoming.push((Instant::now(), data)),
+                FRAME_READ_USER => self.user_output.extend(&data),
+                _ => {}
+            }
+
+            if frame_kind == kind && reply_id == id {
+                return Ok(());
+            }
+        }
+    }
+}
+
+impl Connection for RemoteConnection {
+    type Error = ConnectionError;
+
+    fn connection_type(&self) -> ConnectionType {
+        self.connection_type
+    }
+
+    async fn send(&mut self, packet: impl Encode) -> Result<(), ConnectionError> {
+        let mut encoded = vec![0; packet.size()];
+        packet.encode(&mut encoded);
+
+        write_frame(&mut self.writer, FRAME_SEND, &encoded).await?;
+        Ok(())
+    }
+
+    async fn recv<P: Decode + CheckHeader>(
+        &mut self,
+        timeout: Duration,
+    ) -> Result<P, ConnectionError> {
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            self.incoming
+                .retain(|(received, _)| received.elapsed() < PACKET_LIFETIME);
+            if let Some(index) = self
+                .incoming
+                .iter()
+                .position(|(_, packet)| P::has_valid_header(packet))
+            {
+                let (_, packet) = self.incoming.remove(index);
+                return Ok(P::decode(&mut packet.as_slice())?);
+            }
+
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(ConnectionError::RemoteTimeout);
+            }
+
+            let id = self
+                .request(FRAME_RECV, &(remaining.as_millis() as u32).to_le_bytes())
+                .await?;
+            let reply_deadline = tokio::time::Instant::now() + remaining + NETWORK_GRACE;
+            self.reply(FRAME_RECV, id, reply_deadline).await?;
+        }
+    }
+
+    async fn read_user(&mut self, buf: &mut [u8]) -> Result<usize, ConnectionError> {
+        // Like a local connection, wait until the program has printed something.
+        while self.user_output.is_empty() {
+            let id = self.request(FRAME_READ_USER, &[]).await?;
+            let reply_deadline = tokio::time::Instant::now() + NETWORK_GRACE * 2;
+            self.reply(FRAME_READ_USER, id, reply_deadline).await?;
+        }
+
+        let len = buf.len().min(self.user_output.len());
+        for (byte, output) in buf.iter_mut().zip(self.user_output.drain(..len)) {
+            *byte = output;
+        }
+
+        Ok(len)
+    }
+
+    async fn write_user(&mut self, buf: &[u8]) -> Result<usize, ConnectionError> {
+        write_frame(&mut self.writer, FRAME_WRITE_USER, buf).await?;
+        Ok(buf.len())
+    }
+}
@@ -0,0 +1,287 @@
+//! Reporting of progress and status from commands.
+//!
+//! Commands that transfer files or build programs describe what they're doing through a
+//! [`Reporter`] rather than printing directly, so that tools embedding cargo-v5 can receive the
+//! same events as structured data. [`TerminalReporter`] draws progress bars and status lines for
+//! humans, and [`JsonReporter`] writes line-delimited JSON to stdout.
+
+use std::{
+    sync::{Arc, OnceLock},
+    time::Instant,
+};
+
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use serde_json::{Value, json};
+
+use crate::commands::upload::{MessageFormat, emit_message, progress_chars};
+
+/// The kind of work a long-running task does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskKind {
+    /// Uploading a file to the Brain.
+    Upload,
+
+    /// Uploading a patch to a program already on the Brain.
+    Patch,
+
+    /// Downloading a file from the Brain.
+    Download,
+
+    /// Downloading a capture of the Brain's screen.
+    Capture,
+}
+
+impl TaskKind {
+    /// Verb shown next to the task's progress bar.
+    pub fn verb(self) -> &'static str {
+        match self {
+            Self::Upload => "Uploading",
+            Self::Patch => "Patching",
+            Self::Download => "Downloading",
+            Self::Capture => "Capturing",
+        }
+    }
+
+    /// `reason` of the JSON messages reporting the task's progress.
+    pub fn progress_reason(self) -> &'static str {
+        match self {
+            Self::Upload | Self::Patch => "upload-progress",
+            Self::Download => "download-progress",
+            Self::Capture => "capture-progress",
+        }
+    }
+}
+
+/// A long-running task, such as a file transfer.
+#[derive(Debug, Clone, Copy)]
+pub struct Task<'a> {
+    pub kind: TaskKind,
+
+    /// What the task is working on, usually a file name.
+    pub name: &'a str,
+
+    /// Color of the task's progress bar in a terminal.
+    pub color: &'a str,
+}
+
+/// Progress of a task started with [`Reporter::start_task`].
+pub trait Progress: Send + Sync {
+    /// Updates how much of the task is done, as a percentage.
+    fn set_percent(&self, percent: f32);
+
+    /// Marks the task as done.
+    fn finish(&self);
+}
+
+/// Receives events describing what a command is doing.
+pub trait Reporter: Send + Sync {
+    /// Reports a completed step, like `Objcopy` followed by the path of the binary.
+    fn status(&self, verb: &str, message: &str);
+
+    /// Reports something that might be wrong but doesn't stop the command.
+    fn warning(&self, message: &str);
+
+    /// Reports a hint about how to resolve a problem.
+    fn note(&self, message: &str);
+
+    /// Starts reporting the progress of a long-running task.
+    fn start_task(&self, task: Task<'_>) -> Arc<dyn Progress>;
+
+    /// Reports a line printed by the program running on the Brain.
+    fn program_output(&self, line: &str);
+
+    /// Reports an event that only matters to other programs, like the path of a built artifact.
+    fn event(&self, reason: &str, data: Value) {
+        _ = (reason, data);
+    }
+}
+
+/// Wraps a task's progress in the callback expected by file transfers.
+pub fn progress_callback(progress: &Arc<dyn Progress>) -> Box<dyn FnMut(f32) + Send> {
+    let progress = progress.clone();
+    Box::new(move |percent| progress.set_percent(percent))
+}
+
+impl MessageFormat {
+    /// Creates the reporter that prints messages in this format.
+    pub fn reporter(self) -> Box<dyn Reporter> {
+        match self {
+            Self::Human => Box::new(TerminalReporter::new()),
+            Self::Json => Box::new(JsonReporter::new()),
+        }
+    }
+}
+
+/// Draws progress bars and status lines on stderr.
+#[derive(Debug, Clone, Default)]
+pub struct TerminalReporter {
+    multi_progress: MultiProgress,
+}
+
+impl TerminalReporter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Prints a line above any progress bars.
+    fn println(&self, line: String) {
+        self.multi_progress.suspend(|| eprintln!("{line}"));
+    }
+}
+
+impl Reporter for TerminalReporter {
+    fn status(&self, verb: &str, message: &str) {
+        self.println(format!("\x1b[1;92m{verb:>12}\x1b[0m {message}"));
+    }
+
+    fn warning(&self, message: &str) {
+        self.println(format!("\x1b[1;93m{:>12}\x1b[0m {message}", "Warning"));
+    }
+
+    fn note(&self, message: &str) {
+        self.println(format!("\x1b[1;96m{:>12}\x1b[0m {message}", "Note"));
+    }
+
+    fn start_task(&self, task: Task<'_>) -> Arc<dyn Progress> {
+        let bar = self
+            .multi_progress
+            .add(ProgressBar::new(10000))
+            .with_style(
+                ProgressStyle::with_template(&format!(
+                    "\x1b[1;96m{:>12}\x1b[0m {{percent_precise:>7}}% {{bar:40.{}}} {{msg}} ({{prefix}})",
+                    task.kind.verb(),
+                    task.color,
+                ))
+                .unwrap() // Okay to unwrap, since this just validates style formatting.
+                .progress_chars(progress_chars()),
+            )
+            .with_message(task.name.to_string());
+
+        Arc::new(TerminalProgress {
+            bar,
+            started: OnceLock::new(),
+        })
+    }
+
+    fn program_output(&self, line: &str) {
+        self.println(format!("\x1b[2m{:>12}\x1b[0m {line}", "Program"));
+    }
+}
+
+/// A progress bar drawn by a [`TerminalReporter`].
+struct TerminalProgress {
+    bar: ProgressBar,
+
+    /// When the first progress was reported. indicatif times bars from when they're created,
+    /// which includes time spent waiting to start.
+    started: OnceLock<Instant>,
+}
+
+impl Progress for TerminalProgress {
+    fn set_percent(&self, percent: f32) {
+        let started = self.started.get_or_init(Instant::now);
+
+        self.bar.set_prefix(format!("{:.2?}", started.elapsed()));
+        self.bar.set_position((percent * 100.0) as u64);
+    }
+
+    fn finish(&self) {
+        self.bar.finish();
+    }
+}
+
+/// Writes one JSON object per event to stdout, or passes them to another sink.
+///
+/// Every message has a `reason` field naming the event, like Cargo's `--message-format json`.
+#[derive(Clone)]
+pub struct JsonReporter {
+    emit: Arc<dyn Fn(Value) + Send + Sync>,
+}
+
+impl JsonReporter {
+    /// Creates a reporter that writes messages to stdout.
+    pub fn new() -> Self {
+        Self::with_sink(emit_message)
+    }
+
+    /// Creates a reporter that passes each message to `emit`.
+    pub fn with_sink(emit: impl Fn(Value) + Send + Sync + 'static) -> Self {
+        Self {
+            emit: Arc::new(emit),
+        }
+    }
+}
+
+impl Default for JsonReporter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Reporter for JsonReporter {
+    fn status(&self, verb: &str, message: &str) {
+        (self.emit)(json!({
+            "reason": "status",
+            "status": verb.to_lowercase(),
+            "message": message,
+        }));
+    }
+
+    fn warning(&self, message: &str) {
+        (self.emit)(json!({
+            "reason": "warning",
+            "message": message,
+        }));
+    }
+
+    fn note(&self, message: &str) {
+        (self.emit)(json!({
+            "reason": "note",
+            "message": message,
+        }));
+    }
+
+    fn start_task(&self, task: Task<'_>) -> Arc<dyn Progress> {
+        Arc::new(JsonProgress {
+            emit: self.emit.clone(),
+            reason: task.kind.progress_reason(),
+            name: task.name.to_string(),
+        })
+    }
+
+    fn program_output(&self, line: &str) {
+        (self.emit)(json!({
+            "reason": "program-output",
+            "line": line,
+        }));
+    }
+
+    fn event(&self, reason: &str, mut data: Value) {
+        if let Value::Object(fields) = &mut data {
+            fields.insert("reason".to_string(), reason.into());
+        } else {
+            data = json!({ "reason": reason });
+        }
+
+        (self.emit)(data);
+    }
+}
+
+/// Progress reported by a [`JsonReporter`].
+struct JsonProgress {
+    emit: Arc<dyn Fn(Value) + Send + Sync>,
+    reason: &'static str,
+    name: String,
+}
+
+impl Progress for JsonProgress {
+    fn set_percent(&self, percent: f32) {
+        (self.emit)(json!({
+            "reason": self.reason,
+            "file": self.name,
+            "percent": percent,
+        }));
+    }
+
+    fn finish(&self) {}
+}